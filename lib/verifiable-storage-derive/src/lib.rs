@@ -29,18 +29,234 @@ fn has_attr(field: &syn::Field, attr_name: &str) -> bool {
         .any(|attr| attr.path().is_ident(attr_name))
 }
 
+/// Whether a `#[created_at(hybrid)]` field takes its timestamps from the hybrid clock.
+fn created_at_hybrid(field: &syn::Field) -> bool {
+    let Some(attr) = field
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("created_at"))
+    else {
+        return false;
+    };
+    if matches!(attr.meta, syn::Meta::Path(_)) {
+        return false;
+    }
+
+    let mut hybrid = false;
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("hybrid") {
+            hybrid = true;
+            Ok(())
+        } else {
+            Err(meta.error("unsupported #[created_at(...)] flag (expected `hybrid`)"))
+        }
+    })
+    .expect("Failed to parse #[created_at(...)] attribute");
+    hybrid
+}
+
+/// Parsed `#[said(fields = ["a", "b"], algorithm = "sha3-256")]`
+#[derive(Default)]
+struct SaidArgs {
+    /// The listed field names, if the SAID covers a subset
+    fields: Option<Vec<String>>,
+    /// The `SaidAlgorithm` variant to hash with
+    algorithm: Option<syn::Ident>,
+}
+
+fn said_args(field: &syn::Field) -> SaidArgs {
+    let mut args = SaidArgs::default();
+    let Some(attr) = field.attrs.iter().find(|attr| attr.path().is_ident("said")) else {
+        return args;
+    };
+    if matches!(attr.meta, syn::Meta::Path(_)) {
+        return args;
+    }
+
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("fields") {
+            let value = meta.value()?;
+            let content;
+            syn::bracketed!(content in value);
+            let names =
+                syn::punctuated::Punctuated::<syn::LitStr, syn::Token![,]>::parse_terminated(
+                    &content,
+                )?;
+            args.fields = Some(names.iter().map(|name| name.value()).collect());
+            Ok(())
+        } else if meta.path.is_ident("algorithm") {
+            let name: syn::LitStr = meta.value()?.parse()?;
+            let variant = match name.value().as_str() {
+                "blake3" | "blake3-256" => "Blake3",
+                "sha3-256" => "Sha3_256",
+                "sha2-256" => "Sha2_256",
+                "blake2b" | "blake2b-256" => "Blake2b256",
+                _ => {
+                    return Err(meta.error(
+                        "unknown algorithm (expected blake3, sha3-256, sha2-256 or blake2b)",
+                    ));
+                }
+            };
+            args.algorithm = Some(quote::format_ident!("{}", variant));
+            Ok(())
+        } else {
+            Err(meta.error("unsupported #[said(...)] key (expected `fields` or `algorithm`)"))
+        }
+    })
+    .expect("Failed to parse #[said(...)] attribute");
+    args
+}
+
+/// Skip the value of a serde attribute key we don't care about.
+fn skip_serde_value(meta: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.input.parse::<proc_macro2::TokenTree>()?;
+    }
+    Ok(())
+}
+
+/// Parse a serde `rename`/`rename_all` value: `= "..."`, or
+/// `(serialize = "...", deserialize = "...")` when both sides agree. Panics
+/// when they differ, since rows are written under one name and read back
+/// under the other.
+fn serde_name_value(meta: &syn::meta::ParseNestedMeta, context: &str) -> syn::Result<String> {
+    if meta.input.peek(syn::Token![=]) {
+        let lit: syn::LitStr = meta.value()?.parse()?;
+        return Ok(lit.value());
+    }
+    let (mut serialize, mut deserialize) = (None, None);
+    meta.parse_nested_meta(|side| {
+        let lit: syn::LitStr = side.value()?.parse()?;
+        if side.path.is_ident("serialize") {
+            serialize = Some(lit.value());
+        } else if side.path.is_ident("deserialize") {
+            deserialize = Some(lit.value());
+        }
+        Ok(())
+    })?;
+    match (serialize, deserialize) {
+        (Some(serialize), Some(deserialize)) if serialize == deserialize => Ok(serialize),
+        _ => panic!(
+            "{} must name the same JSON key for serialize and deserialize to be stored",
+            context
+        ),
+    }
+}
+
+/// The `#[serde(rename_all = "...")]` of a struct, if any.
+fn serde_rename_all(input: &DeriveInput) -> Option<String> {
+    let mut rename_all = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                rename_all = Some(serde_name_value(&meta, "#[serde(rename_all)]")?);
+                Ok(())
+            } else {
+                skip_serde_value(&meta)
+            }
+        });
+    }
+    rename_all
+}
+
+/// The naming rule for a struct's JSON keys: `#[storable(json_naming = "...")]`
+/// if given, otherwise its `#[serde(rename_all = "...")]`.
+fn json_naming(storable_attr: Option<&StorableAttr>, input: &DeriveInput) -> Option<String> {
+    storable_attr
+        .and_then(|attr| attr.json_naming.clone())
+        .or_else(|| serde_rename_all(input))
+}
+
+/// Apply one of serde's `rename_all` rules to a snake_case field name.
+fn apply_rename_rule(name: &str, rule: &str) -> String {
+    match rule {
+        "snake_case" => name.to_string(),
+        "camelCase" => to_camel_case(name),
+        "PascalCase" => {
+            let camel = to_camel_case(name);
+            let mut chars = camel.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        }
+        "lowercase" => name.to_ascii_lowercase(),
+        "UPPERCASE" => name.to_ascii_uppercase(),
+        "SCREAMING_SNAKE_CASE" => name.to_ascii_uppercase(),
+        "kebab-case" => name.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => name.replace('_', "-").to_ascii_uppercase(),
+        other => panic!("unsupported JSON naming rule \"{}\"", other),
+    }
+}
+
+/// The JSON key serde serializes a field under: its `#[serde(rename = "...")]`,
+/// or its name under the struct's naming rule. Panics for a `flatten`ed field,
+/// which has no key of its own.
+fn serde_json_key(field: &syn::Field, rename_all: Option<&str>) -> String {
+    let name = field.ident.as_ref().unwrap().to_string();
+    let mut rename = None;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                rename = Some(serde_name_value(&meta, "#[serde(rename)]")?);
+                Ok(())
+            } else if meta.path.is_ident("flatten") {
+                panic!(
+                    "field `{}` is #[serde(flatten)]ed, so it has no JSON key; mark it #[column(skip)]",
+                    name
+                )
+            } else {
+                skip_serde_value(&meta)
+            }
+        });
+    }
+
+    match (rename, rename_all) {
+        (Some(rename), _) => rename,
+        (None, None) => name,
+        (None, Some(rule)) => apply_rename_rule(&name, rule),
+    }
+}
+
+/// [`serde_json_key`] for a stored column, which serde must also serialize:
+/// a `#[serde(skip)]` column would be inserted as NULL.
+fn column_json_key(field: &syn::Field, rename_all: Option<&str>) -> String {
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") || meta.path.is_ident("skip_serializing") {
+                panic!(
+                    "field `{}` is stored but not serialized; drop #[serde(skip...)] or mark it #[column(skip)]",
+                    field.ident.as_ref().unwrap()
+                )
+            }
+            skip_serde_value(&meta)
+        });
+    }
+    serde_json_key(field, rename_all)
+}
+
 /// Check if a field has #[column(skip)]
 fn has_column_skip(field: &syn::Field) -> bool {
+    has_column_flag(field, "skip")
+}
+
+/// Check if a field has a bare flag like #[column(expires_at)]
+fn has_column_flag(field: &syn::Field, flag: &str) -> bool {
     for attr in &field.attrs {
         if attr.path().is_ident("column") {
-            let mut skip = false;
+            let mut found = false;
             let _ = attr.parse_nested_meta(|meta| {
-                if meta.path.is_ident("skip") {
-                    skip = true;
+                if meta.path.is_ident(flag) {
+                    found = true;
+                } else if meta.input.peek(syn::Token![=]) {
+                    // Skip the value of `name = "..."` and similar
+                    meta.value()?.parse::<Lit>()?;
                 }
                 Ok(())
             });
-            if skip {
+            if found {
                 return true;
             }
         }
@@ -50,27 +266,127 @@ fn has_column_skip(field: &syn::Field) -> bool {
 
 /// Get custom column name from #[column(name = "...")] or None
 fn get_column_name(field: &syn::Field) -> Option<String> {
+    get_column_str(field, "name")
+}
+
+/// Get a string option like #[column(assert = "...")] or None
+fn get_column_str(field: &syn::Field, key: &str) -> Option<String> {
     for attr in &field.attrs {
         if attr.path().is_ident("column") {
-            let mut name = None;
+            let mut value = None;
             let _ = attr.parse_nested_meta(|meta| {
-                if meta.path.is_ident("name") {
-                    meta.input.parse::<syn::Token![=]>()?;
-                    let lit: Lit = meta.input.parse()?;
-                    if let Lit::Str(s) = lit {
-                        name = Some(s.value());
+                if meta.input.peek(syn::Token![=]) {
+                    let lit: Lit = meta.value()?.parse()?;
+                    if meta.path.is_ident(key)
+                        && let Lit::Str(s) = lit
+                    {
+                        value = Some(s.value());
                     }
                 }
                 Ok(())
             });
-            if name.is_some() {
-                return name;
+            if value.is_some() {
+                return value;
             }
         }
     }
     None
 }
 
+/// Get an integer option like #[column(position = 3)] or None
+fn get_column_int(field: &syn::Field, key: &str) -> Option<usize> {
+    for attr in &field.attrs {
+        if attr.path().is_ident("column") {
+            let mut value = None;
+            let _ = attr.parse_nested_meta(|meta| {
+                if meta.input.peek(syn::Token![=]) {
+                    let lit: Lit = meta.value()?.parse()?;
+                    if meta.path.is_ident(key)
+                        && let Lit::Int(n) = lit
+                    {
+                        value = Some(n.base10_parse().expect("Invalid column integer"));
+                    }
+                }
+                Ok(())
+            });
+            if value.is_some() {
+                return value;
+            }
+        }
+    }
+    None
+}
+
+/// Value constraints from #[column(max_length = N, pattern = "...", range = "a..b")]
+fn column_constraints(field: &syn::Field) -> Vec<proc_macro2::TokenStream> {
+    let mut constraints = Vec::new();
+    if let Some(max) = get_column_int(field, "max_length") {
+        constraints.push(quote! { verifiable_storage::ColumnConstraint::MaxLength(#max) });
+    }
+    if let Some(pattern) = get_column_str(field, "pattern") {
+        constraints.push(quote! { verifiable_storage::ColumnConstraint::Pattern(#pattern) });
+    }
+    if let Some(range) = get_column_str(field, "range") {
+        let bound = |bound: &str| -> i64 {
+            bound
+                .trim()
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid #[column(range = \"{}\")]", range))
+        };
+        let (min, max) = if let Some((min, max)) = range.split_once("..=") {
+            (bound(min), bound(max))
+        } else if let Some((min, max)) = range.split_once("..") {
+            (bound(min), bound(max) - 1)
+        } else {
+            panic!("#[column(range = \"{}\")] must be `a..b` or `a..=b`", range)
+        };
+        constraints.push(quote! { verifiable_storage::ColumnConstraint::Range(#min, #max) });
+    }
+    constraints
+}
+
+/// The stored (non-skipped) fields in column order: declaration order, or
+/// `#[column(position = N)]` order when positions are given. Positions are
+/// all-or-nothing and must be unique, so no column's place is left implicit.
+fn ordered_columns<'a>(
+    name: &syn::Ident,
+    fields: impl Iterator<Item = &'a syn::Field>,
+) -> Vec<&'a syn::Field> {
+    let mut stored: Vec<_> = fields
+        .filter(|field| !has_column_skip(field))
+        .map(|field| (get_column_int(field, "position"), field))
+        .collect();
+    if stored.iter().all(|(position, _)| position.is_none()) {
+        return stored.into_iter().map(|(_, field)| field).collect();
+    }
+    for (position, field) in &stored {
+        assert!(
+            position.is_some(),
+            "{}: field `{}` needs #[column(position = N)] since other columns have one",
+            name,
+            field.ident.as_ref().unwrap()
+        );
+    }
+    stored.sort_by_key(|(position, _)| *position);
+    for pair in stored.windows(2) {
+        assert!(
+            pair[0].0 != pair[1].0,
+            "{}: fields `{}` and `{}` share #[column(position = {})]",
+            name,
+            pair[0].1.ident.as_ref().unwrap(),
+            pair[1].1.ident.as_ref().unwrap(),
+            pair[0].0.unwrap_or_default()
+        );
+    }
+    stored.into_iter().map(|(_, field)| field).collect()
+}
+
+/// Check if a field's type is `Option<...>`
+fn is_option_type(ty: &syn::Type) -> bool {
+    let type_str = quote::quote!(#ty).to_string().replace(' ', "");
+    type_str.starts_with("Option<") && type_str.ends_with('>')
+}
+
 /// Map Rust type to generic SQL type name
 fn rust_type_to_sql_type(ty: &syn::Type) -> &'static str {
     let type_str = quote::quote!(#ty).to_string();
@@ -98,25 +414,133 @@ fn rust_type_to_sql_type(ty: &syn::Type) -> &'static str {
     }
 }
 
-/// Parse #[storable(table = "...")] attribute and return table name
-fn parse_storable_attr(input: &DeriveInput) -> Option<String> {
+/// Map a Rust type to the shape of its serialized value. Structs, enums and
+/// anything else serde may shape more than one way are "any".
+fn rust_type_to_value_kind(ty: &syn::Type) -> &'static str {
+    let type_str = quote::quote!(#ty).to_string().replace(' ', "");
+    let inner_type = if type_str.starts_with("Option<") && type_str.ends_with('>') {
+        &type_str[7..type_str.len() - 1]
+    } else {
+        type_str.as_str()
+    };
+    // `std::collections::HashMap<K, V>` -> `HashMap`
+    let outer = inner_type.split('<').next().unwrap_or(inner_type);
+    let base = outer.rsplit("::").next().unwrap_or(outer);
+
+    match base {
+        "String" | "str" | "&str" => "string",
+        "u8" | "u16" | "u32" | "u64" | "usize" | "i8" | "i16" | "i32" | "i64" | "isize" => "int",
+        "f32" | "f64" => "float",
+        "bool" => "bool",
+        "StorageDatetime" | "DateTime" => "datetime",
+        "Vec" | "VecDeque" | "HashSet" | "BTreeSet" => "array",
+        "HashMap" | "BTreeMap" => "object",
+        _ => "any",
+    }
+}
+
+/// Parsed #[storable(...)] attribute
+struct StorableAttr {
+    table: Option<String>,
+    partition_by: Option<String>,
+    interval: Option<String>,
+    partitions: Option<u32>,
+    encoding: Option<String>,
+    json_naming: Option<String>,
+    raw_column: Option<String>,
+    checked: bool,
+}
+
+/// Parse #[storable(table = "...", partition_by = "...", interval = "..." | partitions = N,
+/// encoding = "...", json_naming = "...", raw_column = "...", checked)]
+///
+/// Unknown or malformed keys are a `syn::Error` spanning the offending key.
+fn parse_storable_attr(input: &DeriveInput) -> syn::Result<Option<StorableAttr>> {
     for attr in &input.attrs {
         if attr.path().is_ident("storable") {
-            let mut table_name = None;
-            let _ = attr.parse_nested_meta(|meta| {
-                if meta.path.is_ident("table") {
-                    meta.input.parse::<syn::Token![=]>()?;
-                    let lit: Lit = meta.input.parse()?;
-                    if let Lit::Str(s) = lit {
-                        table_name = Some(s.value());
+            let mut parsed = StorableAttr {
+                table: None,
+                partition_by: None,
+                interval: None,
+                partitions: None,
+                encoding: None,
+                json_naming: None,
+                raw_column: None,
+                checked: false,
+            };
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("checked") {
+                    parsed.checked = true;
+                    return Ok(());
+                }
+                let lit: Lit = meta.value()?.parse()?;
+                match (
+                    &lit,
+                    meta.path.get_ident().map(|i| i.to_string()).as_deref(),
+                ) {
+                    (Lit::Str(s), Some("table")) => parsed.table = Some(s.value()),
+                    (Lit::Str(s), Some("partition_by")) => parsed.partition_by = Some(s.value()),
+                    (Lit::Str(s), Some("interval")) => parsed.interval = Some(s.value()),
+                    (Lit::Str(s), Some("encoding")) => parsed.encoding = Some(s.value()),
+                    (Lit::Str(s), Some("json_naming")) => parsed.json_naming = Some(s.value()),
+                    (Lit::Str(s), Some("raw_column")) => parsed.raw_column = Some(s.value()),
+                    (Lit::Int(i), Some("partitions")) => {
+                        parsed.partitions = Some(i.base10_parse()?)
                     }
+                    _ => return Err(meta.error("unsupported storable attribute")),
                 }
                 Ok(())
-            });
-            return table_name;
+            })?;
+            return Ok(Some(parsed));
+        }
+    }
+    Ok(None)
+}
+
+/// Generate the `Storable::partitioning()` override, if the type is partitioned
+fn partitioning_impl(attr: &StorableAttr, column_names: &[String]) -> proc_macro2::TokenStream {
+    let Some(column) = &attr.partition_by else {
+        assert!(
+            attr.interval.is_none() && attr.partitions.is_none(),
+            "`interval` and `partitions` require `partition_by`"
+        );
+        return quote! {};
+    };
+    assert!(
+        column_names.contains(column),
+        "partition_by column `{}` is not a stored column",
+        column
+    );
+
+    let partitioning = match (&attr.interval, attr.partitions) {
+        (Some(interval), None) => {
+            let interval = match interval.as_str() {
+                "day" => quote! { verifiable_storage::PartitionInterval::Day },
+                "month" => quote! { verifiable_storage::PartitionInterval::Month },
+                "year" => quote! { verifiable_storage::PartitionInterval::Year },
+                other => panic!(
+                    "unsupported partition interval `{}` (expected day, month, or year)",
+                    other
+                ),
+            };
+            quote! {
+                verifiable_storage::Partitioning::Range { column: #column, interval: #interval }
+            }
+        }
+        (None, Some(partitions)) => {
+            assert!(partitions > 0, "`partitions` must be at least 1");
+            quote! {
+                verifiable_storage::Partitioning::Hash { column: #column, partitions: #partitions }
+            }
+        }
+        _ => panic!("partition_by requires exactly one of `interval` or `partitions`"),
+    };
+
+    quote! {
+        fn partitioning() -> Option<verifiable_storage::Partitioning> {
+            Some(#partitioning)
         }
     }
-    None
 }
 
 /// Derive macro for SelfAddressed trait (and optionally Versioned)
@@ -133,6 +557,12 @@ fn parse_storable_attr(input: &DeriveInput) -> Option<String> {
 /// - `new(params...)` - Constructor excluding storage-managed fields
 /// - `create(params...)` - Constructor that also derives SAID/prefix, returns `Result`
 ///
+/// ### Generated with a `#[created_at]` field (inherent):
+/// - `create_with_timestamp(params..., created_at)` - `create()` keeping a given timestamp,
+///   for imports; `Versioned::increment_at()` does the same for later versions. Without
+///   std there is no clock, so types with a `#[created_at]` field get only this, not
+///   `new()` or `create()`
+///
 /// ### Always generated (SelfAddressed trait):
 /// - `derive_said()` - Compute content-based SAID
 /// - `verify_said()` - Verify SAID matches content
@@ -140,9 +570,11 @@ fn parse_storable_attr(input: &DeriveInput) -> Option<String> {
 ///
 /// ### Generated when versioned (Versioned trait):
 /// - `derive_prefix()` - Compute prefix from inception SAID
-/// - `verify_prefix()` - Verify prefix matches content
+/// - `verify_prefix()` - Verify an inception's prefix matches content (deprecated in favor of
+///   `verify_inception()` and `verify_lineage()`, which the trait provides)
 /// - `get_prefix()` - Get current prefix
-/// - `increment()` - Increment version for updates
+/// - `increment_at(created_at)` - Increment version for updates; `increment()` (std only)
+///   stamps it now
 /// - `verify_unchanged(proposed)` - Check if proposed update has actual changes
 /// - `get_version()`, `get_previous()`, `get_created_at()`, `set_created_at()`
 ///
@@ -153,7 +585,78 @@ fn parse_storable_attr(input: &DeriveInput) -> Option<String> {
 /// - `#[prefix]` - empty string (computed by `derive_prefix()`)
 /// - `#[previous]` - None
 /// - `#[version]` - 0
-/// - `#[created_at]` - current timestamp
+/// - `#[created_at]` - current timestamp; `#[created_at(hybrid)]` takes it from the
+///   process-wide `HybridClock` instead, so `increment()` always moves it forward
+/// - `#[column(seal)]` - None; an `Option<String>` filled in by a `RowSealer` and
+///   left out of the SAID
+/// - `#[deleted_at]` - None; an `Option<StorageDatetime>` set by `delete_soft()`,
+///   left out of the SAID and cleared by `increment()`. Queries skip rows
+///   where it is set unless built with `Query::include_deleted()`
+///
+/// ## Validation
+///
+/// `#[validate]` on the struct makes `derive_said()`, and so `create()`,
+/// `derive_prefix()` and `increment()`, run the type's `Validate` impl first,
+/// so invalid content fails before it is digested into a chain. Verification
+/// doesn't validate, so stored records survive stricter rules.
+///
+/// On a storable type, `#[column(max_length = 255)]`, `#[column(pattern = "^[a-z]+$")]`
+/// and `#[column(range = "0..100")]` (or `"0..=99"`, integers only) declare
+/// constraints that generate the `Validate` impl, and become CHECK constraints
+/// or asserts in the backends' schema DDL. A type with its own `#[validate]`
+/// impl can call `check_column_constraints(self)` from it.
+///
+/// ## Hash algorithm
+///
+/// SAIDs are Blake3-256 digests unless `#[said(algorithm = "...")]` picks
+/// `"sha3-256"`, `"sha2-256"` or `"blake2b"` (Blake2b-256); the CESR code in
+/// the SAID records which was used.
+///
+/// ## CBOR
+///
+/// `#[storable(encoding = "cbor")]` computes the SAID over canonical CBOR
+/// rather than JSON (needs the `cbor` feature), and `#[column(cbor)]` stores
+/// a field as a CBOR blob. SAIDs from the two encodings differ.
+///
+/// ## JSON keys
+///
+/// `Storable::json_keys()` names fields the way serde does, honouring
+/// `#[serde(rename = "...")]` and the struct's `#[serde(rename_all = "...")]`
+/// (fields keep their Rust names without one). `#[storable(json_naming =
+/// "snake_case")]` supplies the rule when serde's can't be read from the
+/// attributes, e.g. behind `cfg_attr`. A stored field whose key can't be
+/// determined fails the build: a `rename` or `rename_all` that differs between
+/// serialize and deserialize, `#[serde(flatten)]`, or `#[serde(skip)]` /
+/// `skip_serializing` without `#[column(skip)]`.
+///
+/// ## Raw records
+///
+/// `#[storable(raw_column = "raw")]` also stores each record's serialized JSON
+/// in a text column, and backends read records back from it; see
+/// `Storable::raw_column()`. It can't be combined with `encoding = "cbor"`.
+///
+/// ## Checked columns
+///
+/// `#[storable(checked)]` has `sqlx::query!` verify, at compile time, that
+/// every stored column exists in the table and can be selected and inserted,
+/// so a renamed, dropped or added column fails the build. It emits a hidden
+/// `checked_columns(pool)` that a Postgres repository's `checked` calls. Needs
+/// `sqlx` (with `macros`) and `DATABASE_URL` (or `SQLX_OFFLINE` query data) at
+/// build time.
+///
+/// ## Partial SAIDs
+///
+/// `#[said(fields = ["name", "owner"])]` makes the SAID cover only the listed
+/// fields plus the storage-managed fields above. Other fields (notes, display
+/// metadata) can then change without changing the SAID or needing a new version.
+///
+/// ## Column order
+///
+/// Columns follow field declaration order, which fixes the parameter order of
+/// `insert_sql()`. To keep that layout independent of how the struct is laid
+/// out, give every stored field `#[column(position = N)]`; columns are then
+/// ordered by position, and a missing or repeated position fails the build.
+/// `Storable::schema_hash()` changes whenever the layout does.
 ///
 /// ## Example (unversioned)
 ///
@@ -190,7 +693,9 @@ fn parse_storable_attr(input: &DeriveInput) -> Option<String> {
 /// ```
 #[proc_macro_derive(
     SelfAddressed,
-    attributes(said, prefix, previous, version, created_at, storable, column)
+    attributes(
+        said, prefix, previous, version, created_at, deleted_at, storable, column, validate
+    )
 )]
 pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -202,6 +707,10 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
         },
         _ => panic!("SelfAddressed only supports structs"),
     };
+    let storable_attr = match parse_storable_attr(&input) {
+        Ok(attr) => attr,
+        Err(e) => return e.to_compile_error().into(),
+    };
 
     let said_field = fields
         .iter()
@@ -209,11 +718,98 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
         .expect("No field marked with #[said] attribute found");
     let said_field_name = said_field.ident.as_ref().unwrap();
 
+    // With #[said(fields = [...])], the SAID covers the listed fields plus the
+    // SAID and versioning fields, leaving the rest editable
+    let rename_all = json_naming(storable_attr.as_ref(), &input);
+    // Seals and deletion marks are set after the SAID, so it can't cover them
+    let unsigned_keys: Vec<String> = fields
+        .iter()
+        .filter(|f| has_column_flag(f, "seal") || has_attr(f, "deleted_at"))
+        .map(|f| serde_json_key(f, rename_all.as_deref()))
+        .collect();
+    let said_args = said_args(said_field);
+    let algorithm = said_args
+        .algorithm
+        .unwrap_or_else(|| quote::format_ident!("Blake3"));
+    let algorithm = quote! { verifiable_storage::SaidAlgorithm::#algorithm };
+    let placeholder = quote! { verifiable_storage::said_placeholder(#algorithm.digest_code()) };
+    let said_keys = said_args.fields.map(|listed| {
+        for name in &listed {
+            assert!(
+                fields
+                    .iter()
+                    .any(|f| f.ident.as_ref().is_some_and(|i| i == name)),
+                "#[said(fields = ...)] names unknown field `{}`",
+                name
+            );
+        }
+        fields
+            .iter()
+            .filter(|f| {
+                ["said", "prefix", "previous", "version", "created_at"]
+                    .iter()
+                    .any(|attr| has_attr(f, attr))
+                    || listed
+                        .iter()
+                        .any(|name| f.ident.as_ref().is_some_and(|i| i == name))
+            })
+            .map(|f| serde_json_key(f, rename_all.as_deref()))
+            .collect::<Vec<_>>()
+    });
+    let said_keys = said_keys.or_else(|| {
+        (!unsigned_keys.is_empty()).then(|| {
+            fields
+                .iter()
+                .map(|f| serde_json_key(f, rename_all.as_deref()))
+                .filter(|key| !unsigned_keys.contains(key))
+                .collect::<Vec<_>>()
+        })
+    });
+    // #[storable(encoding = "cbor")] digests canonical CBOR instead of JSON
+    let cbor = match storable_attr
+        .as_ref()
+        .and_then(|attr| attr.encoding.as_deref())
+    {
+        None => false,
+        Some(encoding) => match encoding {
+            "json" => false,
+            "cbor" => true,
+            other => panic!("unsupported encoding `{}` (expected json or cbor)", other),
+        },
+    };
+    let compute_said_of = |target: proc_macro2::TokenStream| match (&said_keys, cbor) {
+        (Some(keys), false) => {
+            quote! { verifiable_storage::compute_said_over_with(#target, &[#(#keys),*], #algorithm) }
+        }
+        (Some(keys), true) => {
+            quote! { verifiable_storage::compute_said_cbor_over_with(#target, &[#(#keys),*], #algorithm) }
+        }
+        (None, false) => quote! { verifiable_storage::compute_said_with(#target, #algorithm) },
+        (None, true) => quote! { verifiable_storage::compute_said_cbor_with(#target, #algorithm) },
+    };
+    let compute_said = compute_said_of(quote! { self });
+    let verify_compute_said = compute_said_of(quote! { &copy });
+
+    // #[validate] runs the type's Validate impl before every SAID derivation.
+    // Column constraints on a storable type generate that impl unless the
+    // type writes its own.
+    let custom_validate = input
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("validate"));
+    let constrained = storable_attr
+        .as_ref()
+        .is_some_and(|attr| attr.table.is_some())
+        && fields.iter().any(|f| !column_constraints(f).is_empty());
+    let validate_call = (custom_validate || constrained)
+        .then(|| quote! { verifiable_storage::Validate::validate(self)?; });
+
     // Check for versioned fields
     let prefix_field = fields.iter().find(|f| has_attr(f, "prefix"));
     let previous_field = fields.iter().find(|f| has_attr(f, "previous"));
     let version_field = fields.iter().find(|f| has_attr(f, "version"));
     let created_at_field = fields.iter().find(|f| has_attr(f, "created_at"));
+    let deleted_at_field = fields.iter().find(|f| has_attr(f, "deleted_at"));
 
     let is_versioned =
         prefix_field.is_some() && previous_field.is_some() && version_field.is_some();
@@ -222,26 +818,37 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
     let mut new_params = Vec::new();
     let mut new_param_names = Vec::new();
     let mut new_field_inits = Vec::new();
+    // As new_field_inits, with created_at taken from a `created_at` argument
+    let mut timestamp_field_inits = Vec::new();
 
     for field in fields.iter() {
         let field_name = field.ident.as_ref().unwrap();
         let field_ty = &field.ty;
 
-        if has_attr(field, "said") || has_attr(field, "prefix") {
-            new_field_inits.push(quote! { #field_name: String::new() });
+        let init = if has_attr(field, "said") || has_attr(field, "prefix") {
+            quote! { #field_name: verifiable_storage::__private::String::new() }
         } else if has_attr(field, "previous") {
-            new_field_inits.push(quote! { #field_name: None });
+            quote! { #field_name: None }
         } else if has_attr(field, "version") {
-            new_field_inits.push(quote! { #field_name: 0 });
+            quote! { #field_name: 0 }
+        } else if has_column_flag(field, "seal") || has_attr(field, "deleted_at") {
+            quote! { #field_name: None }
         } else if has_attr(field, "created_at") {
-            new_field_inits
-                .push(quote! { #field_name: verifiable_storage::StorageDatetime::now() });
+            new_field_inits.push(if created_at_hybrid(field) {
+                quote! { #field_name: verifiable_storage::StorageDatetime::hybrid_now() }
+            } else {
+                quote! { #field_name: verifiable_storage::StorageDatetime::now() }
+            });
+            timestamp_field_inits.push(quote! { #field_name: created_at });
+            continue;
         } else {
             // Regular field - add as parameter
             new_params.push(quote! { #field_name: #field_ty });
             new_param_names.push(quote! { #field_name });
-            new_field_inits.push(quote! { #field_name });
-        }
+            quote! { #field_name }
+        };
+        new_field_inits.push(init.clone());
+        timestamp_field_inits.push(init);
     }
 
     // Generate create() - calls derive_prefix() for versioned, derive_said() for unversioned
@@ -257,6 +864,30 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
         }
     };
 
+    // Generate create_with_timestamp() for imports that must keep their original time
+    let create_with_timestamp = if created_at_field.is_some() {
+        quote! {
+            impl #name {
+                /// Like `create()`, but with the given `created_at` instead of now.
+                ///
+                /// For importing historical records, and without std, where there
+                /// is no clock; pair with `increment_at()` for later versions.
+                pub fn create_with_timestamp(
+                    #(#new_params,)*
+                    created_at: verifiable_storage::StorageDatetime,
+                ) -> Result<Self, verifiable_storage::StorageError> {
+                    let mut item = Self {
+                        #(#timestamp_field_inits),*
+                    };
+                    #create_derive_call
+                    Ok(item)
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     // Generate Versioned impl if applicable
     let versioned_impl = if is_versioned {
         let prefix_field_name = prefix_field.unwrap().ident.as_ref().unwrap();
@@ -270,6 +901,21 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
             quote! { None }
         };
 
+        // With #[created_at(hybrid)], each version is stamped strictly after the last
+        let next_created_at = match created_at_field {
+            Some(field) if created_at_hybrid(field) => {
+                let field_name = field.ident.as_ref().unwrap();
+                quote! {
+                    verifiable_storage::__if_std! {
+                        fn next_created_at(&self) -> verifiable_storage::StorageDatetime {
+                            verifiable_storage::StorageDatetime::hybrid_after(&self.#field_name)
+                        }
+                    }
+                }
+            }
+            _ => quote! {},
+        };
+
         let created_at_set = if let Some(field) = created_at_field {
             let field_name = field.ident.as_ref().unwrap();
             quote! { self.#field_name = created_at.clone(); }
@@ -277,22 +923,30 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
             quote! {}
         };
 
+        // A new version starts out live even if the one it follows was deleted
+        let deleted_at_reset = deleted_at_field.map(|field| {
+            let field_name = field.ident.as_ref().unwrap();
+            quote! { self.#field_name = None; }
+        });
+
         quote! {
             impl verifiable_storage::Versioned for #name {
                 fn derive_prefix(&mut self) -> Result<(), verifiable_storage::StorageError> {
                     use verifiable_storage::SelfAddressed;
-                    self.#prefix_field_name = "#".repeat(44);
+                    self.#prefix_field_name = #placeholder?;
                     self.derive_said()?;
                     self.#prefix_field_name = self.#said_field_name.clone();
                     Ok(())
                 }
 
                 fn verify_prefix(&self) -> Result<(), verifiable_storage::StorageError> {
-                    use verifiable_storage::SelfAddressed;
                     let mut copy = self.clone();
-                    copy.derive_prefix()?;
+                    copy.#prefix_field_name = #placeholder?;
+                    copy.#said_field_name = #placeholder?;
+                    copy.#said_field_name = #verify_compute_said?;
+                    copy.#prefix_field_name = copy.#said_field_name.clone();
                     if copy.#said_field_name != self.#said_field_name || copy.#prefix_field_name != self.#prefix_field_name {
-                        return Err(verifiable_storage::StorageError::InvalidSaid(format!(
+                        return Err(verifiable_storage::StorageError::InvalidSaid(verifiable_storage::__private::format!(
                             "SAID prefix verification failed: expected said={}, prefix={}, got said={}, prefix={}",
                             self.#said_field_name, self.#prefix_field_name,
                             copy.#said_field_name, copy.#prefix_field_name
@@ -301,26 +955,34 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
                     Ok(())
                 }
 
-                fn get_prefix(&self) -> String {
+                fn get_prefix(&self) -> verifiable_storage::__private::String {
                     self.#prefix_field_name.clone()
                 }
 
-                fn increment(&mut self) -> Result<(), verifiable_storage::StorageError> {
+                fn increment_at(
+                    &mut self,
+                    created_at: verifiable_storage::StorageDatetime,
+                ) -> Result<(), verifiable_storage::StorageError> {
                     use verifiable_storage::SelfAddressed;
                     let old_id = self.#said_field_name.clone();
                     self.#previous_field_name = Some(old_id);
                     self.#version_field_name += 1;
-                    self.set_created_at(verifiable_storage::StorageDatetime::now());
+                    self.set_created_at(created_at);
+                    #deleted_at_reset
                     self.derive_said()?;
                     Ok(())
                 }
 
+                #next_created_at
+
                 fn verify_unchanged(&self, proposed: &Self) -> Result<bool, verifiable_storage::StorageError> {
                     use verifiable_storage::SelfAddressed;
                     let mut next_if_unchanged = self.clone();
                     next_if_unchanged.#previous_field_name = Some(self.#said_field_name.clone());
                     next_if_unchanged.#version_field_name += 1;
-                    next_if_unchanged.set_created_at(proposed.get_created_at().unwrap_or_else(verifiable_storage::StorageDatetime::now));
+                    if let Some(created_at) = proposed.get_created_at() {
+                        next_if_unchanged.set_created_at(created_at);
+                    }
                     next_if_unchanged.derive_said()?;
                     Ok(next_if_unchanged.#said_field_name == proposed.#said_field_name)
                 }
@@ -337,7 +999,7 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
                     #created_at_set
                 }
 
-                fn get_previous(&self) -> Option<String> {
+                fn get_previous(&self) -> Option<verifiable_storage::__private::String> {
                     self.#previous_field_name.clone()
                 }
             }
@@ -352,13 +1014,13 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
             impl Eq for #name {}
 
             impl PartialOrd for #name {
-                fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
                     Some(self.cmp(other))
                 }
             }
 
             impl Ord for #name {
-                fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                fn cmp(&self, other: &Self) -> core::cmp::Ordering {
                     (&self.#prefix_field_name, self.#version_field_name)
                         .cmp(&(&other.#prefix_field_name, other.#version_field_name))
                 }
@@ -369,38 +1031,137 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
     };
 
     // Generate Storable impl if #[storable(table = "...")] is present
-    let storable_impl = if let Some(table_name) = parse_storable_attr(&input) {
+    let storable_impl = if let Some(storable_attr) = &storable_attr
+        && let Some(table_name) = storable_attr.table.clone()
+    {
         // Collect column names, types, and JSON keys for all non-skipped fields
         let mut column_names: Vec<String> = Vec::new();
         let mut column_types: Vec<&'static str> = Vec::new();
+        let mut value_kinds: Vec<&'static str> = Vec::new();
         let mut json_keys: Vec<String> = Vec::new();
+        let mut managed_columns = Vec::new();
+        let mut expires_at_column: Option<String> = None;
+        let mut effective_at_column: Option<String> = None;
+        let mut access_policy_column: Option<String> = None;
+        let mut seal_column: Option<String> = None;
+        let mut deleted_at_column: Option<String> = None;
+        let mut nullable_columns: Vec<String> = Vec::new();
+        let mut column_asserts = Vec::new();
+        let mut constraints = Vec::new();
 
-        for field in fields.iter() {
-            if has_column_skip(field) {
-                continue;
-            }
-
+        for field in ordered_columns(name, fields.iter()) {
             let field_name = field.ident.as_ref().unwrap();
             let col_name = get_column_name(field).unwrap_or_else(|| field_name.to_string());
-            let col_type = rust_type_to_sql_type(&field.ty);
-            let json_key = to_camel_case(&field_name.to_string());
+            let managed = [
+                ("said", quote! { Said }),
+                ("prefix", quote! { Prefix }),
+                ("previous", quote! { Previous }),
+                ("version", quote! { Version }),
+                ("created_at", quote! { CreatedAt }),
+            ]
+            .into_iter()
+            .find(|(attr, _)| has_attr(field, attr));
+            if let Some((_, variant)) = managed {
+                managed_columns.push(quote! {
+                    (#col_name, verifiable_storage::ManagedField::#variant)
+                });
+            }
+            if has_column_flag(field, "expires_at") {
+                expires_at_column = Some(col_name.clone());
+            }
+            if has_column_flag(field, "effective_at") {
+                effective_at_column = Some(col_name.clone());
+            }
+            if has_column_flag(field, "access_policy") {
+                access_policy_column = Some(col_name.clone());
+            }
+            if has_column_flag(field, "seal") {
+                seal_column = Some(col_name.clone());
+            }
+            if has_attr(field, "deleted_at") {
+                deleted_at_column = Some(col_name.clone());
+            }
+            if is_option_type(&field.ty) {
+                nullable_columns.push(col_name.clone());
+            }
+            if let Some(assert) = get_column_str(field, "assert") {
+                column_asserts.push(quote! { (#col_name, #assert) });
+            }
+            for constraint in column_constraints(field) {
+                constraints.push(quote! { (#col_name, #constraint) });
+            }
+            let col_type = if has_column_flag(field, "cbor") {
+                "cbor"
+            } else {
+                rust_type_to_sql_type(&field.ty)
+            };
+            let json_key = column_json_key(field, rename_all.as_deref());
 
             column_names.push(col_name);
             column_types.push(col_type);
+            value_kinds.push(rust_type_to_value_kind(&field.ty));
             json_keys.push(json_key);
         }
 
+        // The raw column holds the serialized JSON, so it can't coexist with CBOR digests
+        if let Some(raw) = &storable_attr.raw_column {
+            assert!(
+                !cbor,
+                "raw_column stores the JSON a SAID digests, but this type digests CBOR"
+            );
+            assert!(
+                !column_names.contains(raw),
+                "raw_column `{}` is already a stored column",
+                raw
+            );
+        }
+        let raw_column = storable_attr.raw_column.as_ref().map(|column| {
+            quote! {
+                fn raw_column() -> Option<&'static str> {
+                    Some(#column)
+                }
+            }
+        });
+
         // Generate INSERT SQL: INSERT INTO table (col1, col2, ...) VALUES ($1, $2, ...)
-        let columns_str = column_names.join(", ");
-        let placeholders: Vec<String> = (1..=column_names.len())
-            .map(|i| format!("${}", i))
+        let inserted: Vec<&String> = column_names
+            .iter()
+            .chain(&storable_attr.raw_column)
             .collect();
+        let columns_str = inserted
+            .iter()
+            .map(|column| column.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let placeholders: Vec<String> = (1..=inserted.len()).map(|i| format!("${}", i)).collect();
         let placeholders_str = placeholders.join(", ");
         let insert_sql = format!(
             "INSERT INTO {} ({}) VALUES ({})",
             table_name, columns_str, placeholders_str
         );
 
+        // Select and insert every stored column, so sqlx checks each one
+        let checked_columns = storable_attr.checked.then(|| {
+            let select = format!("SELECT {} FROM {} WHERE false", columns_str, table_name);
+            let insert = format!(
+                "INSERT INTO {} ({}) SELECT {} FROM {} WHERE false",
+                table_name, columns_str, columns_str, table_name
+            );
+            quote! {
+                impl #name {
+                    /// Never called; `sqlx::query!` checks these statements
+                    /// against the database when the crate is compiled.
+                    #[doc(hidden)]
+                    #[allow(dead_code)]
+                    pub async fn checked_columns(pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+                        sqlx::query!(#select).fetch_optional(pool).await?;
+                        sqlx::query!(#insert).execute(pool).await?;
+                        Ok(())
+                    }
+                }
+            }
+        });
+
         // Generate SELECT SQLs
         let select_all_sql = format!("SELECT * FROM {}", table_name);
         let select_by_id_sql = format!("SELECT * FROM {} WHERE said = $1", table_name);
@@ -410,6 +1171,52 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
         let column_literals: Vec<_> = column_names.iter().map(|s| s.as_str()).collect();
         let column_type_literals: Vec<_> = column_types.to_vec();
         let json_key_literals: Vec<_> = json_keys.iter().map(|s| s.as_str()).collect();
+        let partitioning = partitioning_impl(storable_attr, &column_names);
+        let expires_at = expires_at_column.map(|column| {
+            quote! {
+                fn expires_at_column() -> Option<&'static str> {
+                    Some(#column)
+                }
+            }
+        });
+        let effective_at = effective_at_column.map(|column| {
+            quote! {
+                fn effective_at_column() -> Option<&'static str> {
+                    Some(#column)
+                }
+            }
+        });
+        let access_policy = access_policy_column.map(|column| {
+            quote! {
+                fn access_policy_column() -> Option<&'static str> {
+                    Some(#column)
+                }
+            }
+        });
+
+        let generated_validate = (constrained && !custom_validate).then(|| {
+            quote! {
+                impl verifiable_storage::Validate for #name {
+                    fn validate(&self) -> Result<(), verifiable_storage::StorageError> {
+                        verifiable_storage::check_column_constraints(self)
+                    }
+                }
+            }
+        });
+        let seal = seal_column.map(|column| {
+            quote! {
+                fn seal_column() -> Option<&'static str> {
+                    Some(#column)
+                }
+            }
+        });
+        let deleted_at = deleted_at_column.map(|column| {
+            quote! {
+                fn deleted_at_column() -> Option<&'static str> {
+                    Some(#column)
+                }
+            }
+        });
 
         quote! {
             impl verifiable_storage::Storable for #name {
@@ -429,6 +1236,10 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
                     &[#(#json_key_literals),*]
                 }
 
+                fn value_kinds() -> &'static [&'static str] {
+                    &[#(#value_kinds),*]
+                }
+
                 fn insert_sql() -> &'static str {
                     #insert_sql
                 }
@@ -452,13 +1263,49 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
                 fn is_versioned() -> bool {
                     #is_versioned
                 }
+
+                fn managed_columns() -> &'static [(&'static str, verifiable_storage::ManagedField)] {
+                    &[#(#managed_columns),*]
+                }
+
+                #partitioning
+
+                #expires_at
+
+                #effective_at
+
+                #access_policy
+
+                #seal
+
+                #deleted_at
+
+                #raw_column
+
+                fn nullable_columns() -> &'static [&'static str] {
+                    &[#(#nullable_columns),*]
+                }
+
+                fn column_asserts() -> &'static [(&'static str, &'static str)] {
+                    &[#(#column_asserts),*]
+                }
+
+                fn column_constraints() -> &'static [(&'static str, verifiable_storage::ColumnConstraint)] {
+                    &[#(#constraints),*]
+                }
             }
+
+            #generated_validate
+
+            #checked_columns
         }
     } else {
         quote! {}
     };
 
-    let expanded = quote! {
+    // new() and create() read the clock for created_at, and without std there
+    // is none; create_with_timestamp() takes the time instead
+    let constructors = quote! {
         impl #name {
             /// Create a new instance with storage-managed fields initialized to defaults.
             ///
@@ -486,19 +1333,33 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
                 Ok(item)
             }
         }
+    };
+    let constructors = if created_at_field.is_some() {
+        quote! { verifiable_storage::__if_std! { #constructors } }
+    } else {
+        constructors
+    };
+
+    let expanded = quote! {
+        #constructors
+
+        #create_with_timestamp
 
         impl verifiable_storage::SelfAddressed for #name {
             fn derive_said(&mut self) -> Result<(), verifiable_storage::StorageError> {
-                self.#said_field_name = "#".repeat(44);
-                self.#said_field_name = verifiable_storage::compute_said(self)?;
+                #validate_call
+                self.#said_field_name = #placeholder?;
+                self.#said_field_name = #compute_said?;
                 Ok(())
             }
 
             fn verify_said(&self) -> Result<(), verifiable_storage::StorageError> {
+                // Not via derive_said(), so stored records aren't held to newer #[validate] rules
                 let mut copy = self.clone();
-                copy.derive_said()?;
+                copy.#said_field_name = #placeholder?;
+                copy.#said_field_name = #verify_compute_said?;
                 if copy.#said_field_name != self.#said_field_name {
-                    return Err(verifiable_storage::StorageError::InvalidSaid(format!(
+                    return Err(verifiable_storage::StorageError::InvalidSaid(verifiable_storage::__private::format!(
                         "SAID verification failed: expected {}, got {}",
                         self.#said_field_name, copy.#said_field_name
                     )));
@@ -506,7 +1367,7 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
                 Ok(())
             }
 
-            fn get_said(&self) -> String {
+            fn get_said(&self) -> verifiable_storage::__private::String {
                 self.#said_field_name.clone()
             }
         }
@@ -518,3 +1379,129 @@ pub fn derive_self_addressed(input: TokenStream) -> TokenStream {
 
     TokenStream::from(expanded)
 }
+
+/// Derive `Storable` for a read model: a projection of rows from a SQL view or
+/// reporting query, with no SAID or versioning of its own.
+///
+/// `#[storable(table = "...")]` names the view, and fields map to its columns
+/// as they do for `SelfAddressed` (`#[column(name = ...)]`, `#[column(skip)]`,
+/// JSON keys following serde's renames). A field marked `#[said]` is the projection's `id()`;
+/// without one, `id()` is empty. Projections are fetched, never written.
+///
+/// ```text
+/// #[derive(Clone, Serialize, Deserialize, Projection)]
+/// #[storable(table = "active_domains_view")]
+/// #[serde(rename_all = "camelCase")]
+/// struct ActiveDomain {
+///     #[said]
+///     pub said: String,
+///     pub name: String,
+///     pub record_count: i64,
+/// }
+/// ```
+#[proc_macro_derive(Projection, attributes(said, storable, column))]
+pub fn derive_projection(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => panic!("Projection only supports structs with named fields"),
+        },
+        _ => panic!("Projection only supports structs"),
+    };
+    let storable_attr = match parse_storable_attr(&input) {
+        Ok(attr) => attr,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let view = storable_attr
+        .as_ref()
+        .and_then(|attr| attr.table.clone())
+        .expect("Projection needs #[storable(table = \"...\")] naming its view");
+
+    let mut column_names: Vec<String> = Vec::new();
+    let mut column_types: Vec<&'static str> = Vec::new();
+    let mut json_keys: Vec<String> = Vec::new();
+    let mut nullable_columns: Vec<String> = Vec::new();
+    let rename_all = json_naming(storable_attr.as_ref(), &input);
+    for field in ordered_columns(name, fields.iter()) {
+        let field_name = field.ident.as_ref().unwrap();
+        let col_name = get_column_name(field).unwrap_or_else(|| field_name.to_string());
+        if is_option_type(&field.ty) {
+            nullable_columns.push(col_name.clone());
+        }
+        column_types.push(if has_column_flag(field, "cbor") {
+            "cbor"
+        } else {
+            rust_type_to_sql_type(&field.ty)
+        });
+        json_keys.push(column_json_key(field, rename_all.as_deref()));
+        column_names.push(col_name);
+    }
+
+    let id = match fields.iter().find(|f| has_attr(f, "said")) {
+        Some(field) => {
+            let field_name = field.ident.as_ref().unwrap();
+            quote! { &self.#field_name }
+        }
+        None => quote! { "" },
+    };
+    let column_count = column_names.len();
+    let placeholders: Vec<String> = (1..=column_count).map(|i| format!("${}", i)).collect();
+    let insert_sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        view,
+        column_names.join(", "),
+        placeholders.join(", ")
+    );
+    let select_all_sql = format!("SELECT * FROM {}", view);
+    let select_by_id_sql = format!("SELECT * FROM {} WHERE said = $1", view);
+
+    TokenStream::from(quote! {
+        impl verifiable_storage::Storable for #name {
+            fn table_name() -> &'static str {
+                #view
+            }
+
+            fn columns() -> &'static [&'static str] {
+                &[#(#column_names),*]
+            }
+
+            fn column_types() -> &'static [&'static str] {
+                &[#(#column_types),*]
+            }
+
+            fn json_keys() -> &'static [&'static str] {
+                &[#(#json_keys),*]
+            }
+
+            fn insert_sql() -> &'static str {
+                #insert_sql
+            }
+
+            fn select_all_sql() -> &'static str {
+                #select_all_sql
+            }
+
+            fn select_by_id_sql() -> &'static str {
+                #select_by_id_sql
+            }
+
+            fn column_count() -> usize {
+                #column_count
+            }
+
+            fn id(&self) -> &str {
+                #id
+            }
+
+            fn is_versioned() -> bool {
+                false
+            }
+
+            fn nullable_columns() -> &'static [&'static str] {
+                &[#(#nullable_columns),*]
+            }
+        }
+    })
+}
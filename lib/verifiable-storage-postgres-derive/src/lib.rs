@@ -8,10 +8,28 @@ use syn::{DeriveInput, Lit, parse_macro_input};
 ///
 /// ## Individual Repository Mode
 /// Applied to a repository struct with `item_type` and `table`, generates:
-/// - `new(pool: PgPool) -> Self` constructor
+/// - `new(pool: impl Into<PgPool>) -> Self` constructor, also taking an `Arc<PgPool>`
+/// - `maintain_partitions()` creating missing partitions for partitioned item types
 /// - `VersionedRepository<T>` or `UnversionedRepository<T>` implementation
+/// - `insert_or_ignore(&item)` and `upsert(&item)`, which don't fail when a row with the
+///   item's SAID exists: the first leaves it, the second rewrites any differing columns.
+///   Both return whether a row was written. Multi-type repositories suffix them with the
+///   item type (`insert_or_ignore_{item}`, `upsert_{item}`)
+/// - `version_count(prefix)` and `latest_version_number(prefix)` for versioned items,
+///   counting a prefix's versions and finding its highest with one aggregate query
+///   (`{item}_version_count` and `latest_{item}_version_number` on multi-type repositories)
+/// - `list_prefixes(query)` and `list_prefix_versions(query)` for versioned items, listing
+///   the distinct prefixes matching a query's filters without fetching their rows
+///   (`list_{item}_prefixes` and `list_{item}_prefix_versions` on multi-type repositories)
+/// - `delete_soft(said)`, marking a row's `#[deleted_at]` column. Reads by SAID or
+///   prefix still return marked rows so histories stay verifiable; `Query` reads leave
+///   them out unless built with `include_deleted()`
 ///
-/// The struct must have a `pool: PgPool` field.
+/// The struct must have a `pool: PgPool` field. With `sharded` it also has a
+/// `shard: Option<String>` field, and `for_shard(pool, shard)` is generated, which
+/// targets `{table}_{shard}` for every generated query. `PgPool` clones share one connection pool, so
+/// `#[derive(Clone)]` on the struct gives cheap handles for shared state; wrap it
+/// in an `Arc` (see `SharedVersionedRepository`) to erase its type.
 /// The item type must implement `Storable + Serialize + DeserializeOwned`.
 ///
 /// Attributes:
@@ -20,6 +38,73 @@ use syn::{DeriveInput, Lit, parse_macro_input};
 /// - `id_field`: The field name containing the SAID (default: "said")
 /// - `prefix_field`: The field name containing the prefix (default: "prefix", only for versioned)
 /// - `versioned`: Whether to generate VersionedRepository (default: true)
+/// - `read_only`: Generate write methods that return `StorageError::ReadOnly` (default: false)
+/// - `signatures`: A `RecordSignature` type; implements `SignedRepository<T, S>`, storing
+///   signatures in that type's own table, and adds `gc_orphans()`/`gc_orphans_with(collector)`
+///   removing signatures whose item is gone (`gc_{item}_orphans` on multi-type repositories)
+/// - `unique_versions`: Fail `get_history` when a prefix has two rows with the same version,
+///   instead of returning them ordered by `created_at` then SAID (default: false)
+/// - `sharded`: Generate `for_shard()`; the struct needs a `shard: Option<String>` field
+///   (default: false)
+/// - `generate_tests`: Emit `#[sqlx::test]` round-trip tests for the repository. Bare
+///   `generate_tests` builds items with `Default::default()`; `generate_tests = "path::to::fn"`
+///   calls the given function instead. Ignored for read-only repositories.
+///
+/// - `checked`: Verify the standard read statements against the database at compile
+///   time with `sqlx::query!`, so a renamed or retyped table, SAID, prefix or version
+///   column fails the build. The item type must be `#[storable(checked)]`, whose
+///   `SELECT` and `INSERT` of every stored column are checked too. Needs `sqlx` (with
+///   `macros`) as a dependency and `DATABASE_URL` (or `SQLX_OFFLINE` query data) at
+///   build time. Shard tables are not checked.
+///
+/// - `current`: Maintain a `{table}_current` projection holding each prefix's latest
+///   version, replaced in the same transaction as every insert (versioned only). Also
+///   generates a `{Repository}Current` companion repository over the projection,
+///   returned by `current()`.
+///
+/// - `schema`: Qualify the table with a Postgres schema (`schema = "adns"` reads and
+///   writes `adns.{table}`). `ensure_schema()` creates it, and a combined repository's
+///   `initialize()` calls that before running migrations.
+///
+/// - `latest_trigger`: Read `get_latest` through a `{table}_latest` pointer table kept by
+///   a database trigger, so writes from outside the repository keep it current too.
+///   `ensure_latest_trigger()` installs the trigger and fills the pointers (versioned only).
+///
+/// - `index_by`: Keep a `{table}_{field}_index` table mapping the field's value to its
+///   prefix, updated in the same transaction as every insert, and generate
+///   `find_prefix_by_{field}(value)`. Repeat for several fields (versioned only).
+///
+/// - `unique_index_by`: As `index_by`, but a key may only be indexed to one prefix; an
+///   insert claiming a key another prefix holds fails with `StorageError::Duplicate`
+///   naming the index table and the holding prefix. Unique violations of real constraints
+///   (a duplicate SAID, say) are reported as `Duplicate` too, with the conflicting value.
+///
+/// - `paginate_by`: Generate `list_page(after, limit)`, returning a `Page` of rows in
+///   ascending order of the listed columns (`paginate_by = "created_at,said"`) and a
+///   `Cursor` to pass back for the next page. End the list with a unique column so the
+///   order is total. Every stored row is listed, so versioned tables list each version.
+///
+/// ## View Repositories
+/// `view` in place of `table` declares a read model over a SQL view, typed to a projection
+/// struct (usually `#[derive(Projection)]`). It generates `new()`, `VIEW_NAME`, `query()`,
+/// `fetch()`, `fetch_optional()`, `count()` and `all()`, plus `list_page` with `paginate_by`,
+/// and no repository trait impl, so there are no write methods to guard. `read_only = true`
+/// may be given to say so; `schema` qualifies the view as it does a table. The view itself
+/// comes from a migration, and a view repository can be a field of a combined repository.
+///
+/// ```text
+/// #[derive(Stored)]
+/// #[stored(item_type = ActiveDomain, view = "active_domains_view", read_only = true)]
+/// pub struct ActiveDomainRepository {
+///     pool: PgPool,
+/// }
+///
+/// let large = repo.fetch(repo.query().gte("record_count", 100)).await?;
+/// ```
+///
+/// Generated tests run under `cargo test` with `DATABASE_URL` set, and need `sqlx` (with
+/// the `macros` and `migrate` features) and `tokio` as dev-dependencies. As with any
+/// `#[sqlx::test]`, each test gets a fresh database with `./migrations` applied.
 ///
 /// Example:
 /// ```text
@@ -30,12 +115,55 @@ use syn::{DeriveInput, Lit, parse_macro_input};
 /// }
 /// ```
 ///
+/// With signatures, which live in the signature type's own table (created by a migration,
+/// ideally indexed on the signed SAID column) and are fingerprinted with the item's:
+/// ```text
+/// #[derive(Stored)]
+/// #[stored(item_type = KeyEvent, table = "key_events", signatures = EventSignature)]
+/// pub struct KeyEventRepository {
+///     pool: PgPool,
+/// }
+///
+/// repo.create_with_signatures(event, signatures).await?;
+/// let signed = repo.get_signed_history(&prefix).await?;
+/// ```
+///
+/// ## Multi-type Repositories
+/// Repeat `#[stored(...)]` to serve several related item types from one struct and
+/// pool. Each attribute generates its repository trait impl, a `{TYPE}_TABLE_NAME`
+/// constant and a `{type}_table_name()` accessor (index lookups become
+/// `find_{type}_prefix_by_{field}`); `new()`, `for_shard()` and `maintain_partitions()`
+/// are generated once. `current` and `generate_tests` need a single-type repository.
+/// Callers name the item type where a method would be ambiguous:
+///
+/// ```text
+/// #[derive(Stored)]
+/// #[stored(item_type = KeyEvent, table = "key_events")]
+/// #[stored(item_type = Receipt, table = "receipts", versioned = false)]
+/// pub struct KelRepository {
+///     pool: PgPool,
+/// }
+///
+/// let receipt = UnversionedRepository::<Receipt>::get_by_said(&repo, said).await?;
+/// ```
+///
 /// ## Combined Repository Mode
 /// Applied to a repository struct with `migrations`, generates:
-/// - `RepositoryConnection` implementation
+/// - `RepositoryConnection` implementation (`initialize()` creates sub-repository schemas,
+///   runs migrations, records each table's layout fingerprint, then creates partitions)
+/// - `schema_fingerprints()`; `connect()` fails with `StorageError::SchemaMismatch` when a
+///   recorded fingerprint disagrees with this build's types. After changing a layout,
+///   migrate through `new(pool).initialize()`, which records the new fingerprints.
+/// - `maintain()` to create upcoming partitions for partitioned tables
 ///
 /// The struct must have sub-repository fields with `PgPool` as their first constructor arg.
 ///
+/// A sub-repository on another backend is marked `#[stored(backend = "name")]`; its type
+/// must implement `RepositoryConnection`, and `connect()` gives it the config named
+/// `name` from a `ConnectionConfig::Backends` map (the Postgres pool uses `"postgres"`).
+/// `initialize()` initializes it after the Postgres migrations, and `new()` takes it,
+/// already connected, after the pool.
+///
 /// Attributes:
 /// - `migrations`: Path to migrations directory (required for this mode)
 ///
@@ -48,82 +176,569 @@ use syn::{DeriveInput, Lit, parse_macro_input};
 ///     pub records: RecordRepository,
 /// }
 /// ```
+///
+/// Mixing backends:
+/// ```text
+/// #[derive(Stored)]
+/// #[stored(migrations = "services/adns/migrations")]
+/// pub struct AdnsRepository {
+///     pub domains: DomainRepository,
+///     #[stored(backend = "surreal")]
+///     pub lookups: LookupIndex,
+/// }
+///
+/// let repo = AdnsRepository::connect(ConnectionConfig::backends([
+///     ("postgres", "postgres://localhost/adns"),
+///     ("surreal", "ws://localhost:8000"),
+/// ]))
+/// .await?;
+/// ```
 #[proc_macro_derive(Stored, attributes(stored))]
 pub fn derive_stored(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let repo_name = &input.ident;
 
-    // Parse #[stored(...)] attribute
-    let stored_attr = input
+    // Parse every #[stored(...)] attribute; several describe several item types
+    let stored: Vec<StoredArgs> = input
         .attrs
         .iter()
-        .find(|attr| attr.path().is_ident("stored"))
-        .expect("No #[stored(...)] attribute found");
-
-    // Parse the attribute arguments
-    let mut item_type: Option<syn::Type> = None;
-    let mut table_name: Option<String> = None;
-    let mut id_field = "said".to_string();
-    let mut prefix_field = "prefix".to_string();
-    let mut versioned = true;
-    let mut migrations: Option<String> = None;
-
-    stored_attr
-        .parse_nested_meta(|meta| {
-            if meta.path.is_ident("item_type") {
+        .filter(|attr| attr.path().is_ident("stored"))
+        .map(parse_stored_attr)
+        .collect();
+    let first = stored.first().expect("No #[stored(...)] attribute found");
+
+    // Check which mode we're in
+    if first.migrations.is_some() {
+        // Combined repository mode - generate RepositoryConnection
+        return generate_combined_repository(repo_name, &input, first.migrations.as_deref());
+    }
+
+    // Individual repository mode - generate VersionedRepository/UnversionedRepository
+    let sharded = stored.iter().any(|args| args.sharded);
+    if sharded {
+        let has_shard_field = match &input.data {
+            syn::Data::Struct(data) => data
+                .fields
+                .iter()
+                .any(|f| f.ident.as_ref().is_some_and(|i| i == "shard")),
+            _ => false,
+        };
+        assert!(
+            has_shard_field,
+            "#[stored(sharded)] needs a `shard: Option<String>` field"
+        );
+    }
+    if stored.len() > 1 {
+        assert!(
+            stored.iter().all(|args| args.view.is_none()),
+            "#[stored(view = ...)] needs a single-type repository"
+        );
+        return generate_multi_repository(repo_name, &stored, sharded);
+    }
+    if first.view.is_some() {
+        return generate_view_repository(repo_name, first, sharded);
+    }
+
+    let item_type = first
+        .item_type
+        .as_ref()
+        .expect("Missing item_type in #[stored(...)]");
+    let table_name = &first
+        .qualified_table()
+        .expect("Missing table in #[stored(...)]");
+    let versioned = first.versioned;
+    let current = first.current && versioned;
+    let index_by: &[String] = if versioned { &first.index_by } else { &[] };
+    let flags = RepositoryFlags {
+        versioned,
+        sharded,
+        read_only: first.read_only,
+        unique_versions: first.unique_versions,
+        current,
+        index_by,
+        unique_index_by: &first.unique_index_by,
+        latest_trigger: first.latest_trigger && versioned,
+        multi: false,
+    };
+    let mut expanded = generate_individual_repository(
+        repo_name,
+        item_type,
+        table_name,
+        &first.id_field,
+        &first.prefix_field,
+        flags,
+    );
+    if current {
+        expanded.extend(generate_current_repository(
+            repo_name,
+            &input.vis,
+            item_type,
+            &first.prefix_field,
+        ));
+    }
+    expanded.extend(generate_ensure_schema(repo_name, first.schema.iter()));
+    expanded.extend(generate_schema_fingerprints(
+        repo_name,
+        [(item_type, table_name.as_str())],
+        first.signatures.iter(),
+    ));
+    if !index_by.is_empty() {
+        expanded.extend(generate_index_lookups(
+            repo_name, item_type, index_by, false,
+        ));
+    }
+    if !first.paginate_by.is_empty() {
+        expanded.extend(generate_list_page(
+            repo_name,
+            item_type,
+            &first.paginate_by,
+            false,
+        ));
+    }
+    if let Some(signature_type) = &first.signatures {
+        expanded.extend(generate_signed_repository(
+            repo_name,
+            item_type,
+            signature_type,
+            flags,
+        ));
+    }
+    if first.checked {
+        expanded.extend(generate_checked_queries(
+            item_type,
+            table_name,
+            &first.id_field,
+            &first.prefix_field,
+            versioned,
+        ));
+    }
+    if let Some(sample) = &first.generate_tests
+        && !first.read_only
+    {
+        expanded.extend(generate_repository_tests(
+            repo_name, item_type, sample, versioned,
+        ));
+    }
+    expanded
+}
+
+/// Parsed #[stored(...)] attribute
+struct StoredArgs {
+    item_type: Option<syn::Type>,
+    table_name: Option<String>,
+    id_field: String,
+    prefix_field: String,
+    versioned: bool,
+    read_only: bool,
+    unique_versions: bool,
+    sharded: bool,
+    generate_tests: Option<proc_macro2::TokenStream>,
+    checked: bool,
+    current: bool,
+    latest_trigger: bool,
+    index_by: Vec<String>,
+    unique_index_by: Vec<String>,
+    paginate_by: Vec<String>,
+    schema: Option<String>,
+    view: Option<String>,
+    migrations: Option<String>,
+    signatures: Option<syn::Type>,
+}
+
+impl StoredArgs {
+    /// The table, qualified with its schema if one is set.
+    fn qualified_table(&self) -> Option<String> {
+        let table = self.table_name.as_ref().or(self.view.as_ref())?;
+        Some(match &self.schema {
+            Some(schema) => format!("{}.{}", schema, table),
+            None => table.clone(),
+        })
+    }
+}
+
+fn parse_stored_attr(attr: &syn::Attribute) -> StoredArgs {
+    let mut args = StoredArgs {
+        item_type: None,
+        table_name: None,
+        id_field: "said".to_string(),
+        prefix_field: "prefix".to_string(),
+        versioned: true,
+        read_only: false,
+        unique_versions: false,
+        sharded: false,
+        generate_tests: None,
+        checked: false,
+        current: false,
+        latest_trigger: false,
+        index_by: Vec::new(),
+        unique_index_by: Vec::new(),
+        paginate_by: Vec::new(),
+        schema: None,
+        view: None,
+        migrations: None,
+        signatures: None,
+    };
+
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("item_type") {
+            meta.input.parse::<syn::Token![=]>()?;
+            args.item_type = Some(meta.input.parse()?);
+        } else if meta.path.is_ident("table") {
+            meta.input.parse::<syn::Token![=]>()?;
+            let lit: Lit = meta.input.parse()?;
+            if let Lit::Str(s) = lit {
+                args.table_name = Some(s.value());
+            }
+        } else if meta.path.is_ident("id_field") {
+            meta.input.parse::<syn::Token![=]>()?;
+            let lit: Lit = meta.input.parse()?;
+            if let Lit::Str(s) = lit {
+                args.id_field = s.value();
+            }
+        } else if meta.path.is_ident("prefix_field") {
+            meta.input.parse::<syn::Token![=]>()?;
+            let lit: Lit = meta.input.parse()?;
+            if let Lit::Str(s) = lit {
+                args.prefix_field = s.value();
+            }
+        } else if meta.path.is_ident("versioned") {
+            meta.input.parse::<syn::Token![=]>()?;
+            let lit: Lit = meta.input.parse()?;
+            if let Lit::Bool(b) = lit {
+                args.versioned = b.value();
+            }
+        } else if meta.path.is_ident("read_only") {
+            meta.input.parse::<syn::Token![=]>()?;
+            let lit: Lit = meta.input.parse()?;
+            if let Lit::Bool(b) = lit {
+                args.read_only = b.value();
+            }
+        } else if meta.path.is_ident("generate_tests") {
+            args.generate_tests = Some(if meta.input.peek(syn::Token![=]) {
                 meta.input.parse::<syn::Token![=]>()?;
-                item_type = Some(meta.input.parse()?);
-            } else if meta.path.is_ident("table") {
+                let lit: syn::LitStr = meta.input.parse()?;
+                let sample: syn::Path = lit.parse()?;
+                quote! { #sample() }
+            } else {
+                quote! { Default::default() }
+            });
+        } else if meta.path.is_ident("unique_versions") {
+            args.unique_versions = if meta.input.peek(syn::Token![=]) {
                 meta.input.parse::<syn::Token![=]>()?;
-                let lit: Lit = meta.input.parse()?;
-                if let Lit::Str(s) = lit {
-                    table_name = Some(s.value());
-                }
-            } else if meta.path.is_ident("id_field") {
+                meta.input.parse::<syn::LitBool>()?.value()
+            } else {
+                true
+            };
+        } else if meta.path.is_ident("sharded") {
+            args.sharded = if meta.input.peek(syn::Token![=]) {
                 meta.input.parse::<syn::Token![=]>()?;
-                let lit: Lit = meta.input.parse()?;
-                if let Lit::Str(s) = lit {
-                    id_field = s.value();
-                }
-            } else if meta.path.is_ident("prefix_field") {
+                meta.input.parse::<syn::LitBool>()?.value()
+            } else {
+                true
+            };
+        } else if meta.path.is_ident("checked") {
+            args.checked = if meta.input.peek(syn::Token![=]) {
                 meta.input.parse::<syn::Token![=]>()?;
-                let lit: Lit = meta.input.parse()?;
-                if let Lit::Str(s) = lit {
-                    prefix_field = s.value();
-                }
-            } else if meta.path.is_ident("versioned") {
+                meta.input.parse::<syn::LitBool>()?.value()
+            } else {
+                true
+            };
+        } else if meta.path.is_ident("current") {
+            args.current = if meta.input.peek(syn::Token![=]) {
                 meta.input.parse::<syn::Token![=]>()?;
-                let lit: Lit = meta.input.parse()?;
-                if let Lit::Bool(b) = lit {
-                    versioned = b.value();
-                }
-            } else if meta.path.is_ident("migrations") {
+                meta.input.parse::<syn::LitBool>()?.value()
+            } else {
+                true
+            };
+        } else if meta.path.is_ident("latest_trigger") {
+            args.latest_trigger = if meta.input.peek(syn::Token![=]) {
                 meta.input.parse::<syn::Token![=]>()?;
-                let lit: Lit = meta.input.parse()?;
-                if let Lit::Str(s) = lit {
-                    migrations = Some(s.value());
-                }
+                meta.input.parse::<syn::LitBool>()?.value()
+            } else {
+                true
+            };
+        } else if meta.path.is_ident("index_by") {
+            meta.input.parse::<syn::Token![=]>()?;
+            args.index_by
+                .push(meta.input.parse::<syn::LitStr>()?.value());
+        } else if meta.path.is_ident("unique_index_by") {
+            meta.input.parse::<syn::Token![=]>()?;
+            let field = meta.input.parse::<syn::LitStr>()?.value();
+            args.index_by.push(field.clone());
+            args.unique_index_by.push(field);
+        } else if meta.path.is_ident("paginate_by") {
+            meta.input.parse::<syn::Token![=]>()?;
+            args.paginate_by = meta
+                .input
+                .parse::<syn::LitStr>()?
+                .value()
+                .split(',')
+                .map(|column| column.trim().to_string())
+                .collect();
+            assert!(
+                args.paginate_by.iter().all(|column| !column.is_empty()),
+                "#[stored(paginate_by = ...)] must list columns separated by commas"
+            );
+        } else if meta.path.is_ident("schema") {
+            meta.input.parse::<syn::Token![=]>()?;
+            let schema = meta.input.parse::<syn::LitStr>()?.value();
+            assert!(
+                !schema.is_empty()
+                    && schema
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'),
+                "#[stored(schema = ...)] must be a lowercase identifier"
+            );
+            args.schema = Some(schema);
+        } else if meta.path.is_ident("view") {
+            meta.input.parse::<syn::Token![=]>()?;
+            args.view = Some(meta.input.parse::<syn::LitStr>()?.value());
+        } else if meta.path.is_ident("signatures") {
+            meta.input.parse::<syn::Token![=]>()?;
+            // `signatures = true` was the old form, before the signature type was named
+            if meta.input.peek(syn::LitBool) {
+                return Err(meta.error(
+                    "#[stored(signatures = ...)] now names the signature type, e.g. `signatures = EventSignature`",
+                ));
             }
-            Ok(())
+            args.signatures = Some(meta.input.parse()?);
+        } else if meta.path.is_ident("migrations") {
+            meta.input.parse::<syn::Token![=]>()?;
+            let lit: Lit = meta.input.parse()?;
+            if let Lit::Str(s) = lit {
+                args.migrations = Some(s.value());
+            }
+        }
+        Ok(())
+    })
+    .expect("Failed to parse #[stored(...)] attribute");
+
+    args
+}
+
+/// Attribute macro implementing a repository method from a SQL statement.
+///
+/// Apply to a body-less method in an `impl` block of a `Stored` repository (any
+/// type with a `pool: PgPool` field). Parameters are referenced as `$name`, where
+/// `name` is a method argument; they are rewritten to positional placeholders and
+/// bound through `Value::from`, so each argument's type needs a `From` impl into
+/// `Value` (`&str`, `String`, `i64`, `u64`, `bool`, `Vec<String>`, `StorageDatetime`, ...).
+///
+/// The return type picks how the statement runs:
+/// - `Result<Vec<T>, StorageError>`: all rows, deserialized via `T`'s `Storable` metadata
+/// - `Result<Option<T>, StorageError>`: the first row, if any
+/// - `Result<u64, StorageError>`: executes the statement and returns rows affected
+/// - `Result<T, StorageError>`: the first row, or `StorageError::NotFound`
+///
+/// Example:
+/// ```text
+/// impl DomainRepository {
+///     #[query("SELECT * FROM adns_domains WHERE owner = $owner ORDER BY version DESC")]
+///     pub async fn by_owner(&self, owner: &str) -> Result<Vec<Domain>, StorageError>;
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn query(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let sql = parse_macro_input!(attr as syn::LitStr);
+    let method = parse_macro_input!(item as QueryMethod);
+    match generate_query_method(&sql, &method) {
+        Ok(tokens) => TokenStream::from(tokens),
+        Err(e) => TokenStream::from(e.to_compile_error()),
+    }
+}
+
+/// A method signature without a body, as written under `#[query(...)]`.
+struct QueryMethod {
+    attrs: Vec<syn::Attribute>,
+    vis: syn::Visibility,
+    sig: syn::Signature,
+}
+
+impl syn::parse::Parse for QueryMethod {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(syn::Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let sig = input.parse()?;
+        input.parse::<syn::Token![;]>()?;
+        Ok(Self { attrs, vis, sig })
+    }
+}
+
+/// How a `#[query]` method runs its statement, from its return type.
+enum QueryShape {
+    Many,
+    Optional,
+    Execute,
+    One,
+}
+
+fn generate_query_method(
+    sql: &syn::LitStr,
+    method: &QueryMethod,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let QueryMethod { attrs, vis, sig } = method;
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            sig,
+            "#[query] methods must be async",
+        ));
+    }
+
+    let arguments: Vec<&syn::Ident> = sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            syn::FnArg::Typed(typed) => match typed.pat.as_ref() {
+                syn::Pat::Ident(pat) => Some(&pat.ident),
+                _ => None,
+            },
+            syn::FnArg::Receiver(_) => None,
         })
-        .expect("Failed to parse #[stored(...)] attribute");
+        .collect();
 
-    // Check which mode we're in
-    if migrations.is_some() {
-        // Combined repository mode - generate RepositoryConnection
-        generate_combined_repository(repo_name, &input, migrations.as_deref())
-    } else {
-        // Individual repository mode - generate VersionedRepository/UnversionedRepository
-        let item_type = item_type.expect("Missing item_type in #[stored(...)]");
-        let table_name = table_name.expect("Missing table in #[stored(...)]");
-        generate_individual_repository(
-            repo_name,
-            &item_type,
-            &table_name,
-            &id_field,
-            &prefix_field,
-            versioned,
-        )
+    let (positional_sql, names) = positional_placeholders(&sql.value());
+    let mut params = Vec::with_capacity(names.len());
+    for name in &names {
+        let Some(argument) = arguments.iter().find(|a| **a == name) else {
+            return Err(syn::Error::new_spanned(
+                sql,
+                format!("${} does not name an argument of {}", name, sig.ident),
+            ));
+        };
+        params.push(quote! {
+            (#name.to_string(), verifiable_storage::Value::from(#argument))
+        });
+    }
+
+    let body = match query_shape(&sig.output) {
+        QueryShape::Many => quote! { self.pool.fetch_sql(#positional_sql, params).await },
+        QueryShape::Optional => quote! {
+            Ok(self.pool.fetch_sql(#positional_sql, params).await?.into_iter().next())
+        },
+        QueryShape::Execute => quote! { self.pool.execute_sql(#positional_sql, params).await },
+        QueryShape::One => {
+            let name = sig.ident.to_string();
+            quote! {
+                self.pool
+                    .fetch_sql(#positional_sql, params)
+                    .await?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| {
+                        verifiable_storage::StorageError::NotFound(format!("{} returned no rows", #name))
+                    })
+            }
+        }
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            let params: verifiable_storage::SqlParams = vec![#(#params),*];
+            #body
+        }
+    })
+}
+
+/// Rewrite `$name` placeholders to `$1`, `$2`, ... in order of first use,
+/// returning the SQL and the names in placeholder order. Quoted text,
+/// dollar-quoted bodies (`$$...$$`, `$tag$...$tag$`) and comments are left alone.
+fn positional_placeholders(sql: &str) -> (String, Vec<String>) {
+    let mut rewritten = String::with_capacity(sql.len());
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = sql;
+
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        match c {
+            '\'' | '"' => {
+                let end = after.find(c).map_or(rest.len(), |end| end + 2);
+                rewritten.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+            '-' if after.starts_with('-') => {
+                let end = rest.find('\n').unwrap_or(rest.len());
+                rewritten.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+            '/' if after.starts_with('*') => {
+                let end = rest[2..].find("*/").map_or(rest.len(), |end| end + 4);
+                rewritten.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+            '$' => {
+                let name_len = after
+                    .find(|n: char| !(n.is_alphanumeric() || n == '_'))
+                    .unwrap_or(after.len());
+                let name = &after[..name_len];
+                if after[name_len..].starts_with('$')
+                    && !name.starts_with(|n: char| n.is_ascii_digit())
+                {
+                    // A dollar-quote opener: copy through its matching closer
+                    let tag = &rest[..name_len + 2];
+                    let end = rest[tag.len()..]
+                        .find(tag)
+                        .map_or(rest.len(), |end| end + 2 * tag.len());
+                    rewritten.push_str(&rest[..end]);
+                    rest = &rest[end..];
+                } else if name.starts_with(|n: char| n.is_alphabetic() || n == '_') {
+                    let index = match names.iter().position(|existing| existing == name) {
+                        Some(index) => index,
+                        None => {
+                            names.push(name.to_string());
+                            names.len() - 1
+                        }
+                    };
+                    rewritten.push_str(&format!("${}", index + 1));
+                    rest = &after[name_len..];
+                } else {
+                    rewritten.push(c);
+                    rest = after;
+                }
+            }
+            _ => {
+                rewritten.push(c);
+                rest = after;
+            }
+        }
+    }
+    (rewritten, names)
+}
+
+fn query_shape(output: &syn::ReturnType) -> QueryShape {
+    let syn::ReturnType::Type(_, ty) = output else {
+        return QueryShape::Execute;
+    };
+    let Some(ok) = generic_arguments(ty).and_then(|args| args.into_iter().next()) else {
+        return QueryShape::One;
+    };
+    match last_segment(ok).map(|s| s.ident.to_string()).as_deref() {
+        Some("Vec") => QueryShape::Many,
+        Some("Option") => QueryShape::Optional,
+        Some("u64") => QueryShape::Execute,
+        _ => QueryShape::One,
+    }
+}
+
+fn last_segment(ty: &syn::Type) -> Option<&syn::PathSegment> {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last(),
+        _ => None,
+    }
+}
+
+fn generic_arguments(ty: &syn::Type) -> Option<Vec<&syn::Type>> {
+    match &last_segment(ty)?.arguments {
+        syn::PathArguments::AngleBracketed(args) => Some(
+            args.args
+                .iter()
+                .filter_map(|arg| match arg {
+                    syn::GenericArgument::Type(ty) => Some(ty),
+                    _ => None,
+                })
+                .collect(),
+        ),
+        _ => None,
     }
 }
 
@@ -141,8 +756,11 @@ fn generate_combined_repository(
         _ => panic!("Stored can only be derived for structs"),
     };
 
-    // Build field construction code
-    let field_constructions: Vec<_> = fields
+    // Fields marked #[stored(backend = "...")] connect themselves; the rest share the pool
+    let (backend_fields, postgres_fields): (Vec<_>, Vec<_>) =
+        fields.iter().partition(|f| field_backend(f).is_some());
+
+    let field_constructions: Vec<_> = postgres_fields
         .iter()
         .map(|f| {
             let name = f.ident.as_ref().expect("Field must have a name");
@@ -153,25 +771,42 @@ fn generate_combined_repository(
         })
         .collect();
 
-    let field_names: Vec<_> = fields
+    let field_names: Vec<_> = postgres_fields
+        .iter()
+        .map(|f| f.ident.as_ref().expect("Field must have a name"))
+        .collect();
+    let field_types: Vec<_> = postgres_fields.iter().map(|f| &f.ty).collect();
+
+    let backend_names: Vec<_> = backend_fields
         .iter()
         .map(|f| f.ident.as_ref().expect("Field must have a name"))
         .collect();
+    let backend_types: Vec<_> = backend_fields.iter().map(|f| &f.ty).collect();
+    let backend_keys: Vec<_> = backend_fields
+        .iter()
+        .filter_map(|f| field_backend(f))
+        .collect();
 
     // Get the first field name for pool access
     let first_field = field_names
         .first()
-        .expect("Combined repository must have at least one field");
+        .expect("Combined repository must have at least one Postgres sub-repository");
 
     // Generate the migrations path as a string literal for migrate!
     let migrations_path = migrations.unwrap_or("./migrations");
 
     let expanded = quote! {
         impl #repo_name {
-            /// Create a new combined repository with the given pool.
-            pub fn new(pool: verifiable_storage_postgres::PgPool) -> Self {
+            /// Create a new combined repository with the given pool and any
+            /// sub-repositories on other backends, already connected.
+            pub fn new(
+                pool: impl Into<verifiable_storage_postgres::PgPool>,
+                #( #backend_names: #backend_types, )*
+            ) -> Self {
+                let pool = pool.into();
                 Self {
-                    #(#field_constructions),*
+                    #(#field_constructions,)*
+                    #(#backend_names,)*
                 }
             }
 
@@ -180,6 +815,19 @@ fn generate_combined_repository(
                 // Access pool from first field
                 &self.#first_field.pool
             }
+
+            /// Create any missing table partitions (run periodically for range partitions).
+            pub async fn maintain(&self) -> Result<(), verifiable_storage::StorageError> {
+                #( self.#field_names.maintain_partitions().await?; )*
+                Ok(())
+            }
+
+            /// Layout fingerprints of every Postgres sub-repository's tables.
+            pub fn schema_fingerprints() -> Vec<verifiable_storage::SchemaFingerprint> {
+                let mut fingerprints = Vec::new();
+                #( fingerprints.extend(<#field_types>::schema_fingerprints()); )*
+                fingerprints
+            }
         }
 
         #[async_trait::async_trait]
@@ -188,20 +836,35 @@ fn generate_combined_repository(
                 config: impl Into<verifiable_storage::ConnectionConfig> + Send,
             ) -> Result<Self, verifiable_storage::StorageError> {
                 let config = config.into();
-                let url = match config {
-                    verifiable_storage::ConnectionConfig::Url(url) => url,
+                let url = match config.for_backend("postgres")? {
+                    verifiable_storage::ConnectionConfig::Url(url) => url.clone(),
+                    verifiable_storage::ConnectionConfig::Backends(_) => {
+                        return Err(verifiable_storage::StorageError::StorageError(
+                            "Expected a URL for the postgres backend".to_string(),
+                        ));
+                    }
                 };
 
                 let pool = verifiable_storage_postgres::PgPool::connect(&url)
                     .await
                     .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
 
+                verifiable_storage_postgres::verify_fingerprints(&pool, &Self::schema_fingerprints())
+                    .await?;
+                #(
+                    let #backend_names = <#backend_types as verifiable_storage::RepositoryConnection>::connect(
+                        config.for_backend(#backend_keys)?.clone(),
+                    )
+                    .await?;
+                )*
                 Ok(Self {
-                    #(#field_constructions),*
+                    #(#field_constructions,)*
+                    #(#backend_names,)*
                 })
             }
 
             async fn initialize(&self) -> Result<(), verifiable_storage::StorageError> {
+                #( self.#field_names.ensure_schema().await?; )*
                 let migrations_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(#migrations_path);
                 verifiable_storage_postgres::Migrator::new(migrations_path)
                     .await
@@ -209,7 +872,10 @@ fn generate_combined_repository(
                     .run(self.pool().inner())
                     .await
                     .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
-                Ok(())
+                verifiable_storage_postgres::record_fingerprints(self.pool(), &Self::schema_fingerprints())
+                    .await?;
+                #( verifiable_storage::RepositoryConnection::initialize(&self.#backend_names).await?; )*
+                self.maintain().await
             }
         }
     };
@@ -217,91 +883,522 @@ fn generate_combined_repository(
     TokenStream::from(expanded)
 }
 
+/// Switches controlling the generated individual repository
+#[derive(Clone, Copy)]
+struct RepositoryFlags<'a> {
+    /// Generate VersionedRepository rather than UnversionedRepository
+    versioned: bool,
+    /// `#[stored(sharded)]`: the struct has a `shard` field; generate `for_shard()`
+    sharded: bool,
+    /// Generate write methods that return `StorageError::ReadOnly`
+    read_only: bool,
+    /// Fail `get_history` when a prefix has two rows with the same version
+    unique_versions: bool,
+    /// Replace the prefix's row in `{table}_current` on every insert
+    current: bool,
+    /// Fields whose `{table}_{field}_index` is updated on every insert
+    index_by: &'a [String],
+    /// The `index_by` fields whose keys may only point at one prefix
+    unique_index_by: &'a [String],
+    /// Read `get_latest` through the trigger-maintained `{table}_latest`
+    latest_trigger: bool,
+    /// One of several item types on the struct, so the constructor is
+    /// generated separately and table accessors are named per type
+    multi: bool,
+}
+
+/// Statements writing `item`'s companion rows (its current-version row and
+/// index entries) with `tx` into `table`'s companion tables, once `item`
+/// itself is inserted.
+fn companion_writes(
+    current: bool,
+    index_by: &[String],
+    unique_index_by: &[String],
+    item: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let current_step = current.then(|| {
+        quote! {
+            verifiable_storage_postgres::replace_current(&mut tx, &table, #item).await?;
+        }
+    });
+    let index_fns: Vec<syn::Ident> = index_by
+        .iter()
+        .map(|field| {
+            if unique_index_by.contains(field) {
+                quote::format_ident!("claim_unique_index")
+            } else {
+                quote::format_ident!("update_index")
+            }
+        })
+        .collect();
+    quote! {
+        #current_step
+        #(
+            verifiable_storage_postgres::#index_fns(&mut tx, &table, #index_by, #item).await?;
+        )*
+    }
+}
+
 fn generate_individual_repository(
     repo_name: &syn::Ident,
     item_type: &syn::Type,
     table_name: &str,
     id_field: &str,
     prefix_field: &str,
-    versioned: bool,
+    flags: RepositoryFlags,
 ) -> TokenStream {
-    // Generate the new() constructor and table_name method
-    let new_impl = quote! {
-        impl #repo_name {
-            /// The table name for this repository.
-            pub const TABLE_NAME: &'static str = #table_name;
+    let RepositoryFlags {
+        versioned,
+        sharded,
+        read_only,
+        unique_versions,
+        current,
+        index_by,
+        unique_index_by,
+        latest_trigger,
+        multi,
+    } = flags;
+    let table_fn = table_accessor(item_type, multi);
+    let companion =
+        |item: proc_macro2::TokenStream| companion_writes(current, index_by, unique_index_by, item);
 
-            /// Create a new repository with the given pool.
-            pub fn new(pool: verifiable_storage_postgres::PgPool) -> Self {
-                Self { pool }
+    // Read-only repositories reject writes before touching the database
+    let insert_body = if read_only {
+        quote! {
+            let _ = item;
+            Err(verifiable_storage::StorageError::ReadOnly(format!(
+                "{} is a read-only repository",
+                stringify!(#repo_name)
+            )))
+        }
+    } else if current && index_by.is_empty() {
+        quote! {
+            verifiable_storage_postgres::insert_with_current(&self.pool, &self.#table_fn(), &item).await?;
+            Ok(item)
+        }
+    } else if current || !index_by.is_empty() {
+        // Companion tables are written in the same transaction as the item
+        let companion = companion(quote! { &item });
+        quote! {
+            use verifiable_storage_postgres::{QueryExecutor, TransactionExecutor};
+            let table = self.#table_fn();
+            let mut tx = self.pool.begin_transaction().await?;
+            let written = async {
+                tx.insert_with_table(&item, &table).await?;
+                #companion
+                Ok::<(), verifiable_storage::StorageError>(())
+            }
+            .await;
+            match written {
+                Ok(()) => {
+                    tx.commit().await?;
+                    Ok(item)
+                }
+                Err(e) => {
+                    tx.rollback().await?;
+                    Err(e)
+                }
+            }
+        }
+    } else {
+        quote! {
+            self.pool.insert_with_table(&item, &self.#table_fn()).await?;
+            Ok(item)
+        }
+    };
+    let delete_soft_body = if read_only {
+        quote! {
+            let _ = said;
+            Err(verifiable_storage::StorageError::ReadOnly(format!(
+                "{} is a read-only repository",
+                stringify!(#repo_name)
+            )))
+        }
+    } else {
+        quote! {
+            use verifiable_storage_postgres::QueryExecutor;
+            let delete = verifiable_storage_postgres::Delete::<#item_type>::for_table(self.#table_fn())
+                .eq(#id_field, said);
+            Ok(self.pool.soft_delete(delete).await? > 0)
+        }
+    };
+    // Imports write every version, and its companion rows, in one transaction
+    let import_body = if read_only {
+        quote! {
+            let _ = items;
+            Err(verifiable_storage::StorageError::ReadOnly(format!(
+                "{} is a read-only repository",
+                stringify!(#repo_name)
+            )))
+        }
+    } else {
+        let companion = companion(quote! { item });
+        quote! {
+            use verifiable_storage_postgres::{QueryExecutor, TransactionExecutor};
+            verifiable_storage::verify_history(&items)?;
+            let table = self.#table_fn();
+            let mut tx = self.pool.begin_transaction().await?;
+            let written = async {
+                for item in &items {
+                    tx.insert_with_table(item, &table).await?;
+                    #companion
+                }
+                Ok::<(), verifiable_storage::StorageError>(())
+            }
+            .await;
+            match written {
+                Ok(()) => {
+                    tx.commit().await?;
+                    Ok(items)
+                }
+                Err(e) => {
+                    tx.rollback().await?;
+                    Err(e)
+                }
             }
         }
     };
+    let maintain_body = if read_only {
+        quote! { Ok(()) }
+    } else {
+        quote! {
+            verifiable_storage_postgres::maintain_partitions::<#item_type>(&self.pool, &self.#table_fn()).await
+        }
+    };
 
-    let expanded = if versioned {
+    // Generate the new() constructor and table_name method, plus for_shard()
+    // with #[stored(sharded)]
+    let shard_impl = if sharded {
         quote! {
-            #new_impl
+            /// Create a new repository with the given pool (a `PgPool`, `Arc<PgPool>` or sqlx pool).
+            pub fn new(pool: impl Into<verifiable_storage_postgres::PgPool>) -> Self {
+                Self { pool: pool.into(), shard: None }
+            }
 
-            #[async_trait::async_trait]
-            impl verifiable_storage::VersionedRepository<#item_type> for #repo_name {
-                async fn create(
-                    &self,
-                    mut item: #item_type,
-                ) -> Result<#item_type, verifiable_storage::StorageError> {
-                    use verifiable_storage::Versioned;
-                    item.derive_prefix()?;
-                    self.insert(item).await
-                }
+            /// Create a repository over the `{TABLE_NAME}_{shard}` table.
+            pub fn for_shard(
+                pool: impl Into<verifiable_storage_postgres::PgPool>,
+                shard: impl Into<String>,
+            ) -> Result<Self, verifiable_storage::StorageError> {
+                let pool = pool.into();
+                let shard = shard.into();
+                verifiable_storage_postgres::shard_table_name(Self::TABLE_NAME, &shard)?;
+                Ok(Self { pool, shard: Some(shard) })
+            }
 
-                async fn update(
-                    &self,
-                    mut item: #item_type,
-                ) -> Result<#item_type, verifiable_storage::StorageError> {
-                    use verifiable_storage::Versioned;
-                    item.increment()?;
-                    self.insert(item).await
+            /// The table this repository reads and writes, including any shard suffix.
+            pub fn table_name(&self) -> std::borrow::Cow<'static, str> {
+                match &self.shard {
+                    Some(shard) => std::borrow::Cow::Owned(format!("{}_{}", Self::TABLE_NAME, shard)),
+                    None => std::borrow::Cow::Borrowed(Self::TABLE_NAME),
                 }
+            }
+        }
+    } else {
+        quote! {
+            /// Create a new repository with the given pool (a `PgPool`, `Arc<PgPool>` or sqlx pool).
+            pub fn new(pool: impl Into<verifiable_storage_postgres::PgPool>) -> Self {
+                Self { pool: pool.into() }
+            }
 
-                async fn insert(
-                    &self,
-                    item: #item_type,
-                ) -> Result<#item_type, verifiable_storage::StorageError> {
-                    verifiable_storage_postgres::bind_insert_with_table(&self.pool, &item, Self::TABLE_NAME).await?;
-                    Ok(item)
-                }
+            /// The table this repository reads and writes.
+            pub fn table_name(&self) -> std::borrow::Cow<'static, str> {
+                std::borrow::Cow::Borrowed(Self::TABLE_NAME)
+            }
+        }
+    };
 
-                async fn get_by_said(
-                    &self,
-                    said: &str,
-                ) -> Result<Option<#item_type>, verifiable_storage::StorageError> {
-                    use verifiable_storage_postgres::QueryExecutor;
-                    let query = verifiable_storage_postgres::Query::<#item_type>::for_table(Self::TABLE_NAME)
-                        .eq(#id_field, said)
-                        .limit(1);
-                    self.pool.fetch_optional(query).await
+    let new_impl = if multi {
+        let table_const = quote::format_ident!("{}", table_fn.to_string().to_uppercase());
+        let doc = format!("The table holding `{}` items.", quote!(#item_type));
+        let table_body = if sharded {
+            quote! {
+                match &self.shard {
+                    Some(shard) => std::borrow::Cow::Owned(format!("{}_{}", Self::#table_const, shard)),
+                    None => std::borrow::Cow::Borrowed(Self::#table_const),
                 }
+            }
+        } else {
+            quote! { std::borrow::Cow::Borrowed(Self::#table_const) }
+        };
+        quote! {
+            impl #repo_name {
+                #[doc = #doc]
+                pub const #table_const: &'static str = #table_name;
 
-                async fn get_latest(
+                /// The table this item type is read from and written to, including any shard suffix.
+                pub fn #table_fn(&self) -> std::borrow::Cow<'static, str> {
+                    #table_body
+                }
+            }
+        }
+    } else {
+        quote! {
+        impl #repo_name {
+            /// The table name for this repository.
+            pub const TABLE_NAME: &'static str = #table_name;
+
+            #shard_impl
+
+            /// Create any missing partitions of this table (no-op if unpartitioned or read-only).
+            pub async fn maintain_partitions(&self) -> Result<(), verifiable_storage::StorageError> {
+                #maintain_body
+            }
+        }
+        }
+    };
+
+    let get_latest_body = if latest_trigger {
+        quote! {
+            verifiable_storage_postgres::get_latest_tracked(&self.pool, &self.#table_fn(), prefix).await
+        }
+    } else {
+        quote! {
+            use verifiable_storage_postgres::QueryExecutor;
+            let query = verifiable_storage_postgres::Query::<#item_type>::for_table(self.#table_fn())
+                .include_deleted()
+                .eq(#prefix_field, prefix)
+                .order_by("version", verifiable_storage_postgres::Order::Desc)
+                .limit(1);
+            self.pool.fetch_optional(query).await
+        }
+    };
+    let latest_trigger_impl = latest_trigger.then(|| {
+        let method = if multi {
+            quote::format_ident!("ensure_{}_latest_trigger", item_type_snake(item_type))
+        } else {
+            quote::format_ident!("ensure_latest_trigger")
+        };
+        quote! {
+            impl #repo_name {
+                /// Install the trigger maintaining this table's latest-version pointers,
+                /// and fill them in from existing rows.
+                pub async fn #method(&self) -> Result<(), verifiable_storage::StorageError> {
+                    verifiable_storage_postgres::ensure_latest_trigger::<#item_type>(&self.pool, &self.#table_fn()).await
+                }
+            }
+        }
+    });
+
+    let (version_count_fn, latest_version_fn, list_prefixes_fn, list_prefix_versions_fn) = if multi
+    {
+        let snake = item_type_snake(item_type);
+        (
+            quote::format_ident!("{}_version_count", snake),
+            quote::format_ident!("latest_{}_version_number", snake),
+            quote::format_ident!("list_{}_prefixes", snake),
+            quote::format_ident!("list_{}_prefix_versions", snake),
+        )
+    } else {
+        (
+            quote::format_ident!("version_count"),
+            quote::format_ident!("latest_version_number"),
+            quote::format_ident!("list_prefixes"),
+            quote::format_ident!("list_prefix_versions"),
+        )
+    };
+    // Dashboards and retention need these without loading whole histories
+    let version_counts_impl = quote! {
+        impl #repo_name {
+            /// How many versions are stored for `prefix`.
+            pub async fn #version_count_fn(
+                &self,
+                prefix: &str,
+            ) -> Result<u64, verifiable_storage::StorageError> {
+                use verifiable_storage_postgres::QueryExecutor;
+                let query = verifiable_storage_postgres::Query::<#item_type>::for_table(self.#table_fn())
+                    .include_deleted()
+                    .eq(#prefix_field, prefix);
+                self.pool.count(query).await
+            }
+
+            /// The highest version stored for `prefix`, or `None` if it has none.
+            pub async fn #latest_version_fn(
+                &self,
+                prefix: &str,
+            ) -> Result<Option<u64>, verifiable_storage::StorageError> {
+                use verifiable_storage_postgres::QueryExecutor;
+                let query = verifiable_storage_postgres::Query::<#item_type>::for_table(self.#table_fn())
+                    .include_deleted()
+                    .eq(#prefix_field, prefix);
+                self.pool.max(query, "version").await
+            }
+
+            /// The distinct prefixes matching `query`'s filters, ascending and paged
+            /// by its limit and offset. The query runs against this repository's table.
+            pub async fn #list_prefixes_fn(
+                &self,
+                mut query: verifiable_storage_postgres::Query<#item_type>,
+            ) -> Result<Vec<String>, verifiable_storage::StorageError> {
+                use verifiable_storage_postgres::QueryExecutor;
+                query.table = self.#table_fn().into_owned();
+                self.pool.list_prefixes(query).await
+            }
+
+            /// As the prefix listing, with each prefix's highest matching version.
+            pub async fn #list_prefix_versions_fn(
+                &self,
+                mut query: verifiable_storage_postgres::Query<#item_type>,
+            ) -> Result<Vec<verifiable_storage::PrefixVersion>, verifiable_storage::StorageError> {
+                use verifiable_storage_postgres::QueryExecutor;
+                query.table = self.#table_fn().into_owned();
+                self.pool.list_prefix_versions(query).await
+            }
+        }
+    };
+
+    let (insert_or_ignore_fn, upsert_fn) = if multi {
+        let snake = item_type_snake(item_type);
+        (
+            quote::format_ident!("insert_or_ignore_{}", snake),
+            quote::format_ident!("upsert_{}", snake),
+        )
+    } else {
+        (
+            quote::format_ident!("insert_or_ignore"),
+            quote::format_ident!("upsert"),
+        )
+    };
+    // Companion rows are only touched when the item's row was written
+    let conditional_write_body = |method: proc_macro2::TokenStream| {
+        if read_only {
+            quote! {
+                let _ = item;
+                Err(verifiable_storage::StorageError::ReadOnly(format!(
+                    "{} is a read-only repository",
+                    stringify!(#repo_name)
+                )))
+            }
+        } else if current || !index_by.is_empty() {
+            let companion = companion(quote! { item });
+            quote! {
+                use verifiable_storage_postgres::{QueryExecutor, TransactionExecutor};
+                let table = self.#table_fn();
+                let mut tx = self.pool.begin_transaction().await?;
+                let written = async {
+                    let written = tx.#method(item, &table).await? > 0;
+                    if written {
+                        #companion
+                    }
+                    Ok::<bool, verifiable_storage::StorageError>(written)
+                }
+                .await;
+                match written {
+                    Ok(written) => {
+                        tx.commit().await?;
+                        Ok(written)
+                    }
+                    Err(e) => {
+                        tx.rollback().await?;
+                        Err(e)
+                    }
+                }
+            }
+        } else {
+            quote! {
+                Ok(self.pool.#method(item, &self.#table_fn()).await? > 0)
+            }
+        }
+    };
+    let insert_or_ignore_body = conditional_write_body(quote! { insert_or_ignore_with_table });
+    let upsert_body = conditional_write_body(quote! { upsert_with_table });
+    let write_impl = quote! {
+        impl #repo_name {
+            /// Insert `item` unless a row with its SAID exists, returning whether
+            /// it was written.
+            pub async fn #insert_or_ignore_fn(
+                &self,
+                item: &#item_type,
+            ) -> Result<bool, verifiable_storage::StorageError> {
+                #insert_or_ignore_body
+            }
+
+            /// Insert `item`, rewriting the row stored under its SAID where any
+            /// column differs. Returns whether a row was written.
+            pub async fn #upsert_fn(
+                &self,
+                item: &#item_type,
+            ) -> Result<bool, verifiable_storage::StorageError> {
+                #upsert_body
+            }
+        }
+    };
+
+    let expanded = if versioned {
+        quote! {
+            #new_impl
+
+            #latest_trigger_impl
+
+            #version_counts_impl
+
+            #write_impl
+
+            #[async_trait::async_trait]
+            impl verifiable_storage::VersionedRepository<#item_type> for #repo_name {
+                async fn create(
                     &self,
-                    prefix: &str,
+                    mut item: #item_type,
+                ) -> Result<#item_type, verifiable_storage::StorageError> {
+                    use verifiable_storage::Versioned;
+                    item.derive_prefix()?;
+                    self.insert(item).await
+                }
+
+                async fn update(
+                    &self,
+                    mut item: #item_type,
+                ) -> Result<#item_type, verifiable_storage::StorageError> {
+                    use verifiable_storage::Versioned;
+                    item.increment()?;
+                    self.insert(item).await
+                }
+
+                async fn insert(
+                    &self,
+                    item: #item_type,
+                ) -> Result<#item_type, verifiable_storage::StorageError> {
+                    #insert_body
+                }
+
+                async fn import_history(
+                    &self,
+                    items: Vec<#item_type>,
+                ) -> Result<Vec<#item_type>, verifiable_storage::StorageError> {
+                    #import_body
+                }
+
+                async fn get_by_said(
+                    &self,
+                    said: &str,
                 ) -> Result<Option<#item_type>, verifiable_storage::StorageError> {
                     use verifiable_storage_postgres::QueryExecutor;
-                    let query = verifiable_storage_postgres::Query::<#item_type>::for_table(Self::TABLE_NAME)
-                        .eq(#prefix_field, prefix)
-                        .order_by("version", verifiable_storage_postgres::Order::Desc)
+                    let query = verifiable_storage_postgres::Query::<#item_type>::for_table(self.#table_fn())
+                        .include_deleted()
+                        .eq(#id_field, said)
                         .limit(1);
                     self.pool.fetch_optional(query).await
                 }
 
+                async fn get_latest(
+                    &self,
+                    prefix: &str,
+                ) -> Result<Option<#item_type>, verifiable_storage::StorageError> {
+                    #get_latest_body
+                }
+
                 async fn get_history(
                     &self,
                     prefix: &str,
                 ) -> Result<Vec<#item_type>, verifiable_storage::StorageError> {
                     use verifiable_storage_postgres::QueryExecutor;
-                    let query = verifiable_storage_postgres::Query::<#item_type>::for_table(Self::TABLE_NAME)
+                    let query = verifiable_storage_postgres::Query::<#item_type>::for_table(self.#table_fn())
+                        .include_deleted()
                         .eq(#prefix_field, prefix)
                         .order_by("version", verifiable_storage_postgres::Order::Asc);
-                    self.pool.fetch(query).await
+                    let mut history = self.pool.fetch(query).await?;
+                    verifiable_storage::order_history(&mut history, #unique_versions)?;
+                    Ok(history)
                 }
 
                 async fn exists(
@@ -309,18 +1406,28 @@ fn generate_individual_repository(
                     prefix: &str,
                 ) -> Result<bool, verifiable_storage::StorageError> {
                     use verifiable_storage_postgres::QueryExecutor;
-                    let query = verifiable_storage_postgres::Query::<#item_type>::for_table(Self::TABLE_NAME)
+                    let query = verifiable_storage_postgres::Query::<#item_type>::for_table(self.#table_fn())
+                        .include_deleted()
                         .eq(#prefix_field, prefix)
                         .limit(1);
                     let result = self.pool.fetch_optional(query).await?;
                     Ok(result.is_some())
                 }
+
+                async fn delete_soft(
+                    &self,
+                    said: &str,
+                ) -> Result<bool, verifiable_storage::StorageError> {
+                    #delete_soft_body
+                }
             }
         }
     } else {
         quote! {
             #new_impl
 
+            #write_impl
+
             #[async_trait::async_trait]
             impl verifiable_storage::UnversionedRepository<#item_type> for #repo_name {
                 async fn create(
@@ -336,8 +1443,7 @@ fn generate_individual_repository(
                     &self,
                     item: #item_type,
                 ) -> Result<#item_type, verifiable_storage::StorageError> {
-                    verifiable_storage_postgres::bind_insert_with_table(&self.pool, &item, Self::TABLE_NAME).await?;
-                    Ok(item)
+                    #insert_body
                 }
 
                 async fn get_by_said(
@@ -345,14 +1451,842 @@ fn generate_individual_repository(
                     said: &str,
                 ) -> Result<Option<#item_type>, verifiable_storage::StorageError> {
                     use verifiable_storage_postgres::QueryExecutor;
-                    let query = verifiable_storage_postgres::Query::<#item_type>::for_table(Self::TABLE_NAME)
+                    let query = verifiable_storage_postgres::Query::<#item_type>::for_table(self.#table_fn())
+                        .include_deleted()
                         .eq(#id_field, said)
                         .limit(1);
                     self.pool.fetch_optional(query).await
                 }
+
+                async fn delete_soft(
+                    &self,
+                    said: &str,
+                ) -> Result<bool, verifiable_storage::StorageError> {
+                    #delete_soft_body
+                }
             }
         }
     };
 
     TokenStream::from(expanded)
 }
+
+/// Generate one repository struct serving several item types, each from its
+/// own `#[stored(...)]` attribute, over a shared pool.
+fn generate_multi_repository(
+    repo_name: &syn::Ident,
+    stored: &[StoredArgs],
+    sharded: bool,
+) -> TokenStream {
+    let mut expanded = TokenStream::new();
+    let mut maintained = Vec::new();
+    let mut shard_check = None;
+    let mut fingerprinted = Vec::new();
+
+    for args in stored {
+        let item_type = args
+            .item_type
+            .as_ref()
+            .expect("Missing item_type in #[stored(...)]");
+        let table_name = &args
+            .qualified_table()
+            .expect("Missing table in #[stored(...)]");
+        assert!(
+            args.migrations.is_none() && !args.current && args.generate_tests.is_none(),
+            "migrations, current and generate_tests are not supported on multi-type repositories"
+        );
+        let index_by: &[String] = if args.versioned { &args.index_by } else { &[] };
+        let flags = RepositoryFlags {
+            versioned: args.versioned,
+            sharded,
+            read_only: args.read_only,
+            unique_versions: args.unique_versions,
+            current: false,
+            index_by,
+            unique_index_by: &args.unique_index_by,
+            latest_trigger: args.latest_trigger && args.versioned,
+            multi: true,
+        };
+
+        expanded.extend(generate_individual_repository(
+            repo_name,
+            item_type,
+            table_name,
+            &args.id_field,
+            &args.prefix_field,
+            flags,
+        ));
+        if !index_by.is_empty() {
+            expanded.extend(generate_index_lookups(repo_name, item_type, index_by, true));
+        }
+        if !args.paginate_by.is_empty() {
+            expanded.extend(generate_list_page(
+                repo_name,
+                item_type,
+                &args.paginate_by,
+                true,
+            ));
+        }
+        if let Some(signature_type) = &args.signatures {
+            expanded.extend(generate_signed_repository(
+                repo_name,
+                item_type,
+                signature_type,
+                flags,
+            ));
+        }
+        if args.checked {
+            expanded.extend(generate_checked_queries(
+                item_type,
+                table_name,
+                &args.id_field,
+                &args.prefix_field,
+                args.versioned,
+            ));
+        }
+
+        shard_check.get_or_insert_with(|| table_name.clone());
+        fingerprinted.push((item_type, table_name.clone()));
+        if !args.read_only {
+            let table_fn = table_accessor(item_type, true);
+            maintained.push(quote! {
+                verifiable_storage_postgres::maintain_partitions::<#item_type>(&self.pool, &self.#table_fn()).await?;
+            });
+        }
+    }
+
+    let constructors = if sharded {
+        quote! {
+            /// Create a new repository with the given pool (a `PgPool`, `Arc<PgPool>` or sqlx pool).
+            pub fn new(pool: impl Into<verifiable_storage_postgres::PgPool>) -> Self {
+                Self { pool: pool.into(), shard: None }
+            }
+
+            /// Create a repository over each table's `{table}_{shard}` shard.
+            pub fn for_shard(
+                pool: impl Into<verifiable_storage_postgres::PgPool>,
+                shard: impl Into<String>,
+            ) -> Result<Self, verifiable_storage::StorageError> {
+                let pool = pool.into();
+                let shard = shard.into();
+                verifiable_storage_postgres::shard_table_name(#shard_check, &shard)?;
+                Ok(Self { pool, shard: Some(shard) })
+            }
+        }
+    } else {
+        quote! {
+            /// Create a new repository with the given pool (a `PgPool`, `Arc<PgPool>` or sqlx pool).
+            pub fn new(pool: impl Into<verifiable_storage_postgres::PgPool>) -> Self {
+                Self { pool: pool.into() }
+            }
+        }
+    };
+
+    let mut schemas: Vec<&String> = stored
+        .iter()
+        .filter_map(|args| args.schema.as_ref())
+        .collect();
+    schemas.sort();
+    schemas.dedup();
+    expanded.extend(generate_ensure_schema(repo_name, schemas));
+    expanded.extend(generate_schema_fingerprints(
+        repo_name,
+        fingerprinted
+            .iter()
+            .map(|(item_type, table)| (*item_type, table.as_str())),
+        stored.iter().filter_map(|args| args.signatures.as_ref()),
+    ));
+    expanded.extend(TokenStream::from(quote! {
+        impl #repo_name {
+            #constructors
+
+            /// Create any missing partitions of each writable item type's table.
+            pub async fn maintain_partitions(&self) -> Result<(), verifiable_storage::StorageError> {
+                #(#maintained)*
+                Ok(())
+            }
+        }
+    }));
+    expanded
+}
+
+/// `SignedRepository<T, S>` for `#[stored(signatures = S)]`, keeping the
+/// signatures in `S`'s own table, and `gc_orphans()` clearing out signatures
+/// whose items are gone.
+fn generate_signed_repository(
+    repo_name: &syn::Ident,
+    item_type: &syn::Type,
+    signature_type: &syn::Type,
+    flags: RepositoryFlags,
+) -> TokenStream {
+    let RepositoryFlags {
+        read_only,
+        current,
+        index_by,
+        unique_index_by,
+        multi,
+        ..
+    } = flags;
+    let table_fn = table_accessor(item_type, multi);
+    let gc_orphans = if multi {
+        quote::format_ident!("gc_{}_orphans", item_type_snake(item_type))
+    } else {
+        quote::format_ident!("gc_orphans")
+    };
+    let gc_orphans_with = quote::format_ident!("{}_with", gc_orphans);
+    let create_body = if read_only {
+        quote! {
+            let _ = (item, signatures);
+            Err(verifiable_storage::StorageError::ReadOnly(format!(
+                "{} is a read-only repository",
+                stringify!(#repo_name)
+            )))
+        }
+    } else {
+        // The item and its signatures commit together, so neither is ever
+        // stored without the other
+        let companion = companion_writes(current, index_by, unique_index_by, quote! { &item });
+        quote! {
+            use verifiable_storage_postgres::{QueryExecutor, TransactionExecutor};
+            verifiable_storage::check_signatures_cover(&item, &signatures)?;
+            let table = self.#table_fn();
+            let mut tx = self.pool.begin_transaction().await?;
+            let written = async {
+                for signature in &signatures {
+                    tx.insert_or_ignore_with_table(
+                        signature,
+                        <#signature_type as verifiable_storage::Storable>::table_name(),
+                    )
+                    .await?;
+                }
+                tx.insert_with_table(&item, &table).await?;
+                #companion
+                Ok::<(), verifiable_storage::StorageError>(())
+            }
+            .await;
+            match written {
+                Ok(()) => {
+                    tx.commit().await?;
+                    Ok(item)
+                }
+                Err(e) => {
+                    tx.rollback().await?;
+                    Err(e)
+                }
+            }
+        }
+    };
+    let gc_body = if read_only {
+        quote! {
+            let _ = collector;
+            Err(verifiable_storage::StorageError::ReadOnly(format!(
+                "{} is a read-only repository",
+                stringify!(#repo_name)
+            )))
+        }
+    } else {
+        quote! {
+            collector.item_table(self.#table_fn()).run(&self.pool).await
+        }
+    };
+
+    TokenStream::from(quote! {
+        impl #repo_name {
+            /// Remove signatures whose item no longer exists, in batches.
+            pub async fn #gc_orphans(
+                &self,
+            ) -> Result<verifiable_storage::OrphanReport, verifiable_storage::StorageError> {
+                self.#gc_orphans_with(verifiable_storage::OrphanCollector::new()).await
+            }
+
+            /// As `gc_orphans`, with `collector`'s batch size, grace period, archiving and
+            /// progress reporting. Items are looked up in this repository's table.
+            pub async fn #gc_orphans_with(
+                &self,
+                collector: verifiable_storage::OrphanCollector<#item_type, #signature_type>,
+            ) -> Result<verifiable_storage::OrphanReport, verifiable_storage::StorageError> {
+                #gc_body
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl verifiable_storage::SignedRepository<#item_type, #signature_type> for #repo_name {
+            async fn create_with_signatures(
+                &self,
+                item: #item_type,
+                signatures: Vec<#signature_type>,
+            ) -> Result<#item_type, verifiable_storage::StorageError> {
+                #create_body
+            }
+
+            async fn fetch_signatures(
+                &self,
+                saids: &[String],
+            ) -> Result<Vec<#signature_type>, verifiable_storage::StorageError> {
+                use verifiable_storage_postgres::QueryExecutor;
+                let column = <#signature_type as verifiable_storage::RecordSignature>::signed_said_column();
+                let query = verifiable_storage_postgres::Query::<#signature_type>::new()
+                    .r#in(column, saids.to_vec());
+                self.pool.fetch(query).await
+            }
+        }
+    })
+}
+
+/// The backend named by a combined repository field's `#[stored(backend = "...")]`.
+fn field_backend(field: &syn::Field) -> Option<String> {
+    let attr = field
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("stored"))?;
+    let mut backend = None;
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("backend") {
+            let lit: Lit = meta.value()?.parse()?;
+            if let Lit::Str(s) = lit {
+                backend = Some(s.value());
+            }
+            Ok(())
+        } else {
+            Err(meta.error("unsupported field #[stored(...)] key (expected `backend`)"))
+        }
+    })
+    .expect("Failed to parse field #[stored(...)] attribute");
+    backend
+}
+
+/// Generate `ensure_schema()`, creating the repository's Postgres schemas if missing.
+fn generate_ensure_schema<'a>(
+    repo_name: &syn::Ident,
+    schemas: impl IntoIterator<Item = &'a String>,
+) -> TokenStream {
+    let statements: Vec<String> = schemas
+        .into_iter()
+        .map(|schema| format!("CREATE SCHEMA IF NOT EXISTS {}", schema))
+        .collect();
+
+    TokenStream::from(quote! {
+        impl #repo_name {
+            /// Create the schemas this repository's tables live in (no-op without `schema`).
+            pub async fn ensure_schema(&self) -> Result<(), verifiable_storage::StorageError> {
+                #( self.pool.execute_sql(#statements, Vec::new()).await?; )*
+                Ok(())
+            }
+        }
+    })
+}
+
+/// Generate `schema_fingerprints()`, the layout fingerprint of each table served.
+fn generate_schema_fingerprints<'a>(
+    repo_name: &syn::Ident,
+    tables: impl IntoIterator<Item = (&'a syn::Type, &'a str)>,
+    signatures: impl IntoIterator<Item = &'a syn::Type>,
+) -> TokenStream {
+    let fingerprints = tables
+        .into_iter()
+        .map(|(item_type, table)| {
+            quote! { verifiable_storage::SchemaFingerprint::for_table::<#item_type>(#table) }
+        })
+        .chain(signatures.into_iter().map(|signature_type| {
+            quote! { <#signature_type as verifiable_storage::Storable>::schema_fingerprint() }
+        }));
+
+    TokenStream::from(quote! {
+        impl #repo_name {
+            /// Layout fingerprints of this repository's tables, checked when a
+            /// combined repository connects.
+            pub fn schema_fingerprints() -> Vec<verifiable_storage::SchemaFingerprint> {
+                vec![#(#fingerprints),*]
+            }
+        }
+    })
+}
+
+/// The method returning an item type's table: `table_name`, or
+/// `{type}_table_name` on multi-type repositories.
+fn table_accessor(item_type: &syn::Type, multi: bool) -> syn::Ident {
+    if multi {
+        quote::format_ident!("{}_table_name", item_type_snake(item_type))
+    } else {
+        quote::format_ident!("table_name")
+    }
+}
+
+/// The snake_case name of an item type's last path segment.
+fn item_type_snake(item_type: &syn::Type) -> String {
+    match item_type {
+        syn::Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|segment| to_snake_case(&segment.ident.to_string())),
+        _ => None,
+    }
+    .expect("item_type must be a type path")
+}
+
+/// Generate the `{Repository}Current` companion reading the `{table}_current` projection.
+fn generate_current_repository(
+    repo_name: &syn::Ident,
+    vis: &syn::Visibility,
+    item_type: &syn::Type,
+    prefix_field: &str,
+) -> TokenStream {
+    let current_name = syn::Ident::new(&format!("{}Current", repo_name), repo_name.span());
+
+    TokenStream::from(quote! {
+        /// Reads the latest version of each prefix from the current projection.
+        #[derive(Clone, Debug)]
+        #vis struct #current_name {
+            pool: verifiable_storage_postgres::PgPool,
+            table: String,
+        }
+
+        impl #current_name {
+            /// The projection of the repository's table (including any shard suffix).
+            pub fn table_name(&self) -> &str {
+                &self.table
+            }
+
+            /// A query over the projection, for filters beyond `get`.
+            pub fn query(&self) -> verifiable_storage_postgres::Query<#item_type> {
+                verifiable_storage_postgres::Query::<#item_type>::for_table(self.table.as_str())
+            }
+
+            /// Run a query built with `query()`.
+            pub async fn fetch(
+                &self,
+                query: verifiable_storage_postgres::Query<#item_type>,
+            ) -> Result<Vec<#item_type>, verifiable_storage::StorageError> {
+                use verifiable_storage_postgres::QueryExecutor;
+                self.pool.fetch(query).await
+            }
+
+            /// The latest version of `prefix`.
+            pub async fn get(
+                &self,
+                prefix: &str,
+            ) -> Result<Option<#item_type>, verifiable_storage::StorageError> {
+                use verifiable_storage_postgres::QueryExecutor;
+                self.pool.fetch_optional(self.query().eq(#prefix_field, prefix).limit(1)).await
+            }
+        }
+
+        impl #repo_name {
+            /// The companion repository over this table's current projection.
+            pub fn current(&self) -> #current_name {
+                #current_name {
+                    pool: self.pool.clone(),
+                    table: verifiable_storage_postgres::current_table_name(&self.table_name()),
+                }
+            }
+
+            /// Repopulate the current projection from full history.
+            pub async fn rebuild_current(&self) -> Result<u64, verifiable_storage::StorageError> {
+                verifiable_storage_postgres::rebuild_current::<#item_type>(&self.pool, &self.table_name()).await
+            }
+        }
+    })
+}
+
+/// Generate a fetch-only repository over a SQL view, typed to its projection.
+fn generate_view_repository(
+    repo_name: &syn::Ident,
+    args: &StoredArgs,
+    sharded: bool,
+) -> TokenStream {
+    assert!(
+        args.table_name.is_none(),
+        "#[stored(view = ...)] replaces `table`; give one or the other"
+    );
+    assert!(
+        !sharded
+            && !args.current
+            && !args.latest_trigger
+            && args.index_by.is_empty()
+            && args.generate_tests.is_none()
+            && !args.checked,
+        "#[stored(view = ...)] repositories only read; `current`, `latest_trigger`, \
+         `index_by`, `checked`, `generate_tests` and shards need a table"
+    );
+    let item_type = args
+        .item_type
+        .as_ref()
+        .expect("Missing item_type in #[stored(...)]");
+    let view_name = args.qualified_table().unwrap_or_default();
+
+    let mut expanded = TokenStream::from(quote! {
+        impl #repo_name {
+            /// The view this repository reads.
+            pub const VIEW_NAME: &'static str = #view_name;
+
+            /// Create a new repository with the given pool (a `PgPool`, `Arc<PgPool>` or sqlx pool).
+            pub fn new(pool: impl Into<verifiable_storage_postgres::PgPool>) -> Self {
+                Self { pool: pool.into() }
+            }
+
+            /// The view this repository reads.
+            pub fn table_name(&self) -> std::borrow::Cow<'static, str> {
+                std::borrow::Cow::Borrowed(Self::VIEW_NAME)
+            }
+
+            /// A query over the view.
+            pub fn query(&self) -> verifiable_storage_postgres::Query<#item_type> {
+                verifiable_storage_postgres::Query::<#item_type>::for_table(Self::VIEW_NAME)
+            }
+
+            /// Run a query built with `query()`.
+            pub async fn fetch(
+                &self,
+                query: verifiable_storage_postgres::Query<#item_type>,
+            ) -> Result<Vec<#item_type>, verifiable_storage::StorageError> {
+                use verifiable_storage_postgres::QueryExecutor;
+                self.pool.fetch(query).await
+            }
+
+            /// The first row of a query built with `query()`, if any.
+            pub async fn fetch_optional(
+                &self,
+                query: verifiable_storage_postgres::Query<#item_type>,
+            ) -> Result<Option<#item_type>, verifiable_storage::StorageError> {
+                use verifiable_storage_postgres::QueryExecutor;
+                self.pool.fetch_optional(query).await
+            }
+
+            /// The number of rows a query built with `query()` matches.
+            pub async fn count(
+                &self,
+                query: verifiable_storage_postgres::Query<#item_type>,
+            ) -> Result<u64, verifiable_storage::StorageError> {
+                use verifiable_storage_postgres::QueryExecutor;
+                self.pool.count(query).await
+            }
+
+            /// Every row of the view.
+            pub async fn all(&self) -> Result<Vec<#item_type>, verifiable_storage::StorageError> {
+                self.fetch(self.query()).await
+            }
+
+            /// No-op: views have no partitions. Lets the repository sit in a combined repository.
+            pub async fn maintain_partitions(&self) -> Result<(), verifiable_storage::StorageError> {
+                Ok(())
+            }
+        }
+    });
+    expanded.extend(generate_ensure_schema(repo_name, args.schema.iter()));
+    // A view's layout is its migration's, so there is no table layout to fingerprint
+    expanded.extend(generate_schema_fingerprints(
+        repo_name,
+        std::iter::empty(),
+        std::iter::empty(),
+    ));
+    if !args.paginate_by.is_empty() {
+        expanded.extend(generate_list_page(
+            repo_name,
+            item_type,
+            &args.paginate_by,
+            false,
+        ));
+    }
+    expanded
+}
+
+/// Generate a `find_prefix_by_{field}` lookup for each `index_by` field
+/// (`find_{type}_prefix_by_{field}` on multi-type repositories).
+fn generate_index_lookups(
+    repo_name: &syn::Ident,
+    item_type: &syn::Type,
+    index_by: &[String],
+    multi: bool,
+) -> TokenStream {
+    let table_fn = table_accessor(item_type, multi);
+    let lookups = index_by.iter().map(|field| {
+        let method = if multi {
+            quote::format_ident!("find_{}_prefix_by_{}", item_type_snake(item_type), field)
+        } else {
+            quote::format_ident!("find_prefix_by_{}", field)
+        };
+        let doc = format!("The prefix whose latest version has `{}` equal to `value`.", field);
+        quote! {
+            #[doc = #doc]
+            pub async fn #method(
+                &self,
+                value: &str,
+            ) -> Result<Option<String>, verifiable_storage::StorageError> {
+                verifiable_storage_postgres::find_indexed_prefix(&self.pool, &self.#table_fn(), #field, value).await
+            }
+        }
+    });
+
+    TokenStream::from(quote! {
+        impl #repo_name {
+            #(#lookups)*
+        }
+    })
+}
+
+/// Generate the keyset-paginated `list_page()` for `#[stored(paginate_by = "...")]`.
+fn generate_list_page(
+    repo_name: &syn::Ident,
+    item_type: &syn::Type,
+    columns: &[String],
+    multi: bool,
+) -> TokenStream {
+    let table_fn = table_accessor(item_type, multi);
+    let method = if multi {
+        quote::format_ident!("list_{}_page", item_type_snake(item_type))
+    } else {
+        quote::format_ident!("list_page")
+    };
+    let doc = format!(
+        "Up to `limit` rows after `after`, ordered by {}, with the cursor for the next page.",
+        columns.join(", ")
+    );
+
+    TokenStream::from(quote! {
+        impl #repo_name {
+            #[doc = #doc]
+            pub async fn #method(
+                &self,
+                after: Option<verifiable_storage::Cursor>,
+                limit: u64,
+            ) -> Result<verifiable_storage::Page<#item_type>, verifiable_storage::StorageError> {
+                verifiable_storage::fetch_page(
+                    &self.pool,
+                    verifiable_storage_postgres::Query::for_table(self.#table_fn()),
+                    &[#(#columns),*],
+                    after.as_ref(),
+                    limit,
+                )
+                .await
+            }
+        }
+    })
+}
+
+/// Generate `#[sqlx::test]` round-trip tests for an individual repository.
+fn generate_repository_tests(
+    repo_name: &syn::Ident,
+    item_type: &syn::Type,
+    sample: &proc_macro2::TokenStream,
+    versioned: bool,
+) -> TokenStream {
+    let snake = to_snake_case(&repo_name.to_string());
+    let module = quote::format_ident!("{}_generated_tests", snake);
+
+    let tests = if versioned {
+        quote! {
+            #[sqlx::test]
+            async fn create_and_get_by_said(
+                pool: sqlx::PgPool,
+            ) -> Result<(), verifiable_storage::StorageError> {
+                let repo = repository(pool).await?;
+                let created = repo.create(sample()).await?;
+
+                let fetched = repo.get_by_said(&created.get_said()).await?;
+                assert_eq!(fetched.map(|item| item.get_said()), Some(created.get_said()));
+                assert!(repo.exists(&created.get_prefix()).await?);
+                Ok(())
+            }
+
+            #[sqlx::test]
+            async fn update_and_get_latest(
+                pool: sqlx::PgPool,
+            ) -> Result<(), verifiable_storage::StorageError> {
+                let repo = repository(pool).await?;
+                let created = repo.create(sample()).await?;
+                let updated = repo.update(created.clone()).await?;
+
+                assert_eq!(updated.get_version(), created.get_version() + 1);
+                assert_eq!(updated.get_previous(), Some(created.get_said()));
+                let latest = repo.get_latest(&created.get_prefix()).await?;
+                assert_eq!(latest.map(|item| item.get_said()), Some(updated.get_said()));
+                Ok(())
+            }
+
+            #[sqlx::test]
+            async fn get_history_in_version_order(
+                pool: sqlx::PgPool,
+            ) -> Result<(), verifiable_storage::StorageError> {
+                let repo = repository(pool).await?;
+                let created = repo.create(sample()).await?;
+                let updated = repo.update(created.clone()).await?;
+
+                let history: Vec<String> = repo
+                    .get_history(&created.get_prefix())
+                    .await?
+                    .iter()
+                    .map(|item| item.get_said())
+                    .collect();
+                assert_eq!(history, vec![created.get_said(), updated.get_said()]);
+                assert!(!repo.exists("unknown").await?);
+                Ok(())
+            }
+        }
+    } else {
+        quote! {
+            #[sqlx::test]
+            async fn create_and_get_by_said(
+                pool: sqlx::PgPool,
+            ) -> Result<(), verifiable_storage::StorageError> {
+                let repo = repository(pool).await?;
+                let created = repo.create(sample()).await?;
+
+                let fetched = repo.get_by_said(&created.get_said()).await?;
+                assert_eq!(fetched.map(|item| item.get_said()), Some(created.get_said()));
+                assert!(repo.get_by_said("unknown").await?.is_none());
+                Ok(())
+            }
+        }
+    };
+
+    let expanded = quote! {
+        #[cfg(test)]
+        mod #module {
+            use super::*;
+            #[allow(unused_imports)]
+            use verifiable_storage::{
+                SelfAddressed, UnversionedRepository, Versioned, VersionedRepository,
+            };
+
+            fn sample() -> #item_type {
+                #sample
+            }
+
+            async fn repository(
+                pool: sqlx::PgPool,
+            ) -> Result<#repo_name, verifiable_storage::StorageError> {
+                let repo = #repo_name::new(verifiable_storage_postgres::PgPool::new(pool));
+                repo.maintain_partitions().await?;
+                Ok(repo)
+            }
+
+            #tests
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Emit `sqlx::query!` calls mirroring the repository's standard reads, plus the
+/// item's `checked_columns`, so sqlx checks them against the database when the
+/// crate is compiled. The function is never called; only its type-checking matters.
+fn generate_checked_queries(
+    item_type: &syn::Type,
+    table_name: &str,
+    id_field: &str,
+    prefix_field: &str,
+    versioned: bool,
+) -> TokenStream {
+    let span = proc_macro2::Span::call_site();
+    let by_said = syn::LitStr::new(
+        &format!(
+            "SELECT * FROM {} WHERE {} = $1 LIMIT 1",
+            table_name, id_field
+        ),
+        span,
+    );
+    let versioned_checks = if versioned {
+        let latest = syn::LitStr::new(
+            &format!(
+                "SELECT * FROM {} WHERE {} = $1 ORDER BY version DESC LIMIT 1",
+                table_name, prefix_field
+            ),
+            span,
+        );
+        let history = syn::LitStr::new(
+            &format!(
+                "SELECT * FROM {} WHERE {} = $1 ORDER BY version ASC",
+                table_name, prefix_field
+            ),
+            span,
+        );
+        quote! {
+            sqlx::query!(#latest, "").fetch_optional(pool).await?;
+            sqlx::query!(#history, "").fetch_all(pool).await?;
+        }
+    } else {
+        quote! {}
+    };
+
+    TokenStream::from(quote! {
+        const _: () = {
+            #[allow(dead_code)]
+            async fn checked_queries(pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+                sqlx::query!(#by_said, "").fetch_optional(pool).await?;
+                #versioned_checks
+                <#item_type>::checked_columns(pool).await?;
+                Ok(())
+            }
+        };
+    })
+}
+
+/// Convert a type name like `DomainRepository` to `domain_repository`.
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_numbered_by_first_use() {
+        let (sql, names) = positional_placeholders(
+            "SELECT * FROM t WHERE a = $owner AND b > $since OR c = $owner",
+        );
+        assert_eq!(sql, "SELECT * FROM t WHERE a = $1 AND b > $2 OR c = $1");
+        assert_eq!(names, vec!["owner", "since"]);
+    }
+
+    #[test]
+    fn quoted_text_is_left_alone() {
+        let (sql, names) =
+            positional_placeholders(r#"SELECT '$not', "$col" FROM t WHERE a = $value"#);
+        assert_eq!(sql, r#"SELECT '$not', "$col" FROM t WHERE a = $1"#);
+        assert_eq!(names, vec!["value"]);
+    }
+
+    #[test]
+    fn dollar_quoted_bodies_are_left_alone() {
+        let (sql, names) = positional_placeholders(
+            "SELECT $$ costs $amount $$, $fn$ $x $fn$, $tag$ a $$ b $tag$ WHERE id = $id",
+        );
+        assert_eq!(
+            sql,
+            "SELECT $$ costs $amount $$, $fn$ $x $fn$, $tag$ a $$ b $tag$ WHERE id = $1"
+        );
+        assert_eq!(names, vec!["id"]);
+    }
+
+    #[test]
+    fn comments_are_left_alone() {
+        let (sql, names) = positional_placeholders(
+            "SELECT * FROM t -- keyed by $prefix\nWHERE /* not $said */ said = $said",
+        );
+        assert_eq!(
+            sql,
+            "SELECT * FROM t -- keyed by $prefix\nWHERE /* not $said */ said = $1"
+        );
+        assert_eq!(names, vec!["said"]);
+    }
+
+    #[test]
+    fn positional_and_unterminated_text_pass_through() {
+        let (sql, names) = positional_placeholders("SELECT $1::int, 'open $x");
+        assert_eq!(sql, "SELECT $1::int, 'open $x");
+        assert!(names.is_empty());
+    }
+}
@@ -0,0 +1,13 @@
+//! Admin CLI for the tables verifiable-storage manages itself.
+//!
+//! Services wanting these commands over their own types call
+//! `verifiable_storage_postgres::run_cli` from a binary of their own.
+
+use std::process::ExitCode;
+
+use verifiable_storage_postgres::{builtin_tables, run_cli};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    run_cli(builtin_tables).await
+}
@@ -0,0 +1,233 @@
+//! The `vstorage` admin CLI.
+//!
+//! The shipped binary knows only the tables this library manages itself.
+//! Services get the same commands over their own types with a small binary:
+//!
+//! ```text
+//! #[tokio::main]
+//! async fn main() -> ExitCode {
+//!     run_cli(|registry| builtin_tables(registry).versioned::<Document>()).await
+//! }
+//! ```
+
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use verifiable_storage::{
+    ExecutorMode, HistoryBundle, LogLeaf, OutboxRecord, Registry, SignedTreeHead, StorageDatetime,
+    StorageError, TimestampToken,
+};
+
+use crate::{PgPool, schema_diff};
+
+#[derive(Parser)]
+#[command(
+    name = "vstorage",
+    about = "Verify and maintain verifiable-storage tables"
+)]
+struct Cli {
+    /// PostgreSQL connection URL.
+    #[arg(long, env = "DATABASE_URL")]
+    database_url: String,
+
+    /// Print the writes a command would make instead of executing them.
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the registered tables.
+    Tables,
+    /// Verify a prefix (or an unversioned SAID), or every row of a table.
+    Verify { table: String, key: Option<String> },
+    /// Show each version of a prefix and any broken links.
+    Chain { table: String, prefix: String },
+    /// Write a prefix's history (or an unversioned record) as a JSON bundle.
+    Export {
+        table: String,
+        key: String,
+        /// File to write; defaults to stdout.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Verify and insert a bundle written by `export` (`-` reads stdin).
+    Import { path: PathBuf },
+    /// Delete rows of an unversioned table created before a time.
+    Retention {
+        table: String,
+        /// RFC 3339 cutoff, e.g. 2024-01-01T00:00:00Z.
+        #[arg(long)]
+        before: String,
+    },
+    /// Compare registered tables (or one table) with the database schema.
+    SchemaDiff { table: Option<String> },
+}
+
+/// Register the tables verifiable-storage manages itself: the outbox,
+/// timestamp tokens and transparency log leaves and heads.
+pub fn builtin_tables(registry: Registry<PgPool>) -> Registry<PgPool> {
+    registry
+        .unversioned::<OutboxRecord>()
+        .unversioned::<TimestampToken>()
+        .unversioned::<LogLeaf>()
+        .unversioned::<SignedTreeHead>()
+}
+
+/// Parse the command line, connect, and run one command against the tables
+/// `configure` registers.
+///
+/// Fails when the command does, when verification finds bad records, or when
+/// a schema differs from its declaration.
+pub async fn run_cli(configure: impl FnOnce(Registry<PgPool>) -> Registry<PgPool>) -> ExitCode {
+    let cli = Cli::parse();
+    let mode = if cli.dry_run {
+        ExecutorMode::DryRun
+    } else {
+        ExecutorMode::Execute
+    };
+    let pool = match PgPool::connect(&cli.database_url).await {
+        Ok(pool) => pool.with_mode(mode),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let registry = configure(Registry::new(pool));
+
+    let outcome = execute(&registry, cli.command).await;
+    for statement in registry.executor().take_dry_run() {
+        println!("would run: {}", statement.sql);
+    }
+    match outcome {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run `command`, returning whether everything it checked was sound.
+async fn execute(registry: &Registry<PgPool>, command: Command) -> Result<bool, StorageError> {
+    match command {
+        Command::Tables => {
+            for table in registry.tables() {
+                let kind = if table.versioned {
+                    "versioned"
+                } else {
+                    "unversioned"
+                };
+                println!("{} ({}, {} columns)", table.name, kind, table.columns.len());
+            }
+            Ok(true)
+        }
+        Command::Verify { table, key } => {
+            let report = registry.verify(&table, key.as_deref()).await?;
+            for (key, reason) in &report.failures {
+                println!("FAIL {}: {}", key, reason);
+            }
+            println!(
+                "checked {} records, {} failures",
+                report.checked,
+                report.failures.len()
+            );
+            Ok(report.is_valid())
+        }
+        Command::Chain { table, prefix } => {
+            let links = registry.chain(&table, &prefix).await?;
+            for link in &links {
+                let created_at = link
+                    .created_at
+                    .as_ref()
+                    .map_or_else(|| "-".to_string(), |t| t.to_string());
+                println!(
+                    "{:>6}  {}  {}  {}{}",
+                    link.version,
+                    link.said,
+                    link.previous.as_deref().unwrap_or("-"),
+                    created_at,
+                    link.error
+                        .as_ref()
+                        .map_or_else(String::new, |e| format!("  FAIL: {}", e)),
+                );
+            }
+            Ok(links.iter().all(|link| link.error.is_none()))
+        }
+        Command::Export { table, key, out } => {
+            let bundle = serde_json::to_string_pretty(&registry.export(&table, &key).await?)?;
+            match out {
+                Some(path) => std::fs::write(&path, bundle)
+                    .map_err(|e| StorageError::StorageError(e.to_string()))?,
+                None => println!("{}", bundle),
+            }
+            Ok(true)
+        }
+        Command::Import { path } => {
+            let bundle: HistoryBundle = serde_json::from_str(&read_input(&path)?)?;
+            let table = bundle.table.clone();
+            let written = registry.import(bundle).await?;
+            println!("imported {} new records into {}", written, table);
+            Ok(true)
+        }
+        Command::Retention { table, before } => {
+            let before: StorageDatetime =
+                serde_json::from_value(serde_json::Value::String(before))?;
+            let deleted = registry.prune(&table, &before).await?;
+            println!("deleted {} rows from {}", deleted, table);
+            Ok(true)
+        }
+        Command::SchemaDiff { table } => {
+            let tables: Vec<_> = registry
+                .tables()
+                .into_iter()
+                .filter(|info| table.as_deref().is_none_or(|name| name == info.name))
+                .collect();
+            if let Some(name) = &table
+                && tables.is_empty()
+            {
+                return Err(StorageError::NotFound(format!(
+                    "Table {} is not registered",
+                    name
+                )));
+            }
+            let mut clean = true;
+            for info in tables {
+                let diff = schema_diff(registry.executor(), &info).await?;
+                if diff.is_empty() {
+                    println!("{}: matches", diff.table);
+                    continue;
+                }
+                clean = false;
+                println!("{}:", diff.table);
+                for column in &diff.missing {
+                    println!("  missing {}", column);
+                }
+                for column in &diff.extra {
+                    println!("  extra {}", column);
+                }
+                for (column, declared, actual) in &diff.mismatched {
+                    println!("  {} declared {}, found {}", column, declared, actual);
+                }
+            }
+            Ok(clean)
+        }
+    }
+}
+
+fn read_input(path: &PathBuf) -> Result<String, StorageError> {
+    let mut input = String::new();
+    let read = if path.as_os_str() == "-" {
+        std::io::stdin().read_to_string(&mut input).map(|_| ())
+    } else {
+        std::fs::File::open(path).and_then(|mut file| file.read_to_string(&mut input).map(|_| ()))
+    };
+    read.map_err(|e| StorageError::StorageError(format!("{}: {}", path.display(), e)))?;
+    Ok(input)
+}
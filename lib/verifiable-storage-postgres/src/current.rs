@@ -0,0 +1,124 @@
+//! Materialized "current" projections of versioned tables.
+//!
+//! A `<table>_current` companion holds the latest version of each prefix with
+//! the same columns as the table, so read-heavy consumers can query it instead
+//! of `DISTINCT ON` over full history. Repositories derived with
+//! `#[stored(current)]` keep it up to date on every insert:
+//!
+//! ```text
+//! CREATE TABLE documents_current (LIKE documents INCLUDING ALL);
+//! CREATE UNIQUE INDEX documents_current_prefix ON documents_current (prefix);
+//! ```
+
+use serde::{Serialize, de::DeserializeOwned};
+use verifiable_storage::{
+    ManagedField, Query, QueryExecutor, Storable, StorageError, TransactionExecutor, Versioned,
+};
+
+use crate::{PgPool, PgTransaction};
+
+/// The projection table of `table`.
+pub fn current_table_name(table: &str) -> String {
+    format!("{}_current", table)
+}
+
+/// Insert `item` into `table` and, if it is the newest version of its prefix,
+/// replace the prefix's row in the projection, in one transaction.
+pub async fn insert_with_current<T>(
+    pool: &PgPool,
+    table: &str,
+    item: &T,
+) -> Result<(), StorageError>
+where
+    T: Storable + Versioned + Serialize + DeserializeOwned,
+{
+    let mut tx = pool.begin_transaction().await?;
+    let written = async {
+        tx.insert_with_table(item, table).await?;
+        replace_current(&mut tx, table, item).await
+    }
+    .await;
+
+    match written {
+        Ok(()) => tx.commit().await,
+        Err(e) => {
+            tx.rollback().await?;
+            Err(e)
+        }
+    }
+}
+
+/// Within `tx`, make `item` the projection's row for its prefix unless a newer
+/// version is already there.
+///
+/// Writers of the same prefix are serialized with an advisory lock, so an
+/// out-of-order insert (e.g. from sync) never regresses the projection.
+pub async fn replace_current<T>(
+    tx: &mut PgTransaction,
+    table: &str,
+    item: &T,
+) -> Result<(), StorageError>
+where
+    T: Storable + Versioned + Serialize + DeserializeOwned,
+{
+    let current = current_table_name(table);
+    let prefix = item.get_prefix();
+    let prefix_column = T::managed_column(ManagedField::Prefix);
+
+    tx.acquire_advisory_lock(&format!("{}:{}", current, prefix))
+        .await?;
+    let existing = tx
+        .fetch(
+            Query::<T>::for_table(current.as_str())
+                .include_deleted()
+                .eq(prefix_column, prefix.as_str())
+                .limit(1),
+        )
+        .await?;
+    if existing
+        .first()
+        .is_none_or(|row| row.get_version() < item.get_version())
+    {
+        tx.execute_sql(
+            &format!("DELETE FROM {} WHERE {} = $1", current, prefix_column),
+            vec![("$1".to_string(), prefix.as_str().into())],
+        )
+        .await?;
+        tx.insert_with_table(item, &current).await?;
+    }
+    Ok(())
+}
+
+/// Repopulate the projection of `table` from its full history, returning the
+/// number of prefixes written. Use after creating the projection or to repair it.
+pub async fn rebuild_current<T: Storable>(pool: &PgPool, table: &str) -> Result<u64, StorageError> {
+    let current = current_table_name(table);
+    let prefix_column = T::managed_column(ManagedField::Prefix);
+    let version_column = T::managed_column(ManagedField::Version);
+
+    let mut tx = pool.begin_transaction().await?;
+    let written = async {
+        tx.execute_sql(&format!("DELETE FROM {}", current), Vec::new())
+            .await?;
+        tx.execute_sql(
+            &format!(
+                "INSERT INTO {} SELECT DISTINCT ON ({}) * FROM {} ORDER BY {}, {} DESC",
+                current, prefix_column, table, prefix_column, version_column
+            ),
+            Vec::new(),
+        )
+        .await
+    }
+    .await;
+
+    match written {
+        Ok(rows) => {
+            tx.commit().await?;
+            Ok(rows)
+        }
+        Err(e) => {
+            tx.rollback().await?;
+            Err(e)
+        }
+    }
+}
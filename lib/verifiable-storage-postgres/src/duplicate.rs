@@ -0,0 +1,73 @@
+//! Unique violations reported as [`StorageError::Duplicate`].
+//!
+//! Postgres names the violated constraint and, in the error detail, the key
+//! that collided (`Key (said)=(E...) already exists.`), so callers can answer
+//! with a 409 naming the conflicting record instead of an opaque failure.
+
+use verifiable_storage::StorageError;
+
+/// SQLSTATE for `unique_violation`.
+const UNIQUE_VIOLATION: &str = "23505";
+
+/// Convert a sqlx error, surfacing unique violations as `Duplicate`.
+pub(crate) fn db_error(e: sqlx::Error) -> StorageError {
+    if let Some(db) = e.as_database_error()
+        && db.code().as_deref() == Some(UNIQUE_VIOLATION)
+    {
+        let detail = db
+            .try_downcast_ref::<sqlx::postgres::PgDatabaseError>()
+            .and_then(|pg| pg.detail());
+        return duplicate(db.constraint(), detail);
+    }
+    StorageError::StorageError(e.to_string())
+}
+
+fn duplicate(constraint: Option<&str>, detail: Option<&str>) -> StorageError {
+    StorageError::Duplicate {
+        constraint: constraint.unwrap_or("unique constraint").to_string(),
+        said: detail.and_then(conflicting_value),
+    }
+}
+
+/// The value in `Key (column)=(value) already exists.`; `None` for
+/// multi-column keys, whose values don't name one record.
+fn conflicting_value(detail: &str) -> Option<String> {
+    let (columns, rest) = detail.strip_prefix("Key (")?.split_once(")=(")?;
+    let value = rest.strip_suffix(") already exists.")?;
+    (!columns.contains(',')).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_violations_name_the_constraint_and_conflicting_said() {
+        let error = duplicate(
+            Some("events_pkey"),
+            Some("Key (said)=(EAbc123) already exists."),
+        );
+        assert!(matches!(
+            &error,
+            StorageError::Duplicate { constraint, said: Some(said) }
+                if constraint == "events_pkey" && said == "EAbc123"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Duplicate: events_pkey already holds EAbc123"
+        );
+
+        let composite = duplicate(
+            Some("events_prefix_version_key"),
+            Some("Key (prefix, version)=(EAbc123, 2) already exists."),
+        );
+        assert!(matches!(
+            composite,
+            StorageError::Duplicate { said: None, .. }
+        ));
+        assert!(matches!(
+            duplicate(None, None),
+            StorageError::Duplicate { said: None, .. }
+        ));
+    }
+}
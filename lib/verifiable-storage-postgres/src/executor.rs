@@ -2,27 +2,66 @@
 
 const DEFAULT_MAX_CONNECTIONS: u32 = 16;
 
+/// Tables estimated below this many rows are sampled by ordering every
+/// matching row at random, which is cheap at that size.
+const SAMPLE_SCAN_ROWS: f64 = 100_000.0;
+
+/// How many more rows a `TABLESAMPLE` reads than it needs, leaving room for
+/// the query's filters to discard some.
+const SAMPLE_OVERDRAW: f64 = 10.0;
+
 use async_trait::async_trait;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
 use sqlx::postgres::{PgArguments, PgPoolOptions};
 use sqlx::{Arguments, Postgres, Transaction};
 use std::ops::Deref;
+use std::sync::Arc;
 use verifiable_storage::{
-    ColumnQuery, Delete, Filter, Join, Order, Query, QueryExecutor, Storable, StorageError,
-    TransactionExecutor, Value,
+    Aggregate, ColumnQuery, Delete, Dialect, DryRunLog, ExecutorMode, PrefixVersion, Query,
+    QueryExecutor, RenderedStatement, SqlParams, Storable, StorageDatetime, StorageError,
+    TOTAL_COUNT_COLUMN, TransactionExecutor, Value,
 };
 
-use crate::{bind_insert_values, bind_insert_values_tx, deserialize_row};
+use crate::duplicate::db_error;
+use crate::serde_bind::{OnConflict, execute_insert, render_insert};
+use crate::{ALLOW_DELETE_SETTING, deserialize_row};
 
 /// Wrapper around sqlx::PgPool that implements QueryExecutor.
+///
+/// In [`ExecutorMode::DryRun`] inserts and deletes (including those made by
+/// repositories and transactions built on this pool) are recorded instead of
+/// executed; collect them with [`PgPool::take_dry_run`].
 #[derive(Clone, Debug)]
-pub struct PgPool(sqlx::PgPool);
+pub struct PgPool {
+    pool: sqlx::PgPool,
+    mode: ExecutorMode,
+    dry_run: DryRunLog,
+    guard_deletes: bool,
+}
+
+/// The pool is reference-counted internally, so this clone shares its connections.
+impl From<Arc<PgPool>> for PgPool {
+    fn from(pool: Arc<PgPool>) -> Self {
+        (*pool).clone()
+    }
+}
+
+impl From<sqlx::PgPool> for PgPool {
+    fn from(pool: sqlx::PgPool) -> Self {
+        Self::new(pool)
+    }
+}
 
 impl PgPool {
     /// Create a new PgPool from an sqlx PgPool.
     pub fn new(pool: sqlx::PgPool) -> Self {
-        Self(pool)
+        Self {
+            pool,
+            mode: ExecutorMode::Execute,
+            dry_run: DryRunLog::default(),
+            guard_deletes: false,
+        }
     }
 
     /// Connect to a PostgreSQL database.
@@ -32,98 +71,156 @@ impl PgPool {
             .connect(url)
             .await
             .map_err(|e| StorageError::StorageError(e.to_string()))?;
-        Ok(Self(pool))
+        Ok(Self::new(pool))
+    }
+
+    /// Switch between executing writes and recording them.
+    pub fn with_mode(mut self, mode: ExecutorMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Refuse deletes from versioned tables, here and in this pool's
+    /// transactions, unless they call `allow_versioned_delete()`.
+    pub fn with_delete_guard(mut self) -> Self {
+        self.guard_deletes = true;
+        self
+    }
+
+    /// The current execution mode.
+    pub fn mode(&self) -> ExecutorMode {
+        self.mode
+    }
+
+    /// Remove and return the writes recorded in dry-run mode, oldest first.
+    pub fn take_dry_run(&self) -> Vec<RenderedStatement> {
+        self.dry_run.take()
     }
 
     /// Get the inner sqlx::PgPool.
     pub fn inner(&self) -> &sqlx::PgPool {
-        &self.0
+        &self.pool
     }
-}
-
-impl Deref for PgPool {
-    type Target = sqlx::PgPool;
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    /// Insert an item into an explicit table, honouring the execution mode.
+    pub async fn insert_with_table<T: Storable + Serialize>(
+        &self,
+        item: &T,
+        table: &str,
+    ) -> Result<u64, StorageError> {
+        self.write_with_table(item, table, OnConflict::Fail).await
     }
-}
 
-/// Build a WHERE clause from filters and return the SQL and argument count.
-fn build_where_clause(filters: &[Filter], start_param: usize) -> (String, usize) {
-    if filters.is_empty() {
-        return (String::new(), 0);
+    /// Insert an item into an explicit table unless a row with its key exists.
+    ///
+    /// Returns 1 if the item was written and 0 if it was already present.
+    pub async fn insert_or_ignore_with_table<T: Storable + Serialize>(
+        &self,
+        item: &T,
+        table: &str,
+    ) -> Result<u64, StorageError> {
+        self.write_with_table(item, table, OnConflict::Ignore).await
     }
 
-    let mut clauses = Vec::new();
-    let mut param_idx = start_param;
+    /// Insert an item into an explicit table, rewriting the row stored under
+    /// its SAID if any column differs.
+    ///
+    /// Returns 1 if a row was inserted or rewritten and 0 if it already matched.
+    pub async fn upsert_with_table<T: Storable + Serialize>(
+        &self,
+        item: &T,
+        table: &str,
+    ) -> Result<u64, StorageError> {
+        self.write_with_table(item, table, OnConflict::Update).await
+    }
 
-    for filter in filters {
-        let clause = match filter {
-            Filter::Eq(field, _) => {
-                let c = format!("{} = ${}", field, param_idx);
-                param_idx += 1;
-                c
-            }
-            Filter::Ne(field, _) => {
-                let c = format!("{} != ${}", field, param_idx);
-                param_idx += 1;
-                c
-            }
-            Filter::Gt(field, _) => {
-                let c = format!("{} > ${}", field, param_idx);
-                param_idx += 1;
-                c
-            }
-            Filter::Gte(field, _) => {
-                let c = format!("{} >= ${}", field, param_idx);
-                param_idx += 1;
-                c
-            }
-            Filter::Lt(field, _) => {
-                let c = format!("{} < ${}", field, param_idx);
-                param_idx += 1;
-                c
-            }
-            Filter::Lte(field, _) => {
-                let c = format!("{} <= ${}", field, param_idx);
-                param_idx += 1;
-                c
-            }
-            Filter::In(field, _) => {
-                let c = format!("{} = ANY(${})", field, param_idx);
-                param_idx += 1;
-                c
+    async fn write_with_table<T: Storable + Serialize>(
+        &self,
+        item: &T,
+        table: &str,
+        on_conflict: OnConflict,
+    ) -> Result<u64, StorageError> {
+        match self.mode {
+            ExecutorMode::Execute => execute_insert(&self.pool, item, table, on_conflict).await,
+            ExecutorMode::DryRun => {
+                self.dry_run
+                    .record(render_insert(item, table, on_conflict)?);
+                Ok(0)
             }
-            Filter::IsNull(field) => format!("{} IS NULL", field),
-            Filter::IsNotNull(field) => format!("{} IS NOT NULL", field),
-        };
-        clauses.push(clause);
+        }
     }
 
-    let param_count = param_idx - start_param;
-    (format!(" WHERE {}", clauses.join(" AND ")), param_count)
-}
+    /// Run a raw SELECT with positional (`$1`, `$2`, ...) parameters and
+    /// deserialize the rows via `T`'s `Storable` metadata.
+    pub async fn fetch_sql<T: Storable + DeserializeOwned>(
+        &self,
+        sql: &str,
+        params: SqlParams,
+    ) -> Result<Vec<T>, StorageError> {
+        let args = bind_params(&params)?;
+        let rows = sqlx::query_with(sql, args)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
 
-/// Bind filter values to PgArguments.
-fn bind_filters(args: &mut PgArguments, filters: &[Filter]) -> Result<(), StorageError> {
-    for filter in filters {
-        match filter {
-            Filter::Eq(_, value)
-            | Filter::Ne(_, value)
-            | Filter::Gt(_, value)
-            | Filter::Gte(_, value)
-            | Filter::Lt(_, value)
-            | Filter::Lte(_, value)
-            | Filter::In(_, value) => {
-                bind_value(args, value)?;
-            }
-            Filter::IsNull(_) | Filter::IsNotNull(_) => {
-                // No binding needed
-            }
+        rows.iter().map(|row| deserialize_row::<T>(row)).collect()
+    }
+
+    /// Run a raw statement with positional parameters, honouring the execution
+    /// mode, and return the number of rows affected.
+    pub async fn execute_sql(&self, sql: &str, params: SqlParams) -> Result<u64, StorageError> {
+        if let Some(log) = self.dry_run_log() {
+            log.record(render((sql.to_string(), params)));
+            return Ok(0);
         }
+
+        let args = bind_params(&params)?;
+        let result = sqlx::query_with(sql, args)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected())
     }
-    Ok(())
+
+    /// The planner's row estimate for `table`, summed over its partitions.
+    async fn estimated_rows(&self, table: &str) -> Result<f64, StorageError> {
+        let (estimate,): (Option<f64>,) = sqlx::query_as(
+            "SELECT SUM(GREATEST(reltuples, 0))::float8 FROM pg_class \
+             WHERE oid = to_regclass($1) \
+             OR oid IN (SELECT inhrelid FROM pg_inherits WHERE inhparent = to_regclass($1))",
+        )
+        .bind(table)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| StorageError::StorageError(e.to_string()))?;
+        Ok(estimate.unwrap_or(0.0))
+    }
+
+    /// The dry-run log, if writes should be recorded rather than executed.
+    fn dry_run_log(&self) -> Option<&DryRunLog> {
+        match self.mode {
+            ExecutorMode::Execute => None,
+            ExecutorMode::DryRun => Some(&self.dry_run),
+        }
+    }
+}
+
+impl Deref for PgPool {
+    type Target = sqlx::PgPool;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pool
+    }
+}
+
+/// Bind rendered parameters to PgArguments, in placeholder order.
+fn bind_params(params: &SqlParams) -> Result<PgArguments, StorageError> {
+    let mut args = PgArguments::default();
+    for (_, value) in params {
+        bind_value(&mut args, value)?;
+    }
+    Ok(args)
 }
 
 /// Bind a Value to PgArguments.
@@ -162,6 +259,10 @@ fn bind_value(args: &mut PgArguments, value: &Value) -> Result<(), StorageError>
             args.add(chrono_dt)
                 .map_err(|e| StorageError::StorageError(e.to_string()))?;
         }
+        Value::Json(v) => {
+            args.add(v)
+                .map_err(|e| StorageError::StorageError(e.to_string()))?;
+        }
         Value::Null => {
             args.add(None::<String>)
                 .map_err(|e| StorageError::StorageError(e.to_string()))?;
@@ -170,42 +271,15 @@ fn bind_value(args: &mut PgArguments, value: &Value) -> Result<(), StorageError>
     Ok(())
 }
 
-/// Build ORDER BY clause.
-fn build_order_clause(order_by: &[(String, Order)]) -> String {
-    if order_by.is_empty() {
-        return String::new();
-    }
-
-    let clauses: Vec<String> = order_by
-        .iter()
-        .map(|(field, order)| {
-            let dir = match order {
-                Order::Asc => "ASC",
-                Order::Desc => "DESC",
-            };
-            format!("{} {}", field, dir)
-        })
-        .collect();
-
-    format!(" ORDER BY {}", clauses.join(", "))
-}
-
-/// Build JOIN clauses.
-fn build_join_clause(main_table: &str, joins: &[Join]) -> String {
-    if joins.is_empty() {
-        return String::new();
+/// Render a statement without executing it (for dry runs).
+fn render((sql, params): (String, SqlParams)) -> RenderedStatement {
+    RenderedStatement {
+        sql,
+        params: params
+            .into_iter()
+            .map(|(name, value)| (name, value.to_json()))
+            .collect(),
     }
-
-    joins
-        .iter()
-        .map(|join| {
-            format!(
-                " JOIN {} ON {}.{} = {}.{}",
-                join.table, main_table, join.left_field, join.table, join.right_field
-            )
-        })
-        .collect::<Vec<_>>()
-        .join("")
 }
 
 #[async_trait]
@@ -216,41 +290,11 @@ impl QueryExecutor for PgPool {
         &self,
         query: Query<T>,
     ) -> Result<Vec<T>, StorageError> {
-        let join_clause = build_join_clause(&query.table, &query.joins);
-        let (where_clause, _) = build_where_clause(&query.filters, 1);
-        let order_clause = build_order_clause(&query.order_by);
-
-        // Build DISTINCT ON clause if specified
-        let distinct_clause = if query.distinct_on.is_empty() {
-            String::new()
-        } else {
-            format!("DISTINCT ON ({}) ", query.distinct_on.join(", "))
-        };
-
-        // Use table.* when joining to only return columns from the main table
-        let select_cols = if query.joins.is_empty() {
-            "*".to_string()
-        } else {
-            format!("{}.*", query.table)
-        };
-
-        let mut sql = format!(
-            "SELECT {}{} FROM {}{}{}{}",
-            distinct_clause, select_cols, query.table, join_clause, where_clause, order_clause
-        );
-
-        if let Some(limit) = query.limit {
-            sql.push_str(&format!(" LIMIT {}", limit));
-        }
-        if let Some(offset) = query.offset {
-            sql.push_str(&format!(" OFFSET {}", offset));
-        }
-
-        let mut args = PgArguments::default();
-        bind_filters(&mut args, &query.filters)?;
+        let (sql, params) = query.to_sql(Dialect::Postgres);
+        let args = bind_params(&params)?;
 
         let rows = sqlx::query_with(&sql, args)
-            .fetch_all(&self.0)
+            .fetch_all(&self.pool)
             .await
             .map_err(|e| StorageError::StorageError(e.to_string()))?;
 
@@ -269,17 +313,11 @@ impl QueryExecutor for PgPool {
     }
 
     async fn exists<T: Storable + Send>(&self, query: Query<T>) -> Result<bool, StorageError> {
-        let (where_clause, _) = build_where_clause(&query.filters, 1);
-        let sql = format!(
-            "SELECT EXISTS(SELECT 1 FROM {}{})",
-            query.table, where_clause
-        );
-
-        let mut args = PgArguments::default();
-        bind_filters(&mut args, &query.filters)?;
+        let (sql, params) = query.to_exists_sql(Dialect::Postgres);
+        let args = bind_params(&params)?;
 
         let row = sqlx::query_with(&sql, args)
-            .fetch_one(&self.0)
+            .fetch_one(&self.pool)
             .await
             .map_err(|e| StorageError::StorageError(e.to_string()))?;
 
@@ -287,15 +325,178 @@ impl QueryExecutor for PgPool {
         Ok(row.get::<bool, _>(0))
     }
 
+    async fn count<T: Storable + Send>(&self, query: Query<T>) -> Result<u64, StorageError> {
+        let (sql, params) = query.to_count_sql(Dialect::Postgres);
+        let args = bind_params(&params)?;
+
+        let row = sqlx::query_with(&sql, args)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+
+        use sqlx::Row;
+        Ok(row.get::<i64, _>(0) as u64)
+    }
+
+    async fn aggregate<T: Storable + Send>(
+        &self,
+        query: Query<T>,
+        aggregate: Aggregate,
+        column: &str,
+    ) -> Result<Option<serde_json::Value>, StorageError> {
+        let (sql, params) = query.to_aggregate_sql(aggregate, column, Dialect::Postgres);
+        let args = bind_params(&params)?;
+
+        let row = sqlx::query_with(&sql, args)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+
+        use sqlx::Row;
+        row.get::<Option<String>, _>(0)
+            .map(|json| serde_json::from_str(&json))
+            .transpose()
+            .map_err(|e| StorageError::StorageError(e.to_string()))
+    }
+
+    async fn list_prefix_versions<T: Storable + Send>(
+        &self,
+        query: Query<T>,
+    ) -> Result<Vec<PrefixVersion>, StorageError> {
+        let (sql, params) = query.to_prefix_sql(Dialect::Postgres);
+        let args = bind_params(&params)?;
+
+        let rows = sqlx::query_with(&sql, args)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+
+        use sqlx::Row;
+        rows.iter()
+            .map(|row| {
+                let prefix: String = row
+                    .try_get("prefix")
+                    .map_err(|e| StorageError::StorageError(e.to_string()))?;
+                let version: i64 = row
+                    .try_get("version")
+                    .map_err(|e| StorageError::StorageError(e.to_string()))?;
+                Ok(PrefixVersion {
+                    prefix,
+                    version: version as u64,
+                })
+            })
+            .collect()
+    }
+
+    /// Large tables are block-sampled with `TABLESAMPLE SYSTEM`, sized from the
+    /// planner's row estimate. Small tables, queries a table sample can't
+    /// express, and samples whose filters leave too few rows fall back to
+    /// ordering every matching row at random.
+    async fn sample<T: Storable + DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+        n: u64,
+    ) -> Result<Vec<T>, StorageError> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+
+        let estimate = self.estimated_rows(&query.table).await?;
+        if estimate > SAMPLE_SCAN_ROWS {
+            let percent = (n as f64 * SAMPLE_OVERDRAW / estimate * 100.0).min(100.0);
+            if let Some((sql, params)) = query.to_tablesample_sql(n, percent) {
+                let sampled = self.fetch_sql(&sql, params).await?;
+                if sampled.len() as u64 >= n {
+                    return Ok(sampled);
+                }
+            }
+        }
+
+        let (sql, params) = query.to_sample_sql(n, Dialect::Postgres);
+        self.fetch_sql(&sql, params).await
+    }
+
+    /// One statement, with the total from a `COUNT(*) OVER ()` window. A page
+    /// past the end has no rows to carry it, so that case counts separately.
+    async fn fetch_with_count<T: Storable + DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+    ) -> Result<(Vec<T>, u64), StorageError> {
+        let (sql, params) = query.to_counted_sql();
+        let args = bind_params(&params)?;
+
+        let rows = sqlx::query_with(&sql, args)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+
+        use sqlx::Row;
+        let total = match rows.first() {
+            Some(row) => {
+                row.try_get::<i64, _>(TOTAL_COUNT_COLUMN)
+                    .map_err(|e| StorageError::StorageError(e.to_string()))? as u64
+            }
+            None if query.offset.unwrap_or(0) > 0 => self.count(query).await?,
+            None => 0,
+        };
+        let items = rows
+            .iter()
+            .map(|row| deserialize_row::<T>(row))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((items, total))
+    }
+
     async fn delete<T: Storable + Send>(&self, delete: Delete<T>) -> Result<u64, StorageError> {
-        let (where_clause, _) = build_where_clause(&delete.filters, 1);
-        let sql = format!("DELETE FROM {}{}", delete.table, where_clause);
+        if self.guard_deletes {
+            delete.check_allowed()?;
+        }
+        if delete.allow_versioned && T::is_versioned() && self.dry_run_log().is_none() {
+            // In a transaction of its own so it can pass a delete guard trigger
+            let mut tx = self.begin_transaction().await?;
+            return match tx.delete(delete).await {
+                Ok(deleted) => {
+                    tx.commit().await?;
+                    Ok(deleted)
+                }
+                Err(e) => {
+                    tx.rollback().await?;
+                    Err(e)
+                }
+            };
+        }
+
+        let rendered = delete.to_sql(Dialect::Postgres);
+        if let Some(log) = self.dry_run_log() {
+            log.record(render(rendered));
+            return Ok(0);
+        }
 
-        let mut args = PgArguments::default();
-        bind_filters(&mut args, &delete.filters)?;
+        let (sql, params) = rendered;
+        let args = bind_params(&params)?;
 
         let result = sqlx::query_with(&sql, args)
-            .execute(&self.0)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn soft_delete<T: Storable + Send>(
+        &self,
+        delete: Delete<T>,
+    ) -> Result<u64, StorageError> {
+        let rendered = delete.to_soft_delete_sql(StorageDatetime::now(), Dialect::Postgres)?;
+        if let Some(log) = self.dry_run_log() {
+            log.record(render(rendered));
+            return Ok(0);
+        }
+
+        let (sql, params) = rendered;
+        let args = bind_params(&params)?;
+
+        let result = sqlx::query_with(&sql, args)
+            .execute(&self.pool)
             .await
             .map_err(|e| StorageError::StorageError(e.to_string()))?;
 
@@ -306,43 +507,45 @@ impl QueryExecutor for PgPool {
         &self,
         item: &T,
     ) -> Result<u64, StorageError> {
-        bind_insert_values(&self.0, item).await
+        self.insert_with_table(item, T::table_name()).await
+    }
+
+    async fn insert_or_ignore<T: Storable + Serialize + Send + Sync>(
+        &self,
+        item: &T,
+    ) -> Result<u64, StorageError> {
+        self.insert_or_ignore_with_table(item, T::table_name())
+            .await
+    }
+
+    async fn upsert<T: Storable + Serialize + Send + Sync>(
+        &self,
+        item: &T,
+    ) -> Result<u64, StorageError> {
+        self.upsert_with_table(item, T::table_name()).await
     }
 
     async fn begin_transaction(&self) -> Result<Self::Transaction, StorageError> {
         let tx = self
-            .0
+            .pool
             .begin()
             .await
             .map_err(|e| StorageError::StorageError(e.to_string()))?;
-        Ok(PgTransaction { tx })
+        Ok(PgTransaction {
+            tx,
+            dry_run: self.dry_run_log().cloned(),
+            guard_deletes: self.guard_deletes,
+        })
     }
 
     async fn fetch_column(&self, query: ColumnQuery) -> Result<Vec<String>, StorageError> {
         use sqlx::Row;
 
-        let distinct = if query.distinct { "DISTINCT " } else { "" };
-        let (where_clause, _) = build_where_clause(&query.filters, 1);
-        let order_clause = match query.order {
-            Some(Order::Asc) => format!(" ORDER BY {} ASC", query.column),
-            Some(Order::Desc) => format!(" ORDER BY {} DESC", query.column),
-            None => String::new(),
-        };
-        let limit_clause = query
-            .limit
-            .map(|l| format!(" LIMIT {}", l))
-            .unwrap_or_default();
-
-        let sql = format!(
-            "SELECT {}{} FROM {}{}{}{}",
-            distinct, query.column, query.table, where_clause, order_clause, limit_clause
-        );
-
-        let mut args = PgArguments::default();
-        bind_filters(&mut args, &query.filters)?;
+        let (sql, params) = query.to_sql(Dialect::Postgres);
+        let args = bind_params(&params)?;
 
         let rows = sqlx::query_with(&sql, args)
-            .fetch_all(&self.0)
+            .fetch_all(&self.pool)
             .await
             .map_err(|e| StorageError::StorageError(e.to_string()))?;
 
@@ -352,46 +555,105 @@ impl QueryExecutor for PgPool {
 }
 
 /// PostgreSQL transaction wrapper implementing TransactionExecutor.
+///
+/// Reads, deletes and inserts all run inside the transaction, so a
+/// read-modify-write under `acquire_advisory_lock` sees its own writes and
+/// commits or rolls back as one.
 pub struct PgTransaction {
     tx: Transaction<'static, Postgres>,
+    /// Set when the pool is in dry-run mode.
+    dry_run: Option<DryRunLog>,
+    guard_deletes: bool,
 }
 
-#[async_trait]
-impl TransactionExecutor for PgTransaction {
-    async fn fetch<T: Storable + DeserializeOwned + Send>(
+impl PgTransaction {
+    /// Insert an item into an explicit table, honouring the execution mode.
+    pub async fn insert_with_table<T: Storable + Serialize>(
         &mut self,
-        query: Query<T>,
-    ) -> Result<Vec<T>, StorageError> {
-        let join_clause = build_join_clause(&query.table, &query.joins);
-        let (where_clause, _) = build_where_clause(&query.filters, 1);
-        let order_clause = build_order_clause(&query.order_by);
-
-        let distinct_clause = if query.distinct_on.is_empty() {
-            String::new()
-        } else {
-            format!("DISTINCT ON ({}) ", query.distinct_on.join(", "))
-        };
+        item: &T,
+        table: &str,
+    ) -> Result<u64, StorageError> {
+        self.write_with_table(item, table, OnConflict::Fail).await
+    }
 
-        let select_cols = if query.joins.is_empty() {
-            "*".to_string()
-        } else {
-            format!("{}.*", query.table)
-        };
+    /// Insert an item into an explicit table unless a row with its key exists.
+    ///
+    /// Returns 1 if the item was written and 0 if it was already present.
+    pub async fn insert_or_ignore_with_table<T: Storable + Serialize>(
+        &mut self,
+        item: &T,
+        table: &str,
+    ) -> Result<u64, StorageError> {
+        self.write_with_table(item, table, OnConflict::Ignore).await
+    }
 
-        let mut sql = format!(
-            "SELECT {}{} FROM {}{}{}{}",
-            distinct_clause, select_cols, query.table, join_clause, where_clause, order_clause
-        );
+    /// Insert an item into an explicit table, rewriting the row stored under
+    /// its SAID if any column differs.
+    ///
+    /// Returns 1 if a row was inserted or rewritten and 0 if it already matched.
+    pub async fn upsert_with_table<T: Storable + Serialize>(
+        &mut self,
+        item: &T,
+        table: &str,
+    ) -> Result<u64, StorageError> {
+        self.write_with_table(item, table, OnConflict::Update).await
+    }
 
-        if let Some(limit) = query.limit {
-            sql.push_str(&format!(" LIMIT {}", limit));
+    async fn write_with_table<T: Storable + Serialize>(
+        &mut self,
+        item: &T,
+        table: &str,
+        on_conflict: OnConflict,
+    ) -> Result<u64, StorageError> {
+        if let Some(log) = &self.dry_run {
+            log.record(render_insert(item, table, on_conflict)?);
+            return Ok(0);
         }
-        if let Some(offset) = query.offset {
-            sql.push_str(&format!(" OFFSET {}", offset));
+
+        execute_insert(&mut *self.tx, item, table, on_conflict).await
+    }
+
+    /// Run a raw statement with positional parameters, honouring the execution
+    /// mode, and return the number of rows affected.
+    pub async fn execute_sql(&mut self, sql: &str, params: SqlParams) -> Result<u64, StorageError> {
+        if let Some(log) = &self.dry_run {
+            log.record(render((sql.to_string(), params)));
+            return Ok(0);
         }
 
-        let mut args = PgArguments::default();
-        bind_filters(&mut args, &query.filters)?;
+        let args = bind_params(&params)?;
+        let result = sqlx::query_with(sql, args)
+            .execute(&mut *self.tx)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Fetch a single column's values within the transaction.
+    pub async fn fetch_column(&mut self, query: ColumnQuery) -> Result<Vec<String>, StorageError> {
+        use sqlx::Row;
+
+        let (sql, params) = query.to_sql(Dialect::Postgres);
+        let args = bind_params(&params)?;
+
+        let rows = sqlx::query_with(&sql, args)
+            .fetch_all(&mut *self.tx)
+            .await
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+}
+
+#[async_trait]
+impl TransactionExecutor for PgTransaction {
+    async fn fetch<T: Storable + DeserializeOwned + Send>(
+        &mut self,
+        query: Query<T>,
+    ) -> Result<Vec<T>, StorageError> {
+        let (sql, params) = query.to_sql(Dialect::Postgres);
+        let args = bind_params(&params)?;
 
         let rows = sqlx::query_with(&sql, args)
             .fetch_all(&mut *self.tx)
@@ -402,11 +664,26 @@ impl TransactionExecutor for PgTransaction {
     }
 
     async fn delete<T: Storable + Send>(&mut self, delete: Delete<T>) -> Result<u64, StorageError> {
-        let (where_clause, _) = build_where_clause(&delete.filters, 1);
-        let sql = format!("DELETE FROM {}{}", delete.table, where_clause);
+        if self.guard_deletes {
+            delete.check_allowed()?;
+        }
+        let rendered = delete.to_sql(Dialect::Postgres);
+        if let Some(log) = &self.dry_run {
+            log.record(render(rendered));
+            return Ok(0);
+        }
+
+        if delete.allow_versioned && T::is_versioned() {
+            // Lets the rest of this transaction past a delete guard trigger
+            sqlx::query("SELECT set_config($1, 'on', true)")
+                .bind(ALLOW_DELETE_SETTING)
+                .execute(&mut *self.tx)
+                .await
+                .map_err(|e| StorageError::StorageError(e.to_string()))?;
+        }
 
-        let mut args = PgArguments::default();
-        bind_filters(&mut args, &delete.filters)?;
+        let (sql, params) = rendered;
+        let args = bind_params(&params)?;
 
         let result = sqlx::query_with(&sql, args)
             .execute(&mut *self.tx)
@@ -420,9 +697,11 @@ impl TransactionExecutor for PgTransaction {
         &mut self,
         item: &T,
     ) -> Result<u64, StorageError> {
-        bind_insert_values_tx(&mut self.tx, item).await
+        self.insert_with_table(item, T::table_name()).await
     }
 
+    /// Takes `pg_advisory_xact_lock` on the key's `hashtext`. Keys whose
+    /// hashes collide serialize against each other too, which is safe.
     async fn acquire_advisory_lock(&mut self, key: &str) -> Result<(), StorageError> {
         sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
             .bind(key)
@@ -446,3 +725,79 @@ impl TransactionExecutor for PgTransaction {
             .map_err(|e| StorageError::StorageError(e.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use verifiable_storage::Filter;
+
+    #[derive(
+        Clone, Debug, serde::Serialize, serde::Deserialize, verifiable_storage::SelfAddressed,
+    )]
+    #[storable(table = "notes")]
+    #[serde(rename_all = "camelCase")]
+    struct Note {
+        #[said]
+        said: String,
+        body: String,
+    }
+
+    #[test]
+    fn renders_delete_with_params() {
+        let delete = Delete::<Note>::new()
+            .eq("body", "stale")
+            .filter(Filter::IsNull("said".to_string()))
+            .gte("said", "E");
+
+        let rendered = render(delete.to_sql(Dialect::Postgres));
+        assert_eq!(
+            rendered.sql,
+            "DELETE FROM notes WHERE body = $1 AND said IS NULL AND said >= $2"
+        );
+        assert_eq!(
+            rendered.params,
+            vec![
+                ("$1".to_string(), json!("stale")),
+                ("$2".to_string(), json!("E")),
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_insert_with_params() {
+        let note = Note {
+            said: "Eabc".to_string(),
+            body: "hello".to_string(),
+        };
+
+        let rendered = render_insert(&note, "notes_2024", OnConflict::Ignore).unwrap();
+        assert_eq!(
+            rendered.sql,
+            "INSERT INTO notes_2024 (said, body) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+        );
+        assert_eq!(
+            rendered.params,
+            vec![
+                ("$1".to_string(), json!("Eabc")),
+                ("$2".to_string(), json!("hello")),
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_upsert_guarded_by_changed_columns() {
+        let note = Note {
+            said: "Eabc".to_string(),
+            body: "hello".to_string(),
+        };
+
+        let rendered = render_insert(&note, "notes", OnConflict::Update).unwrap();
+        assert_eq!(
+            rendered.sql,
+            "INSERT INTO notes (said, body) VALUES ($1, $2) \
+             ON CONFLICT (said) DO UPDATE SET body = EXCLUDED.body \
+             WHERE (notes.body) IS DISTINCT FROM (EXCLUDED.body)"
+        );
+    }
+}
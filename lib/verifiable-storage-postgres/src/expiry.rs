@@ -0,0 +1,33 @@
+//! Archiving of expired rows.
+//!
+//! [`purge_expired`](verifiable_storage::purge_expired) deletes expired rows
+//! outright; [`archive_expired`] moves them to a table with the same columns
+//! instead, in one statement:
+//!
+//! ```text
+//! CREATE TABLE sessions_archive (LIKE sessions INCLUDING ALL);
+//! ```
+
+use verifiable_storage::{Storable, StorageDatetime, StorageError};
+
+use crate::PgPool;
+
+/// Move `T`'s expired rows from `table` to `archive`, returning how many moved.
+pub async fn archive_expired<T: Storable>(
+    pool: &PgPool,
+    table: &str,
+    archive: &str,
+) -> Result<u64, StorageError> {
+    let column = T::expires_at_column().ok_or_else(|| {
+        StorageError::StorageError(format!("{} has no expires_at column", T::table_name()))
+    })?;
+    pool.execute_sql(
+        &format!(
+            "WITH expired AS (DELETE FROM {} WHERE {} <= $1 RETURNING *) \
+             INSERT INTO {} SELECT * FROM expired",
+            table, column, archive
+        ),
+        vec![("$1".to_string(), StorageDatetime::now().into())],
+    )
+    .await
+}
@@ -0,0 +1,72 @@
+//! Database-side protection against deleting versioned rows.
+//!
+//! A pool built with [`PgPool::with_delete_guard`] refuses deletes from
+//! versioned tables unless they call `allow_versioned_delete()`. The trigger
+//! from [`delete_guard_ddl`] enforces the same rule for every client, guarded
+//! or not: a `DELETE` on the table fails unless the transaction has set
+//! [`ALLOW_DELETE_SETTING`], which the executors do only for allowed deletes.
+//!
+//! Raw SQL such as [`archive_expired`](crate::archive_expired) is blocked too;
+//! run it in a transaction that sets the setting first:
+//!
+//! ```text
+//! SELECT set_config('verifiable_storage.allow_versioned_delete', 'on', true);
+//! ```
+
+use verifiable_storage::StorageError;
+
+use crate::PgPool;
+
+/// The transaction-local setting that lets a delete past the guard trigger.
+pub const ALLOW_DELETE_SETTING: &str = "verifiable_storage.allow_versioned_delete";
+
+const GUARD_FUNCTION: &str = "verifiable_storage_guard_delete";
+
+/// Statements creating the guard function and `table`'s delete trigger.
+/// Safe to re-run.
+pub fn delete_guard_ddl(table: &str) -> Vec<String> {
+    let trigger = format!("{}_no_delete", table.replace('.', "_"));
+    vec![
+        format!(
+            "CREATE OR REPLACE FUNCTION {function}() RETURNS trigger AS $$ \
+             BEGIN \
+             IF current_setting('{setting}', true) = 'on' THEN RETURN OLD; END IF; \
+             RAISE EXCEPTION 'Refusing to delete from versioned table %', TG_TABLE_NAME; \
+             END; $$ LANGUAGE plpgsql",
+            function = GUARD_FUNCTION,
+            setting = ALLOW_DELETE_SETTING,
+        ),
+        format!("DROP TRIGGER IF EXISTS {} ON {}", trigger, table),
+        format!(
+            "CREATE TRIGGER {} BEFORE DELETE ON {} FOR EACH ROW EXECUTE FUNCTION {}()",
+            trigger, table, GUARD_FUNCTION
+        ),
+    ]
+}
+
+/// Install the delete guard on `table`.
+pub async fn ensure_delete_guard(pool: &PgPool, table: &str) -> Result<(), StorageError> {
+    for statement in delete_guard_ddl(table) {
+        pool.execute_sql(&statement, Vec::new()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guard_trigger_names_follow_the_table() {
+        let ddl = delete_guard_ddl("audit.events");
+        assert_eq!(ddl.len(), 3);
+        assert!(
+            ddl[0].contains("current_setting('verifiable_storage.allow_versioned_delete', true)")
+        );
+        assert_eq!(
+            ddl[2],
+            "CREATE TRIGGER audit_events_no_delete BEFORE DELETE ON audit.events \
+             FOR EACH ROW EXECUTE FUNCTION verifiable_storage_guard_delete()"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Secondary lookup tables mapping a field's value to the prefix holding it.
+//!
+//! Repositories derived with `#[stored(index_by = "name")]` keep a
+//! `<table>_<field>_index` table in step with every insert, so a mutable,
+//! human-readable key resolves to its prefix with one primary-key lookup. The
+//! index is only a pointer; the SAID chain it points at stays authoritative.
+//!
+//! ```text
+//! CREATE TABLE documents_name_index (
+//!     key TEXT PRIMARY KEY,
+//!     prefix TEXT NOT NULL,
+//!     version BIGINT NOT NULL
+//! );
+//! CREATE INDEX documents_name_index_prefix ON documents_name_index (prefix);
+//! ```
+
+use serde::Serialize;
+use verifiable_storage::{
+    ColumnQuery, Filter, QueryExecutor, Storable, StorageError, TransactionExecutor, Value,
+    Versioned,
+};
+
+use crate::{PgPool, PgTransaction};
+
+/// The lookup table indexing `field` of `table`.
+pub fn index_table_name(table: &str, field: &str) -> String {
+    format!("{}_{}_index", table, field)
+}
+
+/// Within `tx`, point the index of `field` at `item`'s prefix.
+///
+/// Keys held by older versions of the prefix are dropped, so a renamed item
+/// stops resolving under its old key. An insert older than the indexed
+/// version leaves the index alone. A `null` field just drops the old keys.
+pub async fn update_index<T>(
+    tx: &mut PgTransaction,
+    table: &str,
+    field: &str,
+    item: &T,
+) -> Result<(), StorageError>
+where
+    T: Storable + Versioned + Serialize,
+{
+    let index = index_table_name(table, field);
+    let prefix = Value::from(item.get_prefix());
+    let version = Value::from(item.get_version());
+
+    tx.execute_sql(
+        &format!("DELETE FROM {} WHERE prefix = $1 AND version < $2", index),
+        vec![
+            ("$1".to_string(), prefix.clone()),
+            ("$2".to_string(), version.clone()),
+        ],
+    )
+    .await?;
+
+    let Some(key) = index_key(item, field)? else {
+        return Ok(());
+    };
+    tx.execute_sql(
+        &format!(
+            "INSERT INTO {index} (key, prefix, version) SELECT $1, $2, $3 \
+             WHERE NOT EXISTS (SELECT 1 FROM {index} WHERE prefix = $2 AND version > $3) \
+             ON CONFLICT (key) DO UPDATE SET prefix = EXCLUDED.prefix, version = EXCLUDED.version",
+            index = index
+        ),
+        vec![
+            ("$1".to_string(), key.into()),
+            ("$2".to_string(), prefix),
+            ("$3".to_string(), version),
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// As [`update_index`], but fail with [`StorageError::Duplicate`] naming the
+/// holder when `item`'s key is already indexed to another prefix, making the
+/// field unique across prefixes without a database constraint.
+///
+/// Claims of a key are serialized with an advisory lock, so two writers
+/// racing for the same key can't both win.
+pub async fn claim_unique_index<T>(
+    tx: &mut PgTransaction,
+    table: &str,
+    field: &str,
+    item: &T,
+) -> Result<(), StorageError>
+where
+    T: Storable + Versioned + Serialize,
+{
+    if let Some(key) = index_key(item, field)? {
+        let index = index_table_name(table, field);
+        tx.acquire_advisory_lock(&format!("{}:{}", index, key))
+            .await?;
+        let holder = tx
+            .fetch_column(
+                ColumnQuery::new(index.as_str(), "prefix")
+                    .filter(Filter::Eq("key".to_string(), key.into()))
+                    .limit(1),
+            )
+            .await?
+            .into_iter()
+            .next();
+        if let Some(holder) = holder
+            && holder != item.get_prefix()
+        {
+            return Err(StorageError::Duplicate {
+                constraint: index,
+                said: Some(holder),
+            });
+        }
+    }
+    update_index(tx, table, field, item).await
+}
+
+/// The prefix indexed under `key` for `field` of `table`.
+pub async fn find_indexed_prefix(
+    pool: &PgPool,
+    table: &str,
+    field: &str,
+    key: &str,
+) -> Result<Option<String>, StorageError> {
+    let query = ColumnQuery::new(index_table_name(table, field), "prefix")
+        .filter(Filter::Eq("key".to_string(), key.into()))
+        .limit(1);
+    Ok(pool.fetch_column(query).await?.into_iter().next())
+}
+
+/// The value of `field` in `item`'s serde form, as an index key.
+fn index_key<T: Storable + Serialize>(
+    item: &T,
+    field: &str,
+) -> Result<Option<String>, StorageError> {
+    let json_key = T::columns()
+        .iter()
+        .position(|column| *column == field)
+        .and_then(|position| T::json_keys().get(position))
+        .ok_or_else(|| {
+            StorageError::StorageError(format!(
+                "{} has no column {} to index",
+                T::table_name(),
+                field
+            ))
+        })?;
+    Ok(match serde_json::to_value(item)?.get(json_key) {
+        None | Some(serde_json::Value::Null) => None,
+        Some(serde_json::Value::String(key)) => Some(key.clone()),
+        Some(other) => Some(other.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(
+        Clone, Debug, serde::Serialize, serde::Deserialize, verifiable_storage::SelfAddressed,
+    )]
+    #[storable(table = "domains")]
+    #[serde(rename_all = "camelCase")]
+    struct Domain {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        display_name: Option<String>,
+    }
+
+    #[test]
+    fn keys_come_from_the_serialized_field() {
+        let mut domain = Domain::new(Some("example.com".to_string()));
+        domain.derive_prefix().unwrap();
+        assert_eq!(
+            index_key(&domain, "display_name").unwrap().as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(index_key(&domain, "version").unwrap().as_deref(), Some("0"));
+
+        domain.display_name = None;
+        assert_eq!(index_key(&domain, "display_name").unwrap(), None);
+        assert!(index_key(&domain, "missing").is_err());
+        assert_eq!(
+            index_table_name("domains", "display_name"),
+            "domains_display_name_index"
+        );
+    }
+}
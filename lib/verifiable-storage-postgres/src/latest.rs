@@ -0,0 +1,140 @@
+//! Latest-version pointers kept by database triggers.
+//!
+//! Where writers can't be trusted to maintain a `current` projection (other
+//! services, manual SQL), the trigger from [`latest_trigger_ddl`] keeps a
+//! `<table>_latest` companion holding each prefix's newest SAID and version.
+//! Inserts move the pointer forward, never back, and deleting the row it points
+//! at moves it to the newest remaining version. [`get_latest_tracked`] reads
+//! through it with a primary-key lookup instead of sorting the prefix's history.
+//!
+//! Repositories derived with `#[stored(latest_trigger)]` read `get_latest`
+//! this way and get an `ensure_latest_trigger()` method that installs it:
+//!
+//! ```text
+//! CREATE TABLE documents_latest (prefix TEXT PRIMARY KEY, said TEXT NOT NULL, version BIGINT NOT NULL);
+//! ```
+
+use serde::de::DeserializeOwned;
+use verifiable_storage::{ManagedField, Query, QueryExecutor, Storable, StorageError};
+
+use crate::PgPool;
+
+/// The pointer table of `table`.
+pub fn latest_table_name(table: &str) -> String {
+    format!("{}_latest", table)
+}
+
+/// Statements creating `table`'s pointer table and trigger, then filling the
+/// pointers from existing rows. Safe to re-run.
+pub fn latest_trigger_ddl<T: Storable>(table: &str) -> Vec<String> {
+    let latest = latest_table_name(table);
+    let base = table.replace('.', "_");
+    let function = format!("{}_track_latest", base);
+    let trigger = format!("{}_latest", base);
+    let prefix = T::managed_column(ManagedField::Prefix);
+    let said = T::managed_column(ManagedField::Said);
+    let version = T::managed_column(ManagedField::Version);
+    let upsert = format!(
+        "ON CONFLICT (prefix) DO UPDATE SET said = EXCLUDED.said, version = EXCLUDED.version \
+         WHERE {latest}.version < EXCLUDED.version"
+    );
+    vec![
+        format!(
+            "CREATE TABLE IF NOT EXISTS {latest} \
+             (prefix TEXT PRIMARY KEY, said TEXT NOT NULL, version BIGINT NOT NULL)"
+        ),
+        format!(
+            "CREATE OR REPLACE FUNCTION {function}() RETURNS trigger AS $$ \
+             BEGIN \
+             IF TG_OP = 'INSERT' THEN \
+             INSERT INTO {latest} VALUES (NEW.{prefix}, NEW.{said}, NEW.{version}) {upsert}; \
+             RETURN NEW; \
+             END IF; \
+             DELETE FROM {latest} WHERE said = OLD.{said}; \
+             INSERT INTO {latest} SELECT {prefix}, {said}, {version} FROM {table} \
+             WHERE {prefix} = OLD.{prefix} ORDER BY {version} DESC LIMIT 1 {upsert}; \
+             RETURN OLD; \
+             END; $$ LANGUAGE plpgsql"
+        ),
+        format!("DROP TRIGGER IF EXISTS {trigger} ON {table}"),
+        format!(
+            "CREATE TRIGGER {trigger} AFTER INSERT OR DELETE ON {table} \
+             FOR EACH ROW EXECUTE FUNCTION {function}()"
+        ),
+        format!(
+            "INSERT INTO {latest} SELECT DISTINCT ON ({prefix}) {prefix}, {said}, {version} \
+             FROM {table} ORDER BY {prefix}, {version} DESC {upsert}"
+        ),
+    ]
+}
+
+/// Install the pointer trigger on `table` and fill in its pointers.
+pub async fn ensure_latest_trigger<T: Storable>(
+    pool: &PgPool,
+    table: &str,
+) -> Result<(), StorageError> {
+    for statement in latest_trigger_ddl::<T>(table) {
+        pool.execute_sql(&statement, Vec::new()).await?;
+    }
+    Ok(())
+}
+
+/// The latest version of `prefix` in `table`, found through its pointer.
+pub async fn get_latest_tracked<T>(
+    pool: &PgPool,
+    table: &str,
+    prefix: &str,
+) -> Result<Option<T>, StorageError>
+where
+    T: Storable + DeserializeOwned + Send,
+{
+    let said = T::managed_column(ManagedField::Said);
+    let pointer = Query::<T>::for_table(latest_table_name(table))
+        .include_deleted()
+        .eq("prefix", prefix);
+    let query = Query::<T>::for_table(table)
+        .include_deleted()
+        .in_subquery(said, "said", pointer)
+        .limit(1);
+    pool.fetch_optional(query).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use verifiable_storage::{SelfAddressed, StorageDatetime};
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, SelfAddressed)]
+    #[storable(table = "documents")]
+    #[serde(rename_all = "camelCase")]
+    struct Document {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        #[created_at]
+        created_at: StorageDatetime,
+        body: String,
+    }
+
+    #[test]
+    fn pointers_only_move_forward() {
+        let ddl = latest_trigger_ddl::<Document>("docs.documents");
+        assert_eq!(ddl.len(), 5);
+        assert!(ddl[0].starts_with("CREATE TABLE IF NOT EXISTS docs.documents_latest"));
+        assert!(ddl[1].contains("FUNCTION docs_documents_track_latest()"));
+        assert!(ddl[1].contains("WHERE docs.documents_latest.version < EXCLUDED.version"));
+        assert_eq!(
+            ddl[3],
+            "CREATE TRIGGER docs_documents_latest AFTER INSERT OR DELETE ON docs.documents \
+             FOR EACH ROW EXECUTE FUNCTION docs_documents_track_latest()"
+        );
+        assert!(ddl[4].starts_with(
+            "INSERT INTO docs.documents_latest SELECT DISTINCT ON (prefix) prefix, said, version"
+        ));
+    }
+}
@@ -32,25 +32,71 @@
 //!     pool: PgPool,
 //! }
 //! ```
+//!
+//! Methods the builder can't express can be declared with `#[query]`:
+//!
+//! ```text
+//! impl MyRepository {
+//!     #[query("SELECT * FROM my_table WHERE name = $name")]
+//!     pub async fn named(&self, name: &str) -> Result<Vec<MyType>, StorageError>;
+//! }
+//! ```
+//!
+//! # Features
+//!
+//! - `cli`: the `vstorage` admin binary and [`run_cli`] for building one over a
+//!   service's own types (verify, chain, export/import, retention, schema diff)
+//! - `cbor`: bind `#[column(cbor)]` fields as canonical CBOR in `bytea` columns
 
 #![cfg_attr(
     test,
     allow(clippy::unwrap_used, clippy::expect_used, clippy::unwrap_in_result)
 )]
 
+#[cfg(feature = "cli")]
+mod cli;
+mod current;
+mod duplicate;
 mod executor;
+mod expiry;
+mod guard;
+mod index;
+mod latest;
+mod partition;
+mod raw;
+mod schema;
 mod serde_bind;
+mod shard;
 mod time;
 
-pub use executor::PgPool;
+#[cfg(feature = "cli")]
+pub use cli::{builtin_tables, run_cli};
+pub use current::{current_table_name, insert_with_current, rebuild_current, replace_current};
+pub use executor::{PgPool, PgTransaction};
+pub use expiry::archive_expired;
+pub use guard::{ALLOW_DELETE_SETTING, delete_guard_ddl, ensure_delete_guard};
+pub use index::{claim_unique_index, find_indexed_prefix, index_table_name, update_index};
+pub use latest::{
+    ensure_latest_trigger, get_latest_tracked, latest_table_name, latest_trigger_ddl,
+};
+pub use partition::{
+    DEFAULT_PARTITIONS_AHEAD, ensure_partitions, maintain_partitions, partition_clause,
+    partition_ddl,
+};
+pub use raw::check_raw_records;
+pub use schema::{
+    FINGERPRINT_TABLE, SchemaDiff, check_constraint_ddl, record_fingerprints, schema_diff,
+    verify_fingerprints,
+};
 pub use serde_bind::{
-    bind_insert_values, bind_insert_values_tx, bind_insert_with_table, bind_insert_with_table_tx,
-    deserialize_row,
+    bind_insert_or_ignore_with_table, bind_insert_values, bind_insert_values_tx,
+    bind_insert_with_table, bind_insert_with_table_tx, bind_upsert_with_table, deserialize_row,
 };
+pub use shard::shard_table_name;
 pub use time::PgStorageDatetime;
 
-// Re-export the derive macro
-pub use verifiable_storage_postgres_derive::Stored;
+// Re-export the derive and attribute macros
+pub use verifiable_storage_postgres_derive::{Stored, query};
 
 // Re-export sqlx migration types
 pub use sqlx::migrate;
@@ -58,8 +104,10 @@ pub use sqlx::migrate::Migrator;
 
 // Re-export core types for convenience
 pub use verifiable_storage::{
-    ColumnQuery, ConnectionConfig, Delete, Filter, Order, Query, QueryExecutor,
-    RepositoryConnection, SelfAddressed, Storable, StorageDatetime, StorageError,
-    TransactionExecutor, UnversionedRepository, Value, Versioned, VersionedRepository,
-    compute_said,
+    Aggregate, ColumnQuery, ConnectionConfig, Delete, Dialect, ExecutorMode, Filter, HistoryBundle,
+    Order, PartitionInterval, Partitioning, PrefixVersion, Projection, Query, QueryExecutor,
+    RecordSignature, Registry, RenderedStatement, RepositoryConnection, SchemaFingerprint,
+    SelfAddressed, SignedRepository, SqlParams, Storable, StorageDatetime, StorageError, Subquery,
+    TableInfo, TransactionExecutor, UnversionedRepository, Value, Versioned, VersionedRepository,
+    compute_said, compute_said_over, said_placeholder,
 };
@@ -0,0 +1,144 @@
+//! Partition management for partitioned tables.
+//!
+//! The parent table is created by migrations, using [`partition_clause`] for the
+//! `PARTITION BY` suffix. Note that PostgreSQL requires the partition column to be
+//! part of the primary key. Child partitions are created here: all hash
+//! partitions at once, and range partitions for the current interval plus a few
+//! ahead, so `initialize()` and periodic maintenance keep inserts from failing.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use verifiable_storage::{Partitioning, Storable, StorageError};
+
+/// Number of future range partitions to keep created ahead of time.
+pub const DEFAULT_PARTITIONS_AHEAD: u32 = 2;
+
+/// The `PARTITION BY ...` clause for a parent table's `CREATE TABLE`.
+pub fn partition_clause(partitioning: &Partitioning) -> String {
+    match partitioning {
+        Partitioning::Range { column, .. } => format!("PARTITION BY RANGE ({})", column),
+        Partitioning::Hash { column, .. } => format!("PARTITION BY HASH ({})", column),
+    }
+}
+
+/// DDL creating the child partitions of `table`.
+///
+/// For range partitioning this covers the interval containing `at` and the
+/// `ahead` intervals after it. For hash partitioning it covers every partition
+/// and `at`/`ahead` are ignored. Statements use `IF NOT EXISTS`, so running them
+/// repeatedly is safe.
+pub fn partition_ddl(
+    table: &str,
+    partitioning: &Partitioning,
+    at: DateTime<Utc>,
+    ahead: u32,
+) -> Vec<String> {
+    match partitioning {
+        Partitioning::Range { interval, .. } => {
+            let mut start = interval.start_of(at);
+            let mut statements = Vec::with_capacity(ahead as usize + 1);
+            for _ in 0..=ahead {
+                let end = interval.next(start);
+                statements.push(format!(
+                    "CREATE TABLE IF NOT EXISTS {table}_p{} PARTITION OF {table} FOR VALUES FROM ('{}') TO ('{}')",
+                    interval.suffix(start),
+                    start.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    end.to_rfc3339_opts(SecondsFormat::Secs, true),
+                ));
+                start = end;
+            }
+            statements
+        }
+        Partitioning::Hash { partitions, .. } => (0..*partitions)
+            .map(|remainder| {
+                format!(
+                    "CREATE TABLE IF NOT EXISTS {table}_p{remainder} PARTITION OF {table} FOR VALUES WITH (MODULUS {partitions}, REMAINDER {remainder})"
+                )
+            })
+            .collect(),
+    }
+}
+
+/// Create any missing partitions of `table` for `T`, as of `at`.
+///
+/// Does nothing if `T` is not partitioned.
+pub async fn ensure_partitions<T: Storable>(
+    pool: &sqlx::PgPool,
+    table: &str,
+    at: DateTime<Utc>,
+    ahead: u32,
+) -> Result<(), StorageError> {
+    let Some(partitioning) = T::partitioning() else {
+        return Ok(());
+    };
+
+    for statement in partition_ddl(table, &partitioning, at, ahead) {
+        sqlx::query(&statement)
+            .execute(pool)
+            .await
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Create any missing partitions of `table` for `T`, as of now.
+///
+/// Called from `initialize()` and suitable for running periodically so range
+/// partitions always exist [`DEFAULT_PARTITIONS_AHEAD`] intervals in advance.
+pub async fn maintain_partitions<T: Storable>(
+    pool: &sqlx::PgPool,
+    table: &str,
+) -> Result<(), StorageError> {
+    ensure_partitions::<T>(pool, table, Utc::now(), DEFAULT_PARTITIONS_AHEAD).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use verifiable_storage::PartitionInterval;
+
+    #[test]
+    fn range_ddl_covers_current_and_ahead() {
+        let partitioning = Partitioning::Range {
+            column: "created_at",
+            interval: PartitionInterval::Month,
+        };
+        let at = Utc.with_ymd_and_hms(2024, 11, 20, 8, 0, 0).unwrap();
+
+        let ddl = partition_ddl("events", &partitioning, at, 2);
+        assert_eq!(
+            ddl,
+            vec![
+                "CREATE TABLE IF NOT EXISTS events_p202411 PARTITION OF events FOR VALUES FROM ('2024-11-01T00:00:00Z') TO ('2024-12-01T00:00:00Z')",
+                "CREATE TABLE IF NOT EXISTS events_p202412 PARTITION OF events FOR VALUES FROM ('2024-12-01T00:00:00Z') TO ('2025-01-01T00:00:00Z')",
+                "CREATE TABLE IF NOT EXISTS events_p202501 PARTITION OF events FOR VALUES FROM ('2025-01-01T00:00:00Z') TO ('2025-02-01T00:00:00Z')",
+            ]
+        );
+        assert_eq!(
+            partition_clause(&partitioning),
+            "PARTITION BY RANGE (created_at)"
+        );
+    }
+
+    #[test]
+    fn hash_ddl_covers_every_remainder() {
+        let partitioning = Partitioning::Hash {
+            column: "prefix",
+            partitions: 2,
+        };
+
+        let ddl = partition_ddl("keys", &partitioning, Utc::now(), 5);
+        assert_eq!(
+            ddl,
+            vec![
+                "CREATE TABLE IF NOT EXISTS keys_p0 PARTITION OF keys FOR VALUES WITH (MODULUS 2, REMAINDER 0)",
+                "CREATE TABLE IF NOT EXISTS keys_p1 PARTITION OF keys FOR VALUES WITH (MODULUS 2, REMAINDER 1)",
+            ]
+        );
+        assert_eq!(
+            partition_clause(&partitioning),
+            "PARTITION BY HASH (prefix)"
+        );
+    }
+}
@@ -0,0 +1,57 @@
+//! Checking `#[storable(raw_column = "...")]` rows against their columns.
+//!
+//! Reads come from the raw record, so drift in how columns map values (a
+//! `TIMESTAMPTZ` dropping precision, a float reformatted) would otherwise go
+//! unseen. [`check_raw_records`] rebuilds every row from its columns and
+//! reports each one that no longer reproduces its raw record.
+
+use serde::de::DeserializeOwned;
+use verifiable_storage::{
+    ManagedField, RawCheckReport, SelfAddressed, Storable, StorageError, raw_drift,
+};
+
+use crate::PgPool;
+use crate::serde_bind::{deserialize_columns, raw_record};
+
+/// Compare each row of `T` in `table` that has a raw record with the record
+/// rebuilt from its columns, reading `batch_size` rows at a time.
+pub async fn check_raw_records<T: Storable + SelfAddressed + DeserializeOwned>(
+    pool: &PgPool,
+    table: &str,
+    batch_size: u64,
+) -> Result<RawCheckReport, StorageError> {
+    let raw = T::raw_column().ok_or_else(|| {
+        StorageError::StorageError(format!("{} has no raw column", T::table_name()))
+    })?;
+    let said = T::managed_column(ManagedField::Said);
+    let batch_size = batch_size.max(1);
+    let sql = format!(
+        "SELECT * FROM {} WHERE {} IS NOT NULL ORDER BY {} LIMIT $1 OFFSET $2",
+        table, raw, said
+    );
+
+    let mut report = RawCheckReport::default();
+    let mut offset = 0;
+    loop {
+        let rows = sqlx::query(&sql)
+            .bind(batch_size as i64)
+            .bind(offset as i64)
+            .fetch_all(pool.inner())
+            .await
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+        for row in &rows {
+            let Some(raw) = raw_record::<T>(row)? else {
+                continue;
+            };
+            let rebuilt: T = deserialize_columns(row)?;
+            report.checked += 1;
+            if let Some(drift) = raw_drift(&raw, &rebuilt)? {
+                report.drifted.push(drift);
+            }
+        }
+        if (rows.len() as u64) < batch_size {
+            return Ok(report);
+        }
+        offset += batch_size;
+    }
+}
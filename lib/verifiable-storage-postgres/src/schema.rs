@@ -0,0 +1,248 @@
+//! Comparing a table's live columns with its `Storable` metadata,
+//! recording and checking table layout fingerprints, and CHECK constraints
+//! mirroring `#[column(...)]` value constraints.
+
+use verifiable_storage::{ColumnConstraint, SchemaFingerprint, Storable, StorageError, TableInfo};
+
+use crate::PgPool;
+
+/// Differences between a table as declared and as found in the database.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchemaDiff {
+    pub table: String,
+    /// Declared columns the table lacks.
+    pub missing: Vec<String>,
+    /// Table columns the type doesn't declare.
+    pub extra: Vec<String>,
+    /// Columns whose database type doesn't fit the declared type: (column, declared, actual).
+    pub mismatched: Vec<(String, String, String)>,
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+/// Compare `table` with its columns in the current schema.
+pub async fn schema_diff(pool: &PgPool, table: &TableInfo) -> Result<SchemaDiff, StorageError> {
+    let actual: Vec<(String, String)> = sqlx::query_as(
+        "SELECT column_name::TEXT, data_type::TEXT FROM information_schema.columns \
+         WHERE table_schema = current_schema() AND table_name = $1 ORDER BY ordinal_position",
+    )
+    .bind(table.name)
+    .fetch_all(pool.inner())
+    .await
+    .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    Ok(diff_columns(table, &actual))
+}
+
+fn diff_columns(table: &TableInfo, actual: &[(String, String)]) -> SchemaDiff {
+    let mut diff = SchemaDiff {
+        table: table.name.to_string(),
+        ..Default::default()
+    };
+    for (column, declared) in &table.columns {
+        match actual.iter().find(|(name, _)| name == column) {
+            None => diff.missing.push(column.to_string()),
+            Some((_, data_type)) if !compatible(declared, data_type) => {
+                diff.mismatched
+                    .push((column.to_string(), declared.to_string(), data_type.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    diff.extra = actual
+        .iter()
+        .filter(|(name, _)| !table.columns.iter().any(|(column, _)| column == name))
+        .map(|(name, _)| name.clone())
+        .collect();
+    diff
+}
+
+/// The table holding each table's recorded layout fingerprint.
+pub const FINGERPRINT_TABLE: &str = "verifiable_storage_fingerprints";
+
+/// Check `fingerprints` against those recorded in [`FINGERPRINT_TABLE`].
+///
+/// Tables with nothing recorded, or a database without the table at all, pass.
+pub async fn verify_fingerprints(
+    pool: &PgPool,
+    fingerprints: &[SchemaFingerprint],
+) -> Result<(), StorageError> {
+    let (exists,): (bool,) = sqlx::query_as("SELECT to_regclass($1) IS NOT NULL")
+        .bind(FINGERPRINT_TABLE)
+        .fetch_one(pool.inner())
+        .await
+        .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    if !exists {
+        return Ok(());
+    }
+
+    let recorded: Vec<(String, String)> = sqlx::query_as(&format!(
+        "SELECT table_name, fingerprint FROM {}",
+        FINGERPRINT_TABLE
+    ))
+    .fetch_all(pool.inner())
+    .await
+    .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    for fingerprint in fingerprints {
+        let hash = recorded
+            .iter()
+            .find(|(table, _)| *table == fingerprint.table)
+            .map(|(_, hash)| hash.as_str());
+        fingerprint.check(hash)?;
+    }
+    Ok(())
+}
+
+/// Record `fingerprints` as the deployed layouts, replacing earlier ones.
+pub async fn record_fingerprints(
+    pool: &PgPool,
+    fingerprints: &[SchemaFingerprint],
+) -> Result<(), StorageError> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (\
+         table_name TEXT PRIMARY KEY, \
+         fingerprint TEXT NOT NULL, \
+         recorded_at TIMESTAMPTZ NOT NULL DEFAULT now())",
+        FINGERPRINT_TABLE
+    ))
+    .execute(pool.inner())
+    .await
+    .map_err(|e| StorageError::StorageError(e.to_string()))?;
+
+    let upsert = format!(
+        "INSERT INTO {} (table_name, fingerprint) VALUES ($1, $2) \
+         ON CONFLICT (table_name) DO UPDATE SET fingerprint = EXCLUDED.fingerprint, recorded_at = now()",
+        FINGERPRINT_TABLE
+    );
+    for fingerprint in fingerprints {
+        sqlx::query(&upsert)
+            .bind(&fingerprint.table)
+            .bind(&fingerprint.hash)
+            .execute(pool.inner())
+            .await
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Statements (re)creating a CHECK constraint on `table` for each of `T`'s
+/// column constraints, named `{table}_{column}_{kind}`.
+pub fn check_constraint_ddl<T: Storable>(table: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    for (column, constraint) in T::column_constraints() {
+        let (kind, check) = match constraint {
+            ColumnConstraint::MaxLength(max) => {
+                ("max_length", format!("char_length({}) <= {}", column, max))
+            }
+            ColumnConstraint::Pattern(pattern) => (
+                "pattern",
+                format!("{} ~ '{}'", column, pattern.replace('\'', "''")),
+            ),
+            ColumnConstraint::Range(min, max) => {
+                ("range", format!("{} BETWEEN {} AND {}", column, min, max))
+            }
+        };
+        let name = format!("{}_{}_{}", table.replace('.', "_"), column, kind);
+        statements.push(format!(
+            "ALTER TABLE {} DROP CONSTRAINT IF EXISTS {}",
+            table, name
+        ));
+        statements.push(format!(
+            "ALTER TABLE {} ADD CONSTRAINT {} CHECK ({})",
+            table, name, check
+        ));
+    }
+    statements
+}
+
+/// Whether a PostgreSQL `data_type` can hold a declared column type.
+fn compatible(declared: &str, data_type: &str) -> bool {
+    match declared {
+        "text" => matches!(data_type, "text" | "character varying" | "character"),
+        "datetime" => data_type.starts_with("timestamp"),
+        "bigint" => data_type == "bigint",
+        "integer" => matches!(data_type, "integer" | "bigint"),
+        "boolean" => data_type == "boolean",
+        "json" => matches!(data_type, "jsonb" | "json"),
+        "cbor" => data_type == "bytea",
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_reports_missing_extra_and_mismatched_columns() {
+        let table = TableInfo {
+            name: "docs",
+            versioned: false,
+            columns: vec![
+                ("said", "text"),
+                ("body", "text"),
+                ("created_at", "datetime"),
+            ],
+        };
+        let actual = [
+            ("said".to_string(), "text".to_string()),
+            ("created_at".to_string(), "text".to_string()),
+            ("legacy".to_string(), "integer".to_string()),
+        ];
+
+        let diff = diff_columns(&table, &actual);
+        assert_eq!(diff.missing, vec!["body"]);
+        assert_eq!(diff.extra, vec!["legacy"]);
+        assert_eq!(
+            diff.mismatched,
+            vec![(
+                "created_at".to_string(),
+                "datetime".to_string(),
+                "text".to_string()
+            )]
+        );
+        assert!(!diff.is_empty());
+    }
+
+    #[derive(
+        Clone, Debug, serde::Serialize, serde::Deserialize, verifiable_storage::SelfAddressed,
+    )]
+    #[storable(table = "handles")]
+    #[serde(rename_all = "camelCase")]
+    struct Handle {
+        #[said]
+        said: String,
+        #[column(max_length = 32, pattern = "^[a-z']+$")]
+        name: String,
+        #[column(range = "0..100")]
+        score: i64,
+    }
+
+    #[test]
+    fn check_constraints_mirror_column_constraints() {
+        use verifiable_storage::StorageError;
+
+        assert_eq!(
+            check_constraint_ddl::<Handle>("app.handles"),
+            vec![
+                "ALTER TABLE app.handles DROP CONSTRAINT IF EXISTS app_handles_name_max_length",
+                "ALTER TABLE app.handles ADD CONSTRAINT app_handles_name_max_length CHECK (char_length(name) <= 32)",
+                "ALTER TABLE app.handles DROP CONSTRAINT IF EXISTS app_handles_name_pattern",
+                "ALTER TABLE app.handles ADD CONSTRAINT app_handles_name_pattern CHECK (name ~ '^[a-z'']+$')",
+                "ALTER TABLE app.handles DROP CONSTRAINT IF EXISTS app_handles_score_range",
+                "ALTER TABLE app.handles ADD CONSTRAINT app_handles_score_range CHECK (score BETWEEN 0 AND 99)",
+            ]
+        );
+
+        Handle::create("o'neil".to_string(), 99).unwrap();
+        for (name, score) in [("Upper", 1), ("ok", 100), (&"x".repeat(33)[..], 1)] {
+            assert!(matches!(
+                Handle::create(name.to_string(), score),
+                Err(StorageError::ValidationFailed(_))
+            ));
+        }
+    }
+}
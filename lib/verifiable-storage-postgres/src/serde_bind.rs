@@ -6,7 +6,11 @@
 use serde::{Serialize, de::DeserializeOwned};
 use serde_json::Value;
 use sqlx::{Column, Row, postgres::PgRow};
-use verifiable_storage::{Storable, StorageError};
+use verifiable_storage::{
+    ManagedField, RenderedStatement, Storable, StorageDatetime, StorageError,
+};
+
+use crate::duplicate::db_error;
 
 /// Build INSERT SQL for a table with the given columns.
 fn build_insert_sql(table: &str, columns: &[&str]) -> String {
@@ -20,6 +24,158 @@ fn build_insert_sql(table: &str, columns: &[&str]) -> String {
     )
 }
 
+/// The columns an INSERT writes: `T::columns()`, then the raw column if any.
+fn insert_columns<T: Storable>() -> Vec<&'static str> {
+    T::columns()
+        .iter()
+        .copied()
+        .chain(T::raw_column())
+        .collect()
+}
+
+/// Serialize an item and extract its values in insert column order.
+fn insert_values<T: Storable + Serialize>(item: &T) -> Result<Vec<Value>, StorageError> {
+    let json = serde_json::to_value(item)
+        .map_err(|e| StorageError::StorageError(format!("Serialization error: {}", e)))?;
+
+    let obj = json.as_object().ok_or_else(|| {
+        StorageError::StorageError("Expected JSON object for Storable type".to_string())
+    })?;
+
+    // Use json_keys() to find values in the JSON
+    let mut values: Vec<Value> = T::json_keys()
+        .iter()
+        .map(|json_key| obj.get(*json_key).cloned().unwrap_or(Value::Null))
+        .collect();
+    // The raw column keeps the serialization byte for byte, as the SAID saw it
+    if T::raw_column().is_some() {
+        let raw = serde_json::to_string(item)
+            .map_err(|e| StorageError::StorageError(format!("Serialization error: {}", e)))?;
+        values.push(Value::String(raw));
+    }
+    Ok(values)
+}
+
+/// Serialize an item and bind its values in column order.
+fn build_insert_args<T: Storable + Serialize>(
+    item: &T,
+) -> Result<sqlx::postgres::PgArguments, StorageError> {
+    let mut args = sqlx::postgres::PgArguments::default();
+    let column_types = T::column_types();
+
+    for (idx, value) in insert_values(item)?.iter().enumerate() {
+        let col_type = column_types.get(idx).copied().unwrap_or("text");
+        bind_json_value(&mut args, value, col_type)?;
+    }
+
+    Ok(args)
+}
+
+/// What an INSERT does when a row with the same key already exists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OnConflict {
+    /// Fail with the database's duplicate key error.
+    Fail,
+    /// Skip the row, leaving the stored one as it is.
+    Ignore,
+    /// Rewrite the stored row where any of its columns differ.
+    Update,
+}
+
+/// Build the INSERT for `T` into `table`, with its conflict clause.
+fn insert_sql<T: Storable>(table: &str, on_conflict: OnConflict) -> String {
+    let columns = insert_columns::<T>();
+    let mut sql = build_insert_sql(table, &columns);
+    match on_conflict {
+        OnConflict::Fail => {}
+        OnConflict::Ignore => sql.push_str(" ON CONFLICT DO NOTHING"),
+        OnConflict::Update => sql.push_str(&upsert_clause::<T>(table, &columns)),
+    }
+    sql
+}
+
+/// `ON CONFLICT (said) DO UPDATE`, guarded so a row whose columns already
+/// match is left alone and doesn't count as affected.
+fn upsert_clause<T: Storable>(table: &str, columns: &[&str]) -> String {
+    // PostgreSQL requires the partition column in every unique key
+    let mut key = vec![T::managed_column(ManagedField::Said)];
+    if let Some(partitioning) = T::partitioning()
+        && !key.contains(&partitioning.column())
+    {
+        key.push(partitioning.column());
+    }
+
+    // A soft deletion mark isn't part of the record, so rewriting it can't undo one
+    let updated: Vec<&str> = columns
+        .iter()
+        .copied()
+        .filter(|column| !key.contains(column) && Some(*column) != T::deleted_at_column())
+        .collect();
+    if updated.is_empty() {
+        return " ON CONFLICT DO NOTHING".to_string();
+    }
+
+    let assignments: Vec<String> = updated
+        .iter()
+        .map(|column| format!("{} = EXCLUDED.{}", column, column))
+        .collect();
+    let stored: Vec<String> = updated
+        .iter()
+        .map(|column| format!("{}.{}", table, column))
+        .collect();
+    let excluded: Vec<String> = updated
+        .iter()
+        .map(|column| format!("EXCLUDED.{}", column))
+        .collect();
+    format!(
+        " ON CONFLICT ({}) DO UPDATE SET {} WHERE ({}) IS DISTINCT FROM ({})",
+        key.join(", "),
+        assignments.join(", "),
+        stored.join(", "),
+        excluded.join(", ")
+    )
+}
+
+/// Render the INSERT for an item without executing it (for dry runs).
+pub(crate) fn render_insert<T: Storable + Serialize>(
+    item: &T,
+    table: &str,
+    on_conflict: OnConflict,
+) -> Result<RenderedStatement, StorageError> {
+    let sql = insert_sql::<T>(table, on_conflict);
+
+    let params = insert_values(item)?
+        .into_iter()
+        .enumerate()
+        .map(|(idx, value)| (format!("${}", idx + 1), value))
+        .collect();
+
+    Ok(RenderedStatement { sql, params })
+}
+
+/// Bind an item's values and run its INSERT on a pool or transaction,
+/// returning the number of rows affected.
+pub(crate) async fn execute_insert<'e, T, E>(
+    executor: E,
+    item: &T,
+    table: &str,
+    on_conflict: OnConflict,
+) -> Result<u64, StorageError>
+where
+    T: Storable + Serialize,
+    E: sqlx::PgExecutor<'e>,
+{
+    let args = build_insert_args(item)?;
+
+    let sql = insert_sql::<T>(table, on_conflict);
+    let result = sqlx::query_with(&sql, args)
+        .execute(executor)
+        .await
+        .map_err(db_error)?;
+
+    Ok(result.rows_affected())
+}
+
 /// Bind a Storable type's values to a PostgreSQL INSERT query.
 ///
 /// Serializes the item to JSON, extracts values in column order (matching
@@ -46,30 +202,32 @@ pub async fn bind_insert_with_table<T: Storable + Serialize>(
     item: &T,
     table: &str,
 ) -> Result<u64, StorageError> {
-    let json = serde_json::to_value(item)
-        .map_err(|e| StorageError::StorageError(format!("Serialization error: {}", e)))?;
-
-    let obj = json.as_object().ok_or_else(|| {
-        StorageError::StorageError("Expected JSON object for Storable type".to_string())
-    })?;
-
-    // Build arguments dynamically using json_keys() to find values in the JSON
-    let mut args = sqlx::postgres::PgArguments::default();
-    let column_types = T::column_types();
-
-    for (idx, json_key) in T::json_keys().iter().enumerate() {
-        let value = obj.get(*json_key).cloned().unwrap_or(Value::Null);
-        let col_type = column_types.get(idx).copied().unwrap_or("text");
-        bind_json_value(&mut args, &value, col_type)?;
-    }
+    execute_insert(pool, item, table, OnConflict::Fail).await
+}
 
-    let sql = build_insert_sql(table, T::columns());
-    let result = sqlx::query_with(&sql, args)
-        .execute(pool)
-        .await
-        .map_err(|e| StorageError::StorageError(e.to_string()))?;
+/// Bind a Storable type's values to a PostgreSQL INSERT query that skips existing rows.
+///
+/// Appends `ON CONFLICT DO NOTHING`, so re-inserting an item whose key already
+/// exists is not an error. Returns 1 if the row was written, 0 if it was skipped.
+pub async fn bind_insert_or_ignore_with_table<T: Storable + Serialize>(
+    pool: &sqlx::PgPool,
+    item: &T,
+    table: &str,
+) -> Result<u64, StorageError> {
+    execute_insert(pool, item, table, OnConflict::Ignore).await
+}
 
-    Ok(result.rows_affected())
+/// Bind a Storable type's values to a PostgreSQL INSERT query that rewrites existing rows.
+///
+/// Appends `ON CONFLICT (said) DO UPDATE` for every other column, skipping the
+/// update when the stored row already matches. Returns 1 if a row was inserted
+/// or rewritten, 0 if it was left as it was.
+pub async fn bind_upsert_with_table<T: Storable + Serialize>(
+    pool: &sqlx::PgPool,
+    item: &T,
+    table: &str,
+) -> Result<u64, StorageError> {
+    execute_insert(pool, item, table, OnConflict::Update).await
 }
 
 /// Bind a Storable type's values to a PostgreSQL INSERT query within a transaction.
@@ -88,38 +246,67 @@ pub async fn bind_insert_with_table_tx<'a, T: Storable + Serialize>(
     item: &T,
     table: &str,
 ) -> Result<u64, StorageError> {
-    let json = serde_json::to_value(item)
-        .map_err(|e| StorageError::StorageError(format!("Serialization error: {}", e)))?;
-
-    let obj = json.as_object().ok_or_else(|| {
-        StorageError::StorageError("Expected JSON object for Storable type".to_string())
-    })?;
-
-    // Build arguments dynamically using json_keys() to find values in the JSON
-    let mut args = sqlx::postgres::PgArguments::default();
-    let column_types = T::column_types();
-
-    for (idx, json_key) in T::json_keys().iter().enumerate() {
-        let value = obj.get(*json_key).cloned().unwrap_or(Value::Null);
-        let col_type = column_types.get(idx).copied().unwrap_or("text");
-        bind_json_value(&mut args, &value, col_type)?;
-    }
-
-    let sql = build_insert_sql(table, T::columns());
-    let result = sqlx::query_with(&sql, args)
-        .execute(&mut **tx)
-        .await
-        .map_err(|e| StorageError::StorageError(e.to_string()))?;
-
-    Ok(result.rows_affected())
+    execute_insert(&mut **tx, item, table, OnConflict::Fail).await
 }
 
 /// Deserialize a PostgreSQL row to a Storable type.
 ///
-/// Extracts column values from the row using columns() and inserts them
-/// into JSON using json_keys() to match serde's field naming.
+/// Parses the raw column when the type has one and the row holds it.
+/// Otherwise extracts column values from the row using columns() and inserts
+/// them into JSON using json_keys() to match serde's field naming.
 /// Null values are omitted to match serde's skip_serializing_if behavior.
 pub fn deserialize_row<T: Storable + DeserializeOwned>(row: &PgRow) -> Result<T, StorageError> {
+    // Prefer the raw record where there is one; older rows fall back to columns
+    if let Some(raw) = raw_record::<T>(row)? {
+        let mut record: Value = serde_json::from_str(&raw)
+            .map_err(|e| StorageError::StorageError(format!("Deserialization error: {}", e)))?;
+        overlay_deleted_at::<T>(row, &mut record)?;
+        return serde_json::from_value(record)
+            .map_err(|e| StorageError::StorageError(format!("Deserialization error: {}", e)));
+    }
+    deserialize_columns(row)
+}
+
+/// Copy the row's `#[deleted_at]` column into a raw record, which was written
+/// before any soft deletion marked the row.
+fn overlay_deleted_at<T: Storable>(row: &PgRow, record: &mut Value) -> Result<(), StorageError> {
+    let Some(column) = T::deleted_at_column() else {
+        return Ok(());
+    };
+    let Some(json_key) = T::columns()
+        .iter()
+        .position(|c| *c == column)
+        .and_then(|position| T::json_keys().get(position))
+    else {
+        return Ok(());
+    };
+    let deleted_at = extract_column_value(row, column)?;
+    if let Value::Object(fields) = record
+        && !deleted_at.is_null()
+    {
+        fields.insert((*json_key).to_string(), deleted_at);
+    }
+    Ok(())
+}
+
+/// The row's raw record, if `T` keeps one and the row holds it.
+pub(crate) fn raw_record<T: Storable>(row: &PgRow) -> Result<Option<String>, StorageError> {
+    let Some(raw) = T::raw_column() else {
+        return Ok(None);
+    };
+    if !row.columns().iter().any(|column| column.name() == raw) {
+        return Ok(None);
+    }
+    Ok(match extract_column_value(row, raw)? {
+        Value::String(raw) => Some(raw),
+        _ => None,
+    })
+}
+
+/// Rebuild an item from the row's columns alone, ignoring any raw record.
+pub(crate) fn deserialize_columns<T: Storable + DeserializeOwned>(
+    row: &PgRow,
+) -> Result<T, StorageError> {
     let mut obj = serde_json::Map::new();
     let columns = T::columns();
     let json_keys = T::json_keys();
@@ -144,6 +331,12 @@ fn bind_json_value(
 ) -> Result<(), StorageError> {
     use sqlx::Arguments;
 
+    if col_type == "cbor" && !value.is_null() {
+        return args
+            .add(cbor_bytes(value)?)
+            .map_err(|e| StorageError::StorageError(e.to_string()));
+    }
+
     match value {
         Value::Null => {
             // Use column type to bind the correct null type
@@ -153,6 +346,7 @@ fn bind_json_value(
                 "integer" => args.add(None::<i32>),
                 "boolean" => args.add(None::<bool>),
                 "json" => args.add(None::<Value>),
+                "cbor" => args.add(None::<Vec<u8>>),
                 _ => args.add(None::<String>), // text and default
             }
             .map_err(|e| StorageError::StorageError(e.to_string()))?;
@@ -200,6 +394,37 @@ fn bind_json_value(
     Ok(())
 }
 
+#[cfg(feature = "cbor")]
+fn cbor_bytes(value: &Value) -> Result<Vec<u8>, StorageError> {
+    verifiable_storage::to_canonical_cbor(value)
+}
+
+#[cfg(feature = "cbor")]
+fn cbor_value(bytes: &[u8]) -> Result<Value, StorageError> {
+    verifiable_storage::from_cbor(bytes)
+}
+
+#[cfg(not(feature = "cbor"))]
+fn cbor_bytes(_: &Value) -> Result<Vec<u8>, StorageError> {
+    Err(StorageError::StorageError(
+        "cbor columns need the `cbor` feature".to_string(),
+    ))
+}
+
+#[cfg(not(feature = "cbor"))]
+fn cbor_value(_: &[u8]) -> Result<Value, StorageError> {
+    Err(StorageError::StorageError(
+        "cbor columns need the `cbor` feature".to_string(),
+    ))
+}
+
+/// Render a timestamp exactly as `StorageDatetime` serializes it, so the
+/// string read back is the one that went into the SAID.
+fn timestamp_value(datetime: chrono::DateTime<chrono::Utc>) -> Result<Value, StorageError> {
+    serde_json::to_value(StorageDatetime(datetime.into()))
+        .map_err(|e| StorageError::StorageError(e.to_string()))
+}
+
 /// Extract a column value from a row as JSON
 fn extract_column_value(row: &PgRow, col_name: &str) -> Result<Value, StorageError> {
     use sqlx::TypeInfo;
@@ -239,8 +464,14 @@ fn extract_column_value(row: &PgRow, col_name: &str) -> Result<Value, StorageErr
             let v: Option<chrono::DateTime<chrono::Utc>> = row
                 .try_get(col_idx)
                 .map_err(|e| StorageError::StorageError(e.to_string()))?;
-            // Use microsecond precision with Z to match StorageDatetime's serde format
-            v.map(|dt| Value::String(dt.to_rfc3339_opts(chrono::SecondsFormat::Micros, true)))
+            v.map(timestamp_value).transpose()?.unwrap_or(Value::Null)
+        }
+        "BYTEA" => {
+            let v: Option<Vec<u8>> = row
+                .try_get(col_idx)
+                .map_err(|e| StorageError::StorageError(e.to_string()))?;
+            v.map(|bytes| cbor_value(&bytes))
+                .transpose()?
                 .unwrap_or(Value::Null)
         }
         "JSONB" | "JSON" => {
@@ -260,3 +491,48 @@ fn extract_column_value(row: &PgRow, col_name: &str) -> Result<Value, StorageErr
 
     Ok(value)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use verifiable_storage::SelfAddressed;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, SelfAddressed)]
+    #[storable(table = "notes")]
+    #[serde(rename_all = "camelCase")]
+    struct Note {
+        #[said]
+        said: String,
+        #[deleted_at]
+        deleted_at: Option<StorageDatetime>,
+        body: String,
+    }
+
+    #[test]
+    fn upserts_leave_deletion_marks_alone() {
+        assert_eq!(
+            insert_sql::<Note>("notes", OnConflict::Update),
+            "INSERT INTO notes (said, deleted_at, body) VALUES ($1, $2, $3) \
+             ON CONFLICT (said) DO UPDATE SET body = EXCLUDED.body \
+             WHERE (notes.body) IS DISTINCT FROM (EXCLUDED.body)"
+        );
+    }
+
+    #[test]
+    fn timestamps_read_back_byte_for_byte() {
+        for text in [
+            "2024-05-01T12:00:00Z",
+            "2024-05-01T12:00:00.120000Z",
+            "2024-05-01T12:00:00.123456Z",
+        ] {
+            let datetime: chrono::DateTime<chrono::Utc> = text.parse().unwrap();
+            let stored = StorageDatetime(datetime.into());
+            let value = timestamp_value(datetime).unwrap();
+            assert_eq!(value, serde_json::to_value(&stored).unwrap());
+
+            let back: StorageDatetime = serde_json::from_value(value.clone()).unwrap();
+            assert_eq!(back, stored);
+            assert_eq!(serde_json::to_value(&back).unwrap(), value);
+        }
+    }
+}
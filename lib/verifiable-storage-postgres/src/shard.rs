@@ -0,0 +1,38 @@
+//! Table-name sharding for derived repositories.
+//!
+//! A `#[stored(sharded)]` repository constructed with `for_shard(pool, "2024")`
+//! reads and writes `{table}_2024` instead of `{table}`. Each shard table must
+//! already exist (created by migrations like any other table).
+
+use verifiable_storage::StorageError;
+
+/// Name of the shard table for `table` and `shard`, e.g. `domains_2024`.
+///
+/// The shard key is interpolated into SQL, so it must be non-empty and contain
+/// only ASCII letters, digits, and underscores.
+pub fn shard_table_name(table: &str, shard: &str) -> Result<String, StorageError> {
+    if shard.is_empty() || !shard.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(StorageError::StorageError(format!(
+            "Invalid shard key '{}': use only letters, digits, and underscores",
+            shard
+        )));
+    }
+    Ok(format!("{}_{}", table, shard))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_shard_key() {
+        assert_eq!(shard_table_name("domains", "2024").unwrap(), "domains_2024");
+    }
+
+    #[test]
+    fn rejects_unsafe_shard_keys() {
+        assert!(shard_table_name("domains", "").is_err());
+        assert!(shard_table_name("domains", "2024; DROP TABLE domains").is_err());
+        assert!(shard_table_name("domains", "eu-west").is_err());
+    }
+}
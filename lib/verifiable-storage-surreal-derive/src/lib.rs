@@ -8,7 +8,17 @@ use syn::{DeriveInput, Lit, parse_macro_input};
 /// - `impl VersionedRepository<T>` when `versioned = true` (default)
 /// - `impl UnversionedRepository<T>` when `versioned = false`
 ///
-/// Also generates a `new()` constructor that connects to SurrealDB.
+/// Also generates a `new()` constructor that connects to SurrealDB,
+/// `from_pool()` sharing the client of a `SurrealPool`, and `insert_or_ignore()`
+/// and `upsert()`, which write an item keyed by its SAID without failing when
+/// the record exists. Versioned repositories also get `version_count()` and
+/// `latest_version_number()`, answering from the prefix's rows without
+/// loading its history, and `list_prefixes()` and `list_prefix_versions()`,
+/// listing the distinct prefixes matching a query.
+///
+/// If the item has a `#[deleted_at]` field, `delete_soft()` marks a record
+/// deleted. Reads by SAID or prefix still return marked records, so histories
+/// stay verifiable; `Query` reads leave them out unless `include_deleted()`.
 ///
 /// The struct must have a `db: Surreal<Client>` field.
 ///
@@ -19,7 +29,14 @@ use syn::{DeriveInput, Lit, parse_macro_input};
 /// - `id_field`: The field name containing the SAID (default: "said")
 /// - `prefix_field`: The field name containing the prefix (default: "prefix", only used when versioned)
 /// - `versioned`: Whether to generate VersionedRepository (default: true)
-/// - `signatures`: Whether to generate signature storage methods (default: false, only for versioned)
+/// - `signatures`: A `RecordSignature` type; implements `SignedRepository<T, S>`, storing
+///   signatures in that type's own table, and adds `gc_orphans()`/`gc_orphans_with(collector)`
+///   removing signatures whose item is gone
+/// - `read_only`: Generate write methods that return `StorageError::ReadOnly` (default: false)
+/// - `schemafull`: Generate `define_schema()`, defining the table SCHEMAFULL with typed,
+///   asserted fields from the item's `Storable` metadata (default: false)
+/// - `unique_versions`: Fail `get_history` when a prefix has two records with the same
+///   version, instead of returning them ordered by `created_at` then SAID (default: false)
 ///
 /// Example (versioned):
 /// ```text
@@ -42,7 +59,7 @@ use syn::{DeriveInput, Lit, parse_macro_input};
 /// Example (versioned with signatures):
 /// ```text
 /// #[derive(Stored)]
-/// #[stored(item_type = KeyEvent, table = "key_events", namespace = "kels", signatures = true)]
+/// #[stored(item_type = KeyEvent, table = "key_events", namespace = "kels", signatures = EventSignature)]
 /// pub struct KeyEventRepository {
 ///     db: Surreal<Client>,
 /// }
@@ -66,7 +83,10 @@ pub fn derive_stored(input: TokenStream) -> TokenStream {
     let mut id_field = "said".to_string();
     let mut prefix_field = "prefix".to_string();
     let mut versioned = true;
-    let mut signatures = false;
+    let mut signatures: Option<syn::Type> = None;
+    let mut read_only = false;
+    let mut schemafull = false;
+    let mut unique_versions = false;
 
     stored_attr
         .parse_nested_meta(|meta| {
@@ -104,10 +124,31 @@ pub fn derive_stored(input: TokenStream) -> TokenStream {
                     versioned = b.value();
                 }
             } else if meta.path.is_ident("signatures") {
+                meta.input.parse::<syn::Token![=]>()?;
+                // `signatures = true` was the old form, before the signature type was named
+                if meta.input.peek(syn::LitBool) {
+                    return Err(meta.error(
+                        "#[stored(signatures = ...)] now names the signature type, e.g. `signatures = EventSignature`",
+                    ));
+                }
+                signatures = Some(meta.input.parse()?);
+            } else if meta.path.is_ident("read_only") {
+                meta.input.parse::<syn::Token![=]>()?;
+                let lit: Lit = meta.input.parse()?;
+                if let Lit::Bool(b) = lit {
+                    read_only = b.value();
+                }
+            } else if meta.path.is_ident("schemafull") {
                 meta.input.parse::<syn::Token![=]>()?;
                 let lit: Lit = meta.input.parse()?;
                 if let Lit::Bool(b) = lit {
-                    signatures = b.value();
+                    schemafull = b.value();
+                }
+            } else if meta.path.is_ident("unique_versions") {
+                meta.input.parse::<syn::Token![=]>()?;
+                let lit: Lit = meta.input.parse()?;
+                if let Lit::Bool(b) = lit {
+                    unique_versions = b.value();
                 }
             }
             Ok(())
@@ -134,7 +175,74 @@ pub fn derive_stored(input: TokenStream) -> TokenStream {
         "SELECT * FROM {} WHERE {} = $prefix LIMIT 1",
         table_name, prefix_field
     );
+    let version_count_query = format!(
+        "RETURN count(SELECT VALUE id FROM {} WHERE {} = $prefix)",
+        table_name, prefix_field
+    );
+    let latest_version_query = format!(
+        "SELECT VALUE version FROM {} WHERE {} = $prefix ORDER BY version DESC LIMIT 1",
+        table_name, prefix_field
+    );
+
+    // Read-only repositories reject writes before touching the database
+    let read_only_error = quote! {
+        verifiable_storage::StorageError::ReadOnly(format!(
+            "{} is a read-only repository",
+            stringify!(#repo_name)
+        ))
+    };
+    let insert_body = if read_only {
+        quote! {
+            let _ = item;
+            Err(#read_only_error)
+        }
+    } else {
+        quote! {
+            let _: Option<#item_type> = self.db
+                .create((#table_name, item.#id_field_ident.clone()))
+                .content(item.clone())
+                .await
+                .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
+            Ok(item)
+        }
+    };
+    let (insert_or_ignore_body, upsert_body) = if read_only {
+        let rejected = quote! {
+            let _ = item;
+            Err(#read_only_error)
+        };
+        (rejected.clone(), rejected)
+    } else {
+        (
+            quote! { verifiable_storage_surreal::insert_or_ignore_into(&self.db, #table_name, item).await },
+            quote! { verifiable_storage_surreal::upsert_into(&self.db, #table_name, item).await },
+        )
+    };
+    let delete_soft_body = if read_only {
+        quote! {
+            let _ = said;
+            Err(#read_only_error)
+        }
+    } else {
+        quote! {
+            verifiable_storage_surreal::soft_delete_in::<#item_type>(&self.db, #table_name, #id_field, said).await
+        }
+    };
+    let write_methods = quote! {
+        impl #repo_name {
+            /// Insert `item` unless a record with its SAID exists, returning
+            /// whether it was written.
+            pub async fn insert_or_ignore(&self, item: &#item_type) -> Result<bool, verifiable_storage::StorageError> {
+                #insert_or_ignore_body
+            }
 
+            /// Write `item` over any record with its SAID. SurrealDB doesn't
+            /// report whether the record changed, so this returns `true`.
+            pub async fn upsert(&self, item: &#item_type) -> Result<bool, verifiable_storage::StorageError> {
+                #upsert_body
+            }
+        }
+    };
     // Generate the new() constructor
     let new_impl = quote! {
         impl #repo_name {
@@ -156,110 +264,111 @@ pub fn derive_stored(input: TokenStream) -> TokenStream {
                     .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
                 Ok(Self { db })
             }
+
+            /// Share `pool`'s client, and so its namespace and database, e.g. from
+            /// an `Arc<SurrealPool>` in shared state.
+            pub fn from_pool(pool: &verifiable_storage_surreal::SurrealPool) -> Self {
+                Self { db: pool.inner().clone() }
+            }
         }
     };
 
-    // Generate signature methods if enabled
-    let signature_methods = if signatures {
+    let schema_method = if schemafull {
+        let signature_schema = signatures.as_ref().map(|signature_type| {
+            quote! {
+                verifiable_storage_surreal::define_schema::<#signature_type>(
+                    &self.db,
+                    <#signature_type as verifiable_storage::Storable>::table_name(),
+                )
+                .await?;
+            }
+        });
         quote! {
             impl #repo_name {
-                /// Store an item with its signature (item should already have SAID computed)
-                pub async fn create_with_signatures(
-                    &self,
-                    item: #item_type,
-                    signatures: Vec<adns::EventSignature>
-                ) -> Result<#item_type, verifiable_storage::StorageError> {
-                    use verifiable_storage::SelfAddressed;
-
-                    // Store the signatures separately
-                    for signature in &signatures {
-                        let sig = adns::EventSignature::create(
-                            item.#id_field_ident.clone(),
-                            signature.public_key.clone(),
-                            signature.signature.clone(),
-                        );
-                        let _: Option<adns::EventSignature> = self.db
-                            .create(("signatures", sig.said.clone()))
-                            .content(sig)
-                            .await
-                            .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
-                    }
-
-                    // Store the item
-                    let _: Option<#item_type> = self.db
-                        .create((#table_name, item.#id_field_ident.clone()))
-                        .content(item.clone())
-                        .await
-                        .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
-
-                    Ok(item)
+                /// Define the table, and any signatures table, SCHEMAFULL so
+                /// malformed records are rejected.
+                pub async fn define_schema(&self) -> Result<(), verifiable_storage::StorageError> {
+                    #signature_schema
+                    verifiable_storage_surreal::define_schema::<#item_type>(&self.db, #table_name).await
                 }
+            }
+        }
+    } else {
+        quote! {}
+    };
 
-                /// Get the signature for an item by its SAID
-                pub async fn get_signature_by_said(&self, said: &str) -> Result<Option<adns::EventSignature>, verifiable_storage::StorageError> {
-                    let mut result: Vec<adns::EventSignature> = self.db
-                        .query("SELECT * FROM signatures WHERE eventSaid = $said LIMIT 1")
-                        .bind(("said", said.to_string()))
-                        .await
-                        .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?
-                        .take(0)
-                        .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
-
-                    Ok(result.pop())
+    // SignedRepository, with the signatures in their own type's table
+    let signature_methods = if let Some(signature_type) = &signatures {
+        let repository = if versioned {
+            quote! { verifiable_storage::VersionedRepository<#item_type> }
+        } else {
+            quote! { verifiable_storage::UnversionedRepository<#item_type> }
+        };
+        let create_body = if read_only {
+            quote! {
+                let _ = (item, signatures);
+                Err(#read_only_error)
+            }
+        } else {
+            quote! {
+                verifiable_storage::check_signatures_cover(&item, &signatures)?;
+                for signature in &signatures {
+                    verifiable_storage_surreal::insert_or_ignore_into(
+                        &self.db,
+                        <#signature_type as verifiable_storage::Storable>::table_name(),
+                        signature,
+                    )
+                    .await?;
                 }
-
-                /// Get signatures for multiple SAIDs in one query (returns multiple sigs per event for recovery)
-                pub async fn get_signatures_by_saids(
+                <Self as #repository>::insert(self, item).await
+            }
+        };
+        let gc_body = if read_only {
+            quote! {
+                let _ = collector;
+                Err(#read_only_error)
+            }
+        } else {
+            quote! {
+                let pool = verifiable_storage_surreal::SurrealPool::new(self.db.clone());
+                collector.item_table(#table_name).run(&pool).await
+            }
+        };
+        quote! {
+            impl #repo_name {
+                /// Remove signatures whose item no longer exists, in batches.
+                pub async fn gc_orphans(
                     &self,
-                    saids: &[String],
-                ) -> Result<std::collections::HashMap<String, Vec<adns::EventSignature>>, verifiable_storage::StorageError> {
-                    let result: Vec<adns::EventSignature> = self.db
-                        .query("SELECT * FROM signatures WHERE $saids CONTAINS eventSaid")
-                        .bind(("saids", saids.to_vec()))
-                        .await
-                        .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?
-                        .take(0)
-                        .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
-
-                    let mut map: std::collections::HashMap<String, Vec<adns::EventSignature>> = std::collections::HashMap::new();
-                    for sig in result {
-                        map.entry(sig.event_said.clone()).or_default().push(sig);
-                    }
+                ) -> Result<verifiable_storage::OrphanReport, verifiable_storage::StorageError> {
+                    self.gc_orphans_with(verifiable_storage::OrphanCollector::new()).await
+                }
 
-                    Ok(map)
+                /// As `gc_orphans`, with `collector`'s batch size, grace period, archiving and
+                /// progress reporting. Items are looked up in this repository's table.
+                pub async fn gc_orphans_with(
+                    &self,
+                    collector: verifiable_storage::OrphanCollector<#item_type, #signature_type>,
+                ) -> Result<verifiable_storage::OrphanReport, verifiable_storage::StorageError> {
+                    #gc_body
                 }
+            }
 
-                /// Get the full signed history for a prefix (items with signatures)
-                pub async fn get_signed_history(
+
+            #[async_trait::async_trait]
+            impl verifiable_storage::SignedRepository<#item_type, #signature_type> for #repo_name {
+                async fn create_with_signatures(
                     &self,
-                    prefix: &str,
-                ) -> Result<Vec<adns::SignedKeyEvent>, verifiable_storage::StorageError> {
-                    use verifiable_storage::VersionedRepository;
-
-                    let events = <Self as verifiable_storage::VersionedRepository<#item_type>>::get_history(self, prefix).await?;
-                    let saids: Vec<String> = events.iter().map(|e| e.#id_field_ident.clone()).collect();
-                    let signatures = self.get_signatures_by_saids(&saids).await?;
-
-                    let mut signed_events = Vec::with_capacity(events.len());
-                    for event in events {
-                        let sigs = signatures.get(&event.#id_field_ident)
-                            .ok_or_else(|| verifiable_storage::StorageError::StorageError(
-                                format!("No signatures found for event {}", event.#id_field_ident)
-                            ))?;
-                        let sig_pairs: Vec<(String, String)> = sigs.iter()
-                            .map(|s| (s.public_key.clone(), s.signature.clone()))
-                            .collect();
-                        signed_events.push(adns::SignedKeyEvent::from_signatures(event, sig_pairs));
-                    }
-
-                    Ok(signed_events)
+                    item: #item_type,
+                    signatures: Vec<#signature_type>,
+                ) -> Result<#item_type, verifiable_storage::StorageError> {
+                    #create_body
                 }
 
-                /// Get the full KEL for a prefix as a Kel struct
-                pub async fn get_kel(&self, prefix: &str) -> Result<adns::Kel, verifiable_storage::StorageError> {
-                    let signed_events = self.get_signed_history(prefix).await?;
-                    adns::Kel::from_events(signed_events, false)
-                        .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))
+                async fn fetch_signatures(
+                    &self,
+                    saids: &[String],
+                ) -> Result<Vec<#signature_type>, verifiable_storage::StorageError> {
+                    verifiable_storage_surreal::signatures_in(&self.db, saids).await
                 }
             }
         }
@@ -289,12 +398,7 @@ pub fn derive_stored(input: TokenStream) -> TokenStream {
                 }
 
                 async fn insert(&self, item: #item_type) -> Result<#item_type, verifiable_storage::StorageError> {
-                    let _: Option<#item_type> = self.db
-                        .create((#table_name, item.#id_field_ident.clone()))
-                        .content(item.clone())
-                        .await
-                        .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
-                    Ok(item)
+                    #insert_body
                 }
 
                 async fn get_by_said(&self, said: &str) -> Result<Option<#item_type>, verifiable_storage::StorageError> {
@@ -303,6 +407,10 @@ pub fn derive_stored(input: TokenStream) -> TokenStream {
                     Ok(result)
                 }
 
+                async fn delete_soft(&self, said: &str) -> Result<bool, verifiable_storage::StorageError> {
+                    #delete_soft_body
+                }
+
                 async fn get_latest(&self, prefix: &str) -> Result<Option<#item_type>, verifiable_storage::StorageError> {
                     let mut result: Vec<#item_type> = self.db
                         .query(#get_latest_query)
@@ -320,8 +428,9 @@ pub fn derive_stored(input: TokenStream) -> TokenStream {
                         .bind(("prefix", prefix.to_string()))
                         .await
                         .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
-                    let result: Vec<#item_type> = response.take(0)
+                    let mut result: Vec<#item_type> = response.take(0)
                         .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
+                    verifiable_storage::order_history(&mut result, #unique_versions)?;
                     Ok(result)
                 }
 
@@ -337,6 +446,49 @@ pub fn derive_stored(input: TokenStream) -> TokenStream {
                 }
             }
 
+            impl #repo_name {
+                /// How many versions are stored for `prefix`.
+                pub async fn version_count(&self, prefix: &str) -> Result<u64, verifiable_storage::StorageError> {
+                    let count: Option<u64> = self.db
+                        .query(#version_count_query)
+                        .bind(("prefix", prefix.to_string()))
+                        .await
+                        .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?
+                        .take(0)
+                        .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
+                    Ok(count.unwrap_or(0))
+                }
+
+                /// The highest version stored for `prefix`, or `None` if it has none.
+                pub async fn latest_version_number(&self, prefix: &str) -> Result<Option<u64>, verifiable_storage::StorageError> {
+                    let mut versions: Vec<u64> = self.db
+                        .query(#latest_version_query)
+                        .bind(("prefix", prefix.to_string()))
+                        .await
+                        .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?
+                        .take(0)
+                        .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
+                    Ok(versions.pop())
+                }
+
+                /// The distinct prefixes matching `query`'s filters, ascending and paged
+                /// by its limit and offset. The query runs against this repository's table.
+                pub async fn list_prefixes(&self, query: verifiable_storage::Query<#item_type>) -> Result<Vec<String>, verifiable_storage::StorageError> {
+                    let listed = self.list_prefix_versions(query).await?;
+                    Ok(listed.into_iter().map(|listed| listed.prefix).collect())
+                }
+
+                /// As `list_prefixes`, with each prefix's highest matching version.
+                pub async fn list_prefix_versions(&self, mut query: verifiable_storage::Query<#item_type>) -> Result<Vec<verifiable_storage::PrefixVersion>, verifiable_storage::StorageError> {
+                    query.table = #table_name.to_string();
+                    verifiable_storage_surreal::list_prefix_versions_in(&self.db, &query).await
+                }
+            }
+
+            #write_methods
+
+            #schema_method
+
             #signature_methods
         }
     } else {
@@ -354,12 +506,7 @@ pub fn derive_stored(input: TokenStream) -> TokenStream {
                 }
 
                 async fn insert(&self, item: #item_type) -> Result<#item_type, verifiable_storage::StorageError> {
-                    let _: Option<#item_type> = self.db
-                        .create((#table_name, item.#id_field_ident.clone()))
-                        .content(item.clone())
-                        .await
-                        .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
-                    Ok(item)
+                    #insert_body
                 }
 
                 async fn get_by_said(&self, said: &str) -> Result<Option<#item_type>, verifiable_storage::StorageError> {
@@ -367,8 +514,16 @@ pub fn derive_stored(input: TokenStream) -> TokenStream {
                         .map_err(|e| verifiable_storage::StorageError::StorageError(e.to_string()))?;
                     Ok(result)
                 }
+
+                async fn delete_soft(&self, said: &str) -> Result<bool, verifiable_storage::StorageError> {
+                    #delete_soft_body
+                }
             }
 
+            #write_methods
+
+            #schema_method
+
             #signature_methods
         }
     };
@@ -1,41 +1,203 @@
 //! SurrealDB implementation of QueryExecutor.
 //!
 //! Note: Transactions are not implemented - the methods exist but don't create actual transactions.
-//! This is sufficient for ADNS which doesn't require transactional guarantees.
+//! This is sufficient for ADNS which doesn't require transactional guarantees. Advisory
+//! locks are emulated in-process, so they serialize callers sharing a pool but not
+//! other processes.
 
 use async_trait::async_trait;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use surrealdb::Surreal;
-use surrealdb::engine::remote::ws::Client;
+use surrealdb::engine::remote::ws::{Client, Ws};
+use surrealdb::opt::auth::Root;
 use verifiable_storage::{
-    ColumnQuery, Delete, Filter, Join, Order, Query, QueryExecutor, Storable, StorageError,
-    TransactionExecutor,
+    Aggregate, ColumnQuery, Delete, Dialect, DryRunLog, ExecutorMode, MetricsHook, NoopMetrics,
+    PrefixVersion, Query, QueryExecutor, RecordSignature, RenderedStatement, SqlParams, Storable,
+    StorageDatetime, StorageError, TransactionExecutor,
 };
 
+/// Backend name reported to metrics hooks.
+const BACKEND_NAME: &str = "surrealdb";
+
+/// In-process advisory locks by key, shared by a pool's clones.
+type AdvisoryLocks = Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>;
+
 /// Helper struct for deserializing count() results from SurrealDB.
 #[derive(Debug, Deserialize)]
 struct CountResult {
     count: u64,
 }
 
+/// Helper struct for deserializing aggregate results from SurrealDB.
+#[derive(Debug, Deserialize)]
+struct AggregateResult {
+    value: serde_json::Value,
+}
+
+/// Everything needed to (re-)establish an authenticated SurrealDB session.
+#[derive(Clone)]
+pub struct SurrealConnectOptions {
+    /// WebSocket endpoint, e.g. `localhost:8000`.
+    pub url: String,
+    /// Namespace selected after sign-in.
+    pub namespace: String,
+    /// Database selected after sign-in.
+    pub database: String,
+    /// Root username.
+    pub username: String,
+    /// Root password.
+    pub password: String,
+}
+
+impl std::fmt::Debug for SurrealConnectOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SurrealConnectOptions")
+            .field("url", &self.url)
+            .field("namespace", &self.namespace)
+            .field("database", &self.database)
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
 /// Wrapper around SurrealDB client to enable trait implementations.
 ///
 /// This wrapper exists to satisfy Rust's orphan rules - we can't implement
 /// `QueryExecutor` directly on `Surreal<Client>` since both are external types.
+///
+/// Pools created with [`SurrealPool::connect`] remember their connection options.
+/// The WebSocket engine re-opens a dropped connection by itself; when a read or
+/// write then fails because the session is no longer authenticated, the pool
+/// signs in again, reselects the namespace/database and retries the operation
+/// once. Writes are keyed by SAID, so a retried insert fails rather than
+/// storing the record twice. Pools wrapping an existing client via
+/// [`SurrealPool::new`] cannot sign in again and surface errors as-is.
+///
+/// In [`ExecutorMode::DryRun`] inserts and deletes are recorded instead of
+/// executed; collect them with [`SurrealPool::take_dry_run`].
 #[derive(Clone)]
-pub struct SurrealPool(Surreal<Client>);
+pub struct SurrealPool {
+    db: Surreal<Client>,
+    options: Option<Arc<SurrealConnectOptions>>,
+    reconnect_lock: Arc<tokio::sync::Mutex<()>>,
+    advisory_locks: AdvisoryLocks,
+    metrics: Arc<dyn MetricsHook>,
+    mode: ExecutorMode,
+    dry_run: DryRunLog,
+    guard_deletes: bool,
+}
 
 impl SurrealPool {
     /// Create a new SurrealPool wrapper.
     pub fn new(db: Surreal<Client>) -> Self {
-        Self(db)
+        Self {
+            db,
+            options: None,
+            reconnect_lock: Arc::new(tokio::sync::Mutex::new(())),
+            advisory_locks: AdvisoryLocks::default(),
+            metrics: Arc::new(NoopMetrics),
+            mode: ExecutorMode::Execute,
+            dry_run: DryRunLog::default(),
+            guard_deletes: false,
+        }
+    }
+
+    /// Connect to SurrealDB, sign in, and select the namespace and database.
+    ///
+    /// The options are retained so the pool can sign in again automatically.
+    pub async fn connect(options: SurrealConnectOptions) -> Result<Self, StorageError> {
+        let db = open_session(&options).await?;
+        let mut pool = Self::new(db);
+        pool.options = Some(Arc::new(options));
+        Ok(pool)
+    }
+
+    /// Attach a metrics hook that is notified of recovery attempts.
+    pub fn with_metrics(mut self, metrics: Arc<dyn MetricsHook>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Switch between executing writes and recording them.
+    pub fn with_mode(mut self, mode: ExecutorMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Refuse deletes from versioned tables, here and in this pool's
+    /// transactions, unless they call `allow_versioned_delete()`.
+    pub fn with_delete_guard(mut self) -> Self {
+        self.guard_deletes = true;
+        self
+    }
+
+    /// The current execution mode.
+    pub fn mode(&self) -> ExecutorMode {
+        self.mode
+    }
+
+    /// Remove and return the writes recorded in dry-run mode, oldest first.
+    pub fn take_dry_run(&self) -> Vec<RenderedStatement> {
+        self.dry_run.take()
+    }
+
+    /// The dry-run log, if writes should be recorded rather than executed.
+    fn dry_run_log(&self) -> Option<&DryRunLog> {
+        match self.mode {
+            ExecutorMode::Execute => None,
+            ExecutorMode::DryRun => Some(&self.dry_run),
+        }
     }
 
     /// Get the inner Surreal client.
     pub fn inner(&self) -> &Surreal<Client> {
-        &self.0
+        &self.db
+    }
+
+    /// Check that the server is reachable over the current session.
+    pub async fn ping(&self) -> Result<(), StorageError> {
+        self.db.health().await?;
+        Ok(())
+    }
+
+    /// Sign in again if the current session is no longer usable.
+    ///
+    /// Returns `Ok(true)` when the caller should retry its operation: either this
+    /// call re-established the session or another task already did. Returns
+    /// `Ok(false)` when the session is healthy (the failure was not a session
+    /// problem) or the pool has no options to sign in with.
+    pub async fn recover(&self) -> Result<bool, StorageError> {
+        recover_session(
+            &self.db,
+            self.options.as_deref(),
+            &self.reconnect_lock,
+            self.metrics.as_ref(),
+        )
+        .await
+    }
+
+    /// Spawn a background task that pings the server every `interval` and
+    /// recovers the session when it has died.
+    ///
+    /// The task runs until the returned handle is aborted.
+    pub fn spawn_liveness_check(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                if pool.ping().await.is_err() {
+                    // Failures are reported through the metrics hook; keep checking
+                    let _ = pool.recover().await;
+                }
+            }
+        })
     }
 }
 
@@ -43,74 +205,112 @@ impl Deref for SurrealPool {
     type Target = Surreal<Client>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.db
     }
 }
 
-/// Build a WHERE clause from filters for SurrealQL.
-fn build_where_clause(filters: &[Filter]) -> String {
-    if filters.is_empty() {
-        return String::new();
+/// What recovery needs from a connection, so it can be exercised without a server.
+#[async_trait]
+trait Session: Clone + Send + Sync {
+    /// Whether an authenticated statement still succeeds.
+    async fn is_alive(&self) -> bool;
+
+    /// Sign in and select the namespace and database.
+    async fn sign_in(&self, options: &SurrealConnectOptions) -> Result<(), StorageError>;
+}
+
+#[async_trait]
+impl Session for Surreal<Client> {
+    async fn is_alive(&self) -> bool {
+        // INFO needs a signed-in session with a database selected
+        self.query("INFO FOR DB")
+            .await
+            .and_then(|response| response.check())
+            .is_ok()
     }
 
-    let clauses: Vec<String> = filters
-        .iter()
-        .enumerate()
-        .map(|(i, filter)| {
-            let param = format!("$p{}", i);
-            match filter {
-                Filter::Eq(field, _) => format!("{} = {}", field, param),
-                Filter::Ne(field, _) => format!("{} != {}", field, param),
-                Filter::Gt(field, _) => format!("{} > {}", field, param),
-                Filter::Gte(field, _) => format!("{} >= {}", field, param),
-                Filter::Lt(field, _) => format!("{} < {}", field, param),
-                Filter::Lte(field, _) => format!("{} <= {}", field, param),
-                Filter::In(field, _) => format!("{} CONTAINS {}", param, field),
-                Filter::IsNull(field) => format!("{} IS NULL", field),
-                Filter::IsNotNull(field) => format!("{} IS NOT NULL", field),
-            }
+    async fn sign_in(&self, options: &SurrealConnectOptions) -> Result<(), StorageError> {
+        self.signin(Root {
+            username: &options.username,
+            password: &options.password,
         })
-        .collect();
+        .await?;
+        self.use_ns(options.namespace.as_str())
+            .use_db(options.database.as_str())
+            .await?;
+        Ok(())
+    }
+}
 
-    format!(" WHERE {}", clauses.join(" AND "))
+/// Open a new authenticated session using the given options.
+async fn open_session(options: &SurrealConnectOptions) -> Result<Surreal<Client>, StorageError> {
+    let db = Surreal::new::<Ws>(options.url.as_str()).await?;
+    db.sign_in(options).await?;
+    Ok(db)
 }
 
-/// Build ORDER BY clause for SurrealQL.
-fn build_order_clause(order_by: &[(String, Order)]) -> String {
-    if order_by.is_empty() {
-        return String::new();
+/// Sign `session` in again if it has died, reporting the attempt to `metrics`.
+/// See [`SurrealPool::recover`].
+async fn recover_session<S: Session>(
+    session: &S,
+    options: Option<&SurrealConnectOptions>,
+    reconnect_lock: &tokio::sync::Mutex<()>,
+    metrics: &dyn MetricsHook,
+) -> Result<bool, StorageError> {
+    let Some(options) = options else {
+        return Ok(false);
+    };
+    if session.is_alive().await {
+        return Ok(false);
     }
 
-    let clauses: Vec<String> = order_by
-        .iter()
-        .map(|(field, order)| {
-            let dir = match order {
-                Order::Asc => "ASC",
-                Order::Desc => "DESC",
-            };
-            format!("{} {}", field, dir)
-        })
-        .collect();
+    let _guard = reconnect_lock.lock().await;
 
-    format!(" ORDER BY {}", clauses.join(", "))
-}
+    // Another task may have signed in while we waited for the lock
+    if session.is_alive().await {
+        return Ok(true);
+    }
 
-/// Build JOIN clauses for SurrealQL.
-fn build_join_clause(main_table: &str, joins: &[Join]) -> String {
-    if joins.is_empty() {
-        return String::new();
+    match session.sign_in(options).await {
+        Ok(()) => {
+            metrics.on_reconnect(BACKEND_NAME, true);
+            Ok(true)
+        }
+        Err(e) => {
+            metrics.on_reconnect(BACKEND_NAME, false);
+            Err(e)
+        }
     }
+}
 
-    joins
-        .iter()
-        .map(|join| {
-            format!(
-                " INNER JOIN {} ON {}.{} = {}.{}",
-                join.table, main_table, join.left_field, join.table, join.right_field
-            )
-        })
-        .collect::<Vec<_>>()
-        .join("")
+/// Run `op`, recovering the session and retrying once if it fails because the
+/// session died. `op` must be safe to repeat: a read, or a write keyed so a
+/// second attempt lands on the same record.
+async fn retry_with_recovery<S, R, E, F, Fut>(
+    session: &S,
+    options: Option<&SurrealConnectOptions>,
+    reconnect_lock: &tokio::sync::Mutex<()>,
+    metrics: &dyn MetricsHook,
+    op: F,
+) -> Result<R, StorageError>
+where
+    S: Session,
+    E: std::fmt::Display,
+    F: Fn(S) -> Fut,
+    Fut: std::future::Future<Output = Result<R, E>>,
+{
+    match op(session.clone()).await {
+        Ok(result) => Ok(result),
+        Err(e) => {
+            if recover_session(session, options, reconnect_lock, metrics).await? {
+                op(session.clone())
+                    .await
+                    .map_err(|e| StorageError::StorageError(e.to_string()))
+            } else {
+                Err(StorageError::StorageError(e.to_string()))
+            }
+        }
+    }
 }
 
 /// Helper to bind a Value to a SurrealDB query.
@@ -127,73 +327,323 @@ fn bind_value<'a, C: surrealdb::Connection>(
         verifiable_storage::Value::Bool(b) => q.bind((param.to_owned(), *b)),
         verifiable_storage::Value::Strings(v) => q.bind((param.to_owned(), v.clone())),
         verifiable_storage::Value::Datetime(dt) => q.bind((param.to_owned(), dt.inner().clone())),
+        verifiable_storage::Value::Json(v) => q.bind((param.to_owned(), v.clone())),
         verifiable_storage::Value::Null => q.bind((param.to_owned(), Option::<String>::None)),
     }
 }
 
-#[async_trait]
-impl QueryExecutor for SurrealPool {
-    type Transaction = SurrealTransaction;
+/// Bind rendered parameters by name (placeholders without the `$`).
+fn bind_params<'a, C: surrealdb::Connection>(
+    mut q: surrealdb::method::Query<'a, C>,
+    params: &SqlParams,
+) -> surrealdb::method::Query<'a, C> {
+    for (name, value) in params {
+        q = bind_value(q, name.trim_start_matches('$'), value);
+    }
+    q
+}
 
-    async fn fetch<T: Storable + DeserializeOwned + Send>(
-        &self,
-        query: Query<T>,
-    ) -> Result<Vec<T>, StorageError> {
-        let join_clause = build_join_clause(&query.table, &query.joins);
-        let where_clause = build_where_clause(&query.filters);
-        let order_clause = build_order_clause(&query.order_by);
-
-        // Build GROUP BY clause if distinct_on is specified
-        // SurrealDB's GROUP BY returns one row per unique combination
-        let group_clause = if query.distinct_on.is_empty() {
-            String::new()
-        } else {
-            format!(" GROUP BY {}", query.distinct_on.join(", "))
-        };
+/// Render a statement without executing it (for dry runs).
+fn render((sql, params): (String, SqlParams)) -> RenderedStatement {
+    RenderedStatement {
+        sql,
+        params: params
+            .into_iter()
+            .map(|(name, value)| (name, value.to_json()))
+            .collect(),
+    }
+}
 
-        // Use table.* when joining to only return columns from the main table
-        let select_cols = if query.joins.is_empty() {
-            "*".to_string()
-        } else {
-            format!("{}.*", query.table)
-        };
+/// Render an ignoring insert of a serialized item without executing it (for dry runs).
+fn render_insert_ignore(table: &str, value: serde_json::Value) -> RenderedStatement {
+    RenderedStatement {
+        sql: format!("INSERT IGNORE INTO {} $item", table),
+        params: vec![("$item".to_string(), value)],
+    }
+}
+
+/// Render a write of a serialized item to the record keyed by its SAID
+/// without executing it (for dry runs).
+fn render_keyed(sql: &str, table: &str, id: &str, value: serde_json::Value) -> RenderedStatement {
+    RenderedStatement {
+        sql: sql.to_string(),
+        params: vec![
+            (
+                "$table".to_string(),
+                serde_json::Value::String(table.to_string()),
+            ),
+            ("$id".to_string(), serde_json::Value::String(id.to_string())),
+            ("$item".to_string(), value),
+        ],
+    }
+}
+
+/// Serialize an item for insertion, adding its raw record if `T` keeps one.
+fn insert_document<T: Storable + Serialize>(item: &T) -> Result<serde_json::Value, StorageError> {
+    let mut value =
+        serde_json::to_value(item).map_err(|e| StorageError::StorageError(e.to_string()))?;
+    if let Some(raw) = T::raw_column()
+        && let Some(obj) = value.as_object_mut()
+    {
+        let record =
+            serde_json::to_string(item).map_err(|e| StorageError::StorageError(e.to_string()))?;
+        obj.insert(raw.to_string(), serde_json::Value::String(record));
+    }
+    Ok(value)
+}
 
-        let mut sql = format!(
-            "SELECT {} FROM {}{}{}{}{}",
-            select_cols, query.table, join_clause, where_clause, group_clause, order_clause
+/// Serialize an item keyed by its SAID, so a second insert of it conflicts.
+fn keyed_document<T: Storable + Serialize>(item: &T) -> Result<serde_json::Value, StorageError> {
+    let mut value = insert_document(item)?;
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert(
+            "id".to_string(),
+            serde_json::Value::String(item.id().to_string()),
         );
+    }
+    Ok(value)
+}
 
-        if let Some(limit) = query.limit {
-            sql.push_str(&format!(" LIMIT {}", limit));
-        }
-        if let Some(offset) = query.offset {
-            sql.push_str(&format!(" START {}", offset));
+/// Create the record keyed by an item's SAID, so a second insert of it fails
+/// rather than storing it twice.
+const CREATE_SQL: &str = "CREATE type::thing($table, $id) CONTENT $item";
+
+/// The statement and document upserting `item`. A soft deletion mark isn't
+/// part of the record, so it is left out and merged around, keeping any mark
+/// the stored record has.
+fn upsert_document<T: Storable + Serialize>(
+    item: &T,
+) -> Result<(&'static str, serde_json::Value), StorageError> {
+    let mut value = insert_document(item)?;
+    let deleted_at = T::deleted_at_column().and_then(|column| {
+        T::columns()
+            .iter()
+            .position(|c| *c == column)
+            .and_then(|position| T::json_keys().get(position))
+    });
+    match (deleted_at, value.as_object_mut()) {
+        (Some(key), Some(obj)) => {
+            obj.remove(*key);
+            Ok(("UPSERT type::thing($table, $id) MERGE $item", value))
         }
+        _ => Ok(("UPSERT type::thing($table, $id) CONTENT $item", value)),
+    }
+}
 
-        let mut q = self.0.query(&sql);
-
-        // Bind filter values
-        for (i, filter) in query.filters.iter().enumerate() {
-            let param = format!("p{}", i);
-            q = match filter {
-                Filter::Eq(_, v)
-                | Filter::Ne(_, v)
-                | Filter::Gt(_, v)
-                | Filter::Gte(_, v)
-                | Filter::Lt(_, v)
-                | Filter::Lte(_, v)
-                | Filter::In(_, v) => bind_value(q, &param, v),
-                Filter::IsNull(_) | Filter::IsNotNull(_) => q,
-            };
-        }
+/// Rebuild records from rows fetched as JSON, parsing each row's `raw`
+/// column where it holds one and falling back to the row itself.
+fn from_raw_rows<T: DeserializeOwned>(
+    rows: Vec<serde_json::Value>,
+    raw: &str,
+) -> Result<Vec<T>, StorageError> {
+    rows.into_iter()
+        .map(
+            |row| match row.get(raw).and_then(|record| record.as_str()) {
+                Some(record) => serde_json::from_str(record),
+                None => serde_json::from_value(row),
+            },
+        )
+        .collect::<Result<_, _>>()
+        .map_err(|e| StorageError::StorageError(e.to_string()))
+}
 
-        let result: Vec<T> = q
+/// Run a statement with bound parameters and deserialize the first result set.
+async fn fetch_rows<T: DeserializeOwned>(
+    db: &Surreal<Client>,
+    sql: &str,
+    params: &SqlParams,
+) -> Result<Vec<T>, surrealdb::Error> {
+    bind_params(db.query(sql), params).await?.take(0)
+}
+
+/// Run a `count()` statement with bound parameters.
+async fn fetch_count(
+    db: &Surreal<Client>,
+    sql: &str,
+    params: &SqlParams,
+) -> Result<u64, surrealdb::Error> {
+    let result: Option<CountResult> = bind_params(db.query(sql), params).await?.take(0)?;
+    Ok(result.map(|r| r.count).unwrap_or(0))
+}
+
+/// Run a statement with bound parameters, discarding its result.
+async fn execute(
+    db: &Surreal<Client>,
+    sql: &str,
+    params: &SqlParams,
+) -> Result<(), surrealdb::Error> {
+    bind_params(db.query(sql), params).await?;
+    Ok(())
+}
+
+/// Insert a serialized item keyed by its SAID, skipping it if the record exists.
+///
+/// Returns the number of records written (0 or 1).
+async fn insert_value_ignore(
+    db: &Surreal<Client>,
+    table: &str,
+    value: serde_json::Value,
+) -> Result<u64, surrealdb::Error> {
+    let inserted: Vec<serde::de::IgnoredAny> = db
+        .query(format!("INSERT IGNORE INTO {} $item", table))
+        .bind(("item", value))
+        .await?
+        .take(0)?;
+    Ok(inserted.len() as u64)
+}
+
+/// Run `sql` writing a serialized item to the record keyed by its SAID:
+/// [`CREATE_SQL`], failing if the record exists, or an upsert replacing it.
+async fn write_keyed(
+    db: &Surreal<Client>,
+    sql: &str,
+    table: &str,
+    id: &str,
+    value: serde_json::Value,
+) -> Result<(), surrealdb::Error> {
+    db.query(sql)
+        .bind(("table", table.to_string()))
+        .bind(("id", id.to_string()))
+        .bind(("item", value))
+        .await?
+        .check()?;
+    Ok(())
+}
+
+/// Insert `item` into `table` keyed by its SAID, unless that record exists.
+///
+/// Returns whether the item was written. Generated repositories call this
+/// from their `insert_or_ignore`.
+pub async fn insert_or_ignore_into<T: Storable + Serialize>(
+    db: &Surreal<Client>,
+    table: &str,
+    item: &T,
+) -> Result<bool, StorageError> {
+    let value = keyed_document(item)?;
+    let written = insert_value_ignore(db, table, value)
+        .await
+        .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    Ok(written > 0)
+}
+
+/// The distinct prefixes `query` matches, each with its highest matching
+/// version. Generated repositories call this from their `list_prefixes` and
+/// `list_prefix_versions`.
+pub async fn list_prefix_versions_in<T: Storable>(
+    db: &Surreal<Client>,
+    query: &Query<T>,
+) -> Result<Vec<PrefixVersion>, StorageError> {
+    let (sql, params) = query.to_prefix_sql(Dialect::Surreal);
+    fetch_rows(db, &sql, &params)
+        .await
+        .map_err(|e| StorageError::StorageError(e.to_string()))
+}
+
+/// Mark the live record in `table` with SAID `said` as deleted, returning
+/// whether one was marked. Generated repositories call this from their
+/// `delete_soft`.
+pub async fn soft_delete_in<T: Storable>(
+    db: &Surreal<Client>,
+    table: &str,
+    said_column: &str,
+    said: &str,
+) -> Result<bool, StorageError> {
+    let (sql, params) = Delete::<T>::for_table(table)
+        .eq(said_column, said)
+        .to_soft_delete_sql(StorageDatetime::now(), Dialect::Surreal)?;
+    let marked: Vec<serde::de::IgnoredAny> = fetch_rows(db, &sql, &params)
+        .await
+        .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    Ok(!marked.is_empty())
+}
+
+/// The stored `S` signatures over any of `saids`. Generated repositories
+/// call this from their `fetch_signatures`.
+pub async fn signatures_in<S: RecordSignature>(
+    db: &Surreal<Client>,
+    saids: &[String],
+) -> Result<Vec<S>, StorageError> {
+    // Records are stored under their serde field names, not column names
+    let column = S::signed_said_column();
+    let field = S::columns()
+        .iter()
+        .position(|c| *c == column)
+        .and_then(|position| S::json_keys().get(position).copied())
+        .unwrap_or(column);
+    let (sql, params) = Query::<S>::new()
+        .r#in(field, saids.to_vec())
+        .to_sql(Dialect::Surreal);
+    fetch_rows(db, &sql, &params)
+        .await
+        .map_err(|e| StorageError::StorageError(e.to_string()))
+}
+
+/// Write `item` to the record in `table` keyed by its SAID, replacing any
+/// record already there.
+///
+/// SurrealDB doesn't report whether the record changed, so this always
+/// returns `true`. Generated repositories call this from their `upsert`.
+pub async fn upsert_into<T: Storable + Serialize>(
+    db: &Surreal<Client>,
+    table: &str,
+    item: &T,
+) -> Result<bool, StorageError> {
+    let (sql, value) = upsert_document(item)?;
+    write_keyed(db, sql, table, item.id(), value)
+        .await
+        .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    Ok(true)
+}
+
+impl SurrealPool {
+    /// Run a read or keyed write against the client, signing in again and
+    /// retrying once if it fails because the session died.
+    async fn with_recovery<R, F, Fut>(&self, op: F) -> Result<R, StorageError>
+    where
+        F: Fn(Surreal<Client>) -> Fut + Send + Sync,
+        Fut: std::future::Future<Output = Result<R, surrealdb::Error>> + Send,
+        R: Send,
+    {
+        retry_with_recovery(
+            &self.db,
+            self.options.as_deref(),
+            &self.reconnect_lock,
+            self.metrics.as_ref(),
+            op,
+        )
+        .await
+    }
+
+    /// Run a SELECT of `T`'s rows, rebuilding them from raw records where `T`
+    /// keeps them.
+    async fn fetch_items<T: Storable + DeserializeOwned + Send>(
+        &self,
+        sql: &str,
+        params: &SqlParams,
+    ) -> Result<Vec<T>, StorageError> {
+        if let Some(raw) = T::raw_column() {
+            let rows = self
+                .with_recovery(|db| async move {
+                    fetch_rows::<serde_json::Value>(&db, sql, params).await
+                })
+                .await?;
+            return from_raw_rows(rows, raw);
+        }
+        self.with_recovery(|db| async move { fetch_rows::<T>(&db, sql, params).await })
             .await
-            .map_err(|e| StorageError::StorageError(e.to_string()))?
-            .take(0)
-            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    }
+}
+
+#[async_trait]
+impl QueryExecutor for SurrealPool {
+    type Transaction = SurrealTransaction;
 
-        Ok(result)
+    async fn fetch<T: Storable + DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+    ) -> Result<Vec<T>, StorageError> {
+        let (sql, params) = query.to_sql(Dialect::Surreal);
+        self.fetch_items(&sql, &params).await
     }
 
     async fn fetch_optional<T: Storable + DeserializeOwned + Send>(
@@ -208,78 +658,164 @@ impl QueryExecutor for SurrealPool {
     }
 
     async fn exists<T: Storable + Send>(&self, query: Query<T>) -> Result<bool, StorageError> {
-        let where_clause = build_where_clause(&query.filters);
-        let sql = format!(
-            "SELECT count() FROM {}{} GROUP ALL",
-            query.table, where_clause
-        );
+        let (sql, params) = query.to_exists_sql(Dialect::Surreal);
+        let (sql, params) = (sql.as_str(), &params);
 
-        let mut q = self.0.query(&sql);
-
-        for (i, filter) in query.filters.iter().enumerate() {
-            let param = format!("p{}", i);
-            q = match filter {
-                Filter::Eq(_, v)
-                | Filter::Ne(_, v)
-                | Filter::Gt(_, v)
-                | Filter::Gte(_, v)
-                | Filter::Lt(_, v)
-                | Filter::Lte(_, v)
-                | Filter::In(_, v) => bind_value(q, &param, v),
-                Filter::IsNull(_) | Filter::IsNotNull(_) => q,
-            };
-        }
+        let count = self
+            .with_recovery(|db| async move { fetch_count(&db, sql, params).await })
+            .await?;
+
+        Ok(count > 0)
+    }
 
-        let result: Option<CountResult> = q
+    async fn count<T: Storable + Send>(&self, query: Query<T>) -> Result<u64, StorageError> {
+        let (sql, params) = query.to_count_sql(Dialect::Surreal);
+        let (sql, params) = (sql.as_str(), &params);
+
+        self.with_recovery(|db| async move { fetch_count(&db, sql, params).await })
             .await
-            .map_err(|e| StorageError::StorageError(e.to_string()))?
-            .take(0)
-            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    }
+
+    async fn aggregate<T: Storable + Send>(
+        &self,
+        query: Query<T>,
+        aggregate: Aggregate,
+        column: &str,
+    ) -> Result<Option<serde_json::Value>, StorageError> {
+        let (sql, params) = query.to_aggregate_sql(aggregate, column, Dialect::Surreal);
+        let (sql, params) = (sql.as_str(), &params);
+
+        let rows = self
+            .with_recovery(
+                |db| async move { fetch_rows::<AggregateResult>(&db, sql, params).await },
+            )
+            .await?;
+        Ok(rows.into_iter().next().map(|row| row.value))
+    }
+
+    async fn sample<T: Storable + DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+        n: u64,
+    ) -> Result<Vec<T>, StorageError> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let (sql, params) = query.to_sample_sql(n, Dialect::Surreal);
+        self.fetch_items(&sql, &params).await
+    }
 
-        Ok(result.map(|r| r.count > 0).unwrap_or(false))
+    async fn list_prefix_versions<T: Storable + Send>(
+        &self,
+        query: Query<T>,
+    ) -> Result<Vec<PrefixVersion>, StorageError> {
+        let (sql, params) = query.to_prefix_sql(Dialect::Surreal);
+        let (sql, params) = (sql.as_str(), &params);
+
+        self.with_recovery(|db| async move { fetch_rows::<PrefixVersion>(&db, sql, params).await })
+            .await
     }
 
     async fn delete<T: Storable + Send>(&self, delete: Delete<T>) -> Result<u64, StorageError> {
-        let where_clause = build_where_clause(&delete.filters);
-        let sql = format!("DELETE FROM {}{}", delete.table, where_clause);
-
-        let mut q = self.0.query(&sql);
-
-        // Bind filter values
-        for (i, filter) in delete.filters.iter().enumerate() {
-            let param = format!("p{}", i);
-            q = match filter {
-                Filter::Eq(_, v)
-                | Filter::Ne(_, v)
-                | Filter::Gt(_, v)
-                | Filter::Gte(_, v)
-                | Filter::Lt(_, v)
-                | Filter::Lte(_, v)
-                | Filter::In(_, v) => bind_value(q, &param, v),
-                Filter::IsNull(_) | Filter::IsNotNull(_) => q,
-            };
+        if self.guard_deletes {
+            delete.check_allowed()?;
+        }
+        let rendered = delete.to_sql(Dialect::Surreal);
+        if let Some(log) = self.dry_run_log() {
+            log.record(render(rendered));
+            return Ok(0);
         }
 
-        q.await
-            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+        let (sql, params) = rendered;
+        let (sql, params) = (sql.as_str(), &params);
+
+        self.with_recovery(|db| async move { execute(&db, sql, params).await })
+            .await?;
 
         // SurrealDB doesn't return affected row count easily, return 0
         Ok(0)
     }
 
+    async fn soft_delete<T: Storable + Send>(
+        &self,
+        delete: Delete<T>,
+    ) -> Result<u64, StorageError> {
+        let rendered = delete.to_soft_delete_sql(StorageDatetime::now(), Dialect::Surreal)?;
+        if let Some(log) = self.dry_run_log() {
+            log.record(render(rendered));
+            return Ok(0);
+        }
+
+        let (sql, params) = rendered;
+        let (sql, params) = (sql.as_str(), &params);
+
+        // UPDATE returns the records it changed
+        let marked = self
+            .with_recovery(|db| async move {
+                fetch_rows::<serde::de::IgnoredAny>(&db, sql, params).await
+            })
+            .await?;
+        Ok(marked.len() as u64)
+    }
+
     async fn insert<T: Storable + Serialize + Send + Sync>(
         &self,
         item: &T,
     ) -> Result<u64, StorageError> {
         let table = T::table_name();
-        let value =
-            serde_json::to_value(item).map_err(|e| StorageError::StorageError(e.to_string()))?;
+        let id = item.id();
+        let value = insert_document(item)?;
 
-        self.0
-            .query(format!("INSERT INTO {} $item", table))
-            .bind(("item", value))
+        if let Some(log) = self.dry_run_log() {
+            log.record(render_keyed(CREATE_SQL, table, id, value));
+            return Ok(0);
+        }
+        let value = &value;
+
+        self.with_recovery(|db| async move {
+            write_keyed(&db, CREATE_SQL, table, id, value.clone()).await
+        })
+        .await?;
+
+        Ok(1)
+    }
+
+    async fn insert_or_ignore<T: Storable + Serialize + Send + Sync>(
+        &self,
+        item: &T,
+    ) -> Result<u64, StorageError> {
+        let table = T::table_name();
+        let value = keyed_document(item)?;
+
+        if let Some(log) = self.dry_run_log() {
+            log.record(render_insert_ignore(table, value));
+            return Ok(0);
+        }
+        let value = &value;
+
+        self.with_recovery(|db| async move { insert_value_ignore(&db, table, value.clone()).await })
             .await
-            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    }
+
+    async fn upsert<T: Storable + Serialize + Send + Sync>(
+        &self,
+        item: &T,
+    ) -> Result<u64, StorageError> {
+        let table = T::table_name();
+        let id = item.id();
+        let (sql, value) = upsert_document(item)?;
+
+        if let Some(log) = self.dry_run_log() {
+            log.record(render_keyed(sql, table, id, value));
+            return Ok(0);
+        }
+        let value = &value;
+
+        // SurrealDB doesn't report whether the record changed
+        self.with_recovery(
+            |db| async move { write_keyed(&db, sql, table, id, value.clone()).await },
+        )
+        .await?;
 
         Ok(1)
     }
@@ -288,61 +824,21 @@ impl QueryExecutor for SurrealPool {
         // SurrealDB transactions are not fully implemented here
         // Return a no-op transaction wrapper
         Ok(SurrealTransaction {
-            db: self.0.clone(),
+            db: self.db.clone(),
             committed: false,
+            dry_run: self.dry_run_log().cloned(),
+            advisory_locks: self.advisory_locks.clone(),
+            held: HashMap::new(),
+            guard_deletes: self.guard_deletes,
         })
     }
 
     async fn fetch_column(&self, query: ColumnQuery) -> Result<Vec<String>, StorageError> {
-        let distinct = if query.distinct { "DISTINCT " } else { "" };
-        let where_clause = build_where_clause(&query.filters);
-        let order_clause = match query.order {
-            Some(Order::Asc) => format!(" ORDER BY {} ASC", query.column),
-            Some(Order::Desc) => format!(" ORDER BY {} DESC", query.column),
-            None => String::new(),
-        };
-        let limit_clause = query
-            .limit
-            .map(|l| format!(" LIMIT {}", l))
-            .unwrap_or_default();
-
-        // SurrealDB uses array::distinct() for distinct values
-        let sql = if query.distinct {
-            format!(
-                "SELECT VALUE array::distinct({}) FROM {}{}{}{}",
-                query.column, query.table, where_clause, order_clause, limit_clause
-            )
-        } else {
-            format!(
-                "SELECT {}{} FROM {}{}{}{}",
-                distinct, query.column, query.table, where_clause, order_clause, limit_clause
-            )
-        };
-
-        let mut q = self.0.query(&sql);
-
-        // Bind filter values
-        for (i, filter) in query.filters.iter().enumerate() {
-            let param = format!("p{}", i);
-            q = match filter {
-                Filter::Eq(_, v)
-                | Filter::Ne(_, v)
-                | Filter::Gt(_, v)
-                | Filter::Gte(_, v)
-                | Filter::Lt(_, v)
-                | Filter::Lte(_, v)
-                | Filter::In(_, v) => bind_value(q, &param, v),
-                Filter::IsNull(_) | Filter::IsNotNull(_) => q,
-            };
-        }
+        let (sql, params) = query.to_sql(Dialect::Surreal);
+        let (sql, params) = (sql.as_str(), &params);
 
-        let result: Vec<String> = q
+        self.with_recovery(|db| async move { fetch_rows::<String>(&db, sql, params).await })
             .await
-            .map_err(|e| StorageError::StorageError(e.to_string()))?
-            .take(0)
-            .map_err(|e| StorageError::StorageError(e.to_string()))?;
-
-        Ok(result)
     }
 }
 
@@ -350,9 +846,36 @@ impl QueryExecutor for SurrealPool {
 ///
 /// Note: This doesn't actually create a transaction - operations are executed immediately.
 /// This is a placeholder to satisfy the QueryExecutor trait.
+///
+/// Advisory locks are held until the transaction commits, rolls back or is dropped.
 pub struct SurrealTransaction {
     db: Surreal<Client>,
     committed: bool,
+    /// Set when the pool is in dry-run mode.
+    dry_run: Option<DryRunLog>,
+    advisory_locks: AdvisoryLocks,
+    held: HashMap<String, tokio::sync::OwnedMutexGuard<()>>,
+    guard_deletes: bool,
+}
+
+impl SurrealTransaction {
+    /// Release every advisory lock this transaction holds, forgetting keys
+    /// no other transaction is waiting on.
+    fn release_locks(&mut self) {
+        if self.held.is_empty() {
+            return;
+        }
+        self.held.clear();
+        if let Ok(mut locks) = self.advisory_locks.lock() {
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        }
+    }
+}
+
+impl Drop for SurrealTransaction {
+    fn drop(&mut self) {
+        self.release_locks();
+    }
 }
 
 #[async_trait]
@@ -362,93 +885,56 @@ impl TransactionExecutor for SurrealTransaction {
         query: Query<T>,
     ) -> Result<Vec<T>, StorageError> {
         // Execute immediately (no actual transaction)
-        let join_clause = build_join_clause(&query.table, &query.joins);
-        let where_clause = build_where_clause(&query.filters);
-        let order_clause = build_order_clause(&query.order_by);
-
-        let group_clause = if query.distinct_on.is_empty() {
-            String::new()
-        } else {
-            format!(" GROUP BY {}", query.distinct_on.join(", "))
-        };
-
-        let select_cols = if query.joins.is_empty() {
-            "*".to_string()
-        } else {
-            format!("{}.*", query.table)
-        };
-
-        let mut sql = format!(
-            "SELECT {} FROM {}{}{}{}{}",
-            select_cols, query.table, join_clause, where_clause, group_clause, order_clause
-        );
-
-        if let Some(limit) = query.limit {
-            sql.push_str(&format!(" LIMIT {}", limit));
-        }
-        if let Some(offset) = query.offset {
-            sql.push_str(&format!(" START {}", offset));
-        }
-
-        let mut q = self.db.query(&sql);
-
-        for (i, filter) in query.filters.iter().enumerate() {
-            let param = format!("p{}", i);
-            q = match filter {
-                Filter::Eq(_, v)
-                | Filter::Ne(_, v)
-                | Filter::Gt(_, v)
-                | Filter::Gte(_, v)
-                | Filter::Lt(_, v)
-                | Filter::Lte(_, v)
-                | Filter::In(_, v) => bind_value(q, &param, v),
-                Filter::IsNull(_) | Filter::IsNotNull(_) => q,
-            };
+        let (sql, params) = query.to_sql(Dialect::Surreal);
+        if let Some(raw) = T::raw_column() {
+            let rows = fetch_rows::<serde_json::Value>(&self.db, &sql, &params)
+                .await
+                .map_err(|e| StorageError::StorageError(e.to_string()))?;
+            return from_raw_rows(rows, raw);
         }
-
-        let result: Vec<T> = q
+        fetch_rows(&self.db, &sql, &params)
             .await
-            .map_err(|e| StorageError::StorageError(e.to_string()))?
-            .take(0)
-            .map_err(|e| StorageError::StorageError(e.to_string()))?;
-
-        Ok(result)
+            .map_err(|e| StorageError::StorageError(e.to_string()))
     }
 
     async fn delete<T: Storable + Send>(&mut self, delete: Delete<T>) -> Result<u64, StorageError> {
-        // Execute immediately (no actual transaction)
-        let where_clause = build_where_clause(&delete.filters);
-        let sql = format!("DELETE FROM {}{}", delete.table, where_clause);
-
-        let mut q = self.db.query(&sql);
-
-        for (i, filter) in delete.filters.iter().enumerate() {
-            let param = format!("p{}", i);
-            q = match filter {
-                Filter::Eq(_, v)
-                | Filter::Ne(_, v)
-                | Filter::Gt(_, v)
-                | Filter::Gte(_, v)
-                | Filter::Lt(_, v)
-                | Filter::Lte(_, v)
-                | Filter::In(_, v) => bind_value(q, &param, v),
-                Filter::IsNull(_) | Filter::IsNotNull(_) => q,
-            };
+        if self.guard_deletes {
+            delete.check_allowed()?;
+        }
+        let rendered = delete.to_sql(Dialect::Surreal);
+        if let Some(log) = &self.dry_run {
+            log.record(render(rendered));
+            return Ok(0);
         }
 
-        q.await
+        // Execute immediately (no actual transaction)
+        let (sql, params) = rendered;
+
+        execute(&self.db, &sql, &params)
+            .await
             .map_err(|e| StorageError::StorageError(e.to_string()))?;
 
         // SurrealDB doesn't return affected row count easily, return 0
         Ok(0)
     }
 
-    async fn acquire_advisory_lock(&mut self, _key: &str) -> Result<(), StorageError> {
-        // SurrealDB doesn't support advisory locks
-        // Return an error as this feature is not available
-        Err(StorageError::StorageError(
-            "Advisory locks are not supported in SurrealDB".to_string(),
-        ))
+    async fn acquire_advisory_lock(&mut self, key: &str) -> Result<(), StorageError> {
+        // SurrealDB has no advisory locks; serialize on a per-key mutex shared
+        // by the pool's clones instead. Re-acquiring a held key is a no-op, as
+        // in Postgres.
+        if self.held.contains_key(key) {
+            return Ok(());
+        }
+        let lock = self
+            .advisory_locks
+            .lock()
+            .map_err(|e| StorageError::StorageError(e.to_string()))?
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+        let guard = lock.lock_owned().await;
+        self.held.insert(key.to_string(), guard);
+        Ok(())
     }
 
     async fn insert<T: Storable + Serialize + Send + Sync>(
@@ -457,12 +943,15 @@ impl TransactionExecutor for SurrealTransaction {
     ) -> Result<u64, StorageError> {
         // Execute immediately (no actual transaction)
         let table = T::table_name();
-        let value =
-            serde_json::to_value(item).map_err(|e| StorageError::StorageError(e.to_string()))?;
+        let id = item.id();
+        let value = insert_document(item)?;
 
-        self.db
-            .query(format!("INSERT INTO {} $item", table))
-            .bind(("item", value))
+        if let Some(log) = &self.dry_run {
+            log.record(render_keyed(CREATE_SQL, table, id, value));
+            return Ok(0);
+        }
+
+        write_keyed(&self.db, CREATE_SQL, table, id, value)
             .await
             .map_err(|e| StorageError::StorageError(e.to_string()))?;
 
@@ -471,16 +960,157 @@ impl TransactionExecutor for SurrealTransaction {
 
     async fn commit(mut self) -> Result<(), StorageError> {
         self.committed = true;
+        self.release_locks();
         Ok(())
     }
 
-    async fn rollback(self) -> Result<(), StorageError> {
+    async fn rollback(mut self) -> Result<(), StorageError> {
         if self.committed {
             return Err(StorageError::StorageError(
                 "Cannot rollback committed transaction".to_string(),
             ));
         }
         // No-op since we don't have real transactions
+        self.release_locks();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// A session that can be killed, counting sign-ins.
+    #[derive(Clone, Default)]
+    struct FakeSession {
+        dead: Arc<AtomicBool>,
+        refuse_sign_in: bool,
+        sign_ins: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Session for FakeSession {
+        async fn is_alive(&self) -> bool {
+            !self.dead.load(Ordering::SeqCst)
+        }
+
+        async fn sign_in(&self, _options: &SurrealConnectOptions) -> Result<(), StorageError> {
+            if self.refuse_sign_in {
+                return Err(StorageError::StorageError("refused".to_string()));
+            }
+            self.sign_ins.fetch_add(1, Ordering::SeqCst);
+            self.dead.store(false, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Reconnects(Mutex<Vec<bool>>);
+
+    impl MetricsHook for Reconnects {
+        fn on_reconnect(&self, _backend: &str, success: bool) {
+            self.0.lock().unwrap().push(success);
+        }
+    }
+
+    fn options() -> SurrealConnectOptions {
+        SurrealConnectOptions {
+            url: "localhost:8000".to_string(),
+            namespace: "test".to_string(),
+            database: "test".to_string(),
+            username: "root".to_string(),
+            password: "root".to_string(),
+        }
+    }
+
+    /// Run `op` against `session` the way the pool does, counting attempts.
+    async fn run(
+        session: &FakeSession,
+        options: Option<&SurrealConnectOptions>,
+        metrics: &Reconnects,
+        attempts: &AtomicUsize,
+    ) -> Result<&'static str, StorageError> {
+        let lock = tokio::sync::Mutex::new(());
+        retry_with_recovery(session, options, &lock, metrics, |session| async move {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            if session.is_alive().await {
+                Ok("row")
+            } else {
+                Err("session expired")
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn dead_sessions_sign_in_again_and_retry() {
+        let session = FakeSession::default();
+        session.dead.store(true, Ordering::SeqCst);
+        let metrics = Reconnects::default();
+        let attempts = AtomicUsize::new(0);
+
+        let result = run(&session, Some(&options()), &metrics, &attempts).await;
+
+        assert_eq!(result.unwrap(), "row");
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(session.sign_ins.load(Ordering::SeqCst), 1);
+        assert_eq!(*metrics.0.lock().unwrap(), vec![true]);
+    }
+
+    #[tokio::test]
+    async fn failures_on_live_sessions_are_not_retried() {
+        let session = FakeSession::default();
+        let metrics = Reconnects::default();
+        let attempts = AtomicUsize::new(0);
+        let lock = tokio::sync::Mutex::new(());
+
+        let result: Result<(), StorageError> = retry_with_recovery(
+            &session,
+            Some(&options()),
+            &lock,
+            &metrics,
+            |_| async move {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err("constraint violated")
+            },
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(session.sign_ins.load(Ordering::SeqCst), 0);
+        assert!(metrics.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn refused_sign_ins_surface_and_are_reported() {
+        let session = FakeSession {
+            refuse_sign_in: true,
+            ..FakeSession::default()
+        };
+        session.dead.store(true, Ordering::SeqCst);
+        let metrics = Reconnects::default();
+        let attempts = AtomicUsize::new(0);
+
+        let result = run(&session, Some(&options()), &metrics, &attempts).await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(*metrics.0.lock().unwrap(), vec![false]);
+    }
+
+    #[tokio::test]
+    async fn pools_without_options_do_not_recover() {
+        let session = FakeSession::default();
+        session.dead.store(true, Ordering::SeqCst);
+        let metrics = Reconnects::default();
+        let attempts = AtomicUsize::new(0);
+
+        let result = run(&session, None, &metrics, &attempts).await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(session.sign_ins.load(Ordering::SeqCst), 0);
+    }
+}
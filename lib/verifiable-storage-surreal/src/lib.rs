@@ -7,6 +7,8 @@
 //!
 //! - `SurrealStorageDatetime`: SurrealDB-compatible datetime wrapper
 //! - `Stored` derive macro: Generates SurrealDB repository implementations
+//! - `SurrealPool`: `QueryExecutor` with session recovery and liveness checks
+//! - `define_schema`: SCHEMAFULL table and field definitions with type and assert constraints
 //!
 //! # Example
 //!
@@ -27,9 +29,14 @@
 )]
 
 mod executor;
+mod schema;
 mod time;
 
-pub use executor::{SurrealPool, SurrealTransaction};
+pub use executor::{
+    SurrealConnectOptions, SurrealPool, SurrealTransaction, insert_or_ignore_into,
+    list_prefix_versions_in, signatures_in, soft_delete_in, upsert_into,
+};
+pub use schema::{define_schema, schema_definitions};
 pub use time::SurrealStorageDatetime;
 
 // Re-export the derive macro
@@ -37,7 +44,9 @@ pub use verifiable_storage_surreal_derive::Stored;
 
 // Re-export core types for convenience
 pub use verifiable_storage::{
-    ConnectionConfig, Delete, Filter, Order, Query, QueryExecutor, RepositoryConnection,
-    SelfAddressed, Storable, StorageDatetime, StorageError, TransactionExecutor,
-    UnversionedRepository, Value, Versioned, VersionedRepository, compute_said,
+    Aggregate, ConnectionConfig, Delete, Dialect, ExecutorMode, Filter, MetricsHook, Order,
+    PrefixVersion, Query, QueryExecutor, RecordSignature, RenderedStatement, RepositoryConnection,
+    SelfAddressed, SignedRepository, SqlParams, Storable, StorageDatetime, StorageError, Subquery,
+    TransactionExecutor, UnversionedRepository, Value, Versioned, VersionedRepository,
+    compute_said, compute_said_over, said_placeholder,
 };
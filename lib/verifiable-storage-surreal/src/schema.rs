@@ -0,0 +1,179 @@
+//! SCHEMAFULL table definitions derived from `Storable` metadata.
+//!
+//! Surreal tables are schemaless by default and accept any shape. Defining a
+//! table SCHEMAFULL with a typed field per column makes the database reject
+//! malformed records: fields are typed by their serialized values (see
+//! `Storable::value_kinds()`; structs, enums and other types serde may shape
+//! more than one way are `any`), non-`Option` fields are required, SAID, prefix and
+//! previous fields must be SAID-length strings, versions are non-negative,
+//! `#[column(max_length/pattern/range = ...)]` constraints are asserted as
+//! declared, and `#[column(assert = "...")]` adds any further expression over
+//! `$value`. A `#[storable(raw_column = "...")]` column is an optional string.
+
+use surrealdb::Surreal;
+use surrealdb::engine::remote::ws::Client;
+use verifiable_storage::{
+    ColumnConstraint, ManagedField, SAID_DIGEST_CODE, Storable, StorageError, said_placeholder,
+};
+
+/// `DEFINE TABLE` and `DEFINE FIELD` statements for `T` stored in `table`.
+///
+/// Statements use `OVERWRITE`, so running them again replaces the definitions.
+pub fn schema_definitions<T: Storable>(table: &str) -> Result<Vec<String>, StorageError> {
+    let said_length = said_placeholder(SAID_DIGEST_CODE)?.len();
+    let mut statements = vec![format!("DEFINE TABLE OVERWRITE {} SCHEMAFULL", table)];
+
+    let value_kinds = T::value_kinds();
+    for (index, ((column, column_type), json_key)) in T::columns()
+        .iter()
+        .zip(T::column_types())
+        .zip(T::json_keys())
+        .enumerate()
+    {
+        let field_type = match (*column_type, value_kinds.get(index).copied()) {
+            ("cbor", _) => "any",
+            ("json", _) => "object",
+            (
+                _,
+                Some(
+                    kind @ ("string" | "int" | "float" | "bool" | "datetime" | "array" | "object"),
+                ),
+            ) => kind,
+            (_, Some(_)) => "any",
+            ("datetime", None) => "datetime",
+            ("bigint" | "integer", None) => "int",
+            ("boolean", None) => "bool",
+            (_, None) => "any",
+        };
+        let nullable = T::nullable_columns().contains(column);
+
+        let mut asserts: Vec<String> = Vec::new();
+        match T::managed_columns()
+            .iter()
+            .find(|(managed, _)| managed == column)
+            .map(|(_, field)| *field)
+        {
+            Some(ManagedField::Said | ManagedField::Prefix | ManagedField::Previous) => {
+                asserts.push(format!("string::len($value) = {}", said_length))
+            }
+            Some(ManagedField::Version) => asserts.push("$value >= 0".to_string()),
+            _ => {}
+        }
+        asserts.extend(
+            T::column_constraints()
+                .iter()
+                .filter(|(constrained, _)| constrained == column)
+                .map(|(_, constraint)| match constraint {
+                    ColumnConstraint::MaxLength(max) => format!("string::len($value) <= {}", max),
+                    ColumnConstraint::Pattern(pattern) => {
+                        format!("$value = /{}/", pattern.replace('/', "\\/"))
+                    }
+                    ColumnConstraint::Range(min, max) => {
+                        format!("$value >= {} AND $value <= {}", min, max)
+                    }
+                }),
+        );
+        asserts.extend(
+            T::column_asserts()
+                .iter()
+                .filter(|(asserted, _)| asserted == column)
+                .map(|(_, assert)| format!("({})", assert)),
+        );
+
+        let mut statement = format!(
+            "DEFINE FIELD OVERWRITE {} ON TABLE {} TYPE {}",
+            json_key,
+            table,
+            if nullable {
+                format!("option<null | {}>", field_type)
+            } else {
+                field_type.to_string()
+            }
+        );
+        if !asserts.is_empty() {
+            let condition = asserts.join(" AND ");
+            statement.push_str(&if nullable {
+                format!(" ASSERT $value = NONE OR $value = NULL OR ({})", condition)
+            } else {
+                format!(" ASSERT {}", condition)
+            });
+        }
+        statements.push(statement);
+    }
+    if let Some(raw) = T::raw_column() {
+        statements.push(format!(
+            "DEFINE FIELD OVERWRITE {} ON TABLE {} TYPE option<string>",
+            raw, table
+        ));
+    }
+
+    Ok(statements)
+}
+
+/// Define `table` as SCHEMAFULL for `T`; safe to run on every start.
+pub async fn define_schema<T: Storable>(
+    db: &Surreal<Client>,
+    table: &str,
+) -> Result<(), StorageError> {
+    let statements = schema_definitions::<T>(table)?.join(";\n");
+    db.query(statements)
+        .await
+        .map_err(|e| StorageError::StorageError(e.to_string()))?
+        .check()
+        .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+    struct Location {
+        lat: f64,
+        lon: f64,
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+    enum Kind {
+        Point,
+        Area { radius: f64 },
+    }
+
+    #[derive(
+        Clone, Debug, serde::Serialize, serde::Deserialize, verifiable_storage::SelfAddressed,
+    )]
+    #[storable(table = "sites")]
+    struct Site {
+        #[said]
+        said: String,
+        name: String,
+        score: f64,
+        tags: Vec<String>,
+        labels: HashMap<String, String>,
+        location: Location,
+        kind: Option<Kind>,
+    }
+
+    #[test]
+    fn fields_are_typed_by_their_serialized_values() {
+        let statements = schema_definitions::<Site>("sites").unwrap();
+        let field_type = |field: &str| {
+            let prefix = format!("DEFINE FIELD OVERWRITE {} ON TABLE sites TYPE ", field);
+            statements
+                .iter()
+                .find_map(|statement| statement.strip_prefix(&prefix))
+                .map(|rest| rest.split(" ASSERT").next().unwrap_or(rest).to_string())
+                .unwrap()
+        };
+
+        assert_eq!(field_type("said"), "string");
+        assert_eq!(field_type("name"), "string");
+        assert_eq!(field_type("score"), "float");
+        assert_eq!(field_type("tags"), "array");
+        assert_eq!(field_type("labels"), "object");
+        assert_eq!(field_type("location"), "any");
+        assert_eq!(field_type("kind"), "option<null | any>");
+    }
+}
@@ -1,7 +1,7 @@
 use std::ops::Add;
 use std::time::Duration;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SubsecRound, Utc};
 use serde::{Deserialize, Serialize};
 use surrealdb::sql::Datetime as SurrealDatetime;
 
@@ -12,14 +12,27 @@ use surrealdb::sql::Datetime as SurrealDatetime;
 ///
 /// Wraps SurrealDB's Datetime for database compatibility while providing
 /// the same interface as `verifiable_storage::StorageDatetime`.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct SurrealStorageDatetime(pub SurrealDatetime);
 
+impl<'de> Deserialize<'de> for SurrealStorageDatetime {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // StorageDatetime rejects sub-microsecond values on the way in
+        verifiable_storage::StorageDatetime::deserialize(deserializer).map(Self::from)
+    }
+}
+
 impl SurrealStorageDatetime {
     pub fn now() -> Self {
         SurrealStorageDatetime(datetime_micros())
     }
 
+    /// This time with anything below a microsecond dropped.
+    pub fn truncate_micros(&self) -> Self {
+        let inner: DateTime<Utc> = self.0.clone().into();
+        SurrealStorageDatetime(SurrealDatetime::from(inner.trunc_subsecs(6)))
+    }
+
     pub fn is_from_future(&self) -> bool {
         Self::now() < *self
     }
@@ -93,3 +106,27 @@ fn datetime_micros() -> SurrealDatetime {
 
     SurrealDatetime::from(timestamp)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_round_trip_byte_for_byte() {
+        let now = SurrealStorageDatetime::now();
+        let json = serde_json::to_string(&now).unwrap();
+        let back: SurrealStorageDatetime = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, now);
+        assert_eq!(serde_json::to_string(&back).unwrap(), json);
+
+        // SurrealDB keeps nanoseconds; they must be dropped before digesting
+        let nanos = "2024-05-01T12:00:00.123456789Z";
+        assert!(serde_json::from_value::<SurrealStorageDatetime>(nanos.into()).is_err());
+        let precise: DateTime<Utc> = nanos.parse().unwrap();
+        let truncated = SurrealStorageDatetime(precise.into()).truncate_micros();
+        assert_eq!(
+            serde_json::to_value(&truncated).unwrap(),
+            "2024-05-01T12:00:00.123456Z"
+        );
+    }
+}
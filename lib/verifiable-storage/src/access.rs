@@ -0,0 +1,316 @@
+//! Record-level access control.
+//!
+//! A record opts in with an `Option<AccessPolicy>` field marked
+//! `#[column(access_policy)]`, stored as text such as `read=alice,bob;write=alice`.
+//! [`PolicyEnforcedRepository`] checks each record's policy against the
+//! caller's [`AccessContext`]: unreadable records are hidden from reads and
+//! writes the caller may not make fail with [`StorageError::AccessDenied`].
+//! Records without a policy, and types without the column, are unrestricted.
+//!
+//! Policies travel with the record, so they are covered by its SAID and a
+//! change of policy is a new version like any other edit.
+
+use std::fmt;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    SelfAddressed, Storable, StorageError, UnversionedRepository, Versioned, VersionedRepository,
+    storable::column_value,
+};
+
+/// The principal matching every caller.
+pub const ANYONE: &str = "*";
+
+/// Who may read and who may write a record. Writers may also read.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct AccessPolicy {
+    pub readers: Vec<String>,
+    pub writers: Vec<String>,
+}
+
+impl AccessPolicy {
+    /// A policy letting `writers` write and `readers` (plus writers) read.
+    pub fn new(
+        readers: impl IntoIterator<Item = impl Into<String>>,
+        writers: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            readers: readers.into_iter().map(Into::into).collect(),
+            writers: writers.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn allows_read(&self, principals: &[String]) -> bool {
+        self.allows_write(principals) || matches(&self.readers, principals)
+    }
+
+    pub fn allows_write(&self, principals: &[String]) -> bool {
+        matches(&self.writers, principals)
+    }
+}
+
+fn matches(allowed: &[String], principals: &[String]) -> bool {
+    allowed
+        .iter()
+        .any(|entry| entry == ANYONE || principals.contains(entry))
+}
+
+impl fmt::Display for AccessPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "read={};write={}",
+            self.readers.join(","),
+            self.writers.join(",")
+        )
+    }
+}
+
+impl FromStr for AccessPolicy {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = AccessPolicy::default();
+        for part in s.split(';').filter(|part| !part.is_empty()) {
+            let (kind, principals) = part.split_once('=').ok_or_else(|| {
+                StorageError::StorageError(format!("Malformed access policy: {}", s))
+            })?;
+            let principals = principals
+                .split(',')
+                .filter(|p| !p.is_empty())
+                .map(str::to_string);
+            match kind {
+                "read" => policy.readers.extend(principals),
+                "write" => policy.writers.extend(principals),
+                _ => {
+                    return Err(StorageError::StorageError(format!(
+                        "Unknown access policy part: {}",
+                        kind
+                    )));
+                }
+            }
+        }
+        Ok(policy)
+    }
+}
+
+impl From<AccessPolicy> for String {
+    fn from(policy: AccessPolicy) -> Self {
+        policy.to_string()
+    }
+}
+
+impl TryFrom<String> for AccessPolicy {
+    type Error = StorageError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// The caller a [`PolicyEnforcedRepository`] acts for.
+pub trait AccessContext: Send + Sync {
+    /// Identities the caller holds (e.g. its own prefix and its groups).
+    fn principals(&self) -> &[String];
+}
+
+impl AccessContext for Vec<String> {
+    fn principals(&self) -> &[String] {
+        self
+    }
+}
+
+/// The policy on `item`, if its type has an access policy column and the field is set.
+pub fn access_policy<T: Storable>(item: &T) -> Result<Option<AccessPolicy>, StorageError> {
+    let Some(column) = T::access_policy_column() else {
+        return Ok(None);
+    };
+    match column_value(item, column)? {
+        Some(value) => Ok(Some(serde_json::from_value(value)?)),
+        None => Ok(None),
+    }
+}
+
+/// Wraps a repository so reads and writes honour each record's [`AccessPolicy`].
+#[derive(Debug, Clone)]
+pub struct PolicyEnforcedRepository<R, C> {
+    inner: R,
+    context: C,
+}
+
+impl<R, C: AccessContext> PolicyEnforcedRepository<R, C> {
+    /// Wrap `inner`, acting for `context`.
+    pub fn new(inner: R, context: C) -> Self {
+        Self { inner, context }
+    }
+
+    fn readable<T: Storable>(&self, item: &T) -> Result<bool, StorageError> {
+        Ok(access_policy(item)?.is_none_or(|p| p.allows_read(self.context.principals())))
+    }
+
+    fn check_write<T: Storable>(&self, item: &T) -> Result<(), StorageError> {
+        match access_policy(item)? {
+            Some(policy) if !policy.allows_write(self.context.principals()) => {
+                Err(StorageError::AccessDenied(format!(
+                    "{} {} is not writable by the caller",
+                    T::table_name(),
+                    item.id()
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn visible<T: Storable>(&self, item: Option<T>) -> Result<Option<T>, StorageError> {
+        match item {
+            Some(item) if self.readable(&item)? => Ok(Some(item)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl<T, R, C> VersionedRepository<T> for PolicyEnforcedRepository<R, C>
+where
+    T: Storable + SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+    C: AccessContext,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        self.check_write(&item)?;
+        self.inner.create(item).await
+    }
+
+    /// The version being updated must be writable; the new version may name
+    /// different writers, which is how a record changes hands.
+    async fn update(&self, item: T) -> Result<T, StorageError> {
+        let current = self
+            .inner
+            .get_by_said(&item.get_said())
+            .await?
+            .ok_or_else(|| {
+                StorageError::NotFound(format!("Version being updated: {}", item.get_said()))
+            })?;
+        self.check_write(&current)?;
+        self.inner.update(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        if let Some(previous) = item.get_previous()
+            && let Some(previous) = self.inner.get_by_said(&previous).await?
+        {
+            self.check_write(&previous)?;
+        } else {
+            self.check_write(&item)?;
+        }
+        self.inner.insert(item).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.visible(self.inner.get_by_said(said).await?)
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        self.visible(self.inner.get_latest(prefix).await?)
+    }
+
+    /// Versions the caller can't read are left out.
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        let mut visible = Vec::new();
+        for item in self.inner.get_history(prefix).await? {
+            if self.readable(&item)? {
+                visible.push(item);
+            }
+        }
+        Ok(visible)
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        Ok(self.get_latest(prefix).await?.is_some())
+    }
+
+    /// The record must be writable by the caller.
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        let Some(item) = self.inner.get_by_said(said).await? else {
+            return Ok(false);
+        };
+        self.check_write(&item)?;
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[async_trait]
+impl<T, R, C> UnversionedRepository<T> for PolicyEnforcedRepository<R, C>
+where
+    T: Storable + SelfAddressed + Serialize + DeserializeOwned + Clone + 'static,
+    R: UnversionedRepository<T> + Send + Sync,
+    C: AccessContext,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        self.check_write(&item)?;
+        self.inner.create(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.check_write(&item)?;
+        self.inner.insert(item).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.visible(self.inner.get_by_said(said).await?)
+    }
+
+    /// The record must be writable by the caller.
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        let Some(item) = self.inner.get_by_said(said).await? else {
+            return Ok(false);
+        };
+        self.check_write(&item)?;
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "notes")]
+    #[serde(rename_all = "camelCase")]
+    struct Note {
+        #[said]
+        said: String,
+        body: String,
+        #[column(access_policy)]
+        policy: Option<AccessPolicy>,
+    }
+
+    #[test]
+    fn policies_round_trip_and_gate_principals() {
+        let policy = AccessPolicy::new(["bob"], ["alice"]);
+        assert_eq!(policy.to_string(), "read=bob;write=alice");
+        assert_eq!(
+            "read=bob;write=alice".parse::<AccessPolicy>().unwrap(),
+            policy
+        );
+        assert!("owner=alice".parse::<AccessPolicy>().is_err());
+
+        let alice = vec!["alice".to_string()];
+        let bob = vec!["bob".to_string()];
+        let carol = vec!["carol".to_string()];
+        assert!(policy.allows_read(&alice) && policy.allows_write(&alice));
+        assert!(policy.allows_read(&bob) && !policy.allows_write(&bob));
+        assert!(!policy.allows_read(&carol));
+        assert!(AccessPolicy::new([ANYONE], Vec::<String>::new()).allows_read(&carol));
+
+        let mut note = Note::new("hi".to_string(), Some(policy.clone()));
+        note.derive_said().unwrap();
+        assert_eq!(access_policy(&note).unwrap(), Some(policy));
+        let json = serde_json::to_value(&note).unwrap();
+        assert_eq!(json["policy"], "read=bob;write=alice");
+    }
+}
@@ -0,0 +1,553 @@
+//! Table-level maintenance for operators.
+//!
+//! A [`Registry`] names the stored types a service owns and runs maintenance
+//! over them through any [`QueryExecutor`]: verifying SAIDs and chains, showing
+//! a prefix's chain, exporting and importing [`HistoryBundle`]s, and pruning old
+//! unversioned rows. The `vstorage` CLI in `verifiable-storage-postgres` is a
+//! thin front end over it. Soft-deleted rows are included throughout.
+
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Delete, ManagedField, Order, Query, QueryExecutor, SelfAddressed, Storable, StorageDatetime,
+    StorageError, Versioned,
+    sync::{Divergence, verify_continuation},
+};
+
+/// Default number of rows read per page when verifying a whole table.
+const DEFAULT_BATCH_SIZE: u64 = 1000;
+
+/// A registered table, as described by its `Storable` metadata.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableInfo {
+    pub name: &'static str,
+    pub versioned: bool,
+    /// Column names and their database-agnostic types.
+    pub columns: Vec<(&'static str, &'static str)>,
+}
+
+/// Outcome of verifying a table, prefix or record.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Records checked.
+    pub checked: usize,
+    /// Prefixes (or SAIDs, for unversioned tables) that failed, with the reason.
+    pub failures: Vec<(String, String)>,
+}
+
+impl VerifyReport {
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// One version in a prefix's chain, with any problem found at that link.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainLink {
+    pub version: u64,
+    pub said: String,
+    pub previous: Option<String>,
+    pub created_at: Option<StorageDatetime>,
+    pub error: Option<String>,
+}
+
+/// A portable copy of a prefix's history (or a single unversioned record).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryBundle {
+    pub table: String,
+    /// The prefix, or the SAID for unversioned tables.
+    pub key: String,
+    /// Items in their serde representation, oldest first.
+    pub items: Vec<serde_json::Value>,
+}
+
+/// Maintenance operations over the tables a service owns.
+pub struct Registry<E> {
+    executor: E,
+    tables: Vec<Box<dyn AdminTable<E>>>,
+    batch_size: u64,
+}
+
+impl<E: QueryExecutor + 'static> Registry<E> {
+    /// An empty registry running against `executor`.
+    pub fn new(executor: E) -> Self {
+        Self {
+            executor,
+            tables: Vec::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Register a versioned type's table.
+    pub fn versioned<T>(mut self) -> Self
+    where
+        T: Storable + Versioned + 'static,
+    {
+        self.tables.push(Box::new(VersionedTable::<T>(PhantomData)));
+        self
+    }
+
+    /// Register an unversioned type's table.
+    pub fn unversioned<T>(mut self) -> Self
+    where
+        T: Storable + SelfAddressed + 'static,
+    {
+        self.tables
+            .push(Box::new(UnversionedTable::<T>(PhantomData)));
+        self
+    }
+
+    /// Rows read per page when verifying a whole table (default 1000).
+    pub fn batch_size(mut self, batch_size: u64) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn executor(&self) -> &E {
+        &self.executor
+    }
+
+    /// The registered tables, in registration order.
+    pub fn tables(&self) -> Vec<TableInfo> {
+        self.tables.iter().map(|table| table.info()).collect()
+    }
+
+    /// Verify one prefix (or SAID) of `table`, or the whole table when `key` is `None`.
+    pub async fn verify(
+        &self,
+        table: &str,
+        key: Option<&str>,
+    ) -> Result<VerifyReport, StorageError> {
+        let table = self.table(table)?;
+        match key {
+            Some(key) => table.verify_one(&self.executor, key).await,
+            None => table.verify_all(&self.executor, self.batch_size).await,
+        }
+    }
+
+    /// Every version of `prefix` in `table`, oldest first.
+    pub async fn chain(&self, table: &str, prefix: &str) -> Result<Vec<ChainLink>, StorageError> {
+        self.table(table)?.chain(&self.executor, prefix).await
+    }
+
+    /// Export the history of `key` in `table`.
+    pub async fn export(&self, table: &str, key: &str) -> Result<HistoryBundle, StorageError> {
+        self.table(table)?.export(&self.executor, key).await
+    }
+
+    /// Verify and insert a bundle's items, skipping those already stored.
+    ///
+    /// Returns the number of items written.
+    pub async fn import(&self, bundle: HistoryBundle) -> Result<u64, StorageError> {
+        self.table(&bundle.table)?
+            .import(&self.executor, bundle)
+            .await
+    }
+
+    /// Delete rows of an unversioned `table` created before `before`.
+    ///
+    /// Versioned tables are refused, since pruning would break their chains.
+    pub async fn prune(&self, table: &str, before: &StorageDatetime) -> Result<u64, StorageError> {
+        self.table(table)?.prune(&self.executor, before).await
+    }
+
+    fn table(&self, name: &str) -> Result<&dyn AdminTable<E>, StorageError> {
+        self.tables
+            .iter()
+            .find(|table| table.info().name == name)
+            .map(|table| table.as_ref())
+            .ok_or_else(|| StorageError::NotFound(format!("Table {} is not registered", name)))
+    }
+}
+
+#[async_trait]
+trait AdminTable<E>: Send + Sync {
+    fn info(&self) -> TableInfo;
+    async fn verify_one(&self, executor: &E, key: &str) -> Result<VerifyReport, StorageError>;
+    async fn verify_all(&self, executor: &E, batch_size: u64)
+    -> Result<VerifyReport, StorageError>;
+    async fn chain(&self, executor: &E, prefix: &str) -> Result<Vec<ChainLink>, StorageError>;
+    async fn export(&self, executor: &E, key: &str) -> Result<HistoryBundle, StorageError>;
+    async fn import(&self, executor: &E, bundle: HistoryBundle) -> Result<u64, StorageError>;
+    async fn prune(&self, executor: &E, before: &StorageDatetime) -> Result<u64, StorageError>;
+}
+
+struct VersionedTable<T>(PhantomData<fn() -> T>);
+
+struct UnversionedTable<T>(PhantomData<fn() -> T>);
+
+fn table_info<T: Storable>() -> TableInfo {
+    TableInfo {
+        name: T::table_name(),
+        versioned: T::is_versioned(),
+        columns: T::columns()
+            .iter()
+            .copied()
+            .zip(T::column_types().iter().copied())
+            .chain(T::raw_column().map(|raw| (raw, "text")))
+            .collect(),
+    }
+}
+
+fn divergence_reason(divergence: Divergence) -> String {
+    match divergence {
+        Divergence::Conflict => "History conflicts with the stored chain".to_string(),
+        Divergence::Invalid(e) => e.to_string(),
+    }
+}
+
+fn not_found(table: &str, key: &str) -> StorageError {
+    StorageError::NotFound(format!("Nothing stored under {} in {}", key, table))
+}
+
+fn parse_items<T: Storable>(bundle: HistoryBundle) -> Result<Vec<T>, StorageError> {
+    bundle
+        .items
+        .into_iter()
+        .map(|item| serde_json::from_value(item).map_err(StorageError::from))
+        .collect()
+}
+
+fn to_bundle<T: Storable>(key: &str, items: &[T]) -> Result<HistoryBundle, StorageError> {
+    Ok(HistoryBundle {
+        table: T::table_name().to_string(),
+        key: key.to_string(),
+        items: items
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// Walk a prefix's history, reporting each link's problems independently.
+fn chain_links<T: Versioned>(prefix: &str, history: &[T]) -> Vec<ChainLink> {
+    let mut expected_previous = None;
+    history
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let error = if let Err(e) = item.verify() {
+                Some(e.to_string())
+            } else if item.get_prefix() != prefix {
+                Some(format!("Belongs to {}", item.get_prefix()))
+            } else if item.get_version() != i as u64 {
+                Some(format!("Expected version {}", i))
+            } else if item.get_previous() != expected_previous {
+                Some("Broken previous link".to_string())
+            } else {
+                None
+            };
+            expected_previous = Some(item.get_said());
+            ChainLink {
+                version: item.get_version(),
+                said: item.get_said(),
+                previous: item.get_previous(),
+                created_at: item.get_created_at(),
+                error,
+            }
+        })
+        .collect()
+}
+
+impl<T: Storable + Versioned> VersionedTable<T> {
+    async fn history<E: QueryExecutor>(
+        &self,
+        executor: &E,
+        prefix: &str,
+    ) -> Result<Vec<T>, StorageError> {
+        let history = executor
+            .fetch(
+                Query::<T>::new()
+                    .include_deleted()
+                    .eq(T::managed_column(ManagedField::Prefix), prefix)
+                    .order_by(T::managed_column(ManagedField::Version), Order::Asc),
+            )
+            .await?;
+        if history.is_empty() {
+            return Err(not_found(T::table_name(), prefix));
+        }
+        Ok(history)
+    }
+}
+
+#[async_trait]
+impl<T, E> AdminTable<E> for VersionedTable<T>
+where
+    T: Storable + Versioned + 'static,
+    E: QueryExecutor,
+{
+    fn info(&self) -> TableInfo {
+        table_info::<T>()
+    }
+
+    async fn verify_one(&self, executor: &E, prefix: &str) -> Result<VerifyReport, StorageError> {
+        let history = self.history(executor, prefix).await?;
+        let mut report = VerifyReport {
+            checked: history.len(),
+            ..Default::default()
+        };
+        if let Err(divergence) = verify_continuation(prefix, None, &history) {
+            report
+                .failures
+                .push((prefix.to_string(), divergence_reason(divergence)));
+        }
+        Ok(report)
+    }
+
+    async fn verify_all(
+        &self,
+        executor: &E,
+        batch_size: u64,
+    ) -> Result<VerifyReport, StorageError> {
+        let prefix_column = T::managed_column(ManagedField::Prefix);
+        let version_column = T::managed_column(ManagedField::Version);
+        let mut report = VerifyReport::default();
+        // Rows arrive grouped by prefix; a prefix is checked once its group ends
+        let mut group: Vec<T> = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = executor
+                .fetch(
+                    Query::<T>::new()
+                        .include_deleted()
+                        .order_by(prefix_column, Order::Asc)
+                        .order_by(version_column, Order::Asc)
+                        .limit(batch_size)
+                        .offset(offset),
+                )
+                .await?;
+            let exhausted = (page.len() as u64) < batch_size;
+            offset += page.len() as u64;
+
+            for item in page {
+                if let Some(last) = group.last()
+                    && last.get_prefix() != item.get_prefix()
+                {
+                    check_group(&mut report, std::mem::take(&mut group));
+                }
+                group.push(item);
+            }
+            if exhausted {
+                break;
+            }
+        }
+        if !group.is_empty() {
+            check_group(&mut report, group);
+        }
+        Ok(report)
+    }
+
+    async fn chain(&self, executor: &E, prefix: &str) -> Result<Vec<ChainLink>, StorageError> {
+        Ok(chain_links(prefix, &self.history(executor, prefix).await?))
+    }
+
+    async fn export(&self, executor: &E, prefix: &str) -> Result<HistoryBundle, StorageError> {
+        to_bundle(prefix, &self.history(executor, prefix).await?)
+    }
+
+    async fn import(&self, executor: &E, bundle: HistoryBundle) -> Result<u64, StorageError> {
+        let prefix = bundle.key.clone();
+        let items = parse_items::<T>(bundle)?;
+        verify_continuation(&prefix, None, &items)
+            .map_err(|divergence| StorageError::InvalidSaid(divergence_reason(divergence)))?;
+
+        let mut written = 0;
+        for item in &items {
+            written += executor.insert_or_ignore(item).await?;
+        }
+        Ok(written)
+    }
+
+    async fn prune(&self, _executor: &E, _before: &StorageDatetime) -> Result<u64, StorageError> {
+        Err(StorageError::StorageError(format!(
+            "Refusing to prune versioned table {}; removing versions breaks chains",
+            T::table_name()
+        )))
+    }
+}
+
+fn check_group<T: Versioned>(report: &mut VerifyReport, group: Vec<T>) {
+    let Some(first) = group.first() else {
+        return;
+    };
+    let prefix = first.get_prefix();
+    report.checked += group.len();
+    if let Err(divergence) = verify_continuation(&prefix, None, &group) {
+        report
+            .failures
+            .push((prefix, divergence_reason(divergence)));
+    }
+}
+
+impl<T: Storable + SelfAddressed> UnversionedTable<T> {
+    async fn record<E: QueryExecutor>(&self, executor: &E, said: &str) -> Result<T, StorageError> {
+        executor
+            .fetch_optional(
+                Query::<T>::new()
+                    .include_deleted()
+                    .eq(T::managed_column(ManagedField::Said), said),
+            )
+            .await?
+            .ok_or_else(|| not_found(T::table_name(), said))
+    }
+}
+
+#[async_trait]
+impl<T, E> AdminTable<E> for UnversionedTable<T>
+where
+    T: Storable + SelfAddressed + 'static,
+    E: QueryExecutor,
+{
+    fn info(&self) -> TableInfo {
+        table_info::<T>()
+    }
+
+    async fn verify_one(&self, executor: &E, said: &str) -> Result<VerifyReport, StorageError> {
+        let mut report = VerifyReport {
+            checked: 1,
+            ..Default::default()
+        };
+        if let Err(e) = self.record(executor, said).await?.verify_said() {
+            report.failures.push((said.to_string(), e.to_string()));
+        }
+        Ok(report)
+    }
+
+    async fn verify_all(
+        &self,
+        executor: &E,
+        batch_size: u64,
+    ) -> Result<VerifyReport, StorageError> {
+        let said_column = T::managed_column(ManagedField::Said);
+        let mut report = VerifyReport::default();
+        let mut offset = 0;
+        loop {
+            let page = executor
+                .fetch(
+                    Query::<T>::new()
+                        .include_deleted()
+                        .order_by(said_column, Order::Asc)
+                        .limit(batch_size)
+                        .offset(offset),
+                )
+                .await?;
+            let exhausted = (page.len() as u64) < batch_size;
+            offset += page.len() as u64;
+
+            report.checked += page.len();
+            for item in page {
+                if let Err(e) = item.verify_said() {
+                    report.failures.push((item.get_said(), e.to_string()));
+                }
+            }
+            if exhausted {
+                break;
+            }
+        }
+        Ok(report)
+    }
+
+    async fn chain(&self, _executor: &E, _prefix: &str) -> Result<Vec<ChainLink>, StorageError> {
+        Err(StorageError::StorageError(format!(
+            "Table {} is not versioned",
+            T::table_name()
+        )))
+    }
+
+    async fn export(&self, executor: &E, said: &str) -> Result<HistoryBundle, StorageError> {
+        to_bundle(said, &[self.record(executor, said).await?])
+    }
+
+    async fn import(&self, executor: &E, bundle: HistoryBundle) -> Result<u64, StorageError> {
+        let items = parse_items::<T>(bundle)?;
+        for item in &items {
+            item.verify_said()?;
+        }
+
+        let mut written = 0;
+        for item in &items {
+            written += executor.insert_or_ignore(item).await?;
+        }
+        Ok(written)
+    }
+
+    async fn prune(&self, executor: &E, before: &StorageDatetime) -> Result<u64, StorageError> {
+        let Some((created_at, _)) = T::managed_columns()
+            .iter()
+            .find(|(_, field)| *field == ManagedField::CreatedAt)
+        else {
+            return Err(StorageError::StorageError(format!(
+                "Table {} has no created_at column to prune by",
+                T::table_name()
+            )));
+        };
+        executor
+            .delete(Delete::<T>::new().lt(*created_at, before))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "pages")]
+    #[serde(rename_all = "camelCase")]
+    struct Page {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        body: String,
+    }
+
+    fn history(len: usize) -> Vec<Page> {
+        let mut page = Page::new("v0".to_string());
+        page.derive_prefix().unwrap();
+        let mut pages = vec![page.clone()];
+        for i in 1..len {
+            page.body = format!("v{}", i);
+            page.increment().unwrap();
+            pages.push(page.clone());
+        }
+        pages
+    }
+
+    #[test]
+    fn chain_links_flag_only_the_bad_link() {
+        let mut pages = history(3);
+        let prefix = pages[0].prefix.clone();
+        assert!(
+            chain_links(&prefix, &pages)
+                .iter()
+                .all(|link| link.error.is_none())
+        );
+
+        pages[1].body = "tampered".to_string();
+        let links = chain_links(&prefix, &pages);
+        assert_eq!(links.len(), 3);
+        assert!(links[0].error.is_none());
+        assert!(links[1].error.is_some());
+        assert!(links[2].error.is_none());
+    }
+
+    #[test]
+    fn bundles_round_trip_items() {
+        let pages = history(2);
+        let bundle = to_bundle(&pages[0].prefix, &pages).unwrap();
+        assert_eq!(bundle.table, "pages");
+
+        let json = serde_json::to_string(&bundle).unwrap();
+        let items = parse_items::<Page>(serde_json::from_str(&json).unwrap()).unwrap();
+        assert!(verify_continuation(&pages[0].prefix, None, &items).is_ok());
+    }
+}
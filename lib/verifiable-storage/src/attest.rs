@@ -0,0 +1,235 @@
+//! Signed query results.
+//!
+//! A node holding a signing key can wrap what it serves in a
+//! [`SignedResponse`], so consumers can check that the node attested to exactly
+//! the data they received and when. Signing is pluggable through
+//! [`ResponseSigner`]/[`ResponseVerifier`], e.g. backed by CESR Ed25519 keys.
+//! Raw-byte [`Signer`]s and [`Verifier`]s serve as both, with signatures
+//! carried as lowercase hex, and [`SignedResponse::sign_async`] takes keys
+//! held in an HSM or KMS.
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    AsyncSigner, SelfAddressed, Signer, StorageDatetime, StorageError, Verifier, Versioned,
+    VersionedRepository, compute_said,
+    signing::{decode_hex, encode_hex},
+};
+
+/// Signs response digests on behalf of a serving node.
+pub trait ResponseSigner: Send + Sync {
+    /// Sign `message`, returning an encoded signature (e.g. CESR qb64).
+    fn sign(&self, message: &[u8]) -> Result<String, StorageError>;
+}
+
+/// Checks signatures produced by a [`ResponseSigner`].
+pub trait ResponseVerifier {
+    fn verify(&self, message: &[u8], signature: &str) -> Result<(), StorageError>;
+}
+
+impl<S: Signer> ResponseSigner for S {
+    fn sign(&self, message: &[u8]) -> Result<String, StorageError> {
+        Ok(encode_hex(&Signer::sign(self, message)?))
+    }
+}
+
+impl<V: Verifier> ResponseVerifier for V {
+    fn verify(&self, message: &[u8], signature: &str) -> Result<(), StorageError> {
+        Verifier::verify(self, message, &decode_hex(signature)?)
+    }
+}
+
+/// Query results attested by the node that served them.
+///
+/// `digest` is the SAID of `{"items": ..., "timestamp": ...}` and `signature`
+/// is over the digest's qb64 bytes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedResponse<T> {
+    pub items: T,
+    pub digest: String,
+    pub signature: String,
+    pub timestamp: StorageDatetime,
+}
+
+#[derive(Serialize)]
+struct Attested<'a, T> {
+    items: &'a T,
+    timestamp: &'a StorageDatetime,
+}
+
+impl<T: Serialize> SignedResponse<T> {
+    /// Sign `items` as of now.
+    pub fn sign(items: T, signer: &impl ResponseSigner) -> Result<Self, StorageError> {
+        let timestamp = StorageDatetime::now();
+        let digest = compute_said(&Attested {
+            items: &items,
+            timestamp: &timestamp,
+        })?;
+        let signature = signer.sign(digest.as_bytes())?;
+        Ok(Self {
+            items,
+            digest,
+            signature,
+            timestamp,
+        })
+    }
+
+    /// Sign `items` as of now with a possibly remote key; the signature is hex.
+    pub async fn sign_async(
+        items: T,
+        signer: &(impl AsyncSigner + ?Sized),
+    ) -> Result<Self, StorageError> {
+        let timestamp = StorageDatetime::now();
+        let digest = compute_said(&Attested {
+            items: &items,
+            timestamp: &timestamp,
+        })?;
+        let signature = encode_hex(&signer.sign(digest.as_bytes()).await?);
+        Ok(Self {
+            items,
+            digest,
+            signature,
+            timestamp,
+        })
+    }
+
+    /// Check the digest covers `items` and `timestamp`, and the signature covers the digest.
+    pub fn verify(&self, verifier: &impl ResponseVerifier) -> Result<(), StorageError> {
+        let digest = compute_said(&Attested {
+            items: &self.items,
+            timestamp: &self.timestamp,
+        })?;
+        if digest != self.digest {
+            return Err(StorageError::InvalidSaid(format!(
+                "Response digest mismatch: expected {}, got {}",
+                digest, self.digest
+            )));
+        }
+        verifier.verify(self.digest.as_bytes(), &self.signature)
+    }
+
+    /// Verify, then unwrap the items.
+    pub fn into_verified(self, verifier: &impl ResponseVerifier) -> Result<T, StorageError> {
+        self.verify(verifier)?;
+        Ok(self.items)
+    }
+}
+
+/// Wraps a repository so reads come back as [`SignedResponse`]s.
+///
+/// The versioned read methods are provided directly; results from any other
+/// read (e.g. an unversioned `get_by_said`) can be signed with [`Self::sign`].
+#[derive(Debug, Clone)]
+pub struct SigningRepository<R, S> {
+    inner: R,
+    signer: S,
+}
+
+impl<R, S: ResponseSigner> SigningRepository<R, S> {
+    /// Wrap `inner`, signing its results with `signer`.
+    pub fn new(inner: R, signer: S) -> Self {
+        Self { inner, signer }
+    }
+
+    /// The wrapped repository, for unsigned access.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Sign arbitrary results with this repository's key.
+    pub fn sign<I: Serialize>(&self, items: I) -> Result<SignedResponse<I>, StorageError> {
+        SignedResponse::sign(items, &self.signer)
+    }
+
+    pub async fn get_by_said<T>(
+        &self,
+        said: &str,
+    ) -> Result<SignedResponse<Option<T>>, StorageError>
+    where
+        T: SelfAddressed + Versioned + Serialize + DeserializeOwned + Send + Sync,
+        R: VersionedRepository<T>,
+    {
+        self.sign(self.inner.get_by_said(said).await?)
+    }
+
+    pub async fn get_latest<T>(
+        &self,
+        prefix: &str,
+    ) -> Result<SignedResponse<Option<T>>, StorageError>
+    where
+        T: SelfAddressed + Versioned + Serialize + DeserializeOwned + Send + Sync,
+        R: VersionedRepository<T>,
+    {
+        self.sign(self.inner.get_latest(prefix).await?)
+    }
+
+    pub async fn get_history<T>(&self, prefix: &str) -> Result<SignedResponse<Vec<T>>, StorageError>
+    where
+        T: SelfAddressed + Versioned + Serialize + DeserializeOwned + Send + Sync,
+        R: VersionedRepository<T>,
+    {
+        self.sign(self.inner.get_history(prefix).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keyed-hash stand-in for a real signature scheme.
+    struct MacKey([u8; 32]);
+
+    impl ResponseSigner for MacKey {
+        fn sign(&self, message: &[u8]) -> Result<String, StorageError> {
+            Ok(blake3::keyed_hash(&self.0, message).to_hex().to_string())
+        }
+    }
+
+    impl ResponseVerifier for MacKey {
+        fn verify(&self, message: &[u8], signature: &str) -> Result<(), StorageError> {
+            if self.sign(message)? != signature {
+                return Err(StorageError::StorageError("Bad signature".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn signed_responses_verify() {
+        let key = MacKey([7; 32]);
+        let response =
+            SignedResponse::sign(vec!["Ea".to_string(), "Eb".to_string()], &key).unwrap();
+        assert!(response.digest.starts_with('E'));
+
+        let json = serde_json::to_string(&response).unwrap();
+        let received: SignedResponse<Vec<String>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(received.into_verified(&key).unwrap(), vec!["Ea", "Eb"]);
+    }
+
+    #[test]
+    fn altered_items_or_foreign_keys_fail() {
+        let key = MacKey([7; 32]);
+        let mut response = SignedResponse::sign(vec![1u64, 2], &key).unwrap();
+        assert!(response.verify(&MacKey([8; 32])).is_err());
+
+        response.items.pop();
+        assert!(response.verify(&key).is_err());
+    }
+
+    #[cfg(feature = "ed25519")]
+    #[test]
+    fn byte_signers_attest_with_hex_signatures() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let response = SignedResponse::sign(vec![1u64, 2], &key).unwrap();
+        assert_eq!(response.signature.len(), 128);
+        response.verify(&key.verifying_key()).unwrap();
+
+        let mut tampered = response.clone();
+        tampered.signature.replace_range(..2, "zz");
+        assert!(matches!(
+            tampered.verify(&key.verifying_key()),
+            Err(StorageError::InvalidSignature(_))
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+//! Central authorization of writes.
+//!
+//! A [`WriteAuthorizer`] sees every write an [`AuthorizedRepository`] is asked
+//! to make, as the item's serde JSON, and can veto it. Policy engines (OPA,
+//! custom rules) plug in once here rather than in every handler; closures
+//! taking the same arguments are authorizers too:
+//!
+//! ```text
+//! let rules: Arc<dyn WriteAuthorizer> = Arc::new(|op, table, item: &Value, ctx: &dyn AccessContext| {
+//!     if table == "domains" && op != WriteOperation::Create && !ctx.principals().contains(&admin) {
+//!         return Err(StorageError::AccessDenied("Only admins edit domains".to_string()));
+//!     }
+//!     Ok(())
+//! });
+//! let domains = AuthorizedRepository::new(DomainRepository::new(pool), rules, caller);
+//! ```
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    AccessContext, SelfAddressed, Storable, StorageError, UnversionedRepository, Versioned,
+    VersionedRepository, WriteOperation,
+};
+
+/// Decides whether a write may go ahead.
+pub trait WriteAuthorizer: Send + Sync {
+    /// Return `Err` (usually [`StorageError::AccessDenied`]) to reject the
+    /// write of `item` to `table` by the caller in `context`.
+    fn authorize(
+        &self,
+        operation: WriteOperation,
+        table: &str,
+        item: &serde_json::Value,
+        context: &dyn AccessContext,
+    ) -> Result<(), StorageError>;
+}
+
+impl<F> WriteAuthorizer for F
+where
+    F: Fn(WriteOperation, &str, &serde_json::Value, &dyn AccessContext) -> Result<(), StorageError>
+        + Send
+        + Sync,
+{
+    fn authorize(
+        &self,
+        operation: WriteOperation,
+        table: &str,
+        item: &serde_json::Value,
+        context: &dyn AccessContext,
+    ) -> Result<(), StorageError> {
+        self(operation, table, item, context)
+    }
+}
+
+/// Wraps a repository so every write is put to a [`WriteAuthorizer`] first.
+/// Reads pass through.
+#[derive(Clone)]
+pub struct AuthorizedRepository<R, C> {
+    inner: R,
+    authorizer: Arc<dyn WriteAuthorizer>,
+    context: C,
+}
+
+impl<R, C: AccessContext> AuthorizedRepository<R, C> {
+    /// Wrap `inner`, acting for `context`.
+    pub fn new(inner: R, authorizer: Arc<dyn WriteAuthorizer>, context: C) -> Self {
+        Self {
+            inner,
+            authorizer,
+            context,
+        }
+    }
+
+    fn check<T: Storable + Serialize>(
+        &self,
+        operation: WriteOperation,
+        item: &T,
+    ) -> Result<(), StorageError> {
+        self.authorizer.authorize(
+            operation,
+            T::table_name(),
+            &serde_json::to_value(item)?,
+            &self.context,
+        )
+    }
+}
+
+#[async_trait]
+impl<T, R, C> VersionedRepository<T> for AuthorizedRepository<R, C>
+where
+    T: Storable + SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+    C: AccessContext,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        self.check(WriteOperation::Create, &item)?;
+        self.inner.create(item).await
+    }
+
+    async fn update(&self, item: T) -> Result<T, StorageError> {
+        self.check(WriteOperation::Update, &item)?;
+        self.inner.update(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.check(WriteOperation::Insert, &item)?;
+        self.inner.insert(item).await
+    }
+
+    /// Each imported version is authorized as an insert.
+    async fn import_history(&self, items: Vec<T>) -> Result<Vec<T>, StorageError> {
+        for item in &items {
+            self.check(WriteOperation::Insert, item)?;
+        }
+        self.inner.import_history(items).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_latest(prefix).await
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        self.inner.get_history(prefix).await
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        self.inner.exists(prefix).await
+    }
+
+    /// The stored record is put to the authorizer.
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        let Some(item) = self.inner.get_by_said(said).await? else {
+            return Ok(false);
+        };
+        self.check(WriteOperation::SoftDelete, &item)?;
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[async_trait]
+impl<T, R, C> UnversionedRepository<T> for AuthorizedRepository<R, C>
+where
+    T: Storable + SelfAddressed + Serialize + DeserializeOwned + Clone + 'static,
+    R: UnversionedRepository<T> + Send + Sync,
+    C: AccessContext,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        self.check(WriteOperation::Create, &item)?;
+        self.inner.create(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.check(WriteOperation::Insert, &item)?;
+        self.inner.insert(item).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    /// The stored record is put to the authorizer.
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        let Some(item) = self.inner.get_by_said(said).await? else {
+            return Ok(false);
+        };
+        self.check(WriteOperation::SoftDelete, &item)?;
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "invoices")]
+    #[serde(rename_all = "camelCase")]
+    struct Invoice {
+        #[said]
+        said: String,
+        amount: u64,
+    }
+
+    fn repository(principal: &str) -> AuthorizedRepository<(), Vec<String>> {
+        let limit = |_: WriteOperation,
+                     table: &str,
+                     item: &serde_json::Value,
+                     context: &dyn AccessContext| {
+            let large = item["amount"].as_u64().is_some_and(|amount| amount > 100);
+            if large && !context.principals().iter().any(|p| p == "finance") {
+                return Err(StorageError::AccessDenied(format!(
+                    "Large {} need finance approval",
+                    table
+                )));
+            }
+            Ok(())
+        };
+        AuthorizedRepository::new((), Arc::new(limit), vec![principal.to_string()])
+    }
+
+    #[test]
+    fn authorizers_see_the_item_and_caller() {
+        let small = Invoice::create(20).unwrap();
+        let large = Invoice::create(5000).unwrap();
+
+        let clerk = repository("clerk");
+        clerk.check(WriteOperation::Create, &small).unwrap();
+        assert!(matches!(
+            clerk.check(WriteOperation::Create, &large),
+            Err(StorageError::AccessDenied(message)) if message.contains("invoices")
+        ));
+        repository("finance")
+            .check(WriteOperation::Insert, &large)
+            .unwrap();
+    }
+}
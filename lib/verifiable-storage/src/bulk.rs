@@ -0,0 +1,191 @@
+//! Backpressure-aware bulk writer.
+//!
+//! `BulkWriter<T>` accepts items over a bounded channel, groups them into batches
+//! (flushed when a batch is full or has waited long enough), and writes each batch
+//! in its own transaction. At most `max_in_flight` batches are written
+//! concurrently; when all slots are busy the channel fills up and `write()`
+//! waits, pushing backpressure onto producers.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{Semaphore, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::{QueryExecutor, Storable, StorageError, TransactionExecutor};
+
+/// Tuning knobs for a [`BulkWriter`].
+#[derive(Debug, Clone)]
+pub struct BulkWriterConfig {
+    /// Flush a batch once it holds this many items.
+    pub max_batch_size: usize,
+    /// Flush a non-empty batch after this long, even if it isn't full.
+    pub max_batch_delay: Duration,
+    /// Maximum number of batches being written at the same time.
+    pub max_in_flight: usize,
+    /// Number of items that can be queued before `write()` waits.
+    pub channel_capacity: usize,
+}
+
+impl Default for BulkWriterConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_size: 500,
+            max_batch_delay: Duration::from_millis(50),
+            max_in_flight: 4,
+            channel_capacity: 10_000,
+        }
+    }
+}
+
+/// Outcome of writing a single batch.
+#[derive(Debug)]
+pub struct BatchReport {
+    /// Sequence number of the batch (0, 1, 2, ...), in flush order.
+    pub batch: u64,
+    /// IDs (SAIDs) of the items in the batch, in submission order.
+    pub ids: Vec<String>,
+    /// Rows written, or the error that rolled the batch back.
+    pub result: Result<u64, StorageError>,
+}
+
+/// Batches items from a channel into transactional writes.
+///
+/// Per-batch results are delivered on the report receiver returned by
+/// [`BulkWriter::spawn`]. The report channel is bounded too, so it must be
+/// drained (or dropped) for writing to make progress.
+pub struct BulkWriter<T> {
+    sender: mpsc::Sender<T>,
+    task: JoinHandle<()>,
+}
+
+impl<T: Storable + 'static> BulkWriter<T> {
+    /// Start a writer that writes batches through `executor`.
+    pub fn spawn<E>(
+        executor: Arc<E>,
+        config: BulkWriterConfig,
+    ) -> (Self, mpsc::Receiver<BatchReport>)
+    where
+        E: QueryExecutor + 'static,
+        E::Transaction: 'static,
+    {
+        let (sender, receiver) = mpsc::channel(config.channel_capacity.max(1));
+        let (report_sender, report_receiver) = mpsc::channel(config.max_in_flight.max(1) * 2);
+        let task = tokio::spawn(run(executor, config, receiver, report_sender));
+        (Self { sender, task }, report_receiver)
+    }
+
+    /// Queue an item, waiting while the writer is saturated.
+    pub async fn write(&self, item: T) -> Result<(), StorageError> {
+        self.sender
+            .send(item)
+            .await
+            .map_err(|_| StorageError::StorageError("Bulk writer has stopped".to_string()))
+    }
+
+    /// A sender for producers that run on other tasks.
+    pub fn sender(&self) -> mpsc::Sender<T> {
+        self.sender.clone()
+    }
+
+    /// Stop accepting items, flush what is queued, and wait for all batches to finish.
+    ///
+    /// Clones handed out by [`BulkWriter::sender`] keep the writer open until they are dropped.
+    pub async fn finish(self) -> Result<(), StorageError> {
+        drop(self.sender);
+        self.task
+            .await
+            .map_err(|e| StorageError::StorageError(format!("Bulk writer task failed: {}", e)))
+    }
+}
+
+/// Collect batches and dispatch them until the input channel closes.
+async fn run<T, E>(
+    executor: Arc<E>,
+    config: BulkWriterConfig,
+    mut receiver: mpsc::Receiver<T>,
+    reports: mpsc::Sender<BatchReport>,
+) where
+    T: Storable + 'static,
+    E: QueryExecutor + 'static,
+    E::Transaction: 'static,
+{
+    let max_batch_size = config.max_batch_size.max(1);
+    let max_in_flight = config.max_in_flight.max(1);
+    let slots = Arc::new(Semaphore::new(max_in_flight));
+    let mut sequence = 0u64;
+    let mut closed = false;
+
+    while !closed {
+        let Some(first) = receiver.recv().await else {
+            break;
+        };
+
+        let mut batch = Vec::with_capacity(max_batch_size);
+        batch.push(first);
+
+        let deadline = Instant::now() + config.max_batch_delay;
+        while batch.len() < max_batch_size {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(item)) => batch.push(item),
+                Ok(None) => {
+                    closed = true;
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+
+        // Waiting here (rather than in the batch task) is what stops us pulling
+        // more items off the channel while every slot is busy.
+        let Ok(permit) = slots.clone().acquire_owned().await else {
+            break;
+        };
+
+        let executor = executor.clone();
+        let reports = reports.clone();
+        let batch_number = sequence;
+        sequence += 1;
+
+        tokio::spawn(async move {
+            let ids = batch.iter().map(|item| item.id().to_string()).collect();
+            let result = write_batch(executor.as_ref(), &batch).await;
+            // A dropped report receiver just means nobody is listening
+            let _ = reports
+                .send(BatchReport {
+                    batch: batch_number,
+                    ids,
+                    result,
+                })
+                .await;
+            drop(permit);
+        });
+    }
+
+    // Wait for outstanding batches by reclaiming every slot
+    let _ = slots.acquire_many(max_in_flight as u32).await;
+}
+
+/// Write a batch in one transaction, rolling back on the first failure.
+async fn write_batch<T, E>(executor: &E, items: &[T]) -> Result<u64, StorageError>
+where
+    T: Storable,
+    E: QueryExecutor,
+{
+    let mut tx = executor.begin_transaction().await?;
+    let mut written = 0;
+
+    for item in items {
+        match tx.insert(item).await {
+            Ok(rows) => written += rows,
+            Err(e) => {
+                let _ = tx.rollback().await;
+                return Err(e);
+            }
+        }
+    }
+
+    tx.commit().await?;
+    Ok(written)
+}
@@ -0,0 +1,152 @@
+//! SAIDs over canonical CBOR, and CBOR column values.
+//!
+//! JSON is bulky for large records and its byte form depends on serializer
+//! details. With `#[storable(encoding = "cbor")]` a type's SAID is instead
+//! computed over deterministic CBOR (RFC 8949 §4.2): definite lengths,
+//! shortest integer and float forms, and map keys sorted by their encoded
+//! bytes, so field order no longer affects the digest.
+//!
+//! Fields marked `#[column(cbor)]` get the `"cbor"` column type and are stored
+//! as canonical CBOR blobs (`bytea` in PostgreSQL) rather than JSON.
+
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use cesr::Matter;
+use ciborium::Value;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{SaidAlgorithm, StorageError};
+
+/// Encode `data` as canonical CBOR.
+pub fn to_canonical_cbor<T: Serialize>(data: &T) -> Result<Vec<u8>, StorageError> {
+    encode(&canonical(serialized(data)?)?)
+}
+
+/// Decode CBOR produced by [`to_canonical_cbor`].
+pub fn from_cbor<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, StorageError> {
+    ciborium::from_reader(bytes).map_err(|e| StorageError::StorageError(e.to_string()))
+}
+
+/// Compute a SAID over the canonical CBOR of `data`, with Blake3-256.
+pub fn compute_said_cbor<T: Serialize>(data: &T) -> Result<String, StorageError> {
+    compute_said_cbor_with(data, SaidAlgorithm::Blake3)
+}
+
+/// [`compute_said_cbor`] with the given hash.
+pub fn compute_said_cbor_with<T: Serialize>(
+    data: &T,
+    algorithm: SaidAlgorithm,
+) -> Result<String, StorageError> {
+    said_of(&to_canonical_cbor(data)?, algorithm)
+}
+
+/// [`compute_said_cbor_with`] over only the given top-level keys of `data`.
+pub fn compute_said_cbor_over_with<T: Serialize>(
+    data: &T,
+    keys: &[&str],
+    algorithm: SaidAlgorithm,
+) -> Result<String, StorageError> {
+    let Value::Map(entries) = serialized(data)? else {
+        return Err(StorageError::InvalidSaid(
+            "SAID field subsets require data that serializes to a map".to_string(),
+        ));
+    };
+    let entries = entries
+        .into_iter()
+        .filter(|(key, _)| key.as_text().is_some_and(|key| keys.contains(&key)))
+        .collect();
+    said_of(&encode(&canonical(Value::Map(entries))?)?, algorithm)
+}
+
+fn said_of(bytes: &[u8], algorithm: SaidAlgorithm) -> Result<String, StorageError> {
+    let digest = cesr::Digest::from_raw(algorithm.digest_code(), algorithm.digest(bytes))?;
+    Ok(digest.qb64())
+}
+
+fn serialized<T: Serialize>(data: &T) -> Result<Value, StorageError> {
+    Value::serialized(data).map_err(|e| StorageError::StorageError(e.to_string()))
+}
+
+fn encode(value: &Value) -> Result<Vec<u8>, StorageError> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(value, &mut bytes)
+        .map_err(|e| StorageError::StorageError(e.to_string()))?;
+    Ok(bytes)
+}
+
+/// Sort every map by its keys' encodings. ciborium already writes definite
+/// lengths and the shortest forms of numbers.
+fn canonical(value: Value) -> Result<Value, StorageError> {
+    Ok(match value {
+        Value::Map(entries) => {
+            let mut keyed = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = canonical(key)?;
+                    Ok((encode(&key)?, key, canonical(value)?))
+                })
+                .collect::<Result<Vec<_>, StorageError>>()?;
+            keyed.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Map(
+                keyed
+                    .into_iter()
+                    .map(|(_, key, value)| (key, value))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => {
+            Value::Array(items.into_iter().map(canonical).collect::<Result<_, _>>()?)
+        }
+        Value::Tag(tag, inner) => Value::Tag(tag, Box::new(canonical(*inner)?)),
+        other => other,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SelfAddressed;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(encoding = "cbor")]
+    #[serde(rename_all = "camelCase")]
+    struct Reading {
+        #[said]
+        said: String,
+        sensor: String,
+        samples: Vec<i64>,
+    }
+
+    #[test]
+    fn canonical_cbor_ignores_field_order() {
+        let forward = serde_json::json!({ "b": 1, "a": [1.5, { "z": true, "y": null }] });
+        let reversed = serde_json::json!({ "a": [1.5, { "y": null, "z": true }], "b": 1 });
+        let bytes = to_canonical_cbor(&forward).unwrap();
+        assert_eq!(bytes, to_canonical_cbor(&reversed).unwrap());
+        assert_eq!(bytes[..2], [0xa2, 0x61]);
+        assert_eq!(from_cbor::<serde_json::Value>(&bytes).unwrap(), forward);
+        assert_eq!(
+            compute_said_cbor(&forward).unwrap(),
+            compute_said_cbor(&reversed).unwrap()
+        );
+    }
+
+    #[test]
+    fn cbor_encoded_types_digest_cbor() {
+        let reading = Reading::create("thermo-1".to_string(), vec![20, 21, -3]).unwrap();
+        reading.verify_said().unwrap();
+
+        let mut placeholder = reading.clone();
+        placeholder.said = crate::said_placeholder(crate::SAID_DIGEST_CODE).unwrap();
+        assert_eq!(reading.said, compute_said_cbor(&placeholder).unwrap());
+        assert_ne!(reading.said, crate::compute_said(&placeholder).unwrap());
+        assert_eq!(
+            compute_said_cbor_over_with(&placeholder, &["sensor"], SaidAlgorithm::Blake3).unwrap(),
+            compute_said_cbor(&serde_json::json!({ "sensor": "thermo-1" })).unwrap()
+        );
+    }
+}
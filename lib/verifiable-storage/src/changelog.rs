@@ -0,0 +1,211 @@
+//! Structured changelog records written alongside updates.
+//!
+//! [`ChangelogRepository`] writes each update together with a self-addressed
+//! [`ChangeRecord`] naming the fields that changed, in the same transaction,
+//! so "what changed when" is a lookup rather than a diff of stored payloads:
+//!
+//! ```text
+//! CREATE TABLE change_records (
+//!     said TEXT PRIMARY KEY,
+//!     source_table TEXT NOT NULL,
+//!     prefix TEXT NOT NULL,
+//!     version BIGINT NOT NULL,
+//!     from_said TEXT NOT NULL,
+//!     to_said TEXT NOT NULL,
+//!     changed_fields TEXT NOT NULL,
+//!     created_at TIMESTAMPTZ NOT NULL
+//! );
+//! CREATE INDEX change_records_prefix ON change_records (source_table, prefix, version);
+//! ```
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    Order, Query, QueryExecutor, SelfAddressed, Storable, StorageDatetime, StorageError,
+    TransactionExecutor, Versioned, VersionedRepository, diff_items,
+};
+
+/// The fields changed by one update of a prefix.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, crate::SelfAddressed)]
+#[storable(table = "change_records")]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeRecord {
+    #[said]
+    pub said: String,
+    pub source_table: String,
+    pub prefix: String,
+    /// The version the update produced.
+    pub version: u64,
+    pub from_said: String,
+    pub to_said: String,
+    /// Serde keys of the changed fields, comma-separated.
+    pub changed_fields: String,
+    #[created_at]
+    pub created_at: StorageDatetime,
+}
+
+impl ChangeRecord {
+    /// A record of the change from `from` to `to`, with its SAID computed.
+    pub fn between<T: Storable + Versioned>(from: &T, to: &T) -> Result<Self, StorageError> {
+        let diff = diff_items(from, from.get_version(), to, to.get_version())?;
+        let fields: Vec<&str> = diff
+            .changes
+            .iter()
+            .map(|change| change.field.as_str())
+            .collect();
+        let mut record = Self::new(
+            T::table_name().to_string(),
+            to.get_prefix(),
+            to.get_version(),
+            from.get_said(),
+            to.get_said(),
+            fields.join(","),
+        );
+        record.derive_said()?;
+        Ok(record)
+    }
+
+    /// The changed fields' serde keys.
+    pub fn fields(&self) -> Vec<&str> {
+        self.changed_fields
+            .split(',')
+            .filter(|field| !field.is_empty())
+            .collect()
+    }
+}
+
+/// Wraps a versioned repository so every update also writes a [`ChangeRecord`].
+///
+/// Updates go straight to `T`'s table through the executor, in a transaction
+/// with the change record; everything else is delegated to the wrapped repository.
+#[derive(Debug, Clone)]
+pub struct ChangelogRepository<R, E> {
+    inner: R,
+    executor: E,
+}
+
+impl<R, E: QueryExecutor> ChangelogRepository<R, E> {
+    /// Wrap `inner`, writing updates through `executor`.
+    pub fn new(inner: R, executor: E) -> Self {
+        Self { inner, executor }
+    }
+
+    /// Change records for `prefix` in `T`'s table, oldest first.
+    pub async fn changes<T: Storable>(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<ChangeRecord>, StorageError> {
+        self.executor
+            .fetch(
+                Query::<ChangeRecord>::new()
+                    .eq("source_table", T::table_name())
+                    .eq("prefix", prefix)
+                    .order_by("version", Order::Asc),
+            )
+            .await
+    }
+}
+
+#[async_trait]
+impl<T, R, E> VersionedRepository<T> for ChangelogRepository<R, E>
+where
+    T: Storable + SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+    E: QueryExecutor,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        self.inner.create(item).await
+    }
+
+    async fn update(&self, mut item: T) -> Result<T, StorageError> {
+        let previous = self
+            .inner
+            .get_by_said(&item.get_said())
+            .await?
+            .ok_or_else(|| {
+                StorageError::NotFound(format!("Version being updated: {}", item.get_said()))
+            })?;
+        item.increment()?;
+        let record = ChangeRecord::between(&previous, &item)?;
+
+        let mut tx = self.executor.begin_transaction().await?;
+        let written = async {
+            tx.insert(&item).await?;
+            tx.insert(&record).await
+        }
+        .await;
+        match written {
+            Ok(_) => {
+                tx.commit().await?;
+                Ok(item)
+            }
+            Err(e) => {
+                tx.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.inner.insert(item).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_latest(prefix).await
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        self.inner.get_history(prefix).await
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        self.inner.exists(prefix).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "pages")]
+    #[serde(rename_all = "camelCase")]
+    struct Page {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        title: String,
+        body: String,
+    }
+
+    #[test]
+    fn records_name_the_changed_fields() {
+        let mut v0 = Page::new("Title".to_string(), "v0".to_string());
+        v0.derive_prefix().unwrap();
+        let mut v1 = v0.clone();
+        v1.body = "v1".to_string();
+        v1.increment().unwrap();
+
+        let record = ChangeRecord::between(&v0, &v1).unwrap();
+        record.verify_said().unwrap();
+        assert_eq!(record.source_table, "pages");
+        assert_eq!(record.version, 1);
+        assert_eq!(record.from_said, v0.said);
+        assert_eq!(record.to_said, v1.said);
+        assert_eq!(record.fields(), vec!["body"]);
+    }
+}
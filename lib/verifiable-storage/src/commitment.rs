@@ -0,0 +1,125 @@
+//! Hash commitments for sensitive numeric fields.
+//!
+//! A commitment is a keyed Blake3 hash of the value under a random 256-bit
+//! blinding, CESR-encoded like a SAID. Store the commitment in an ordinary
+//! field so it enters the record's SAID, and hand the [`Opening`] only to
+//! parties allowed to learn the value; they can check it with
+//! [`verify_opening`]. Commitments are hiding and binding, but unlike Pedersen
+//! commitments they are not additively homomorphic.
+
+use cesr::Matter;
+
+use crate::StorageError;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+/// Domain separation for numeric commitments.
+const DOMAIN: &[u8] = b"verifiable-storage/commitment/i128/v1";
+
+/// The secret needed to open a commitment: the value and its blinding.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Opening {
+    pub value: i128,
+    /// 32 random bytes, hex-encoded.
+    pub blinding: String,
+}
+
+impl Opening {
+    /// Open `value` under a fresh random blinding.
+    pub fn new(value: impl Into<i128>) -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self {
+            value: value.into(),
+            blinding: bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+
+    /// The commitment this opening opens.
+    pub fn commitment(&self) -> Result<String, StorageError> {
+        let mut hasher = blake3::Hasher::new_keyed(&self.blinding_key()?);
+        hasher.update(DOMAIN);
+        hasher.update(&self.value.to_be_bytes());
+        let digest = cesr::Digest::from_raw(
+            cesr::DigestCode::Blake3,
+            hasher.finalize().as_bytes().to_vec(),
+        )?;
+        Ok(digest.qb64())
+    }
+
+    fn blinding_key(&self) -> Result<[u8; 32], StorageError> {
+        let invalid =
+            || StorageError::InvalidSaid("Blinding must be 64 hex characters".to_string());
+        if self.blinding.len() != 64 || !self.blinding.is_ascii() {
+            return Err(invalid());
+        }
+        let mut key = [0u8; 32];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte =
+                u8::from_str_radix(&self.blinding[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(key)
+    }
+}
+
+/// Commit to `value`, returning the commitment and the opening to keep secret.
+pub fn commit(value: impl Into<i128>) -> Result<(String, Opening), StorageError> {
+    let opening = Opening::new(value);
+    Ok((opening.commitment()?, opening))
+}
+
+/// Check that `opening` opens `commitment`.
+pub fn verify_opening(commitment: &str, opening: &Opening) -> Result<(), StorageError> {
+    if opening.commitment()? != commitment {
+        return Err(StorageError::InvalidSaid(format!(
+            "Opening does not match commitment {}",
+            commitment
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SelfAddressed;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[serde(rename_all = "camelCase")]
+    struct Payment {
+        #[said]
+        said: String,
+        payee: String,
+        amount_commitment: String,
+    }
+
+    #[test]
+    fn openings_verify_and_commitments_enter_the_said() {
+        let (commitment, opening) = commit(1_250u64).unwrap();
+        let mut payment = Payment::new("Eacme".to_string(), commitment.clone());
+        payment.derive_said().unwrap();
+        verify_opening(&payment.amount_commitment, &opening).unwrap();
+
+        let (other, _) = commit(1_250u64).unwrap();
+        assert_ne!(other, commitment);
+        payment.amount_commitment = other;
+        assert!(payment.verify_said().is_err());
+    }
+
+    #[test]
+    fn wrong_openings_fail() {
+        let (commitment, opening) = commit(-40i64).unwrap();
+        let wrong_value = Opening {
+            value: 40,
+            ..opening.clone()
+        };
+        assert!(verify_opening(&commitment, &wrong_value).is_err());
+
+        let bad_blinding = Opening {
+            blinding: "zz".to_string(),
+            ..opening
+        };
+        assert!(verify_opening(&commitment, &bad_blinding).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! Field-level differences between two versions of an item.
+//!
+//! Items are compared in their serde form, top-level field by field, so fields
+//! skipped by serde never appear and a redacted disclosure block shows up only
+//! as a changed digest. Storage-managed fields (SAID, prefix, previous,
+//! version, created_at) change with every version and are left out.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Storable, StorageError};
+
+/// One top-level field that differs between two versions.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+    /// The field's serde key.
+    pub field: String,
+    /// The earlier value; `None` if the field was absent.
+    pub from: Option<serde_json::Value>,
+    /// The later value; `None` if the field was removed.
+    pub to: Option<serde_json::Value>,
+}
+
+/// The fields that changed between two versions, in serialization order.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldDiff {
+    pub from_version: u64,
+    pub to_version: u64,
+    pub changes: Vec<FieldChange>,
+}
+
+impl FieldDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The change to `field`, if it changed.
+    pub fn get(&self, field: &str) -> Option<&FieldChange> {
+        self.changes.iter().find(|change| change.field == field)
+    }
+}
+
+/// Compare two items of the same type field by field.
+pub fn diff_items<T: Storable>(
+    from: &T,
+    from_version: u64,
+    to: &T,
+    to_version: u64,
+) -> Result<FieldDiff, StorageError> {
+    let from = as_object(serde_json::to_value(from)?)?;
+    let to = as_object(serde_json::to_value(to)?)?;
+    let managed = managed_keys::<T>();
+
+    let mut changes: Vec<FieldChange> = from
+        .iter()
+        .filter(|(key, _)| !managed.contains(&key.as_str()))
+        .filter(|(key, value)| to.get(*key) != Some(*value))
+        .map(|(key, value)| FieldChange {
+            field: key.clone(),
+            from: Some(value.clone()),
+            to: to.get(key).cloned(),
+        })
+        .collect();
+    changes.extend(
+        to.iter()
+            .filter(|(key, _)| !managed.contains(&key.as_str()) && !from.contains_key(*key))
+            .map(|(key, value)| FieldChange {
+                field: key.clone(),
+                from: None,
+                to: Some(value.clone()),
+            }),
+    );
+
+    Ok(FieldDiff {
+        from_version,
+        to_version,
+        changes,
+    })
+}
+
+/// Serde keys of the storage-managed fields.
+fn managed_keys<T: Storable>() -> Vec<&'static str> {
+    T::managed_columns()
+        .iter()
+        .filter_map(|(column, _)| {
+            let position = T::columns().iter().position(|c| c == column)?;
+            T::json_keys().get(position).copied()
+        })
+        .collect()
+}
+
+fn as_object(
+    value: serde_json::Value,
+) -> Result<serde_json::Map<String, serde_json::Value>, StorageError> {
+    match value {
+        serde_json::Value::Object(object) => Ok(object),
+        _ => Err(StorageError::StorageError(
+            "Only items that serialize to objects can be diffed".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Versioned;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "profiles")]
+    #[serde(rename_all = "camelCase")]
+    struct Profile {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        #[created_at]
+        created_at: crate::StorageDatetime,
+        display_name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        email: Option<String>,
+        #[serde(skip)]
+        #[column(skip)]
+        session: String,
+    }
+
+    #[test]
+    fn reports_only_content_changes() {
+        let mut v0 = Profile::new("alice".to_string(), None, String::new());
+        v0.derive_prefix().unwrap();
+        let mut v1 = v0.clone();
+        v1.display_name = "Alice".to_string();
+        v1.email = Some("alice@example.com".to_string());
+        v1.session = "not serialized".to_string();
+        v1.increment().unwrap();
+
+        let diff = diff_items(&v0, 0, &v1, 1).unwrap();
+        assert_eq!(diff.changes.len(), 2);
+        assert_eq!(
+            diff.get("displayName").unwrap().to,
+            Some(serde_json::json!("Alice"))
+        );
+        let email = diff.get("email").unwrap();
+        assert_eq!(email.from, None);
+        assert!(diff_items(&v1, 1, &v1, 1).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+//! Graduated disclosure with salted field digests.
+//!
+//! Following ACDC's compact/full forms, each disclosable field is stored as a
+//! block `{"d": <digest>, "u": <salt>, "v": <value>}`, where `d` is the SAID of
+//! the block. The record's SAID is computed over the *compact* form, in which
+//! every block is replaced by its digest. Redacting a field replaces its block
+//! with the digest alone, so a redacted copy carries the same SAID and still
+//! verifies, while the random salt keeps the hidden value from being guessed.
+
+use crate::keri::DUMMY;
+use crate::{StorageError, compute_said};
+use rand::RngCore;
+use serde_json::{Map, Value};
+
+/// Block label for the digest of a disclosable field.
+const DIGEST: &str = "d";
+/// Block label for the salt.
+const SALT: &str = "u";
+/// Block label for the value.
+const VALUE: &str = "v";
+
+/// Turn `fields` of `record` into salted blocks and set `said_label` to the
+/// SAID of the compact form.
+pub fn blind(record: &Value, fields: &[&str], said_label: &str) -> Result<Value, StorageError> {
+    let mut blinded = record.clone();
+    let object = as_object_mut(&mut blinded)?;
+    for field in fields {
+        let value = object.remove(*field).ok_or_else(|| missing(field))?;
+        object.insert(field.to_string(), block(salt(), value)?);
+    }
+    let said = filled_said(compact(&blinded, fields)?, said_label)?;
+    as_object_mut(&mut blinded)?.insert(said_label.to_string(), Value::String(said));
+    Ok(blinded)
+}
+
+/// The compact form of a blinded record: every field in `fields` reduced to its digest.
+///
+/// Blocks are checked against their digests along the way.
+pub fn compact(record: &Value, fields: &[&str]) -> Result<Value, StorageError> {
+    let mut compacted = record.clone();
+    let object = as_object_mut(&mut compacted)?;
+    for field in fields {
+        let entry = object.get(*field).ok_or_else(|| missing(field))?;
+        let digest = field_digest(field, entry)?;
+        object.insert(field.to_string(), Value::String(digest));
+    }
+    Ok(compacted)
+}
+
+/// A copy of a blinded record with `redacted` fields reduced to their digests.
+///
+/// The copy keeps the original SAID and passes [`verify_disclosure`].
+pub fn redact(record: &Value, redacted: &[&str]) -> Result<Value, StorageError> {
+    compact(record, redacted)
+}
+
+/// Verify a blinded record, whether fully disclosed or partly redacted.
+pub fn verify_disclosure(
+    record: &Value,
+    fields: &[&str],
+    said_label: &str,
+) -> Result<(), StorageError> {
+    let compacted = compact(record, fields)?;
+    let expected = filled_said(compacted.clone(), said_label)?;
+    if compacted.get(said_label) != Some(&Value::String(expected.clone())) {
+        return Err(StorageError::InvalidSaid(format!(
+            "Disclosure SAID verification failed: expected {}",
+            expected
+        )));
+    }
+    Ok(())
+}
+
+/// The disclosed value of `field`, or `None` if it was redacted.
+pub fn disclosed<'a>(record: &'a Value, field: &str) -> Option<&'a Value> {
+    record.get(field)?.as_object()?.get(VALUE)
+}
+
+/// The digest of a field entry: a redacted digest as-is, or a verified block's `d`.
+fn field_digest(field: &str, entry: &Value) -> Result<String, StorageError> {
+    match entry {
+        Value::String(digest) => Ok(digest.clone()),
+        Value::Object(object) => {
+            let (Some(Value::String(digest)), Some(Value::String(salt)), Some(value)) =
+                (object.get(DIGEST), object.get(SALT), object.get(VALUE))
+            else {
+                return Err(StorageError::InvalidSaid(format!(
+                    "Malformed disclosure block for `{}`",
+                    field
+                )));
+            };
+            let expected = block_said(salt, value)?;
+            if &expected != digest {
+                return Err(StorageError::InvalidSaid(format!(
+                    "Disclosure block for `{}` does not match its digest",
+                    field
+                )));
+            }
+            Ok(expected)
+        }
+        _ => Err(StorageError::InvalidSaid(format!(
+            "`{}` is neither a disclosure block nor a digest",
+            field
+        ))),
+    }
+}
+
+fn block(salt: String, value: Value) -> Result<Value, StorageError> {
+    let said = block_said(&salt, &value)?;
+    let mut object = Map::new();
+    object.insert(DIGEST.to_string(), Value::String(said));
+    object.insert(SALT.to_string(), Value::String(salt));
+    object.insert(VALUE.to_string(), value);
+    Ok(Value::Object(object))
+}
+
+fn block_said(salt: &str, value: &Value) -> Result<String, StorageError> {
+    let mut object = Map::new();
+    object.insert(DIGEST.to_string(), Value::String(String::new()));
+    object.insert(SALT.to_string(), Value::String(salt.to_string()));
+    object.insert(VALUE.to_string(), value.clone());
+    filled_said(Value::Object(object), DIGEST)
+}
+
+/// The SAID of `value` with `label` dummy-filled.
+fn filled_said(mut value: Value, label: &str) -> Result<String, StorageError> {
+    as_object_mut(&mut value)?.insert(label.to_string(), Value::String(DUMMY.to_string()));
+    compute_said(&value)
+}
+
+/// 128 random bits, hex-encoded.
+fn salt() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn as_object_mut(value: &mut Value) -> Result<&mut Map<String, Value>, StorageError> {
+    value.as_object_mut().ok_or_else(|| {
+        StorageError::InvalidSaid("Disclosable records must be JSON objects".to_string())
+    })
+}
+
+fn missing(field: &str) -> StorageError {
+    StorageError::InvalidSaid(format!("Missing disclosable field `{}`", field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const FIELDS: &[&str] = &["email", "dateOfBirth"];
+
+    fn record() -> Value {
+        blind(
+            &json!({
+                "said": "",
+                "name": "Ada",
+                "email": "ada@example.com",
+                "dateOfBirth": "1815-12-10"
+            }),
+            FIELDS,
+            "said",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn redacted_copies_keep_the_said_and_verify() {
+        let full = record();
+        verify_disclosure(&full, FIELDS, "said").unwrap();
+        assert_eq!(disclosed(&full, "email"), Some(&json!("ada@example.com")));
+
+        let shared = redact(&full, &["dateOfBirth"]).unwrap();
+        assert_eq!(shared["said"], full["said"]);
+        assert!(shared["dateOfBirth"].is_string());
+        assert_eq!(disclosed(&shared, "dateOfBirth"), None);
+        assert_eq!(disclosed(&shared, "email"), Some(&json!("ada@example.com")));
+        verify_disclosure(&shared, FIELDS, "said").unwrap();
+    }
+
+    #[test]
+    fn salts_hide_equal_values() {
+        assert_ne!(record()["email"]["d"], record()["email"]["d"]);
+    }
+
+    #[test]
+    fn tampering_fails() {
+        let mut disclosed_value = record();
+        disclosed_value["email"]["v"] = json!("mallory@example.com");
+        assert!(verify_disclosure(&disclosed_value, FIELDS, "said").is_err());
+
+        let mut plain_field = redact(&record(), FIELDS).unwrap();
+        plain_field["name"] = json!("Mallory");
+        assert!(verify_disclosure(&plain_field, FIELDS, "said").is_err());
+    }
+}
@@ -0,0 +1,54 @@
+//! Dry-run support for executors.
+//!
+//! An executor in [`ExecutorMode::DryRun`] still runs reads, but renders writes
+//! (inserts and deletes) into [`RenderedStatement`]s instead of executing them.
+//! Operators can preview bulk maintenance this way, and tests can snapshot the
+//! generated statements.
+
+use std::sync::{Arc, Mutex};
+
+/// Whether an executor performs writes or only records them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExecutorMode {
+    /// Execute every statement (the default).
+    #[default]
+    Execute,
+    /// Record writes without executing them; they report 0 rows affected.
+    DryRun,
+}
+
+/// A write statement as it would have been sent to the database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedStatement {
+    /// The statement text, with placeholders.
+    pub sql: String,
+    /// Bound values, keyed by placeholder (`$1`, `$p0`, `$item`, ...), in binding order.
+    pub params: Vec<(String, serde_json::Value)>,
+}
+
+/// Shared, append-only record of statements captured in dry-run mode.
+///
+/// Clones share the same record, so an executor and the transactions it
+/// begins all write into one log.
+#[derive(Debug, Clone, Default)]
+pub struct DryRunLog {
+    statements: Arc<Mutex<Vec<RenderedStatement>>>,
+}
+
+impl DryRunLog {
+    /// Append a statement.
+    pub fn record(&self, statement: RenderedStatement) {
+        match self.statements.lock() {
+            Ok(mut statements) => statements.push(statement),
+            Err(poisoned) => poisoned.into_inner().push(statement),
+        }
+    }
+
+    /// Remove and return every recorded statement, oldest first.
+    pub fn take(&self) -> Vec<RenderedStatement> {
+        match self.statements.lock() {
+            Ok(mut statements) => std::mem::take(&mut *statements),
+            Err(poisoned) => std::mem::take(&mut *poisoned.into_inner()),
+        }
+    }
+}
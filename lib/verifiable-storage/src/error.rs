@@ -1,3 +1,5 @@
+use alloc::string::String;
+
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -16,6 +18,45 @@ pub enum StorageError {
 
     #[error("Not found: {0}")]
     NotFound(String),
+
+    #[error("Read-only: {0}")]
+    ReadOnly(String),
+
+    /// A guarded delete from a versioned table without `allow_versioned_delete()`.
+    #[error("Versioned delete refused: {0}")]
+    VersionedDelete(String),
+
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+
+    #[error("Timestamp from the future: {0}")]
+    FutureTimestamp(String),
+
+    #[error("Schema mismatch: {0}")]
+    SchemaMismatch(String),
+
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+
+    #[error("Invalid key rotation: {0}")]
+    InvalidRotation(String),
+
+    #[error("Invalid seal: {0}")]
+    InvalidSeal(String),
+
+    #[error("Validation failed: {0}")]
+    ValidationFailed(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    /// A write collided with a unique constraint. `said` is the value already
+    /// holding it (a SAID, or the prefix holding an index key) when known.
+    #[error("Duplicate: {constraint} already holds {}", .said.as_deref().unwrap_or("this value"))]
+    Duplicate {
+        constraint: String,
+        said: Option<String>,
+    },
 }
 
 #[cfg(feature = "surrealdb")]
@@ -0,0 +1,312 @@
+//! Write notifications for downstream consumers.
+//!
+//! [`EmittingRepository`] hands a [`WriteEvent`] to an [`EventSink`] after every
+//! successful `create`, `update` or `insert`, so indexers can react to writes
+//! instead of polling. Events are delivered after the write commits; a sink
+//! failure is returned to the caller but does not undo the write. For
+//! guaranteed delivery, use the transactional [`OutboxRepository`](crate::OutboxRepository).
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    ManagedField, SelfAddressed, Storable, StorageError, UnversionedRepository, Versioned,
+    VersionedRepository,
+};
+
+/// The kind of write that produced an event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WriteOperation {
+    Create,
+    Update,
+    Insert,
+    /// A record marked deleted with `delete_soft`; the event carries it as
+    /// it was before.
+    SoftDelete,
+}
+
+/// A successful write.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteEvent {
+    pub table: String,
+    pub operation: WriteOperation,
+    pub said: String,
+    /// The item's prefix, for versioned items.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// The item as written, in its serde representation.
+    pub item: serde_json::Value,
+}
+
+impl WriteEvent {
+    pub(crate) fn new<T: Storable + SelfAddressed>(
+        operation: WriteOperation,
+        item: &T,
+    ) -> Result<Self, StorageError> {
+        let value = serde_json::to_value(item)?;
+        let prefix = T::managed_columns()
+            .iter()
+            .find(|(_, field)| *field == ManagedField::Prefix)
+            .and_then(|(column, _)| {
+                let position = T::columns().iter().position(|c| c == column)?;
+                value.get(T::json_keys().get(position)?)?.as_str()
+            })
+            .map(str::to_string);
+        Ok(Self {
+            table: T::table_name().to_string(),
+            operation,
+            said: item.get_said(),
+            prefix,
+            item: value,
+        })
+    }
+
+    /// The key that keeps a lineage's events in order: its prefix, or the SAID
+    /// for unversioned items.
+    pub fn partition_key(&self) -> &str {
+        self.prefix.as_deref().unwrap_or(&self.said)
+    }
+}
+
+/// Receives write events.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    async fn emit(&self, event: WriteEvent) -> Result<(), StorageError>;
+}
+
+/// Publishes events to a tokio broadcast channel.
+///
+/// Having no subscribers is not an error; the event is dropped.
+#[cfg(feature = "events")]
+#[derive(Debug, Clone)]
+pub struct BroadcastSink {
+    sender: tokio::sync::broadcast::Sender<WriteEvent>,
+}
+
+#[cfg(feature = "events")]
+impl BroadcastSink {
+    /// A sink backed by a new channel holding up to `capacity` undelivered events.
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = tokio::sync::broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Receive events emitted from now on.
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<WriteEvent> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(feature = "events")]
+#[async_trait]
+impl EventSink for BroadcastSink {
+    async fn emit(&self, event: WriteEvent) -> Result<(), StorageError> {
+        let _ = self.sender.send(event);
+        Ok(())
+    }
+}
+
+/// POSTs each event as JSON to a webhook URL.
+#[cfg(feature = "webhooks")]
+#[derive(Debug, Clone)]
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+#[cfg(feature = "webhooks")]
+impl WebhookSink {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self::with_client(reqwest::Client::new(), url)
+    }
+
+    /// Use a preconfigured client (timeouts, auth headers, ...).
+    pub fn with_client(client: reqwest::Client, url: impl Into<String>) -> Self {
+        Self {
+            client,
+            url: url.into(),
+        }
+    }
+}
+
+#[cfg(feature = "webhooks")]
+#[async_trait]
+impl EventSink for WebhookSink {
+    async fn emit(&self, event: WriteEvent) -> Result<(), StorageError> {
+        self.client
+            .post(&self.url)
+            .json(&event)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| StorageError::StorageError(format!("Webhook delivery failed: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// Wraps a repository so every successful write is emitted to a sink.
+#[derive(Debug, Clone)]
+pub struct EmittingRepository<R, S> {
+    inner: R,
+    sink: S,
+}
+
+impl<R, S> EmittingRepository<R, S> {
+    /// Wrap `inner`, emitting its writes to `sink`.
+    pub fn new(inner: R, sink: S) -> Self {
+        Self { inner, sink }
+    }
+}
+
+impl<R, S: EventSink> EmittingRepository<R, S> {
+    async fn emitted<T: Storable + SelfAddressed>(
+        &self,
+        operation: WriteOperation,
+        item: T,
+    ) -> Result<T, StorageError> {
+        self.sink.emit(WriteEvent::new(operation, &item)?).await?;
+        Ok(item)
+    }
+}
+
+#[async_trait]
+impl<T, R, S> VersionedRepository<T> for EmittingRepository<R, S>
+where
+    T: Storable + SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+    S: EventSink,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.create(item).await?;
+        self.emitted(WriteOperation::Create, item).await
+    }
+
+    async fn update(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.update(item).await?;
+        self.emitted(WriteOperation::Update, item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.insert(item).await?;
+        self.emitted(WriteOperation::Insert, item).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_latest(prefix).await
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        self.inner.get_history(prefix).await
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        self.inner.exists(prefix).await
+    }
+
+    /// Emits the record as it was before the deletion.
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        let Some(item) = self.inner.get_by_said(said).await? else {
+            return Ok(false);
+        };
+        if !self.inner.delete_soft(said).await? {
+            return Ok(false);
+        }
+        self.emitted(WriteOperation::SoftDelete, item).await?;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl<T, R, S> UnversionedRepository<T> for EmittingRepository<R, S>
+where
+    T: Storable + SelfAddressed + Serialize + DeserializeOwned + Clone + 'static,
+    R: UnversionedRepository<T> + Send + Sync,
+    S: EventSink,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.create(item).await?;
+        self.emitted(WriteOperation::Create, item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.insert(item).await?;
+        self.emitted(WriteOperation::Insert, item).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    /// Emits the record as it was before the deletion.
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        let Some(item) = self.inner.get_by_said(said).await? else {
+            return Ok(false);
+        };
+        if !self.inner.delete_soft(said).await? {
+            return Ok(false);
+        }
+        self.emitted(WriteOperation::SoftDelete, item).await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "notes")]
+    #[serde(rename_all = "camelCase")]
+    struct Note {
+        #[said]
+        said: String,
+        body: String,
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "pages")]
+    #[serde(rename_all = "camelCase")]
+    struct Page {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        body: String,
+    }
+
+    #[test]
+    fn events_carry_table_said_and_item() {
+        let mut note = Note::new("hello".to_string());
+        note.derive_said().unwrap();
+
+        let event = WriteEvent::new(WriteOperation::Create, &note).unwrap();
+        assert_eq!(event.table, "notes");
+        assert_eq!(event.said, note.said);
+        assert_eq!(event.item["body"], "hello");
+        assert_eq!(serde_json::to_value(&event).unwrap()["operation"], "create");
+        assert_eq!(event.prefix, None);
+        assert_eq!(event.partition_key(), note.said);
+    }
+
+    #[test]
+    fn versioned_events_partition_by_prefix() {
+        let mut page = Page::new("v0".to_string());
+        page.derive_prefix().unwrap();
+        page.body = "v1".to_string();
+        page.increment().unwrap();
+
+        let event = WriteEvent::new(WriteOperation::Update, &page).unwrap();
+        assert_eq!(event.prefix.as_deref(), Some(page.prefix.as_str()));
+        assert_eq!(event.partition_key(), page.prefix);
+    }
+}
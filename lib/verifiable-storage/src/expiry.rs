@@ -0,0 +1,123 @@
+//! Expiry of records carrying an `expires_at` timestamp.
+//!
+//! Mark the field with `#[column(expires_at)]`; a `None` value never expires.
+//! Reads skip expired rows with [`Query::exclude_expired`] or
+//! [`VersionedRepository::get_latest_active`](crate::VersionedRepository::get_latest_active),
+//! and [`purge_expired`] removes them on a schedule.
+
+use crate::{
+    Delete, Filter, Query, QueryExecutor, Storable, StorageDatetime, StorageError,
+    storable::column_value,
+};
+
+impl<T: Storable> Query<T> {
+    /// Skip rows whose `#[column(expires_at)]` time has passed. No effect for
+    /// types without an expiry column.
+    pub fn exclude_expired(self) -> Self {
+        match T::expires_at_column() {
+            Some(column) => self.filter(Filter::Or(vec![
+                Filter::IsNull(column.to_string()),
+                Filter::Gt(column.to_string(), StorageDatetime::now().into()),
+            ])),
+            None => self,
+        }
+    }
+}
+
+/// Whether `item`'s expiry time has passed.
+pub fn is_expired<T: Storable>(item: &T) -> Result<bool, StorageError> {
+    let Some(column) = T::expires_at_column() else {
+        return Ok(false);
+    };
+    Ok(datetime_column(item, column)?
+        .is_some_and(|expires_at| expires_at <= StorageDatetime::now()))
+}
+
+/// The value of the datetime `column` in `item`'s serde form.
+pub(crate) fn datetime_column<T: Storable>(
+    item: &T,
+    column: &str,
+) -> Result<Option<StorageDatetime>, StorageError> {
+    match column_value(item, column)? {
+        Some(value) => Ok(Some(serde_json::from_value(value)?)),
+        None => Ok(None),
+    }
+}
+
+/// Delete `T`'s expired rows, returning how many were removed.
+///
+/// Versioned types fail with [`StorageError::VersionedDelete`], since purging
+/// a version leaves the later versions of its prefix unverifiable; use
+/// [`purge_expired_versions`] to accept that.
+pub async fn purge_expired<T, E>(executor: &E) -> Result<u64, StorageError>
+where
+    T: Storable,
+    E: QueryExecutor,
+{
+    let delete = expired_rows::<T>()?;
+    delete.check_allowed()?;
+    executor.delete(delete).await
+}
+
+/// As [`purge_expired`], for versioned types too. Later versions of a purged
+/// prefix can no longer be verified.
+pub async fn purge_expired_versions<T, E>(executor: &E) -> Result<u64, StorageError>
+where
+    T: Storable,
+    E: QueryExecutor,
+{
+    executor
+        .delete(expired_rows::<T>()?.allow_versioned_delete())
+        .await
+}
+
+/// A delete of `T`'s expired rows.
+fn expired_rows<T: Storable>() -> Result<Delete<T>, StorageError> {
+    let column = T::expires_at_column().ok_or_else(|| {
+        StorageError::StorageError(format!("{} has no expires_at column", T::table_name()))
+    })?;
+    Ok(Delete::<T>::new().filter(Filter::Lte(
+        column.to_string(),
+        StorageDatetime::now().into(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Dialect;
+    use std::time::Duration;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "sessions")]
+    #[serde(rename_all = "camelCase")]
+    struct Session {
+        #[said]
+        said: String,
+        token: String,
+        #[column(expires_at)]
+        valid_until: Option<StorageDatetime>,
+    }
+
+    #[test]
+    fn expiry_follows_the_marked_column() {
+        let live = Session::new(
+            "a".to_string(),
+            Some(StorageDatetime::now() + Duration::from_secs(60)),
+        );
+        let expired = Session::new("b".to_string(), Some(StorageDatetime::now()));
+        assert!(!is_expired(&live).unwrap());
+        assert!(is_expired(&expired).unwrap());
+        assert!(!is_expired(&Session::new("c".to_string(), None)).unwrap());
+
+        let (sql, params) = Query::<Session>::new()
+            .eq("token", "a")
+            .exclude_expired()
+            .to_sql(Dialect::Postgres);
+        assert_eq!(
+            sql,
+            "SELECT * FROM sessions WHERE token = $1 AND (valid_until IS NULL OR valid_until > $2)"
+        );
+        assert_eq!(params.len(), 2);
+    }
+}
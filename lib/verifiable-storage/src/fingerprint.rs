@@ -0,0 +1,66 @@
+//! Fingerprints of table layouts, for catching schema drift at startup.
+//!
+//! A deployment records each table's [`SchemaFingerprint`] after migrating;
+//! a binary whose types lay the table out differently then fails on connect
+//! with [`StorageError::SchemaMismatch`] instead of misbinding columns.
+
+use alloc::{format, string::String};
+
+use crate::{Storable, StorageError};
+
+/// A table and the [`Storable::schema_hash`] of the type stored in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaFingerprint {
+    pub table: String,
+    pub hash: String,
+}
+
+impl SchemaFingerprint {
+    /// The fingerprint of `T` stored in `table` (which may be a shard or
+    /// schema-qualified name rather than `T::table_name()`).
+    pub fn for_table<T: Storable>(table: impl Into<String>) -> Self {
+        Self {
+            table: table.into(),
+            hash: T::schema_hash(),
+        }
+    }
+
+    /// Compare with the hash recorded for the table. A table with nothing
+    /// recorded yet passes.
+    pub fn check(&self, recorded: Option<&str>) -> Result<(), StorageError> {
+        match recorded {
+            Some(recorded) if recorded != self.hash => Err(StorageError::SchemaMismatch(format!(
+                "{} was recorded with layout {} but this build expects {}; \
+                 migrate the table and record the new fingerprint",
+                self.table, recorded, self.hash
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "certificates")]
+    struct Certificate {
+        #[said]
+        said: String,
+        subject: String,
+    }
+
+    #[test]
+    fn mismatched_layouts_fail_with_a_description() {
+        let fingerprint = Certificate::schema_fingerprint();
+        assert_eq!(fingerprint.table, "certificates");
+        fingerprint.check(None).unwrap();
+        fingerprint
+            .check(Some(&Certificate::schema_hash()))
+            .unwrap();
+
+        let err = fingerprint.check(Some("0123")).unwrap_err();
+        assert!(matches!(&err, StorageError::SchemaMismatch(m) if m.contains("certificates")));
+    }
+}
@@ -0,0 +1,246 @@
+//! Repositories implemented purely in terms of [`QueryExecutor`].
+//!
+//! For a type stored in its own table with no companion tables, triggers or
+//! per-backend tuning, [`GenericVersionedRepository`] and
+//! [`GenericUnversionedRepository`] stand in for a `#[derive(Stored)]`
+//! repository struct. They work over any executor, so the same code runs on
+//! Postgres, SurrealDB or a `ShardedExecutor`:
+//!
+//! ```text
+//! let notes = GenericVersionedRepository::<Note, _>::new(pool.clone());
+//! let note = notes.create(Note::new("hello".into())).await?;
+//! ```
+
+use core::marker::PhantomData;
+
+use async_trait::async_trait;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    Delete, ManagedField, Order, Query, QueryExecutor, SelfAddressed, Storable, StorageError,
+    TransactionExecutor, UnversionedRepository, Versioned, VersionedRepository, order_history,
+    verify_history,
+};
+
+/// A [`VersionedRepository`] over `T::table_name()` on any [`QueryExecutor`].
+pub struct GenericVersionedRepository<T, E> {
+    executor: E,
+    reject_duplicate_versions: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, E> GenericVersionedRepository<T, E> {
+    /// Store `T` through `executor`.
+    pub fn new(executor: E) -> Self {
+        Self {
+            executor,
+            reject_duplicate_versions: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Fail `get_history` when a prefix has more than one row for a version,
+    /// instead of returning the fork in a deterministic order.
+    pub fn reject_duplicate_versions(mut self) -> Self {
+        self.reject_duplicate_versions = true;
+        self
+    }
+
+    /// The executor reads and writes go through.
+    pub fn executor(&self) -> &E {
+        &self.executor
+    }
+}
+
+impl<T, E: Clone> Clone for GenericVersionedRepository<T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            executor: self.executor.clone(),
+            reject_duplicate_versions: self.reject_duplicate_versions,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// An [`UnversionedRepository`] over `T::table_name()` on any [`QueryExecutor`].
+pub struct GenericUnversionedRepository<T, E> {
+    executor: E,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, E> GenericUnversionedRepository<T, E> {
+    /// Store `T` through `executor`.
+    pub fn new(executor: E) -> Self {
+        Self {
+            executor,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The executor reads and writes go through.
+    pub fn executor(&self) -> &E {
+        &self.executor
+    }
+}
+
+impl<T, E: Clone> Clone for GenericUnversionedRepository<T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            executor: self.executor.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// Lookups by SAID or prefix see soft-deleted rows so chains stay whole
+fn said_query<T: Storable>(said: &str) -> Query<T> {
+    Query::new()
+        .include_deleted()
+        .eq(T::managed_column(ManagedField::Said), said)
+        .limit(1)
+}
+
+fn said_delete<T: Storable>(said: &str) -> Delete<T> {
+    Delete::new().eq(T::managed_column(ManagedField::Said), said)
+}
+
+fn prefix_query<T: Storable>(prefix: &str) -> Query<T> {
+    Query::new()
+        .include_deleted()
+        .eq(T::managed_column(ManagedField::Prefix), prefix)
+}
+
+fn version_column<T: Storable>() -> &'static str {
+    T::managed_column(ManagedField::Version)
+}
+
+#[async_trait]
+impl<T, E> VersionedRepository<T> for GenericVersionedRepository<T, E>
+where
+    T: Storable + SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + 'static,
+    E: QueryExecutor,
+{
+    async fn create(&self, mut item: T) -> Result<T, StorageError> {
+        item.derive_prefix()?;
+        self.insert(item).await
+    }
+
+    async fn update(&self, mut item: T) -> Result<T, StorageError> {
+        item.increment()?;
+        self.insert(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.executor.insert(&item).await?;
+        Ok(item)
+    }
+
+    async fn import_history(&self, items: Vec<T>) -> Result<Vec<T>, StorageError> {
+        verify_history(&items)?;
+        let mut tx = self.executor.begin_transaction().await?;
+        let written = async {
+            for item in &items {
+                tx.insert(item).await?;
+            }
+            Ok::<(), StorageError>(())
+        }
+        .await;
+        match written {
+            Ok(()) => {
+                tx.commit().await?;
+                Ok(items)
+            }
+            Err(e) => {
+                tx.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.executor.fetch_optional(said_query(said)).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        let query = prefix_query(prefix)
+            .order_by(version_column::<T>(), Order::Desc)
+            .limit(1);
+        self.executor.fetch_optional(query).await
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        let query = prefix_query(prefix).order_by(version_column::<T>(), Order::Asc);
+        let mut history = self.executor.fetch(query).await?;
+        order_history(&mut history, self.reject_duplicate_versions)?;
+        Ok(history)
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        self.executor.exists(prefix_query::<T>(prefix)).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        Ok(self.executor.soft_delete(said_delete::<T>(said)).await? > 0)
+    }
+}
+
+#[async_trait]
+impl<T, E> UnversionedRepository<T> for GenericUnversionedRepository<T, E>
+where
+    T: Storable + SelfAddressed + Serialize + DeserializeOwned + Clone + 'static,
+    E: QueryExecutor,
+{
+    async fn create(&self, mut item: T) -> Result<T, StorageError> {
+        item.derive_said()?;
+        self.insert(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.executor.insert(&item).await?;
+        Ok(item)
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.executor.fetch_optional(said_query(said)).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        Ok(self.executor.soft_delete(said_delete::<T>(said)).await? > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Dialect;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "notes")]
+    #[serde(rename_all = "camelCase")]
+    struct Note {
+        #[said]
+        note_said: String,
+        #[prefix]
+        lineage: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        revision: u64,
+        body: String,
+    }
+
+    #[test]
+    fn queries_use_the_managed_columns() {
+        let (sql, _) = said_query::<Note>("E123").to_sql(Dialect::Postgres);
+        assert_eq!(sql, "SELECT * FROM notes WHERE note_said = $1 LIMIT 1");
+
+        let (sql, _) = prefix_query::<Note>("E123")
+            .order_by(version_column::<Note>(), Order::Desc)
+            .limit(1)
+            .to_sql(Dialect::Postgres);
+        assert_eq!(
+            sql,
+            "SELECT * FROM notes WHERE lineage = $1 ORDER BY revision DESC LIMIT 1"
+        );
+    }
+}
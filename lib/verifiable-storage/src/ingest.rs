@@ -0,0 +1,271 @@
+//! Deduplicating ingestion keyed by SAID.
+//!
+//! Relays redeliver events, so ingestion has to be idempotent and cheap for
+//! items we've already stored. `Ingestor<T, E>` checks each item's SAID, skips
+//! SAIDs it has seen recently without touching the database, and otherwise
+//! relies on `QueryExecutor::insert_or_ignore` so the database settles races.
+
+use std::collections::{HashSet, VecDeque};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use crate::{QueryExecutor, SelfAddressed, Storable, StorageError, Versioned};
+
+/// Default number of recently seen SAIDs to remember.
+const DEFAULT_CACHE_CAPACITY: usize = 10_000;
+
+/// How an ingested item was classified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IngestOutcome {
+    /// The item was written.
+    New,
+    /// The item was already stored (or appeared earlier in the same batch).
+    Duplicate,
+    /// The item failed SAID verification and was not written.
+    Invalid(String),
+}
+
+/// Classification of a single item in a batch.
+#[derive(Debug, Clone)]
+pub struct IngestedItem {
+    /// The item's SAID (as received, for invalid items).
+    pub said: String,
+    /// What happened to the item.
+    pub outcome: IngestOutcome,
+}
+
+/// Result of ingesting a batch, in input order.
+#[derive(Debug, Clone, Default)]
+pub struct IngestReport {
+    /// One entry per input item.
+    pub items: Vec<IngestedItem>,
+}
+
+impl IngestReport {
+    /// Number of items that were written.
+    pub fn new_count(&self) -> usize {
+        self.count(|outcome| matches!(outcome, IngestOutcome::New))
+    }
+
+    /// Number of items that were already stored.
+    pub fn duplicate_count(&self) -> usize {
+        self.count(|outcome| matches!(outcome, IngestOutcome::Duplicate))
+    }
+
+    /// Number of items rejected by verification.
+    pub fn invalid_count(&self) -> usize {
+        self.count(|outcome| matches!(outcome, IngestOutcome::Invalid(_)))
+    }
+
+    fn count(&self, predicate: impl Fn(&IngestOutcome) -> bool) -> usize {
+        self.items
+            .iter()
+            .filter(|item| predicate(&item.outcome))
+            .count()
+    }
+}
+
+/// Bounded set of recently seen SAIDs, evicting the oldest first.
+#[derive(Debug)]
+struct RecentSaids {
+    capacity: usize,
+    order: VecDeque<String>,
+    seen: HashSet<String>,
+}
+
+impl RecentSaids {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            seen: HashSet::with_capacity(capacity),
+        }
+    }
+
+    fn contains(&self, said: &str) -> bool {
+        self.seen.contains(said)
+    }
+
+    fn insert(&mut self, said: &str) {
+        if self.capacity == 0 || self.seen.contains(said) {
+            return;
+        }
+        if self.order.len() >= self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.seen.remove(&oldest);
+        }
+        self.order.push_back(said.to_string());
+        self.seen.insert(said.to_string());
+    }
+}
+
+/// Deduplicating, verifying ingestion pipeline.
+pub struct Ingestor<T, E> {
+    executor: Arc<E>,
+    prepare: fn(&mut T) -> Result<(), StorageError>,
+    recent: Mutex<RecentSaids>,
+    _marker: PhantomData<T>,
+}
+
+impl<T, E> Ingestor<T, E>
+where
+    T: SelfAddressed + Storable,
+    E: QueryExecutor,
+{
+    /// Create an ingestor for unversioned items.
+    ///
+    /// Items with an empty SAID have it computed; all others must verify.
+    pub fn new(executor: Arc<E>) -> Self {
+        Self::with_prepare(executor, |item: &mut T| {
+            if item.get_said().is_empty() {
+                item.derive_said()
+            } else {
+                item.verify_said()
+            }
+        })
+    }
+
+    /// Remember up to `capacity` recently seen SAIDs (0 disables the cache).
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.recent = Mutex::new(RecentSaids::new(capacity));
+        self
+    }
+
+    fn with_prepare(executor: Arc<E>, prepare: fn(&mut T) -> Result<(), StorageError>) -> Self {
+        Self {
+            executor,
+            prepare,
+            recent: Mutex::new(RecentSaids::new(DEFAULT_CACHE_CAPACITY)),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Ingest a batch, classifying each item as new, duplicate, or invalid.
+    ///
+    /// Storage errors abort the batch. Items written before the failure stay
+    /// written and will be reported as duplicates when the batch is redelivered.
+    pub async fn ingest(&self, items: Vec<T>) -> Result<IngestReport, StorageError> {
+        let mut report = IngestReport {
+            items: Vec::with_capacity(items.len()),
+        };
+
+        for mut item in items {
+            if let Err(e) = (self.prepare)(&mut item) {
+                report.items.push(IngestedItem {
+                    said: item.get_said(),
+                    outcome: IngestOutcome::Invalid(e.to_string()),
+                });
+                continue;
+            }
+
+            let said = item.get_said();
+            if self.is_recent(&said) {
+                report.items.push(IngestedItem {
+                    said,
+                    outcome: IngestOutcome::Duplicate,
+                });
+                continue;
+            }
+
+            let written = self.executor.insert_or_ignore(&item).await?;
+            self.remember(&said);
+
+            let outcome = if written > 0 {
+                IngestOutcome::New
+            } else {
+                IngestOutcome::Duplicate
+            };
+            report.items.push(IngestedItem { said, outcome });
+        }
+
+        Ok(report)
+    }
+
+    fn is_recent(&self, said: &str) -> bool {
+        match self.recent.lock() {
+            Ok(recent) => recent.contains(said),
+            Err(poisoned) => poisoned.into_inner().contains(said),
+        }
+    }
+
+    fn remember(&self, said: &str) {
+        match self.recent.lock() {
+            Ok(mut recent) => recent.insert(said),
+            Err(poisoned) => poisoned.into_inner().insert(said),
+        }
+    }
+}
+
+impl<T, E> Ingestor<T, E>
+where
+    T: Versioned + Storable,
+    E: QueryExecutor,
+{
+    /// Create an ingestor for versioned items.
+    ///
+    /// Every item must carry its SAID and pass `Versioned::verify()`.
+    pub fn versioned(executor: Arc<E>) -> Self {
+        Self::with_prepare(executor, |item: &mut T| item.verify())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_saids_evicts_oldest() {
+        let mut recent = RecentSaids::new(2);
+        recent.insert("a");
+        recent.insert("b");
+        recent.insert("c");
+        assert!(!recent.contains("a"));
+        assert!(recent.contains("b"));
+        assert!(recent.contains("c"));
+    }
+
+    #[test]
+    fn recent_saids_ignores_repeats() {
+        let mut recent = RecentSaids::new(2);
+        recent.insert("a");
+        recent.insert("a");
+        recent.insert("b");
+        assert!(recent.contains("a"));
+        assert!(recent.contains("b"));
+    }
+
+    #[test]
+    fn recent_saids_zero_capacity_remembers_nothing() {
+        let mut recent = RecentSaids::new(0);
+        recent.insert("a");
+        assert!(!recent.contains("a"));
+    }
+
+    #[test]
+    fn report_counts() {
+        let report = IngestReport {
+            items: vec![
+                IngestedItem {
+                    said: "a".to_string(),
+                    outcome: IngestOutcome::New,
+                },
+                IngestedItem {
+                    said: "b".to_string(),
+                    outcome: IngestOutcome::Duplicate,
+                },
+                IngestedItem {
+                    said: "c".to_string(),
+                    outcome: IngestOutcome::Invalid("bad".to_string()),
+                },
+                IngestedItem {
+                    said: "d".to_string(),
+                    outcome: IngestOutcome::New,
+                },
+            ],
+        };
+        assert_eq!(report.new_count(), 2);
+        assert_eq!(report.duplicate_count(), 1);
+        assert_eq!(report.invalid_count(), 1);
+    }
+}
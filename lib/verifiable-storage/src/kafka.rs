@@ -0,0 +1,70 @@
+//! Kafka event sink.
+//!
+//! Events are keyed by [`WriteEvent::partition_key`], so Kafka's key-hash
+//! partitioning keeps each prefix's changes in one partition, in order. Use it
+//! behind an [`OutboxRelay`](crate::OutboxRelay) for at-least-once delivery.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rdkafka::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+
+use crate::{EventSink, StorageError, WriteEvent};
+
+/// Default time to wait for the broker to acknowledge an event.
+const DEFAULT_DELIVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Publishes write events to a Kafka topic, keyed by prefix.
+#[derive(Clone)]
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic: String,
+    delivery_timeout: Duration,
+}
+
+impl KafkaSink {
+    /// Connect to `brokers` (comma-separated `host:port`) and publish to `topic`.
+    ///
+    /// The producer is idempotent, so broker retries don't duplicate events.
+    pub fn new(brokers: &str, topic: impl Into<String>) -> Result<Self, StorageError> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("enable.idempotence", "true")
+            .create()
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+        Ok(Self::with_producer(producer, topic))
+    }
+
+    /// Use a preconfigured producer.
+    pub fn with_producer(producer: FutureProducer, topic: impl Into<String>) -> Self {
+        Self {
+            producer,
+            topic: topic.into(),
+            delivery_timeout: DEFAULT_DELIVERY_TIMEOUT,
+        }
+    }
+
+    /// Time to wait for each acknowledgement (default 30s).
+    pub fn delivery_timeout(mut self, timeout: Duration) -> Self {
+        self.delivery_timeout = timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl EventSink for KafkaSink {
+    async fn emit(&self, event: WriteEvent) -> Result<(), StorageError> {
+        let payload = serde_json::to_vec(&event)?;
+        let record = FutureRecord::to(&self.topic)
+            .key(event.partition_key())
+            .payload(&payload);
+        self.producer
+            .send(record, self.delivery_timeout)
+            .await
+            .map_err(|(e, _)| {
+                StorageError::StorageError(format!("Kafka delivery failed: {}", e))
+            })?;
+        Ok(())
+    }
+}
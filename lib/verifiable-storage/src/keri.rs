@@ -0,0 +1,225 @@
+//! KERI-compatible SAIDs for arbitrary JSON payloads.
+//!
+//! `compute_said` already follows the KERI dummy-fill rule for a single field:
+//! the field is filled with 44 `#` characters (the qb64 length of a Blake3-256
+//! digest), the object is serialized as compact JSON in field order with
+//! non-ASCII characters unescaped (keripy's `separators=(",", ":")`,
+//! `ensure_ascii=False`), and the digest is encoded with derivation code `E`.
+//!
+//! This module adds the rest of what keripy's `Saider.saidify` does for KERI
+//! events and ACDCs: several fields may share the SAID (an inception's `d` and
+//! `i`), and a leading `v` version string has its size field set to the length
+//! of the serialization before hashing. Only Blake3-256 (`E`) is supported.
+
+use crate::{StorageError, compute_said};
+
+/// Dummy-fill for a Blake3-256 SAID field; `said_placeholder` for [`SAID_DIGEST_CODE`](crate::SAID_DIGEST_CODE).
+pub(crate) const DUMMY: &str = "############################################";
+
+/// Length of a KERI/ACDC v1 version string, e.g. `KERI10JSON0000fd_`.
+const VERSION_LEN: usize = 17;
+
+/// The default SAID field label in KERI events and ACDCs.
+pub const KERI_SAID_LABEL: &str = "d";
+
+/// Compute the SAID of `sad` as keripy would, dummy-filling every field in `labels`.
+pub fn compute_keri_said(sad: &serde_json::Value, labels: &[&str]) -> Result<String, StorageError> {
+    let filled = dummy_filled(sad, labels)?;
+    compute_said(&filled)
+}
+
+/// Return `sad` with its version string sized and every field in `labels` set to its SAID.
+pub fn saidify_keri(
+    sad: &serde_json::Value,
+    labels: &[&str],
+) -> Result<serde_json::Value, StorageError> {
+    let said = compute_keri_said(sad, labels)?;
+    let mut saidified = dummy_filled(sad, labels)?;
+    if let Some(object) = saidified.as_object_mut() {
+        for label in labels {
+            object.insert(label.to_string(), serde_json::Value::String(said.clone()));
+        }
+    }
+    Ok(saidified)
+}
+
+/// Check that every field in `labels` holds the SAID of `sad`, and that any
+/// version string carries the correct size.
+pub fn verify_keri_said(sad: &serde_json::Value, labels: &[&str]) -> Result<(), StorageError> {
+    let expected = saidify_keri(sad, labels)?;
+    if &expected != sad {
+        return Err(StorageError::InvalidSaid(format!(
+            "KERI SAID verification failed: expected {}",
+            expected
+        )));
+    }
+    Ok(())
+}
+
+/// Fill `labels` with the dummy and size the version string, if present.
+fn dummy_filled(
+    sad: &serde_json::Value,
+    labels: &[&str],
+) -> Result<serde_json::Value, StorageError> {
+    let mut filled = sad.clone();
+    let Some(object) = filled.as_object_mut() else {
+        return Err(StorageError::InvalidSaid(
+            "KERI SAIDs require a JSON object".to_string(),
+        ));
+    };
+
+    for label in labels {
+        if !object.contains_key(*label) {
+            return Err(StorageError::InvalidSaid(format!(
+                "Missing SAID field `{}`",
+                label
+            )));
+        }
+        object.insert(
+            label.to_string(),
+            serde_json::Value::String(DUMMY.to_string()),
+        );
+    }
+
+    if let Some(serde_json::Value::String(version)) = object.get("v").cloned() {
+        // The size is fixed-width, so setting it doesn't change the length
+        let size = serde_json::to_vec(&filled)?.len();
+        let sized = sizeify(&version, size)?;
+        if let Some(object) = filled.as_object_mut() {
+            object.insert("v".to_string(), serde_json::Value::String(sized));
+        }
+    }
+
+    Ok(filled)
+}
+
+/// Set the size field of a v1 version string (`PPPPvvKKKKssssss_`).
+fn sizeify(version: &str, size: usize) -> Result<String, StorageError> {
+    let valid = version.len() == VERSION_LEN
+        && version.is_ascii()
+        && version.ends_with('_')
+        && &version[6..10] == "JSON";
+    if !valid {
+        return Err(StorageError::InvalidSaid(format!(
+            "Unsupported version string `{}` (expected JSON v1, e.g. KERI10JSON000000_)",
+            version
+        )));
+    }
+    if size > 0xff_ffff {
+        return Err(StorageError::InvalidSaid(format!(
+            "Serialization too large for version string: {} bytes",
+            size
+        )));
+    }
+    Ok(format!("{}{:06x}_", &version[..10], size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn inception() -> serde_json::Value {
+        json!({
+            "v": "KERI10JSON000000_",
+            "t": "icp",
+            "d": "",
+            "i": "",
+            "s": "0",
+            "kt": "1",
+            "k": ["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA"],
+            "nt": "1",
+            "n": ["EPYuj8mq_PYYsoBKkzX1kxSPGYBWaIya3slgCOyOtlqU"],
+            "bt": "0",
+            "b": [],
+            "c": [],
+            "a": []
+        })
+    }
+
+    #[test]
+    fn inception_fills_d_and_i_and_sizes_version() {
+        let filled = dummy_filled(&inception(), &["d", "i"]).unwrap();
+        let serialized = String::from_utf8(serde_json::to_vec(&filled).unwrap()).unwrap();
+        assert_eq!(
+            serialized,
+            format!(
+                "{{\"v\":\"KERI10JSON{:06x}_\",\"t\":\"icp\",\"d\":\"{}\",\"i\":\"{}\",\"s\":\"0\",\
+                 \"kt\":\"1\",\"k\":[\"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA\"],\"nt\":\"1\",\
+                 \"n\":[\"EPYuj8mq_PYYsoBKkzX1kxSPGYBWaIya3slgCOyOtlqU\"],\"bt\":\"0\",\"b\":[],\"c\":[],\"a\":[]}}",
+                serialized.len(),
+                DUMMY,
+                DUMMY
+            )
+        );
+
+        let event = saidify_keri(&inception(), &["d", "i"]).unwrap();
+        assert_eq!(event["d"], event["i"]);
+        assert!(event["d"].as_str().unwrap().starts_with('E'));
+        assert_eq!(event["d"].as_str().unwrap().len(), 44);
+        verify_keri_said(&event, &["d", "i"]).unwrap();
+    }
+
+    // keripy's `test_saider`: a `rep` message with a nested block, SAID'd on `d`
+    #[test]
+    fn matches_keripy_saider_vector() {
+        let sad = json!({
+            "v": "KERI10JSON000000_",
+            "t": "rep",
+            "d": "",
+            "dt": "2020-08-22T17:50:12.988921+00:00",
+            "r": "logs/processor",
+            "a": {
+                "d": "EBabiu_JCkE0GbiglDXNB5C4NQq-hiGgxhHKXBxkiojg",
+                "i": "EB0_D51cTh_q6uOQ-byFiv5oNXZ-cxdqCqBAa4JmBLtb",
+                "name": "John Jones",
+                "role": "Founder"
+            }
+        });
+        let said = "ELzewBpZHSENRP-sL_G_2Ji4YDdNkns9AzFzufleJqdw";
+        assert_eq!(compute_keri_said(&sad, &[KERI_SAID_LABEL]).unwrap(), said);
+
+        let saidified = saidify_keri(&sad, &[KERI_SAID_LABEL]).unwrap();
+        assert_eq!(saidified["v"], "KERI10JSON000124_");
+        assert_eq!(saidified["d"], said);
+        verify_keri_said(&saidified, &[KERI_SAID_LABEL]).unwrap();
+    }
+
+    // A non-transferable inception as keripy serializes it; only `d` is the SAID
+    #[test]
+    fn matches_keripy_inception_vector() {
+        let raw = r#"{"v":"KERI10JSON0000fd_","t":"icp","d":"EMW0zK3bagYPO6gx3w7Ua90f-I7x5kGIaI4Xeq9W8_As","i":"BFs8BBx86uytIM0D2BhsE5rrqVIT8ef8mflpNceHo4XH","s":"0","kt":"1","k":["BFs8BBx86uytIM0D2BhsE5rrqVIT8ef8mflpNceHo4XH"],"nt":"0","n":[],"bt":"0","b":[],"c":[],"a":[]}"#;
+        let event: serde_json::Value = serde_json::from_str(raw).unwrap();
+        verify_keri_said(&event, &[KERI_SAID_LABEL]).unwrap();
+        assert_eq!(serde_json::to_string(&event).unwrap(), raw);
+
+        let mut unsaidified = event.clone();
+        unsaidified["v"] = json!("KERI10JSON000000_");
+        unsaidified["d"] = json!("");
+        assert_eq!(
+            saidify_keri(&unsaidified, &[KERI_SAID_LABEL]).unwrap(),
+            event
+        );
+    }
+
+    #[test]
+    fn matches_compute_said_without_version_string() {
+        let sad = json!({"d": "", "name": "naïve café"});
+        let mut filled = sad.clone();
+        filled["d"] = json!(DUMMY);
+        assert_eq!(
+            compute_keri_said(&sad, &[KERI_SAID_LABEL]).unwrap(),
+            compute_said(&filled).unwrap()
+        );
+    }
+
+    #[test]
+    fn tampering_and_bad_input_fail() {
+        let mut event = saidify_keri(&inception(), &["d", "i"]).unwrap();
+        event["s"] = json!("1");
+        assert!(verify_keri_said(&event, &["d", "i"]).is_err());
+
+        assert!(compute_keri_said(&json!({"x": 1}), &["d"]).is_err());
+        assert!(compute_keri_said(&json!({"v": "KERI10CBOR000000_", "d": ""}), &["d"]).is_err());
+    }
+}
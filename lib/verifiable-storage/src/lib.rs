@@ -14,32 +14,336 @@
 //!
 //! - [`SelfAddressed`]: Types with a content-derived SAID
 //! - [`Versioned`]: Versioned types with prefix, version, and previous pointer
+//! - [`Validate`]: Semantic checks run before SAIDs are derived, opted into with `#[validate]`
+//! - [`RotatableKeyRecord`]: Versioned records carrying pre-rotated keys
+//! - [`Signer`] / [`Verifier`]: Keys for signing SAIDs with [`sign_said`]
 //! - [`VersionedRepository`]: Storage for versioned types
 //! - [`UnversionedRepository`]: Storage for simple SAID-addressed types
+//! - [`GenericVersionedRepository`] / [`GenericUnversionedRepository`]: Repositories over any [`QueryExecutor`], without a derive
+//! - [`SharedVersionedRepository`] / [`SharedUnversionedRepository`]: `Arc`-shared repositories for handler state
+//! - [`SignedRepository`]: Items stored with [`RecordSignature`]s over their SAIDs, on any backend
+//! - [`OrphanCollector`]: Batched removal of signatures whose records were deleted or pruned
+//! - [`ReadOnlyRepository`]: Wrapper that rejects writes, for replicas and audits
+//! - [`SigningRepository`]: Wrapper that returns reads as [`SignedResponse`]s
+//! - [`EmittingRepository`]: Wrapper that reports successful writes to an [`EventSink`]
+//! - [`SkewCheckedRepository`]: Wrapper that rejects writes stamped in the future beyond a clock skew
+//! - [`AuthorizedRepository`]: Wrapper that puts every write to a [`WriteAuthorizer`] policy hook
+//! - [`PolicyEnforcedRepository`]: Wrapper that enforces per-record [`AccessPolicy`]s for an [`AccessContext`]
+//! - [`ChangelogRepository`]: Wrapper that records each update's changed fields as a [`ChangeRecord`]
+//! - [`OutboxRepository`]: Wrapper that records writes in a transactional outbox for an [`OutboxRelay`]
+//! - [`SyncNode`]: Pull-based reconciliation of versioned prefixes with a peer
+//! - [`LoggedRepository`]: Wrapper that appends written SAIDs to a [`TransparencyLog`]
+//! - [`NotarizingRepository`]: Wrapper that timestamps written SAIDs with a [`TimestampAuthority`]
+//! - [`SizeLimitedRepository`]: Wrapper that rejects writes serializing to more than a byte limit
+//! - [`MeteredRepository`]: Wrapper that reports each call's table, operation and latency to a [`MetricsHook`]
+//! - `SealedRepository`: Wrapper that HMAC-seals written rows and checks the seal on reads
+//! - [`Migration`]: Resumable, verified copying of a table between backends
+//! - [`backup_table`] / [`verify_backup`]: Table backups with a manifest that can be checked offsite
+//! - [`NameRegistry`]: First-come-first-served name claims recorded as [`NameClaim`] chains
+//! - [`Registry`]: Operator maintenance (verify, export/import, retention) over registered tables
+//!
+//! # Features
+//!
+//! - `std` (default): repositories, queries, wrappers and everything else beyond
+//!   SAIDs. Without it the crate is `no_std` + `alloc` and keeps only SAID
+//!   computation, [`SelfAddressed`], [`Versioned`] and [`Storable`], so firmware
+//!   can verify records. There is no clock then: `StorageDatetime::now()`,
+//!   `increment()` and the derived `new()`/`create()` of types with a
+//!   `#[created_at]` field are left out, and callers pass the time to
+//!   `create_with_timestamp` and `increment_at` instead
+//! - `surrealdb`: Native SurrealDB datetime support for [`StorageDatetime`]
+//! - `bulk`: `BulkWriter` for batched, backpressure-aware ingestion (requires tokio)
+//! - `sharding`: `ShardedExecutor` for routing across multiple databases by prefix
+//! - `compare`: `compare`, a bounded-memory diff of a table across two backends,
+//!   for validating migrations
+//! - `fake`: `Faker` and `seed` helpers generating example data for demos and load tests
+//! - `loadgen`: `run_versioned`/`run_unversioned` load generators reporting latency
+//!   percentiles (requires tokio; implies `fake`)
+//! - `events`: `BroadcastSink` publishing write events to a tokio broadcast channel
+//! - `webhooks`: `WebhookSink` POSTing write events as JSON (requires reqwest)
+//! - `kafka`: `KafkaSink` publishing write events keyed by prefix (requires rdkafka)
+//! - `nats`: `NatsSink` publishing write events to per-prefix JetStream subjects
+//! - `resolver`: `Resolver`, an axum router serving verified reads by SAID and prefix
+//! - `commitment`: hash commitments and openings for sensitive numeric fields
+//! - `uuid` / `ulid`: `UuidV7` and `Ulid` id generators for [`RecordId`] types
+//! - `ed25519`: [`Signer`]/[`Verifier`] for `ed25519_dalek` keys, for [`sign_said`] and
+//!   [`verify_said_signature`]
+//! - `seal`: `RowSealer` and `SealedRepository`, HMAC-SHA256 seals catching direct database edits
+//! - `disclosure`: salted per-field digests with `blind`/`redact` for graduated disclosure
+//! - `prometheus`: `PrometheusMetrics`, a [`MetricsHook`] recording per-table operation
+//!   counters, latency histograms and payload sizes, with `gather()` for a `/metrics` endpoint
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(
     test,
     allow(clippy::unwrap_used, clippy::expect_used, clippy::unwrap_in_result)
 )]
 
+// Lets derive-generated `verifiable_storage::` paths resolve inside this crate
+extern crate self as verifiable_storage;
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod access;
+#[cfg(feature = "std")]
+mod admin;
+#[cfg(feature = "std")]
+mod attest;
+#[cfg(feature = "std")]
+mod authorize;
+#[cfg(feature = "std")]
+mod backup;
+#[cfg(feature = "bulk")]
+mod bulk;
+#[cfg(feature = "cbor")]
+mod cbor;
+#[cfg(feature = "std")]
+mod changelog;
+#[cfg(feature = "commitment")]
+mod commitment;
+#[cfg(feature = "compare")]
+mod compare;
+#[cfg(feature = "std")]
+mod diff;
+#[cfg(feature = "disclosure")]
+mod disclosure;
+#[cfg(feature = "std")]
+mod dry_run;
 mod error;
+#[cfg(feature = "std")]
+mod events;
+#[cfg(feature = "std")]
+mod expiry;
+#[cfg(feature = "fake")]
+mod fake;
+mod fingerprint;
+#[cfg(feature = "std")]
+mod generic;
+#[cfg(feature = "std")]
+mod ingest;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "std")]
+mod keri;
+#[cfg(feature = "std")]
+mod list;
+#[cfg(feature = "loadgen")]
+mod loadgen;
+#[cfg(feature = "std")]
+mod metrics;
+#[cfg(feature = "std")]
+mod migrate;
+#[cfg(feature = "std")]
+mod names;
+#[cfg(feature = "nats")]
+mod nats;
+#[cfg(feature = "std")]
+mod normalize;
+#[cfg(feature = "std")]
+mod notary;
+#[cfg(feature = "std")]
+mod orphans;
+#[cfg(feature = "std")]
+mod outbox;
+mod partition;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+#[cfg(feature = "std")]
 mod query;
+#[cfg(feature = "std")]
+mod raw;
+#[cfg(feature = "std")]
+mod read_only;
+#[cfg(feature = "std")]
+mod record_id;
+#[cfg(feature = "std")]
 mod repository;
+#[cfg(feature = "resolver")]
+mod resolver;
+mod rotation;
 mod said;
+#[cfg(feature = "std")]
+mod schedule;
+#[cfg(feature = "seal")]
+mod seal;
+#[cfg(feature = "sharding")]
+mod shard;
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "std")]
+mod signed;
+mod signing;
+#[cfg(feature = "std")]
+mod size;
+#[cfg(feature = "std")]
+mod skew;
+#[cfg(feature = "std")]
+mod sql;
 mod storable;
+#[cfg(feature = "std")]
+mod sync;
 mod time;
+#[cfg(feature = "std")]
+mod transparency;
+#[cfg(feature = "std")]
+mod vectors;
 
+#[cfg(feature = "std")]
+pub use access::{ANYONE, AccessContext, AccessPolicy, PolicyEnforcedRepository, access_policy};
+#[cfg(feature = "std")]
+pub use admin::{ChainLink, HistoryBundle, Registry, TableInfo, VerifyReport};
+#[cfg(feature = "std")]
+pub use attest::{ResponseSigner, ResponseVerifier, SignedResponse, SigningRepository};
+#[cfg(feature = "std")]
+pub use authorize::{AuthorizedRepository, WriteAuthorizer};
+#[cfg(feature = "std")]
+pub use backup::{BackupManifest, backup_table, verify_backup};
+#[cfg(feature = "bulk")]
+pub use bulk::{BatchReport, BulkWriter, BulkWriterConfig};
+#[cfg(feature = "cbor")]
+pub use cbor::{
+    compute_said_cbor, compute_said_cbor_over_with, compute_said_cbor_with, from_cbor,
+    to_canonical_cbor,
+};
+#[cfg(feature = "std")]
+pub use changelog::{ChangeRecord, ChangelogRepository};
+#[cfg(feature = "commitment")]
+pub use commitment::{Opening, commit, verify_opening};
+#[cfg(feature = "compare")]
+pub use compare::{Comparison, ConsistencyReport, Discrepancy, compare};
+#[cfg(feature = "std")]
+pub use diff::{FieldChange, FieldDiff, diff_items};
+#[cfg(feature = "disclosure")]
+pub use disclosure::{blind, compact, disclosed, redact, verify_disclosure};
+#[cfg(feature = "std")]
+pub use dry_run::{DryRunLog, ExecutorMode, RenderedStatement};
 pub use error::StorageError;
+#[cfg(feature = "events")]
+pub use events::BroadcastSink;
+#[cfg(feature = "webhooks")]
+pub use events::WebhookSink;
+#[cfg(feature = "std")]
+pub use events::{EmittingRepository, EventSink, WriteEvent, WriteOperation};
+#[cfg(feature = "std")]
+pub use expiry::{is_expired, purge_expired, purge_expired_versions};
+#[cfg(feature = "fake")]
+pub use fake::{Faker, seed, seed_versioned};
+pub use fingerprint::SchemaFingerprint;
+#[cfg(feature = "std")]
+pub use generic::{GenericUnversionedRepository, GenericVersionedRepository};
+#[cfg(feature = "std")]
+pub use ingest::{IngestOutcome, IngestReport, IngestedItem, Ingestor};
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaSink;
+#[cfg(feature = "std")]
+pub use keri::{KERI_SAID_LABEL, compute_keri_said, saidify_keri, verify_keri_said};
+#[cfg(feature = "std")]
+pub use list::{Cursor, ListParams, MAX_PAGE_SIZE, Page, PageParams, SortKey, fetch_page};
+#[cfg(feature = "loadgen")]
+pub use loadgen::{
+    LatencySummary, LoadConfig, LoadReport, Operation, OperationMix, run_unversioned, run_versioned,
+};
+#[cfg(feature = "std")]
+pub use metrics::{MeteredRepository, MetricsHook, NoopMetrics};
+#[cfg(feature = "std")]
+pub use migrate::{Migration, MigrationCheckpoint, migrate_table, migrate_unversioned_table};
+#[cfg(feature = "std")]
+pub use names::{NameClaim, NameRegistry};
+#[cfg(feature = "nats")]
+pub use nats::NatsSink;
+#[cfg(feature = "std")]
+pub use notary::{NotarizingRepository, Notary, TimestampAuthority, TimestampToken};
+#[cfg(feature = "std")]
+pub use orphans::{OrphanCollector, OrphanReport, gc_orphans};
+#[cfg(feature = "std")]
+pub use outbox::{OutboxRecord, OutboxRelay, OutboxRepository, insert_with_outbox};
+pub use partition::{PartitionInterval, Partitioning};
+#[cfg(feature = "prometheus")]
+pub use prometheus::PrometheusMetrics;
+#[cfg(feature = "std")]
 pub use query::{
-    ColumnQuery, Delete, Filter, Join, Order, Query, QueryExecutor, TransactionExecutor, Value,
+    Aggregate, ColumnQuery, Delete, Filter, Join, Order, PrefixVersion, Query, QueryExecutor,
+    Subquery, TransactionExecutor, Value,
 };
+#[cfg(feature = "std")]
+pub use raw::{RawCheckReport, RawDrift, raw_drift};
+#[cfg(feature = "std")]
+pub use read_only::ReadOnlyRepository;
+#[cfg(feature = "ulid")]
+pub use record_id::Ulid;
+#[cfg(feature = "uuid")]
+pub use record_id::UuidV7;
+#[cfg(feature = "std")]
+pub use record_id::{IdGenerator, RecordId};
+#[cfg(feature = "std")]
 pub use repository::{
-    ConnectionConfig, RepositoryConnection, UnversionedRepository, VersionedRepository,
+    ChainIssue, ChainProblem, ChainVerificationReport, ConnectionConfig, RepositoryConnection,
+    UnversionedRepository, VerifiedLatest, VersionedRepository, order_history, verify_history,
 };
-pub use said::{SelfAddressed, Versioned, compute_said};
-pub use storable::Storable;
+#[cfg(feature = "resolver")]
+pub use resolver::Resolver;
+pub use rotation::{RotatableKeyRecord, key_digest};
+pub use said::{
+    SAID_DIGEST_CODE, SaidAlgorithm, SelfAddressed, Validate, Versioned, compute_said,
+    compute_said_over, compute_said_over_with, compute_said_with, said_placeholder,
+};
+#[cfg(feature = "std")]
+pub use schedule::{effective_time, in_force_at};
+#[cfg(feature = "seal")]
+pub use seal::{RowSealer, SealedRepository};
+#[cfg(feature = "sharding")]
+pub use shard::{HashShardResolver, ShardResolver, ShardedExecutor, ShardedTransaction};
+#[cfg(feature = "std")]
+pub use shared::{SharedUnversionedRepository, SharedVersionedRepository};
+#[cfg(feature = "std")]
+pub use signed::{RecordSignature, SignedRepository, check_signatures_cover};
+#[cfg(feature = "std")]
+pub use signing::{AsyncSigner, sign_said_async};
+pub use signing::{Signer, Verifier, sign_said, verify_said_signature};
+#[cfg(feature = "std")]
+pub use size::{SizeLimitedRepository, check_payload_size};
+#[cfg(feature = "std")]
+pub use skew::{SkewCheckedRepository, check_created_at};
+#[cfg(feature = "std")]
+pub use sql::{Dialect, SqlParams, TOTAL_COUNT_COLUMN};
+#[cfg(feature = "std")]
+pub use storable::is_soft_deleted;
+pub use storable::{ColumnConstraint, ManagedField, Storable, check_column_constraints};
+#[cfg(feature = "std")]
+pub use sync::{PrefixHead, SyncNode, SyncReport, SyncRequest, SyncResponse, SyncTransport};
+#[cfg(feature = "std")]
+pub use time::HybridClock;
 pub use time::StorageDatetime;
+#[cfg(feature = "std")]
+pub use transparency::{
+    ConsistencyProof, InclusionProof, LogLeaf, LoggedRepository, SignedTreeHead, TransparencyLog,
+};
+#[cfg(feature = "std")]
+pub use vectors::{SAID_VECTORS_V1, SaidVector, said_vectors, verify_vectors};
 
-// Re-export derive macro
+// Re-export derive macros
 // Note: SelfAddressed derive auto-detects versioning by presence of #[prefix], #[previous], #[version] fields
-pub use verifiable_storage_derive::SelfAddressed;
+pub use verifiable_storage_derive::{Projection, SelfAddressed};
+
+// Paths for derive-generated code, which can't rely on the std prelude
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::{format, string::String};
+}
+
+/// Expands to its input only with the `std` feature, so derive-generated code
+/// can leave out what needs a clock.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_std {
+    ($($item:tt)*) => { $($item)* };
+}
+
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_std {
+    ($($item:tt)*) => {};
+}
@@ -0,0 +1,435 @@
+//! Shared query parameters for list endpoints.
+//!
+//! [`ListParams`] deserializes from a handler's query string or JSON body and
+//! [`Query::apply`] turns it into filters, ordering and a page, rejecting
+//! unknown columns and out-of-range pages:
+//!
+//! ```text
+//! { "filters": { "kind": "zone", "version": "0" }, "sort": ["-created_at"], "page": { "number": 2, "size": 25 } }
+//! ```
+//!
+//! Offsets drift when rows are inserted between requests. [`fetch_page`]
+//! pages by key instead: rows come in ascending order of a fixed set of
+//! columns, and each [`Page`] carries an opaque [`Cursor`] naming the last row
+//! returned, so the next page starts strictly after it. Every executor has it
+//! as [`QueryExecutor::fetch_page`] too.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Filter, Order, Query, QueryExecutor, Storable, StorageDatetime, StorageError, Value,
+    signing::{decode_hex, encode_hex},
+};
+
+/// The largest page a caller may request.
+pub const MAX_PAGE_SIZE: u64 = 1000;
+
+/// Filters, ordering and page for a list endpoint.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListParams {
+    /// Column to value; each becomes an equality filter.
+    #[serde(default)]
+    pub filters: BTreeMap<String, String>,
+    /// Sort keys, most significant first.
+    #[serde(default)]
+    pub sort: Vec<SortKey>,
+    #[serde(default)]
+    pub page: PageParams,
+}
+
+/// One sort key, written `field` (ascending) or `-field` (descending).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct SortKey {
+    pub field: String,
+    pub descending: bool,
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.descending {
+            write!(f, "-{}", self.field)
+        } else {
+            write!(f, "{}", self.field)
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, descending) = match s.strip_prefix('-') {
+            Some(field) => (field, true),
+            None => (s, false),
+        };
+        if field.is_empty() {
+            return Err(StorageError::StorageError(format!(
+                "Malformed sort key: {}",
+                s
+            )));
+        }
+        Ok(Self {
+            field: field.to_string(),
+            descending,
+        })
+    }
+}
+
+impl From<SortKey> for String {
+    fn from(key: SortKey) -> Self {
+        key.to_string()
+    }
+}
+
+impl TryFrom<String> for SortKey {
+    type Error = StorageError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// A zero-based page number and page size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageParams {
+    #[serde(default)]
+    pub number: u64,
+    #[serde(default = "default_page_size")]
+    pub size: u64,
+}
+
+fn default_page_size() -> u64 {
+    50
+}
+
+impl Default for PageParams {
+    fn default() -> Self {
+        Self {
+            number: 0,
+            size: default_page_size(),
+        }
+    }
+}
+
+impl<T: Storable> Query<T> {
+    /// Apply `params`: equality filters typed by each column, then sort keys,
+    /// then the page. Filter and sort names must be columns of `T`.
+    pub fn apply(self, params: &ListParams) -> Result<Self, StorageError> {
+        let size = params.page.size;
+        if size == 0 || size > MAX_PAGE_SIZE {
+            return Err(StorageError::StorageError(format!(
+                "Page size must be between 1 and {}",
+                MAX_PAGE_SIZE
+            )));
+        }
+        let offset = params.page.number.checked_mul(size).ok_or_else(|| {
+            StorageError::StorageError(format!("Page out of range: {}", params.page.number))
+        })?;
+
+        let mut query = self;
+        for (field, raw) in &params.filters {
+            query = query.eq(field.clone(), typed_value::<T>(field, raw)?);
+        }
+        for key in &params.sort {
+            column_index::<T>(&key.field)?;
+            let order = if key.descending {
+                Order::Desc
+            } else {
+                Order::Asc
+            };
+            query = query.order_by(key.field.clone(), order);
+        }
+        Ok(query.limit(size).offset(offset))
+    }
+}
+
+fn column_index<T: Storable>(field: &str) -> Result<usize, StorageError> {
+    T::columns()
+        .iter()
+        .position(|column| *column == field)
+        .ok_or_else(|| {
+            StorageError::StorageError(format!("Unknown column for {}: {}", T::table_name(), field))
+        })
+}
+
+/// A position in a keyset-paginated listing: the paging columns' values in the
+/// last row of a page. Serializes as an opaque string.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Cursor {
+    values: Vec<serde_json::Value>,
+}
+
+impl Cursor {
+    /// The cursor just past `item`, when paging by `columns`.
+    pub fn after<T: Storable + Serialize>(
+        item: &T,
+        columns: &[&str],
+    ) -> Result<Self, StorageError> {
+        let serialized = serde_json::to_value(item)?;
+        let values = columns
+            .iter()
+            .map(|column| {
+                let key = T::json_keys()[column_index::<T>(column)?];
+                Ok(serialized
+                    .get(key)
+                    .cloned()
+                    .unwrap_or(serde_json::Value::Null))
+            })
+            .collect::<Result<_, StorageError>>()?;
+        Ok(Self { values })
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_vec(&self.values).map_err(|_| fmt::Error)?;
+        f.write_str(&encode_hex(&json))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let malformed = || StorageError::StorageError(format!("Malformed cursor: {}", s));
+        let json = decode_hex(s).map_err(|_| malformed())?;
+        let values = serde_json::from_slice(&json).map_err(|_| malformed())?;
+        Ok(Self { values })
+    }
+}
+
+impl From<Cursor> for String {
+    fn from(cursor: Cursor) -> Self {
+        cursor.to_string()
+    }
+}
+
+impl TryFrom<String> for Cursor {
+    type Error = StorageError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// One page of a keyset-paginated listing, and where the next one starts.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// `None` on the last page.
+    pub next: Option<Cursor>,
+}
+
+impl<T: Storable> Query<T> {
+    /// Order by `columns` ascending and keep only rows after `cursor`, for
+    /// keyset pagination. The columns should end with a unique one (such as
+    /// `said`) so the order is total.
+    pub fn after(
+        mut self,
+        columns: &[&str],
+        cursor: Option<&Cursor>,
+    ) -> Result<Self, StorageError> {
+        for column in columns {
+            column_index::<T>(column)?;
+            self = self.order_by(column.to_string(), Order::Asc);
+        }
+        let Some(cursor) = cursor else {
+            return Ok(self);
+        };
+        if cursor.values.len() != columns.len() {
+            return Err(StorageError::StorageError(format!(
+                "Cursor has {} values for {} paging columns",
+                cursor.values.len(),
+                columns.len()
+            )));
+        }
+        // (a, b) > (x, y) as: a > x OR (a = x AND b > y)
+        let values = columns
+            .iter()
+            .zip(&cursor.values)
+            .map(|(column, value)| cursor_value::<T>(column, value))
+            .collect::<Result<Vec<_>, _>>()?;
+        let alternatives = (0..columns.len())
+            .map(|last| {
+                let mut conjuncts: Vec<Filter> = columns[..last]
+                    .iter()
+                    .zip(&values)
+                    .map(|(column, value)| Filter::Eq(column.to_string(), value.clone()))
+                    .collect();
+                conjuncts.push(Filter::Gt(columns[last].to_string(), values[last].clone()));
+                Filter::And(conjuncts)
+            })
+            .collect();
+        Ok(self.filter(Filter::Or(alternatives)))
+    }
+}
+
+/// Fetch up to `limit` rows of `query` after `cursor`, in `columns` order.
+pub async fn fetch_page<E, T>(
+    executor: &E,
+    query: Query<T>,
+    columns: &[&str],
+    cursor: Option<&Cursor>,
+    limit: u64,
+) -> Result<Page<T>, StorageError>
+where
+    E: QueryExecutor,
+    T: Storable + serde::de::DeserializeOwned + Send,
+{
+    if limit == 0 || limit > MAX_PAGE_SIZE {
+        return Err(StorageError::StorageError(format!(
+            "Page size must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    // One extra row tells whether another page follows
+    let mut items = executor
+        .fetch(query.after(columns, cursor)?.limit(limit + 1))
+        .await?;
+    let next = if items.len() as u64 > limit {
+        items.truncate(limit as usize);
+        items
+            .last()
+            .map(|last| Cursor::after(last, columns))
+            .transpose()?
+    } else {
+        None
+    };
+    Ok(Page { items, next })
+}
+
+/// A cursor value as a query value of the column's type.
+fn cursor_value<T: Storable>(
+    column: &str,
+    value: &serde_json::Value,
+) -> Result<Value, StorageError> {
+    Ok(
+        match (T::column_types()[column_index::<T>(column)?], value) {
+            (_, serde_json::Value::Null) => {
+                return Err(StorageError::StorageError(format!(
+                    "Cannot page by {}: the cursor's value is null",
+                    column
+                )));
+            }
+            ("datetime", value) => Value::Datetime(serde_json::from_value(value.clone())?),
+            (_, serde_json::Value::Bool(b)) => Value::Bool(*b),
+            (_, serde_json::Value::Number(n)) => match (n.as_i64(), n.as_u64()) {
+                (Some(n), _) => Value::Int(n),
+                (None, Some(n)) => Value::UInt(n),
+                (None, None) => Value::Float(n.as_f64().unwrap_or_default()),
+            },
+            (_, serde_json::Value::String(s)) => Value::String(s.clone()),
+            (_, value) => Value::Json(value.clone()),
+        },
+    )
+}
+
+/// Parse a query-string value according to the column's type.
+fn typed_value<T: Storable>(field: &str, raw: &str) -> Result<Value, StorageError> {
+    let invalid = |e: &dyn fmt::Display| {
+        StorageError::StorageError(format!("Invalid value for {}: {}", field, e))
+    };
+    match T::column_types()[column_index::<T>(field)?] {
+        "bigint" | "integer" => raw.parse::<i64>().map(Value::Int).map_err(|e| invalid(&e)),
+        "boolean" => raw
+            .parse::<bool>()
+            .map(Value::Bool)
+            .map_err(|e| invalid(&e)),
+        "datetime" => serde_json::from_value::<StorageDatetime>(raw.into())
+            .map(Value::Datetime)
+            .map_err(|e| invalid(&e)),
+        _ => Ok(Value::String(raw.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Dialect;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "zones")]
+    #[serde(rename_all = "camelCase")]
+    struct Zone {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        #[created_at]
+        created_at: StorageDatetime,
+        kind: String,
+    }
+
+    #[test]
+    fn list_params_build_a_validated_query() {
+        let params: ListParams = serde_json::from_value(serde_json::json!({
+            "filters": { "kind": "primary", "version": "0" },
+            "sort": ["-created_at", "kind"],
+            "page": { "number": 2, "size": 25 }
+        }))
+        .unwrap();
+        assert_eq!(params.sort[0].to_string(), "-created_at");
+
+        let (sql, params) = Query::<Zone>::new()
+            .apply(&params)
+            .unwrap()
+            .to_sql(Dialect::Postgres);
+        assert_eq!(
+            sql,
+            "SELECT * FROM zones WHERE kind = $1 AND version = $2 \
+             ORDER BY created_at DESC, kind ASC LIMIT 25 OFFSET 50"
+        );
+        assert!(matches!(params[1].1, Value::Int(0)));
+
+        let mut bad = ListParams::default();
+        bad.sort.push("-password".parse().unwrap());
+        assert!(Query::<Zone>::new().apply(&bad).is_err());
+        let mut bad = ListParams::default();
+        bad.filters.insert("version".to_string(), "one".to_string());
+        assert!(Query::<Zone>::new().apply(&bad).is_err());
+        let mut bad = ListParams::default();
+        bad.page.size = MAX_PAGE_SIZE + 1;
+        assert!(Query::<Zone>::new().apply(&bad).is_err());
+    }
+
+    #[test]
+    fn cursors_resume_strictly_after_the_last_row() {
+        let zone = Zone::create("primary".to_string()).unwrap();
+        let columns = ["created_at", "said"];
+        let cursor = Cursor::after(&zone, &columns).unwrap();
+        let encoded = cursor.to_string();
+        assert!(encoded.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(encoded.parse::<Cursor>().unwrap(), cursor);
+        assert!("not a cursor".parse::<Cursor>().is_err());
+
+        let (sql, params) = Query::<Zone>::new()
+            .after(&columns, Some(&cursor))
+            .unwrap()
+            .limit(11)
+            .to_sql(Dialect::Postgres);
+        assert_eq!(
+            sql,
+            "SELECT * FROM zones WHERE ((created_at > $1) OR (created_at = $2 AND said > $3)) \
+             ORDER BY created_at ASC, said ASC LIMIT 11"
+        );
+        assert!(matches!(&params[0].1, Value::Datetime(at) if *at == zone.created_at));
+        assert!(matches!(&params[2].1, Value::String(said) if *said == zone.said));
+
+        assert!(
+            Query::<Zone>::new()
+                .after(&["said"], Some(&cursor))
+                .is_err()
+        );
+        assert!(Query::<Zone>::new().after(&["secret"], None).is_err());
+    }
+}
@@ -0,0 +1,452 @@
+//! Load generation for comparing repository backends.
+//!
+//! [`run_versioned`] and [`run_unversioned`] drive a weighted mix of creates,
+//! updates, and reads against a repository at a fixed target rate, using
+//! [`Faker`] for items. Requests are issued open-loop: if `max_in_flight`
+//! requests are already outstanding when one is due, it is counted as dropped
+//! rather than delayed, so a saturated backend shows up in the report instead
+//! of silently lowering the offered load.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tokio::sync::Semaphore;
+use tokio::time::{Instant, MissedTickBehavior};
+
+use crate::{
+    Faker, SelfAddressed, Storable, StorageError, UnversionedRepository, Versioned,
+    VersionedRepository,
+};
+
+/// Relative weights of each operation in the generated load.
+#[derive(Debug, Clone, Copy)]
+pub struct OperationMix {
+    pub create: u32,
+    pub update: u32,
+    pub read: u32,
+}
+
+impl Default for OperationMix {
+    fn default() -> Self {
+        Self {
+            create: 20,
+            update: 30,
+            read: 50,
+        }
+    }
+}
+
+/// Settings for a load run.
+#[derive(Debug, Clone)]
+pub struct LoadConfig {
+    /// Requests issued per second.
+    pub target_rps: u32,
+    /// How long to issue requests for.
+    pub duration: Duration,
+    /// Maximum outstanding requests before new ones are dropped.
+    pub max_in_flight: usize,
+    /// Operation weights.
+    pub mix: OperationMix,
+    /// Seed for item generation and operation choice, so runs are repeatable.
+    pub seed: u64,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self {
+            target_rps: 100,
+            duration: Duration::from_secs(10),
+            max_in_flight: 64,
+            mix: OperationMix::default(),
+            seed: 0,
+        }
+    }
+}
+
+/// An operation issued by the load generator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Create,
+    Update,
+    Read,
+}
+
+/// Latency percentiles for one operation.
+#[derive(Debug, Clone, Default)]
+pub struct LatencySummary {
+    /// Successful requests.
+    pub count: u64,
+    /// Failed requests (not included in the percentiles).
+    pub errors: u64,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencySummary {
+    /// Summarize successful request latencies.
+    fn from_samples(mut samples: Vec<Duration>, errors: u64) -> Self {
+        samples.sort_unstable();
+        Self {
+            count: samples.len() as u64,
+            errors,
+            p50: percentile(&samples, 50),
+            p90: percentile(&samples, 90),
+            p99: percentile(&samples, 99),
+            max: samples.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// Results of a load run.
+#[derive(Debug, Clone)]
+pub struct LoadReport {
+    /// Time from the first request to the last response.
+    pub elapsed: Duration,
+    /// Completed requests (successful or not) per second.
+    pub achieved_rps: f64,
+    /// Requests not issued because `max_in_flight` were outstanding.
+    pub dropped: u64,
+    pub create: LatencySummary,
+    pub update: LatencySummary,
+    pub read: LatencySummary,
+}
+
+/// Drive load against a versioned repository.
+///
+/// Updates and reads target lineages created earlier in the run; until one
+/// exists they are issued as creates. A lineage is never updated twice
+/// concurrently.
+pub async fn run_versioned<T, R>(
+    repo: Arc<R>,
+    config: LoadConfig,
+) -> Result<LoadReport, StorageError>
+where
+    T: Storable + SelfAddressed + Versioned + Serialize + DeserializeOwned + 'static,
+    R: VersionedRepository<T> + Send + Sync + 'static,
+{
+    run(VersionedTarget(repo), config).await
+}
+
+/// Drive load against an unversioned repository.
+///
+/// Unversioned repositories have no updates, so update weight is issued as
+/// creates. Reads fetch previously created items by SAID.
+pub async fn run_unversioned<T, R>(
+    repo: Arc<R>,
+    config: LoadConfig,
+) -> Result<LoadReport, StorageError>
+where
+    T: Storable + SelfAddressed + Serialize + DeserializeOwned + 'static,
+    R: UnversionedRepository<T> + Send + Sync + 'static,
+{
+    run(UnversionedTarget(repo), config).await
+}
+
+/// The repository operations a load run needs.
+#[async_trait]
+trait Target<T>: Send + Sync + 'static {
+    fn supports_update(&self) -> bool;
+    async fn create(&self, item: T) -> Result<T, StorageError>;
+    async fn update(&self, item: T) -> Result<T, StorageError>;
+    async fn read(&self, item: &T) -> Result<(), StorageError>;
+}
+
+struct VersionedTarget<R>(Arc<R>);
+
+#[async_trait]
+impl<T, R> Target<T> for VersionedTarget<R>
+where
+    T: Storable + SelfAddressed + Versioned + Serialize + DeserializeOwned + 'static,
+    R: VersionedRepository<T> + Send + Sync + 'static,
+{
+    fn supports_update(&self) -> bool {
+        true
+    }
+
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        self.0.create(item).await
+    }
+
+    async fn update(&self, item: T) -> Result<T, StorageError> {
+        self.0.update(item).await
+    }
+
+    async fn read(&self, item: &T) -> Result<(), StorageError> {
+        self.0.get_latest(&item.get_prefix()).await.map(|_| ())
+    }
+}
+
+struct UnversionedTarget<R>(Arc<R>);
+
+#[async_trait]
+impl<T, R> Target<T> for UnversionedTarget<R>
+where
+    T: Storable + SelfAddressed + Serialize + DeserializeOwned + 'static,
+    R: UnversionedRepository<T> + Send + Sync + 'static,
+{
+    fn supports_update(&self) -> bool {
+        false
+    }
+
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        self.0.create(item).await
+    }
+
+    async fn update(&self, item: T) -> Result<T, StorageError> {
+        Ok(item)
+    }
+
+    async fn read(&self, item: &T) -> Result<(), StorageError> {
+        self.0.get_by_said(&item.get_said()).await.map(|_| ())
+    }
+}
+
+/// Latency samples and error counts, per operation.
+#[derive(Default)]
+struct Samples {
+    create: (Vec<Duration>, u64),
+    update: (Vec<Duration>, u64),
+    read: (Vec<Duration>, u64),
+}
+
+impl Samples {
+    fn record(&mut self, operation: Operation, latency: Duration, ok: bool) {
+        let (samples, errors) = match operation {
+            Operation::Create => &mut self.create,
+            Operation::Update => &mut self.update,
+            Operation::Read => &mut self.read,
+        };
+        if ok {
+            samples.push(latency);
+        } else {
+            *errors += 1;
+        }
+    }
+}
+
+/// State shared by in-flight requests.
+struct Shared<T, G> {
+    target: G,
+    faker: Mutex<Faker>,
+    /// Latest known item per created lineage (or created item, when unversioned).
+    items: Mutex<Vec<T>>,
+    samples: Mutex<Samples>,
+}
+
+async fn run<T, G>(target: G, config: LoadConfig) -> Result<LoadReport, StorageError>
+where
+    T: Storable + 'static,
+    G: Target<T>,
+{
+    if config.target_rps == 0 {
+        return Err(StorageError::StorageError(
+            "Load target_rps must be at least 1".to_string(),
+        ));
+    }
+
+    let max_in_flight = config.max_in_flight.max(1);
+    let slots = Arc::new(Semaphore::new(max_in_flight));
+    let shared = Arc::new(Shared {
+        target,
+        faker: Mutex::new(Faker::seeded(config.seed)),
+        items: Mutex::new(Vec::new()),
+        samples: Mutex::new(Samples::default()),
+    });
+    let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(1));
+    let mut dropped = 0u64;
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(1) / config.target_rps);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    let started = Instant::now();
+    let deadline = started + config.duration;
+
+    loop {
+        let tick = ticker.tick().await;
+        if tick >= deadline {
+            break;
+        }
+
+        let Ok(permit) = slots.clone().try_acquire_owned() else {
+            dropped += 1;
+            continue;
+        };
+
+        let operation = choose(&config.mix, shared.target.supports_update(), &mut rng);
+        let pick = rng.r#gen::<usize>();
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            execute(shared.as_ref(), operation, pick).await;
+            drop(permit);
+        });
+    }
+
+    // Wait for outstanding requests by reclaiming every slot
+    let _ = slots.acquire_many(max_in_flight as u32).await;
+    let elapsed = started.elapsed();
+
+    let samples = std::mem::take(
+        &mut *shared
+            .samples
+            .lock()
+            .map_err(|e| StorageError::StorageError(e.to_string()))?,
+    );
+    let completed = [&samples.create, &samples.update, &samples.read]
+        .iter()
+        .map(|(latencies, errors)| latencies.len() as u64 + errors)
+        .sum::<u64>();
+
+    Ok(LoadReport {
+        elapsed,
+        achieved_rps: completed as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        dropped,
+        create: LatencySummary::from_samples(samples.create.0, samples.create.1),
+        update: LatencySummary::from_samples(samples.update.0, samples.update.1),
+        read: LatencySummary::from_samples(samples.read.0, samples.read.1),
+    })
+}
+
+/// Issue one operation and record its latency. `pick` selects the item to use.
+async fn execute<T, G>(shared: &Shared<T, G>, operation: Operation, pick: usize)
+where
+    T: Storable,
+    G: Target<T>,
+{
+    // Updates take their item out of the pool so a lineage is never updated
+    // twice at once; reads clone it. Either falls back to a create when the
+    // pool is empty.
+    let existing = shared.items.lock().ok().and_then(|mut items| {
+        if items.is_empty() {
+            return None;
+        }
+        let index = pick % items.len();
+        match operation {
+            Operation::Update => Some(items.swap_remove(index)),
+            _ => items.get(index).cloned(),
+        }
+    });
+
+    let (operation, ok, latency) = match (operation, existing) {
+        (Operation::Update, Some(item)) => {
+            let started = Instant::now();
+            let result = shared.target.update(item.clone()).await;
+            let latency = started.elapsed();
+            let ok = result.is_ok();
+            // Keep the lineage usable even if the update failed
+            push(shared, result.unwrap_or(item));
+            (Operation::Update, ok, latency)
+        }
+        (Operation::Read, Some(item)) => {
+            let started = Instant::now();
+            let ok = shared.target.read(&item).await.is_ok();
+            (Operation::Read, ok, started.elapsed())
+        }
+        _ => {
+            let item = match shared.faker.lock() {
+                Ok(mut faker) => faker.raw::<T>(),
+                Err(e) => Err(StorageError::StorageError(e.to_string())),
+            };
+            let started = Instant::now();
+            let result = match item {
+                Ok(item) => shared.target.create(item).await,
+                Err(e) => Err(e),
+            };
+            let latency = started.elapsed();
+            let ok = result.is_ok();
+            if let Ok(created) = result {
+                push(shared, created);
+            }
+            (Operation::Create, ok, latency)
+        }
+    };
+
+    if let Ok(mut samples) = shared.samples.lock() {
+        samples.record(operation, latency, ok);
+    }
+}
+
+fn push<T, G>(shared: &Shared<T, G>, item: T) {
+    if let Ok(mut items) = shared.items.lock() {
+        items.push(item);
+    }
+}
+
+/// Pick an operation by weight. Update weight counts as create when unsupported.
+fn choose(mix: &OperationMix, supports_update: bool, rng: &mut impl Rng) -> Operation {
+    let total = mix.create + mix.update + mix.read;
+    if total == 0 {
+        return Operation::Create;
+    }
+
+    let roll = rng.gen_range(0..total);
+    if roll < mix.create {
+        Operation::Create
+    } else if roll < mix.create + mix.update {
+        if supports_update {
+            Operation::Update
+        } else {
+            Operation::Create
+        }
+    } else {
+        Operation::Read
+    }
+}
+
+/// Nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let samples: Vec<Duration> = (1..=100).rev().map(Duration::from_millis).collect();
+        let summary = LatencySummary::from_samples(samples, 3);
+
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.errors, 3);
+        assert_eq!(summary.p50, Duration::from_millis(50));
+        assert_eq!(summary.p90, Duration::from_millis(90));
+        assert_eq!(summary.p99, Duration::from_millis(99));
+        assert_eq!(summary.max, Duration::from_millis(100));
+        assert_eq!(
+            LatencySummary::from_samples(Vec::new(), 0).p99,
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn mix_weights_are_respected() {
+        let mix = OperationMix {
+            create: 1,
+            update: 0,
+            read: 3,
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        let reads = (0..1000)
+            .filter(|_| choose(&mix, true, &mut rng) == Operation::Read)
+            .count();
+        assert!((700..800).contains(&reads));
+
+        let writes_only = OperationMix {
+            create: 0,
+            update: 1,
+            read: 0,
+        };
+        assert_eq!(choose(&writes_only, false, &mut rng), Operation::Create);
+        assert_eq!(choose(&writes_only, true, &mut rng), Operation::Update);
+    }
+}
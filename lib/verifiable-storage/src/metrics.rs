@@ -0,0 +1,154 @@
+//! Metrics hooks for observing storage backends.
+//!
+//! Backends call into a [`MetricsHook`] at interesting points in their lifecycle,
+//! and [`MeteredRepository`] reports every repository call with its table and
+//! duration. The hook is deliberately minimal and synchronous so it can be wired
+//! to any metrics system (Prometheus, StatsD, tracing, ...) without pulling one
+//! in here; the `prometheus` feature provides `PrometheusMetrics`.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    SelfAddressed, Storable, StorageError, UnversionedRepository, Versioned, VersionedRepository,
+};
+
+/// Observer for storage backend events.
+///
+/// Every method has a no-op default, so implementors only override the
+/// events they care about.
+pub trait MetricsHook: Send + Sync {
+    /// A backend session was found dead and re-establishing it was attempted.
+    ///
+    /// `success` is `false` when the attempt failed; the backend will try
+    /// again on the next operation or liveness check.
+    fn on_reconnect(&self, _backend: &str, _success: bool) {}
+
+    /// A repository `operation` (`create`, `get_latest`, ...) on `table`
+    /// finished after `elapsed`, failing when `success` is `false`.
+    fn on_operation(&self, _table: &str, _operation: &str, _elapsed: Duration, _success: bool) {}
+
+    /// A record for `table` serialized to `bytes` on its way to being
+    /// written, reported by [`SizeLimitedRepository`](crate::SizeLimitedRepository).
+    fn on_payload(&self, _table: &str, _bytes: usize) {}
+}
+
+/// A `MetricsHook` that records nothing.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopMetrics;
+
+impl MetricsHook for NoopMetrics {}
+
+/// Wraps a repository so every call is reported to a [`MetricsHook`].
+#[derive(Clone)]
+pub struct MeteredRepository<R> {
+    inner: R,
+    metrics: Arc<dyn MetricsHook>,
+}
+
+impl<R> MeteredRepository<R> {
+    /// Wrap `inner`, reporting its calls to `metrics`.
+    pub fn new(inner: R, metrics: Arc<dyn MetricsHook>) -> Self {
+        Self { inner, metrics }
+    }
+
+    async fn observe<T: Storable, V>(
+        &self,
+        operation: &str,
+        call: impl Future<Output = Result<V, StorageError>>,
+    ) -> Result<V, StorageError> {
+        let started = Instant::now();
+        let result = call.await;
+        self.metrics.on_operation(
+            T::table_name(),
+            operation,
+            started.elapsed(),
+            result.is_ok(),
+        );
+        result
+    }
+}
+
+#[async_trait]
+impl<T, R> VersionedRepository<T> for MeteredRepository<R>
+where
+    T: Storable + SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        self.observe::<T, _>("create", self.inner.create(item))
+            .await
+    }
+
+    async fn update(&self, item: T) -> Result<T, StorageError> {
+        self.observe::<T, _>("update", self.inner.update(item))
+            .await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.observe::<T, _>("insert", self.inner.insert(item))
+            .await
+    }
+
+    async fn import_history(&self, items: Vec<T>) -> Result<Vec<T>, StorageError> {
+        self.observe::<T, _>("import_history", self.inner.import_history(items))
+            .await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.observe::<T, _>("get_by_said", self.inner.get_by_said(said))
+            .await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        self.observe::<T, _>("get_latest", self.inner.get_latest(prefix))
+            .await
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        self.observe::<T, _>("get_history", self.inner.get_history(prefix))
+            .await
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        self.observe::<T, _>("exists", self.inner.exists(prefix))
+            .await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.observe::<T, _>("delete_soft", self.inner.delete_soft(said))
+            .await
+    }
+}
+
+#[async_trait]
+impl<T, R> UnversionedRepository<T> for MeteredRepository<R>
+where
+    T: Storable + SelfAddressed + Serialize + DeserializeOwned + Clone + 'static,
+    R: UnversionedRepository<T> + Send + Sync,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        self.observe::<T, _>("create", self.inner.create(item))
+            .await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.observe::<T, _>("insert", self.inner.insert(item))
+            .await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.observe::<T, _>("get_by_said", self.inner.get_by_said(said))
+            .await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.observe::<T, _>("delete_soft", self.inner.delete_soft(said))
+            .await
+    }
+}
@@ -0,0 +1,232 @@
+//! Copying a table from one backend to another.
+//!
+//! A [`Migration`] reads a table from the source executor in SAID order, one
+//! batch at a time, verifies every record, writes the batch to the destination
+//! with `insert_or_ignore` and counts it back before moving on. Its
+//! [`MigrationCheckpoint`] records the last SAID copied; persist it between
+//! batches and a failed or interrupted run resumes where it stopped, rewriting
+//! nothing already copied. [`migrate_table`] runs a migration to completion.
+//! Soft-deleted records are copied too, keeping their deletion marks.
+//!
+//! Afterwards, `compare` (feature `compare`) confirms the two sides agree.
+
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ManagedField, Order, Query, QueryExecutor, SelfAddressed, Storable, StorageError, Versioned,
+};
+
+/// How far a migration has got.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationCheckpoint {
+    pub table: String,
+    /// The last SAID copied; the next batch starts after it.
+    pub after: Option<String>,
+    /// Records written to the destination.
+    pub copied: u64,
+    /// Records the destination already held.
+    pub skipped: u64,
+    pub complete: bool,
+}
+
+/// A resumable copy of `T`'s table between two executors.
+pub struct Migration<T> {
+    checkpoint: MigrationCheckpoint,
+    batch_size: u64,
+    verify: fn(&T) -> Result<(), StorageError>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Storable + Versioned> Migration<T> {
+    /// Migrate a versioned table, verifying each version's SAID (and prefix, at inception).
+    pub fn versioned(batch_size: u64) -> Self {
+        Self::starting(batch_size, <T as Versioned>::verify)
+    }
+}
+
+impl<T: Storable + SelfAddressed> Migration<T> {
+    /// Migrate an unversioned table, verifying each record's SAID.
+    pub fn unversioned(batch_size: u64) -> Self {
+        Self::starting(batch_size, T::verify_said)
+    }
+
+    fn starting(batch_size: u64, verify: fn(&T) -> Result<(), StorageError>) -> Self {
+        Self {
+            checkpoint: MigrationCheckpoint {
+                table: T::table_name().to_string(),
+                ..Default::default()
+            },
+            batch_size: batch_size.max(1),
+            verify,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Continue from a checkpoint saved by an earlier run over the same table.
+    pub fn resume(mut self, checkpoint: MigrationCheckpoint) -> Result<Self, StorageError> {
+        if checkpoint.table != T::table_name() {
+            return Err(StorageError::SchemaMismatch(format!(
+                "Checkpoint is for {}, not {}",
+                checkpoint.table,
+                T::table_name()
+            )));
+        }
+        self.checkpoint = checkpoint;
+        Ok(self)
+    }
+
+    /// Progress so far; save it after each batch to be able to resume.
+    pub fn checkpoint(&self) -> &MigrationCheckpoint {
+        &self.checkpoint
+    }
+
+    /// Copy the next batch. Returns `false` once the table is fully copied.
+    ///
+    /// The checkpoint only advances after the whole batch is verified, written
+    /// and read back, so an error leaves it pointing at the batch to retry.
+    pub async fn next_batch<S, D>(
+        &mut self,
+        source: &S,
+        destination: &D,
+    ) -> Result<bool, StorageError>
+    where
+        S: QueryExecutor,
+        D: QueryExecutor,
+    {
+        if self.checkpoint.complete {
+            return Ok(false);
+        }
+        let said_column = T::managed_column(ManagedField::Said);
+        let mut query = Query::<T>::new()
+            .include_deleted()
+            .order_by(said_column, Order::Asc)
+            .limit(self.batch_size);
+        if let Some(after) = &self.checkpoint.after {
+            query = query.gt(said_column, after.as_str());
+        }
+        let batch = source.fetch(query).await?;
+        for item in &batch {
+            (self.verify)(item)?;
+        }
+
+        let mut written = 0;
+        for item in &batch {
+            written += destination.insert_or_ignore(item).await?;
+        }
+        let saids: Vec<String> = batch.iter().map(|item| item.get_said()).collect();
+        if !saids.is_empty() {
+            let stored = destination
+                .count(
+                    Query::<T>::new()
+                        .include_deleted()
+                        .r#in(said_column, saids.clone()),
+                )
+                .await?;
+            if stored != saids.len() as u64 {
+                return Err(StorageError::StorageError(format!(
+                    "Only {} of {} {} records after {} reached the destination",
+                    stored,
+                    saids.len(),
+                    T::table_name(),
+                    self.checkpoint.after.as_deref().unwrap_or("the start")
+                )));
+            }
+        }
+
+        self.checkpoint.copied += written;
+        self.checkpoint.skipped += saids.len() as u64 - written;
+        if let Some(last) = saids.into_iter().last() {
+            self.checkpoint.after = Some(last);
+        }
+        self.checkpoint.complete = (batch.len() as u64) < self.batch_size;
+        Ok(!self.checkpoint.complete)
+    }
+
+    /// Copy batches until the table is done.
+    pub async fn run<S, D>(
+        mut self,
+        source: &S,
+        destination: &D,
+    ) -> Result<MigrationCheckpoint, StorageError>
+    where
+        S: QueryExecutor,
+        D: QueryExecutor,
+    {
+        while self.next_batch(source, destination).await? {}
+        Ok(self.checkpoint)
+    }
+}
+
+/// Copy every version in `T`'s table from `source` to `destination`.
+pub async fn migrate_table<T, S, D>(
+    source: &S,
+    destination: &D,
+    batch_size: u64,
+) -> Result<MigrationCheckpoint, StorageError>
+where
+    T: Storable + Versioned,
+    S: QueryExecutor,
+    D: QueryExecutor,
+{
+    Migration::<T>::versioned(batch_size)
+        .run(source, destination)
+        .await
+}
+
+/// Copy every record in an unversioned `T`'s table from `source` to `destination`.
+pub async fn migrate_unversioned_table<T, S, D>(
+    source: &S,
+    destination: &D,
+    batch_size: u64,
+) -> Result<MigrationCheckpoint, StorageError>
+where
+    T: Storable + SelfAddressed,
+    S: QueryExecutor,
+    D: QueryExecutor,
+{
+    Migration::<T>::unversioned(batch_size)
+        .run(source, destination)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "receipts")]
+    #[serde(rename_all = "camelCase")]
+    struct Receipt {
+        #[said]
+        said: String,
+        body: String,
+    }
+
+    #[test]
+    fn checkpoints_resume_only_their_own_table() {
+        let checkpoint = MigrationCheckpoint {
+            table: "receipts".to_string(),
+            after: Some("E123".to_string()),
+            copied: 10,
+            skipped: 2,
+            complete: false,
+        };
+        let saved = serde_json::to_string(&checkpoint).unwrap();
+        let migration = Migration::<Receipt>::unversioned(10)
+            .resume(serde_json::from_str(&saved).unwrap())
+            .unwrap();
+        assert_eq!(migration.checkpoint(), &checkpoint);
+
+        let elsewhere = MigrationCheckpoint {
+            table: "invoices".to_string(),
+            ..checkpoint
+        };
+        assert!(matches!(
+            Migration::<Receipt>::unversioned(10).resume(elsewhere),
+            Err(StorageError::SchemaMismatch(_))
+        ));
+    }
+}
@@ -0,0 +1,220 @@
+//! First-come-first-served name reservation with verifiable claims.
+//!
+//! Each name gets one [`NameClaim`] chain: the inception records the first
+//! claim, and every release, transfer or re-claim is a follow-up version, so
+//! who held a name when is answered by its history. Writers of a name are
+//! serialized with an advisory lock, and the partial unique index rejects a
+//! second chain for the same name even if the lock is bypassed:
+//!
+//! ```text
+//! CREATE TABLE name_claims (
+//!     said TEXT PRIMARY KEY,
+//!     prefix TEXT NOT NULL,
+//!     previous TEXT,
+//!     version BIGINT NOT NULL,
+//!     created_at TIMESTAMPTZ NOT NULL,
+//!     name TEXT NOT NULL,
+//!     holder TEXT,
+//!     UNIQUE (prefix, version)
+//! );
+//! CREATE UNIQUE INDEX name_claims_name ON name_claims (name) WHERE version = 0;
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Order, Query, QueryExecutor, StorageDatetime, StorageError, TransactionExecutor, Versioned,
+};
+
+/// One version of a name's claim chain.
+#[derive(Clone, Debug, Serialize, Deserialize, crate::SelfAddressed)]
+#[storable(table = "name_claims")]
+#[serde(rename_all = "camelCase")]
+pub struct NameClaim {
+    #[said]
+    pub said: String,
+    #[prefix]
+    pub prefix: String,
+    #[previous]
+    pub previous: Option<String>,
+    #[version]
+    pub version: u64,
+    #[created_at]
+    pub created_at: StorageDatetime,
+    pub name: String,
+    /// The prefix holding the name; `None` once released.
+    pub holder: Option<String>,
+}
+
+impl NameClaim {
+    /// Whether `prefix` holds the name as of this version.
+    pub fn is_held_by(&self, prefix: &str) -> bool {
+        self.holder.as_deref() == Some(prefix)
+    }
+}
+
+/// Claims, releases and transfers names through an executor.
+#[derive(Debug, Clone)]
+pub struct NameRegistry<E> {
+    executor: E,
+}
+
+impl<E: QueryExecutor> NameRegistry<E> {
+    pub fn new(executor: E) -> Self {
+        Self { executor }
+    }
+
+    /// The latest claim on `name`, if it was ever claimed.
+    pub async fn lookup(&self, name: &str) -> Result<Option<NameClaim>, StorageError> {
+        self.executor.fetch_optional(latest_claim(name)).await
+    }
+
+    /// The prefix currently holding `name`.
+    pub async fn holder(&self, name: &str) -> Result<Option<String>, StorageError> {
+        Ok(self.lookup(name).await?.and_then(|claim| claim.holder))
+    }
+
+    /// Every claim on `name`, oldest first.
+    pub async fn history(&self, name: &str) -> Result<Vec<NameClaim>, StorageError> {
+        self.executor
+            .fetch(
+                Query::<NameClaim>::new()
+                    .eq("name", name)
+                    .order_by("version", Order::Asc),
+            )
+            .await
+    }
+
+    /// Claim `name` for `prefix`. Fails if another prefix holds it.
+    pub async fn claim(&self, name: &str, prefix: &str) -> Result<NameClaim, StorageError> {
+        self.write(name, |latest| claimed(latest, name, prefix))
+            .await
+    }
+
+    /// Give up `name`, which `prefix` must hold, so anyone can claim it.
+    pub async fn release(&self, name: &str, prefix: &str) -> Result<NameClaim, StorageError> {
+        self.write(name, |latest| reassigned(latest, name, prefix, None))
+            .await
+    }
+
+    /// Hand `name` from `from`, which must hold it, to `to`.
+    pub async fn transfer(
+        &self,
+        name: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<NameClaim, StorageError> {
+        self.write(name, |latest| reassigned(latest, name, from, Some(to)))
+            .await
+    }
+
+    /// Compute and insert the next claim under the name's lock.
+    async fn write(
+        &self,
+        name: &str,
+        next: impl FnOnce(Option<&NameClaim>) -> Result<NameClaim, StorageError>,
+    ) -> Result<NameClaim, StorageError> {
+        let mut tx = self.executor.begin_transaction().await?;
+        let written = async {
+            tx.acquire_advisory_lock(&format!("name_claims:{}", name))
+                .await?;
+            let latest = tx.fetch(latest_claim(name)).await?;
+            let claim = next(latest.first())?;
+            tx.insert(&claim).await?;
+            Ok(claim)
+        }
+        .await;
+        match written {
+            Ok(claim) => {
+                tx.commit().await?;
+                Ok(claim)
+            }
+            Err(e) => {
+                tx.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+fn latest_claim(name: &str) -> Query<NameClaim> {
+    Query::<NameClaim>::new()
+        .eq("name", name)
+        .order_by("version", Order::Desc)
+        .limit(1)
+}
+
+/// The claim following `latest` that gives `name` to `prefix`.
+fn claimed(
+    latest: Option<&NameClaim>,
+    name: &str,
+    prefix: &str,
+) -> Result<NameClaim, StorageError> {
+    match latest {
+        None => {
+            let mut claim = NameClaim::new(name.to_string(), Some(prefix.to_string()));
+            claim.derive_prefix()?;
+            Ok(claim)
+        }
+        Some(latest) => match &latest.holder {
+            None => follow(latest, Some(prefix)),
+            Some(holder) => Err(StorageError::StorageError(format!(
+                "Name {} is already held by {}",
+                name, holder
+            ))),
+        },
+    }
+}
+
+/// The claim following `latest` that moves `name` from `from` to `to`.
+fn reassigned(
+    latest: Option<&NameClaim>,
+    name: &str,
+    from: &str,
+    to: Option<&str>,
+) -> Result<NameClaim, StorageError> {
+    match latest {
+        Some(latest) if latest.is_held_by(from) => follow(latest, to),
+        Some(_) => Err(StorageError::StorageError(format!(
+            "Name {} is not held by {}",
+            name, from
+        ))),
+        None => Err(StorageError::NotFound(format!("Name claim: {}", name))),
+    }
+}
+
+fn follow(latest: &NameClaim, holder: Option<&str>) -> Result<NameClaim, StorageError> {
+    let mut claim = latest.clone();
+    claim.holder = holder.map(str::to_string);
+    claim.increment()?;
+    Ok(claim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_pass_through_claim_transfer_and_release() {
+        let v0 = claimed(None, "example.com", "Ealice").unwrap();
+        v0.verify().unwrap();
+        assert!(claimed(Some(&v0), "example.com", "Ebob").is_err());
+        assert!(reassigned(Some(&v0), "example.com", "Ebob", None).is_err());
+
+        let v1 = reassigned(Some(&v0), "example.com", "Ealice", Some("Ebob")).unwrap();
+        assert!(v1.is_held_by("Ebob"));
+        assert_eq!(v1.prefix, v0.prefix);
+        assert_eq!(v1.previous.as_deref(), Some(v0.said.as_str()));
+
+        let v2 = reassigned(Some(&v1), "example.com", "Ebob", None).unwrap();
+        assert_eq!(v2.holder, None);
+        let v3 = claimed(Some(&v2), "example.com", "Ecarol").unwrap();
+        assert_eq!(v3.version, 3);
+        v3.verify_lineage(&[v0, v1, v2, v3.clone()]).unwrap();
+
+        assert!(matches!(
+            reassigned(None, "unclaimed", "Ealice", None),
+            Err(StorageError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,58 @@
+//! NATS JetStream event sink.
+//!
+//! Each event is published to `<subject>.<table>.<partition key>`, so a
+//! consumer filtering on one prefix's subject sees its changes in order, and
+//! waits for the stream's acknowledgement. Use it behind an
+//! [`OutboxRelay`](crate::OutboxRelay) for at-least-once delivery.
+
+use async_nats::jetstream;
+use async_trait::async_trait;
+
+use crate::{EventSink, StorageError, WriteEvent};
+
+/// Publishes write events to a JetStream stream, one subject per prefix.
+#[derive(Clone)]
+pub struct NatsSink {
+    jetstream: jetstream::Context,
+    subject: String,
+}
+
+impl NatsSink {
+    /// Connect to `url` and publish under `subject`, which a stream must capture
+    /// (e.g. a stream on `subject.>`).
+    pub async fn connect(url: &str, subject: impl Into<String>) -> Result<Self, StorageError> {
+        let client = async_nats::connect(url)
+            .await
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+        Ok(Self::with_client(client, subject))
+    }
+
+    /// Use an existing connection.
+    pub fn with_client(client: async_nats::Client, subject: impl Into<String>) -> Self {
+        Self {
+            jetstream: jetstream::new(client),
+            subject: subject.into(),
+        }
+    }
+
+    fn subject_for(&self, event: &WriteEvent) -> String {
+        format!("{}.{}.{}", self.subject, event.table, event.partition_key())
+    }
+}
+
+#[async_trait]
+impl EventSink for NatsSink {
+    async fn emit(&self, event: WriteEvent) -> Result<(), StorageError> {
+        let failed = |e: &dyn std::fmt::Display| {
+            StorageError::StorageError(format!("NATS delivery failed: {}", e))
+        };
+        let payload = serde_json::to_vec(&event)?;
+        self.jetstream
+            .publish(self.subject_for(&event), payload.into())
+            .await
+            .map_err(|e| failed(&e))?
+            .await
+            .map_err(|e| failed(&e))?;
+        Ok(())
+    }
+}
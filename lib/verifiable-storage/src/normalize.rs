@@ -0,0 +1,431 @@
+//! Canonicalization of query filters.
+//!
+//! Two queries that select the same rows should render the same SQL, so that
+//! prepared statements are reused and snapshot tests are stable regardless of
+//! the order in which filters were added. Normalization only merges conditions
+//! whose values are comparable; anything else is kept as written.
+
+use crate::{Filter, Query, Subquery, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+impl<T> Query<T> {
+    /// Put the query's filters into canonical form.
+    ///
+    /// Filters are grouped by field (in field order) and sorted within each
+    /// field. Duplicate and implied conditions are dropped, range bounds are
+    /// tightened, `IN` lists are intersected and sorted, and an `IN` with one
+    /// value becomes an equality. If the filters can never all hold, they are
+    /// replaced by `field IS NULL AND field IS NOT NULL` for the first
+    /// contradictory field, which matches nothing in every dialect.
+    pub fn normalize(mut self) -> Self {
+        self.filters = normalize_filters(std::mem::take(&mut self.filters));
+        self
+    }
+}
+
+/// A lower or upper bound: the value and whether it is inclusive.
+type Bound = (Value, bool);
+
+/// Everything known about a single field.
+#[derive(Default)]
+struct FieldFilters {
+    null: Option<bool>,
+    eq: Vec<Value>,
+    ne: Vec<Value>,
+    lower: Vec<Bound>,
+    upper: Vec<Bound>,
+    any: Option<Vec<String>>,
+    /// `In` filters whose value is not a string list; kept as written.
+    other_in: Vec<Value>,
+    subqueries: Vec<Subquery>,
+    contradiction: bool,
+}
+
+fn normalize_filters(filters: Vec<Filter>) -> Vec<Filter> {
+    let mut fields: BTreeMap<String, FieldFilters> = BTreeMap::new();
+    // Disjunctions span fields, so they are only deduplicated and sorted
+    let mut disjunctions: Vec<(String, Filter)> = Vec::new();
+
+    for filter in filters {
+        match filter {
+            Filter::IsNull(field) => fields.entry(field).or_default().set_null(true),
+            Filter::IsNotNull(field) => fields.entry(field).or_default().set_null(false),
+            Filter::Eq(field, value) => {
+                push_unique(&mut fields.entry(field).or_default().eq, value)
+            }
+            Filter::Ne(field, value) => {
+                push_unique(&mut fields.entry(field).or_default().ne, value)
+            }
+            Filter::Gt(field, value) => fields.entry(field).or_default().lower.push((value, false)),
+            Filter::Gte(field, value) => fields.entry(field).or_default().lower.push((value, true)),
+            Filter::Lt(field, value) => fields.entry(field).or_default().upper.push((value, false)),
+            Filter::Lte(field, value) => fields.entry(field).or_default().upper.push((value, true)),
+            Filter::In(field, Value::Strings(values)) => {
+                fields.entry(field).or_default().intersect(values)
+            }
+            Filter::In(field, value) => {
+                push_unique(&mut fields.entry(field).or_default().other_in, value)
+            }
+            Filter::InSubquery(field, mut subquery) => {
+                subquery.query = subquery.query.normalize();
+                let subqueries = &mut fields.entry(field).or_default().subqueries;
+                let key = format!("{:?}", subquery);
+                if !subqueries.iter().any(|s| format!("{:?}", s) == key) {
+                    subqueries.push(*subquery);
+                }
+            }
+            filter @ (Filter::Or(_) | Filter::And(_)) => {
+                let key = format!("{:?}", filter);
+                if !disjunctions.iter().any(|(k, _)| *k == key) {
+                    disjunctions.push((key, filter));
+                }
+            }
+        }
+    }
+
+    let mut normalized = Vec::new();
+    for (field, mut state) in fields {
+        state.resolve();
+        if state.contradiction {
+            return vec![Filter::IsNull(field.clone()), Filter::IsNotNull(field)];
+        }
+        state.emit(&field, &mut normalized);
+    }
+    disjunctions.sort_by(|(a, _), (b, _)| a.cmp(b));
+    normalized.extend(disjunctions.into_iter().map(|(_, filter)| filter));
+    normalized
+}
+
+impl FieldFilters {
+    fn set_null(&mut self, null: bool) {
+        if self.null.is_some_and(|existing| existing != null) {
+            self.contradiction = true;
+        }
+        self.null = Some(null);
+    }
+
+    fn intersect(&mut self, values: Vec<String>) {
+        self.any = Some(match self.any.take() {
+            Some(existing) => existing
+                .into_iter()
+                .filter(|v| values.contains(v))
+                .collect(),
+            None => values,
+        });
+    }
+
+    /// Whether any comparison is present (each implies the field is not null).
+    fn has_comparison(&self) -> bool {
+        !self.eq.is_empty()
+            || !self.ne.is_empty()
+            || !self.lower.is_empty()
+            || !self.upper.is_empty()
+            || self.any.is_some()
+            || !self.other_in.is_empty()
+            || !self.subqueries.is_empty()
+    }
+
+    /// Merge redundant conditions and detect contradictions.
+    fn resolve(&mut self) {
+        if self.contradiction {
+            return;
+        }
+        if self.has_comparison() {
+            // Comparisons against NULL are never true
+            if self.null == Some(true) {
+                self.contradiction = true;
+                return;
+            }
+            self.null = None;
+        }
+
+        self.lower = tighten(std::mem::take(&mut self.lower), Ordering::Greater);
+        self.upper = tighten(std::mem::take(&mut self.upper), Ordering::Less);
+
+        if let Some(any) = self.any.take() {
+            let mut any: Vec<String> = any
+                .into_iter()
+                .filter(|v| {
+                    let value = Value::String(v.clone());
+                    !self.ne.iter().any(|ne| same(ne, &value)) && self.within_bounds(&value)
+                })
+                .collect();
+            any.sort();
+            any.dedup();
+
+            match any.len() {
+                0 => {
+                    self.contradiction = true;
+                    return;
+                }
+                1 => push_unique(&mut self.eq, Value::String(any.remove(0))),
+                _ => self.any = Some(any),
+            }
+        }
+
+        // Two equalities on distinct values can never both hold
+        if self.eq.len() > 1 && self.eq.windows(2).any(|w| compare(&w[0], &w[1]).is_some()) {
+            self.contradiction = true;
+            return;
+        }
+
+        // A comparable bound pair must leave room for a value
+        for (low, low_inclusive) in &self.lower {
+            for (high, high_inclusive) in &self.upper {
+                match compare(low, high) {
+                    Some(Ordering::Greater) => self.contradiction = true,
+                    Some(Ordering::Equal) if !(*low_inclusive && *high_inclusive) => {
+                        self.contradiction = true
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if self.contradiction {
+            return;
+        }
+
+        let [eq] = self.eq.as_slice() else {
+            return;
+        };
+        let eq = eq.clone();
+
+        if self.ne.iter().any(|ne| same(ne, &eq))
+            || !self.within_bounds(&eq)
+            || self.any.as_ref().is_some_and(|any| match &eq {
+                Value::String(s) => !any.contains(s),
+                _ => false,
+            })
+        {
+            self.contradiction = true;
+            return;
+        }
+
+        // Everything comparable is implied by the equality
+        self.ne.retain(|ne| compare(ne, &eq).is_none());
+        self.lower.retain(|(low, _)| compare(low, &eq).is_none());
+        self.upper.retain(|(high, _)| compare(high, &eq).is_none());
+        if matches!(eq, Value::String(_)) {
+            self.any = None;
+        }
+    }
+
+    /// Whether `value` satisfies every comparable bound.
+    fn within_bounds(&self, value: &Value) -> bool {
+        let above = self
+            .lower
+            .iter()
+            .all(|(low, inclusive)| match compare(value, low) {
+                Some(Ordering::Less) => false,
+                Some(Ordering::Equal) => *inclusive,
+                _ => true,
+            });
+        let below = self
+            .upper
+            .iter()
+            .all(|(high, inclusive)| match compare(value, high) {
+                Some(Ordering::Greater) => false,
+                Some(Ordering::Equal) => *inclusive,
+                _ => true,
+            });
+        above && below
+    }
+
+    /// Append this field's filters in canonical order.
+    fn emit(mut self, field: &str, out: &mut Vec<Filter>) {
+        match self.null {
+            Some(true) => out.push(Filter::IsNull(field.to_string())),
+            Some(false) => out.push(Filter::IsNotNull(field.to_string())),
+            None => {}
+        }
+
+        sort_values(&mut self.eq);
+        sort_values(&mut self.ne);
+        sort_values(&mut self.other_in);
+        self.lower
+            .sort_by_key(|(value, inclusive)| (sort_key(value), *inclusive));
+        self.upper
+            .sort_by_key(|(value, inclusive)| (sort_key(value), *inclusive));
+
+        for value in self.eq {
+            out.push(Filter::Eq(field.to_string(), value));
+        }
+        if let Some(any) = self.any {
+            out.push(Filter::In(field.to_string(), Value::Strings(any)));
+        }
+        for value in self.other_in {
+            out.push(Filter::In(field.to_string(), value));
+        }
+        self.subqueries.sort_by_cached_key(|s| format!("{:?}", s));
+        for subquery in self.subqueries {
+            out.push(Filter::InSubquery(field.to_string(), Box::new(subquery)));
+        }
+        for value in self.ne {
+            out.push(Filter::Ne(field.to_string(), value));
+        }
+        for (value, inclusive) in self.lower {
+            let filter = if inclusive { Filter::Gte } else { Filter::Gt };
+            out.push(filter(field.to_string(), value));
+        }
+        for (value, inclusive) in self.upper {
+            let filter = if inclusive { Filter::Lte } else { Filter::Lt };
+            out.push(filter(field.to_string(), value));
+        }
+    }
+}
+
+/// Keep only the tightest of each set of comparable bounds.
+///
+/// `tighter` is the ordering a bound must have relative to another to replace
+/// it: `Greater` for lower bounds, `Less` for upper bounds. At equal values the
+/// exclusive bound is tighter.
+fn tighten(bounds: Vec<Bound>, tighter: Ordering) -> Vec<Bound> {
+    let mut kept: Vec<Bound> = Vec::with_capacity(bounds.len());
+    for (value, inclusive) in bounds {
+        let existing = kept.iter_mut().find(|(k, _)| compare(&value, k).is_some());
+        match existing {
+            Some(existing) => match compare(&value, &existing.0) {
+                Some(ordering) if ordering == tighter => *existing = (value, inclusive),
+                Some(Ordering::Equal) => existing.1 &= inclusive,
+                _ => {}
+            },
+            None => kept.push((value, inclusive)),
+        }
+    }
+    kept
+}
+
+fn push_unique(values: &mut Vec<Value>, value: Value) {
+    if !values.iter().any(|v| same(v, &value)) {
+        values.push(value);
+    }
+}
+
+fn sort_values(values: &mut [Value]) {
+    values.sort_by_key(sort_key);
+}
+
+/// A deterministic sort key for any value.
+fn sort_key(value: &Value) -> String {
+    value.to_json().to_string()
+}
+
+/// Whether two values are known to be equal.
+fn same(a: &Value, b: &Value) -> bool {
+    compare(a, b) == Some(Ordering::Equal) || sort_key(a) == sort_key(b)
+}
+
+/// Compare two values if they are of comparable kinds.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        (Value::UInt(a), Value::UInt(b)) => Some(a.cmp(b)),
+        (Value::Int(a), Value::UInt(b)) => Some(i128::from(*a).cmp(&i128::from(*b))),
+        (Value::UInt(a), Value::Int(b)) => Some(i128::from(*a).cmp(&i128::from(*b))),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Datetime(a), Value::Datetime(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Dialect;
+
+    fn sql(filters: Vec<Filter>) -> String {
+        let query = Query::<()> {
+            table: "events".to_string(),
+            joins: Vec::new(),
+            filters,
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+            distinct_on: Vec::new(),
+            latest_per: None,
+            _marker: std::marker::PhantomData,
+        };
+        query.normalize().to_sql(Dialect::Postgres).0
+    }
+
+    #[test]
+    fn order_independent() {
+        let a = sql(vec![
+            Filter::Eq("prefix".to_string(), Value::from("Eabc")),
+            Filter::Gte("version".to_string(), Value::from(2u64)),
+            Filter::IsNotNull("said".to_string()),
+        ]);
+        let b = sql(vec![
+            Filter::IsNotNull("said".to_string()),
+            Filter::Gte("version".to_string(), Value::from(2u64)),
+            Filter::Eq("prefix".to_string(), Value::from("Eabc")),
+        ]);
+        assert_eq!(a, b);
+        assert_eq!(
+            a,
+            "SELECT * FROM events WHERE prefix = $1 AND said IS NOT NULL AND version >= $2"
+        );
+    }
+
+    #[test]
+    fn merges_redundant_conditions() {
+        assert_eq!(
+            sql(vec![
+                Filter::Gt("version".to_string(), Value::from(2u64)),
+                Filter::Gte("version".to_string(), Value::from(5u64)),
+                Filter::Lt("version".to_string(), Value::from(9u64)),
+                Filter::Lte("version".to_string(), Value::from(9u64)),
+                Filter::IsNotNull("version".to_string()),
+                Filter::Eq("kind".to_string(), Value::from("icp")),
+                Filter::In("kind".to_string(), Value::from(vec!["rot", "icp"])),
+            ]),
+            "SELECT * FROM events WHERE kind = $1 AND version >= $2 AND version < $3"
+        );
+
+        assert_eq!(
+            sql(vec![
+                Filter::In("kind".to_string(), Value::from(vec!["rot", "icp", "ixn"])),
+                Filter::In("kind".to_string(), Value::from(vec!["ixn", "icp"])),
+                Filter::Ne("kind".to_string(), Value::from("ixn")),
+            ]),
+            "SELECT * FROM events WHERE kind = $1"
+        );
+    }
+
+    #[test]
+    fn collapses_contradictions() {
+        let none = "SELECT * FROM events WHERE kind IS NULL AND kind IS NOT NULL";
+        assert_eq!(
+            sql(vec![
+                Filter::Eq("kind".to_string(), Value::from("icp")),
+                Filter::Eq("kind".to_string(), Value::from("rot")),
+                Filter::Eq("prefix".to_string(), Value::from("Eabc")),
+            ]),
+            none
+        );
+        assert_eq!(
+            sql(vec![
+                Filter::IsNull("kind".to_string()),
+                Filter::Ne("kind".to_string(), Value::from("icp")),
+            ]),
+            none
+        );
+        assert_eq!(
+            sql(vec![
+                Filter::Gt("kind".to_string(), Value::from("m")),
+                Filter::Lte("kind".to_string(), Value::from("m")),
+            ]),
+            none
+        );
+        assert_eq!(
+            sql(vec![Filter::In(
+                "kind".to_string(),
+                Value::from(Vec::<String>::new())
+            )]),
+            none
+        );
+    }
+}
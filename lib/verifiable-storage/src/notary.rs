@@ -0,0 +1,217 @@
+//! Trusted timestamping of stored SAIDs.
+//!
+//! A [`TimestampAuthority`] (typically an RFC 3161 TSA client) issues a token
+//! over a record's SAID, which [`Notary`] stores as a [`TimestampToken`] in the
+//! `timestamp_tokens` companion table:
+//!
+//! ```text
+//! CREATE TABLE timestamp_tokens (
+//!     said TEXT PRIMARY KEY,
+//!     record_said TEXT NOT NULL,
+//!     authority TEXT NOT NULL,
+//!     token TEXT NOT NULL,
+//!     created_at TIMESTAMPTZ NOT NULL
+//! );
+//! CREATE INDEX timestamp_tokens_record_said ON timestamp_tokens (record_said);
+//! ```
+//!
+//! [`NotarizingRepository`] notarizes every record its inner repository writes.
+//! Tokens are evidence that a SAID existed no later than the time the
+//! authority attests; [`Notary::verify`] re-checks them with the authority.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    Order, Query, QueryExecutor, SelfAddressed, StorageDatetime, StorageError,
+    UnversionedRepository, Versioned, VersionedRepository,
+};
+
+/// Issues and checks trusted timestamps over SAIDs.
+#[async_trait]
+pub trait TimestampAuthority: Send + Sync {
+    /// Identifies the authority, e.g. its URL or certificate fingerprint.
+    fn name(&self) -> &str;
+
+    /// Request a token over `said`, returning it encoded (e.g. base64 DER).
+    async fn timestamp(&self, said: &str) -> Result<String, StorageError>;
+
+    /// Check that `token` is a valid timestamp over `said`, returning the attested time.
+    fn verify(&self, said: &str, token: &str) -> Result<StorageDatetime, StorageError>;
+}
+
+/// A timestamp token over a stored record's SAID.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, crate::SelfAddressed)]
+#[storable(table = "timestamp_tokens")]
+#[serde(rename_all = "camelCase")]
+pub struct TimestampToken {
+    #[said]
+    pub said: String,
+    pub record_said: String,
+    pub authority: String,
+    pub token: String,
+    #[created_at]
+    pub created_at: StorageDatetime,
+}
+
+/// Obtains, stores and verifies timestamp tokens.
+#[derive(Debug, Clone)]
+pub struct Notary<E, A> {
+    executor: E,
+    authority: A,
+}
+
+impl<E: QueryExecutor, A: TimestampAuthority> Notary<E, A> {
+    /// Store tokens from `authority` through `executor`.
+    pub fn new(executor: E, authority: A) -> Self {
+        Self {
+            executor,
+            authority,
+        }
+    }
+
+    /// Timestamp `said` and store the token.
+    pub async fn notarize(&self, said: &str) -> Result<TimestampToken, StorageError> {
+        let token = self.authority.timestamp(said).await?;
+        let mut record =
+            TimestampToken::new(said.to_string(), self.authority.name().to_string(), token);
+        record.derive_said()?;
+        self.executor.insert(&record).await?;
+        Ok(record)
+    }
+
+    /// Stored tokens for `said`, oldest first.
+    pub async fn tokens(&self, said: &str) -> Result<Vec<TimestampToken>, StorageError> {
+        self.executor
+            .fetch(
+                Query::<TimestampToken>::new()
+                    .eq("record_said", said)
+                    .order_by("created_at", Order::Asc),
+            )
+            .await
+    }
+
+    /// The earliest time a stored token from this authority attests for `said`.
+    ///
+    /// Tokens from other authorities are ignored; fails if no token verifies.
+    pub async fn verify(&self, said: &str) -> Result<StorageDatetime, StorageError> {
+        let mut earliest: Option<StorageDatetime> = None;
+        let mut last_error = None;
+        for record in self.tokens(said).await? {
+            if record.authority != self.authority.name() {
+                continue;
+            }
+            if let Err(e) = record.verify_said() {
+                last_error = Some(e);
+                continue;
+            }
+            match self.authority.verify(said, &record.token) {
+                Ok(time) => earliest = earliest.into_iter().chain([time]).min(),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        earliest.ok_or_else(|| {
+            last_error.unwrap_or_else(|| {
+                StorageError::NotFound(format!("No timestamp token for {}", said))
+            })
+        })
+    }
+}
+
+/// Wraps a repository so every written record is notarized.
+///
+/// The record is written first; if notarization then fails, the error is
+/// returned and the record stays stored without a token.
+#[derive(Debug, Clone)]
+pub struct NotarizingRepository<R, E, A> {
+    inner: R,
+    notary: Notary<E, A>,
+}
+
+impl<R, E, A> NotarizingRepository<R, E, A> {
+    /// Wrap `inner`, notarizing its writes with `notary`.
+    pub fn new(inner: R, notary: Notary<E, A>) -> Self {
+        Self { inner, notary }
+    }
+
+    /// The notary, for token lookups and verification.
+    pub fn notary(&self) -> &Notary<E, A> {
+        &self.notary
+    }
+}
+
+#[async_trait]
+impl<T, R, E, A> VersionedRepository<T> for NotarizingRepository<R, E, A>
+where
+    T: SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+    E: QueryExecutor,
+    A: TimestampAuthority,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.create(item).await?;
+        self.notary.notarize(&item.get_said()).await?;
+        Ok(item)
+    }
+
+    async fn update(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.update(item).await?;
+        self.notary.notarize(&item.get_said()).await?;
+        Ok(item)
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.insert(item).await?;
+        self.notary.notarize(&item.get_said()).await?;
+        Ok(item)
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_latest(prefix).await
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        self.inner.get_history(prefix).await
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        self.inner.exists(prefix).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[async_trait]
+impl<T, R, E, A> UnversionedRepository<T> for NotarizingRepository<R, E, A>
+where
+    T: SelfAddressed + Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    R: UnversionedRepository<T> + Send + Sync,
+    E: QueryExecutor,
+    A: TimestampAuthority,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.create(item).await?;
+        self.notary.notarize(&item.get_said()).await?;
+        Ok(item)
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.insert(item).await?;
+        self.notary.notarize(&item.get_said()).await?;
+        Ok(item)
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
@@ -0,0 +1,199 @@
+//! Garbage collection of signatures whose records are gone.
+//!
+//! Pruning or deleting records leaves their [`RecordSignature`]s (signatures,
+//! receipts, anything pointing at a SAID) behind. An [`OrphanCollector`] walks
+//! the signature table in SAID order, a batch at a time, looks up which signed
+//! SAIDs still exist in the record table and removes the signatures of those
+//! that don't, or marks them deleted with [`OrphanCollector::archive`]. A
+//! soft-deleted record still exists, so its signatures are kept.
+//!
+//! Signatures can be written just before their record, so ones younger than
+//! the collector's [`grace`](OrphanCollector::grace) period (by their
+//! `#[created_at]` column) are left for a later run.
+
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use crate::{
+    ColumnQuery, Delete, Filter, ManagedField, Order, Query, QueryExecutor, RecordSignature,
+    Storable, StorageDatetime, StorageError,
+};
+
+/// Default number of signatures examined per batch.
+const DEFAULT_BATCH_SIZE: u64 = 500;
+
+/// Default age below which a signature's record may still be on its way.
+const DEFAULT_GRACE: Duration = Duration::from_secs(300);
+
+/// Progress of an orphan collection, reported after every batch.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrphanReport {
+    /// Signatures examined.
+    pub scanned: u64,
+    /// Signatures removed, or archived, because their record is gone.
+    pub orphaned: u64,
+    /// The last signature SAID examined.
+    pub after: Option<String>,
+    pub complete: bool,
+}
+
+type ProgressFn = Box<dyn Fn(&OrphanReport) + Send + Sync>;
+
+/// Settings for collecting orphaned `S` signatures of `T` records.
+pub struct OrphanCollector<T, S> {
+    batch_size: u64,
+    archive: bool,
+    grace: Option<Duration>,
+    item_table: String,
+    on_progress: Option<ProgressFn>,
+    _marker: PhantomData<fn() -> (T, S)>,
+}
+
+impl<T: Storable, S: RecordSignature> Default for OrphanCollector<T, S> {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_BATCH_SIZE,
+            archive: false,
+            grace: None,
+            item_table: T::table_name().to_string(),
+            on_progress: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Storable, S: RecordSignature> OrphanCollector<T, S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Signatures examined per batch (default 500).
+    pub fn batch_size(mut self, batch_size: u64) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Mark orphans with their `#[deleted_at]` column instead of removing
+    /// them; `S` must have one.
+    pub fn archive(mut self) -> Self {
+        self.archive = true;
+        self
+    }
+
+    /// Leave signatures created less than `grace` ago (default five minutes),
+    /// whose record may not be written yet. Setting it requires `S` to have a
+    /// `#[created_at]` column; without one, the default applies no grace.
+    pub fn grace(mut self, grace: Duration) -> Self {
+        self.grace = Some(grace);
+        self
+    }
+
+    /// Look records up in `table` rather than `T::table_name()`, e.g. a shard.
+    pub fn item_table(mut self, table: impl Into<String>) -> Self {
+        self.item_table = table.into();
+        self
+    }
+
+    /// Call `report` after each batch.
+    pub fn on_progress(mut self, report: impl Fn(&OrphanReport) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Box::new(report));
+        self
+    }
+
+    /// Collect orphans until the signature table has been walked.
+    pub async fn run<E: QueryExecutor>(&self, executor: &E) -> Result<OrphanReport, StorageError> {
+        let mut report = OrphanReport::default();
+        while !report.complete {
+            self.next_batch(executor, &mut report).await?;
+            if let Some(on_progress) = &self.on_progress {
+                on_progress(&report);
+            }
+        }
+        Ok(report)
+    }
+
+    async fn next_batch<E: QueryExecutor>(
+        &self,
+        executor: &E,
+        report: &mut OrphanReport,
+    ) -> Result<(), StorageError> {
+        let said_column = S::managed_column(ManagedField::Said);
+        let mut query = Query::<S>::new()
+            .order_by(said_column, Order::Asc)
+            .limit(self.batch_size);
+        if let Some(cutoff) = self.cutoff()? {
+            query = query.lt(S::managed_column(ManagedField::CreatedAt), cutoff);
+        }
+        if let Some(after) = &report.after {
+            query = query.gt(said_column, after.as_str());
+        }
+        let batch = executor.fetch(query).await?;
+
+        let mut signed: Vec<String> = batch.iter().map(|s| s.signed_said().to_string()).collect();
+        signed.sort();
+        signed.dedup();
+        let existing: HashSet<String> = if signed.is_empty() {
+            HashSet::new()
+        } else {
+            let item_said = T::managed_column(ManagedField::Said);
+            executor
+                .fetch_column(
+                    ColumnQuery::new(self.item_table.as_str(), item_said)
+                        .filter(Filter::In(item_said.to_string(), signed.into())),
+                )
+                .await?
+                .into_iter()
+                .collect()
+        };
+        let orphans: Vec<String> = batch
+            .iter()
+            .filter(|s| !existing.contains(s.signed_said()))
+            .map(|s| s.get_said())
+            .collect();
+
+        if !orphans.is_empty() {
+            let delete = Delete::<S>::new().r#in(said_column, orphans.clone());
+            if self.archive {
+                executor.soft_delete(delete).await?;
+            } else {
+                executor.delete(delete).await?;
+            }
+        }
+
+        report.scanned += batch.len() as u64;
+        report.orphaned += orphans.len() as u64;
+        if let Some(last) = batch.last() {
+            report.after = Some(last.get_said());
+        }
+        report.complete = (batch.len() as u64) < self.batch_size;
+        Ok(())
+    }
+
+    /// Signatures created at or after this time are skipped.
+    fn cutoff(&self) -> Result<Option<StorageDatetime>, StorageError> {
+        let has_created_at = S::managed_columns()
+            .iter()
+            .any(|(_, field)| *field == ManagedField::CreatedAt);
+        match (self.grace, has_created_at) {
+            (grace, true) => Ok(Some(
+                StorageDatetime::now() - grace.unwrap_or(DEFAULT_GRACE),
+            )),
+            (Some(_), false) => Err(StorageError::StorageError(format!(
+                "Can't apply an orphan grace period: {} has no #[created_at] column",
+                S::table_name()
+            ))),
+            (None, false) => Ok(None),
+        }
+    }
+}
+
+/// Remove every `S` signature whose `T` record no longer exists.
+pub async fn gc_orphans<T, S, E>(executor: &E) -> Result<OrphanReport, StorageError>
+where
+    T: Storable,
+    S: RecordSignature,
+    E: QueryExecutor,
+{
+    OrphanCollector::<T, S>::new().run(executor).await
+}
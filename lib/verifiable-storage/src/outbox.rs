@@ -0,0 +1,281 @@
+//! Transactional outbox for at-least-once write notifications.
+//!
+//! [`EmittingRepository`](crate::EmittingRepository) notifies after a write, so
+//! a crash in between loses the event. [`OutboxRepository`] instead writes each
+//! item and a self-addressed [`OutboxRecord`] in the same transaction, and an
+//! [`OutboxRelay`] later publishes pending records to an [`EventSink`] and
+//! removes them once delivered. A record is only removed after its sink call
+//! succeeds, so every committed write is delivered at least once.
+//!
+//! ```text
+//! CREATE TABLE outbox (
+//!     said TEXT PRIMARY KEY,
+//!     source_table TEXT NOT NULL,
+//!     operation TEXT NOT NULL,
+//!     record_said TEXT NOT NULL,
+//!     prefix TEXT,
+//!     payload TEXT NOT NULL,
+//!     created_at TIMESTAMPTZ NOT NULL
+//! );
+//! CREATE INDEX outbox_created_at ON outbox (created_at);
+//! ```
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    Delete, EventSink, Order, Query, QueryExecutor, SelfAddressed, Storable, StorageDatetime,
+    StorageError, TransactionExecutor, UnversionedRepository, Versioned, VersionedRepository,
+    WriteEvent, WriteOperation,
+};
+
+/// Default number of records a relay publishes per pass.
+const DEFAULT_BATCH_SIZE: u64 = 100;
+
+/// A pending write notification.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, crate::SelfAddressed)]
+#[storable(table = "outbox")]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxRecord {
+    #[said]
+    pub said: String,
+    pub source_table: String,
+    pub operation: WriteOperation,
+    pub record_said: String,
+    pub prefix: Option<String>,
+    /// The written item as JSON text.
+    pub payload: String,
+    #[created_at]
+    pub created_at: StorageDatetime,
+}
+
+impl OutboxRecord {
+    /// An outbox record for `event`, with its SAID computed.
+    pub fn from_event(event: &WriteEvent) -> Result<Self, StorageError> {
+        let mut record = Self::new(
+            event.table.clone(),
+            event.operation,
+            event.said.clone(),
+            event.prefix.clone(),
+            serde_json::to_string(&event.item)?,
+        );
+        record.derive_said()?;
+        Ok(record)
+    }
+
+    /// The event this record announces.
+    pub fn event(&self) -> Result<WriteEvent, StorageError> {
+        Ok(WriteEvent {
+            table: self.source_table.clone(),
+            operation: self.operation,
+            said: self.record_said.clone(),
+            prefix: self.prefix.clone(),
+            item: serde_json::from_str(&self.payload)?,
+        })
+    }
+}
+
+/// Insert `item` and its outbox record within `tx`.
+pub async fn insert_with_outbox<T, X>(
+    tx: &mut X,
+    operation: WriteOperation,
+    item: &T,
+) -> Result<(), StorageError>
+where
+    T: Storable + SelfAddressed,
+    X: TransactionExecutor,
+{
+    tx.insert(item).await?;
+    let record = OutboxRecord::from_event(&WriteEvent::new(operation, item)?)?;
+    tx.insert(&record).await?;
+    Ok(())
+}
+
+/// Wraps a repository so writes also append to the outbox, atomically.
+///
+/// Writes go straight to `T`'s table through the executor, in a transaction
+/// with the outbox record; reads are delegated to the wrapped repository.
+#[derive(Debug, Clone)]
+pub struct OutboxRepository<R, E> {
+    inner: R,
+    executor: E,
+}
+
+impl<R, E: QueryExecutor> OutboxRepository<R, E> {
+    /// Wrap `inner`, writing through `executor`.
+    pub fn new(inner: R, executor: E) -> Self {
+        Self { inner, executor }
+    }
+
+    async fn write<T: Storable + SelfAddressed>(
+        &self,
+        operation: WriteOperation,
+        item: T,
+    ) -> Result<T, StorageError> {
+        let mut tx = self.executor.begin_transaction().await?;
+        match insert_with_outbox(&mut tx, operation, &item).await {
+            Ok(()) => {
+                tx.commit().await?;
+                Ok(item)
+            }
+            Err(e) => {
+                tx.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<T, R, E> VersionedRepository<T> for OutboxRepository<R, E>
+where
+    T: Storable + SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+    E: QueryExecutor,
+{
+    async fn create(&self, mut item: T) -> Result<T, StorageError> {
+        item.derive_prefix()?;
+        self.write(WriteOperation::Create, item).await
+    }
+
+    async fn update(&self, mut item: T) -> Result<T, StorageError> {
+        item.increment()?;
+        self.write(WriteOperation::Update, item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.write(WriteOperation::Insert, item).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_latest(prefix).await
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        self.inner.get_history(prefix).await
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        self.inner.exists(prefix).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[async_trait]
+impl<T, R, E> UnversionedRepository<T> for OutboxRepository<R, E>
+where
+    T: Storable + SelfAddressed + Serialize + DeserializeOwned + Clone + 'static,
+    R: UnversionedRepository<T> + Send + Sync,
+    E: QueryExecutor,
+{
+    async fn create(&self, mut item: T) -> Result<T, StorageError> {
+        item.derive_said()?;
+        self.write(WriteOperation::Create, item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.write(WriteOperation::Insert, item).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
+
+/// Publishes pending outbox records to a sink, oldest first.
+#[derive(Debug, Clone)]
+pub struct OutboxRelay<E, S> {
+    executor: E,
+    sink: S,
+    batch_size: u64,
+}
+
+impl<E: QueryExecutor, S: EventSink> OutboxRelay<E, S> {
+    pub fn new(executor: E, sink: S) -> Self {
+        Self {
+            executor,
+            sink,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Records to publish per pass (default 100).
+    pub fn batch_size(mut self, batch_size: u64) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Publish up to one batch, returning how many records were delivered.
+    ///
+    /// Stops at the first failed delivery so records stay in order; that
+    /// record and the ones after it are retried on the next pass.
+    pub async fn relay_once(&self) -> Result<usize, StorageError> {
+        let pending = self
+            .executor
+            .fetch(
+                Query::<OutboxRecord>::new()
+                    .order_by("created_at", Order::Asc)
+                    .order_by("said", Order::Asc)
+                    .limit(self.batch_size),
+            )
+            .await?;
+
+        let mut delivered = 0;
+        for record in pending {
+            self.sink.emit(record.event()?).await?;
+            self.executor
+                .delete(Delete::<OutboxRecord>::new().eq("said", record.said.as_str()))
+                .await?;
+            delivered += 1;
+        }
+        Ok(delivered)
+    }
+
+    /// Relay forever, sleeping `idle` whenever the outbox is empty or a pass fails.
+    #[cfg(feature = "events")]
+    pub async fn run(&self, idle: std::time::Duration) {
+        loop {
+            match self.relay_once().await {
+                Ok(delivered) if delivered > 0 => {}
+                _ => tokio::time::sleep(idle).await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "notes")]
+    #[serde(rename_all = "camelCase")]
+    struct Note {
+        #[said]
+        said: String,
+        body: String,
+    }
+
+    #[test]
+    fn records_round_trip_events() {
+        let mut note = Note::new("hello".to_string());
+        note.derive_said().unwrap();
+        let event = WriteEvent::new(WriteOperation::Insert, &note).unwrap();
+
+        let record = OutboxRecord::from_event(&event).unwrap();
+        record.verify_said().unwrap();
+        assert_eq!(record.source_table, "notes");
+        assert_eq!(record.event().unwrap(), event);
+    }
+}
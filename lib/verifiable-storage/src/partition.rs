@@ -0,0 +1,120 @@
+//! Table partitioning metadata.
+//!
+//! Declared with `#[storable(partition_by = "...", interval = "...")]` (range
+//! partitioning on a timestamp) or `#[storable(partition_by = "...", partitions = N)]`
+//! (hash partitioning, e.g. on the prefix). Backends use this to create partitions
+//! and the query builder uses it to add pruning bounds.
+
+use alloc::string::{String, ToString};
+
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, Utc};
+
+/// Width of each partition for range partitioning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionInterval {
+    Day,
+    Month,
+    Year,
+}
+
+impl PartitionInterval {
+    /// Start of the partition that contains `at`.
+    pub fn start_of(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let date = match self {
+            PartitionInterval::Day => NaiveDate::from_ymd_opt(at.year(), at.month(), at.day()),
+            PartitionInterval::Month => NaiveDate::from_ymd_opt(at.year(), at.month(), 1),
+            PartitionInterval::Year => NaiveDate::from_ymd_opt(at.year(), 1, 1),
+        };
+
+        date.and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|d| d.and_utc())
+            .unwrap_or(at)
+    }
+
+    /// Start of the partition after the one starting at `start`.
+    pub fn next(&self, start: DateTime<Utc>) -> DateTime<Utc> {
+        let next = match self {
+            PartitionInterval::Day => start.checked_add_days(Days::new(1)),
+            PartitionInterval::Month => start.checked_add_months(Months::new(1)),
+            PartitionInterval::Year => start.checked_add_months(Months::new(12)),
+        };
+        next.unwrap_or(start)
+    }
+
+    /// Partition name suffix for the partition starting at `start`
+    /// (`2024`, `202401`, or `20240131`).
+    pub fn suffix(&self, start: DateTime<Utc>) -> String {
+        match self {
+            PartitionInterval::Day => start.format("%Y%m%d").to_string(),
+            PartitionInterval::Month => start.format("%Y%m").to_string(),
+            PartitionInterval::Year => start.format("%Y").to_string(),
+        }
+    }
+}
+
+/// How a table is partitioned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partitioning {
+    /// Range partitions over a timestamp column, one per interval.
+    Range {
+        column: &'static str,
+        interval: PartitionInterval,
+    },
+    /// A fixed number of hash partitions over a column.
+    Hash {
+        column: &'static str,
+        partitions: u32,
+    },
+}
+
+impl Partitioning {
+    /// The partition key column.
+    pub fn column(&self) -> &'static str {
+        match self {
+            Partitioning::Range { column, .. } | Partitioning::Hash { column, .. } => column,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, 30, 15).unwrap()
+    }
+
+    #[test]
+    fn month_bounds() {
+        let start = PartitionInterval::Month.start_of(at(2024, 12, 17, 9));
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 12, 1, 0, 0, 0).unwrap());
+        assert_eq!(
+            PartitionInterval::Month.next(start),
+            Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(PartitionInterval::Month.suffix(start), "202412");
+    }
+
+    #[test]
+    fn day_bounds() {
+        let start = PartitionInterval::Day.start_of(at(2024, 2, 29, 23));
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap());
+        assert_eq!(
+            PartitionInterval::Day.next(start),
+            Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(PartitionInterval::Day.suffix(start), "20240229");
+    }
+
+    #[test]
+    fn year_bounds() {
+        let start = PartitionInterval::Year.start_of(at(2024, 7, 4, 12));
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+        assert_eq!(
+            PartitionInterval::Year.next(start),
+            Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(PartitionInterval::Year.suffix(start), "2024");
+    }
+}
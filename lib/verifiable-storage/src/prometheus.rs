@@ -0,0 +1,168 @@
+//! Prometheus exporter for storage metrics.
+//!
+//! [`PrometheusMetrics`] is a [`MetricsHook`] recording repository calls,
+//! backend reconnects and written payload sizes in a Prometheus registry,
+//! labelled by table and operation. Wire it into a [`MeteredRepository`](crate::MeteredRepository)
+//! and serve [`PrometheusMetrics::gather`] from a `/metrics` endpoint:
+//!
+//! ```text
+//! let metrics = Arc::new(PrometheusMetrics::new()?);
+//! let domains = MeteredRepository::new(DomainRepository::new(pool), metrics.clone());
+//! ```
+
+use std::time::Duration;
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+use crate::{MetricsHook, StorageError};
+
+/// Counters and latency histograms for storage operations.
+#[derive(Clone)]
+pub struct PrometheusMetrics {
+    registry: Registry,
+    operations: IntCounterVec,
+    durations: HistogramVec,
+    reconnects: IntCounterVec,
+    payloads: HistogramVec,
+}
+
+impl PrometheusMetrics {
+    /// Metrics in a registry of their own.
+    pub fn new() -> Result<Self, StorageError> {
+        Self::with_registry(Registry::new())
+    }
+
+    /// Metrics registered in `registry`, alongside a service's own.
+    pub fn with_registry(registry: Registry) -> Result<Self, StorageError> {
+        let operations = IntCounterVec::new(
+            Opts::new(
+                "verifiable_storage_operations_total",
+                "Repository calls by table, operation and outcome",
+            ),
+            &["table", "operation", "outcome"],
+        )
+        .map_err(metrics_error)?;
+        let durations = HistogramVec::new(
+            HistogramOpts::new(
+                "verifiable_storage_operation_duration_seconds",
+                "Repository call latency by table and operation",
+            ),
+            &["table", "operation"],
+        )
+        .map_err(metrics_error)?;
+        let reconnects = IntCounterVec::new(
+            Opts::new(
+                "verifiable_storage_reconnects_total",
+                "Backend reconnect attempts by backend and outcome",
+            ),
+            &["backend", "outcome"],
+        )
+        .map_err(metrics_error)?;
+        let payloads = HistogramVec::new(
+            HistogramOpts::new(
+                "verifiable_storage_payload_bytes",
+                "Serialized size of written records by table",
+            )
+            .buckets(prometheus::exponential_buckets(256.0, 4.0, 8).map_err(metrics_error)?),
+            &["table"],
+        )
+        .map_err(metrics_error)?;
+        registry
+            .register(Box::new(operations.clone()))
+            .map_err(metrics_error)?;
+        registry
+            .register(Box::new(durations.clone()))
+            .map_err(metrics_error)?;
+        registry
+            .register(Box::new(reconnects.clone()))
+            .map_err(metrics_error)?;
+        registry
+            .register(Box::new(payloads.clone()))
+            .map_err(metrics_error)?;
+        Ok(Self {
+            registry,
+            operations,
+            durations,
+            reconnects,
+            payloads,
+        })
+    }
+
+    /// The registry the metrics are recorded in.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Everything in the registry, in the Prometheus text exposition format.
+    pub fn gather(&self) -> Result<String, StorageError> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(metrics_error)?;
+        String::from_utf8(buffer).map_err(|e| StorageError::StorageError(e.to_string()))
+    }
+}
+
+fn outcome(success: bool) -> &'static str {
+    if success { "ok" } else { "error" }
+}
+
+fn metrics_error(e: prometheus::Error) -> StorageError {
+    StorageError::StorageError(e.to_string())
+}
+
+impl MetricsHook for PrometheusMetrics {
+    fn on_reconnect(&self, backend: &str, success: bool) {
+        self.reconnects
+            .with_label_values(&[backend, outcome(success)])
+            .inc();
+    }
+
+    fn on_operation(&self, table: &str, operation: &str, elapsed: Duration, success: bool) {
+        self.operations
+            .with_label_values(&[table, operation, outcome(success)])
+            .inc();
+        self.durations
+            .with_label_values(&[table, operation])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    fn on_payload(&self, table: &str, bytes: usize) {
+        self.payloads
+            .with_label_values(&[table])
+            .observe(bytes as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operations_are_labelled_by_table() {
+        let metrics = PrometheusMetrics::new().unwrap();
+        metrics.on_operation("domains", "create", Duration::from_millis(3), true);
+        metrics.on_operation("domains", "create", Duration::from_millis(5), false);
+        metrics.on_reconnect("surrealdb", true);
+        metrics.on_payload("domains", 300);
+
+        let text = metrics.gather().unwrap();
+        assert!(text.contains(
+            r#"verifiable_storage_operations_total{operation="create",outcome="ok",table="domains"} 1"#
+        ));
+        assert!(text.contains(
+            r#"verifiable_storage_operation_duration_seconds_count{operation="create",table="domains"} 2"#
+        ));
+        assert!(text.contains(
+            r#"verifiable_storage_reconnects_total{backend="surrealdb",outcome="ok"} 1"#
+        ));
+        assert!(
+            text.contains(
+                r#"verifiable_storage_payload_bytes_bucket{table="domains",le="1024"} 1"#
+            )
+        );
+        assert!(PrometheusMetrics::with_registry(metrics.registry().clone()).is_err());
+    }
+}
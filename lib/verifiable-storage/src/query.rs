@@ -3,9 +3,10 @@
 //! This module provides a query abstraction that can be translated to
 //! different database backends (PostgreSQL, SurrealDB, etc.).
 
-use crate::{Storable, StorageDatetime, StorageError};
+use crate::{Cursor, ManagedField, Page, Partitioning, Storable, StorageDatetime, StorageError};
 use async_trait::async_trait;
 use serde::de::DeserializeOwned;
+use std::collections::HashMap;
 use std::marker::PhantomData;
 
 /// A value that can be bound to a query parameter.
@@ -18,9 +19,30 @@ pub enum Value {
     Bool(bool),
     Strings(Vec<String>),
     Datetime(StorageDatetime),
+    /// A JSON document, bound as JSONB (Postgres) or an object (SurrealDB).
+    Json(serde_json::Value),
     Null,
 }
 
+impl Value {
+    /// JSON form of the value, as reported for dry-run parameters.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::String(s) => serde_json::Value::String(s.clone()),
+            Value::Int(n) => serde_json::Value::from(*n),
+            Value::UInt(n) => serde_json::Value::from(*n),
+            Value::Float(n) => serde_json::Number::from_f64(*n)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            Value::Strings(v) => serde_json::Value::from(v.clone()),
+            Value::Datetime(dt) => serde_json::Value::String(dt.to_string()),
+            Value::Json(v) => v.clone(),
+            Value::Null => serde_json::Value::Null,
+        }
+    }
+}
+
 impl From<&str> for Value {
     fn from(s: &str) -> Self {
         Value::String(s.to_string())
@@ -99,6 +121,12 @@ impl From<&StorageDatetime> for Value {
     }
 }
 
+impl From<serde_json::Value> for Value {
+    fn from(v: serde_json::Value) -> Self {
+        Value::Json(v)
+    }
+}
+
 /// Filter conditions for queries.
 #[derive(Debug, Clone)]
 pub enum Filter {
@@ -120,6 +148,61 @@ pub enum Filter {
     IsNull(String),
     /// field IS NOT NULL
     IsNotNull(String),
+    /// field IN (SELECT column FROM ...)
+    InSubquery(String, Box<Subquery>),
+    /// Any of the filters holds; an empty list matches nothing
+    Or(Vec<Filter>),
+    /// All of the filters hold, for nesting inside [`Filter::Or`]; an empty
+    /// list matches everything
+    And(Vec<Filter>),
+}
+
+impl Filter {
+    /// `field = value`, or `field IS NULL` when `value` is `None`.
+    pub fn eq_or_null(field: impl Into<String>, value: Option<impl Into<Value>>) -> Self {
+        match value {
+            Some(value) => Filter::Eq(field.into(), value.into()),
+            None => Filter::IsNull(field.into()),
+        }
+    }
+
+    /// `field != value`, or `field IS NOT NULL` when `value` is `None`.
+    pub fn ne_null(field: impl Into<String>, value: Option<impl Into<Value>>) -> Self {
+        match value {
+            Some(value) => Filter::Ne(field.into(), value.into()),
+            None => Filter::IsNotNull(field.into()),
+        }
+    }
+}
+
+/// A single-column SELECT used as the right-hand side of [`Filter::InSubquery`].
+#[derive(Debug, Clone)]
+pub struct Subquery {
+    /// The column the subquery selects.
+    pub column: String,
+    /// The query to select from. Its row type is erased; only the table,
+    /// joins, filters, ordering, and limits are used.
+    pub query: Query<()>,
+}
+
+impl Subquery {
+    /// Select `column` from the rows matched by `query`.
+    pub fn new<U>(column: impl Into<String>, query: Query<U>) -> Self {
+        Self {
+            column: column.into(),
+            query: Query {
+                table: query.table,
+                joins: query.joins,
+                filters: query.filters,
+                order_by: query.order_by,
+                limit: query.limit,
+                offset: query.offset,
+                distinct_on: query.distinct_on,
+                latest_per: query.latest_per,
+                _marker: PhantomData,
+            },
+        }
+    }
 }
 
 /// Sort order.
@@ -158,6 +241,8 @@ pub struct Query<T> {
     /// DISTINCT ON fields (PostgreSQL) / GROUP BY fields (SurrealDB).
     /// Returns one row per unique combination of these fields.
     pub distinct_on: Vec<String>,
+    /// Partition and order fields for [`Query::latest_per`].
+    pub latest_per: Option<(String, String)>,
     pub(crate) _marker: PhantomData<T>,
 }
 
@@ -167,11 +252,12 @@ impl<T: Storable> Query<T> {
         Self {
             table: T::table_name().to_string(),
             joins: Vec::new(),
-            filters: Vec::new(),
+            filters: live_filters::<T>(),
             order_by: Vec::new(),
             limit: None,
             offset: None,
             distinct_on: Vec::new(),
+            latest_per: None,
             _marker: PhantomData,
         }
     }
@@ -181,15 +267,25 @@ impl<T: Storable> Query<T> {
         Self {
             table: table.into(),
             joins: Vec::new(),
-            filters: Vec::new(),
+            filters: live_filters::<T>(),
             order_by: Vec::new(),
             limit: None,
             offset: None,
             distinct_on: Vec::new(),
+            latest_per: None,
             _marker: PhantomData,
         }
     }
 
+    /// Also match soft-deleted rows, which queries otherwise leave out.
+    pub fn include_deleted(mut self) -> Self {
+        if let Some(column) = T::deleted_at_column() {
+            self.filters
+                .retain(|filter| !matches!(filter, Filter::IsNull(field) if field == column));
+        }
+        self
+    }
+
     /// Add a JOIN clause.
     ///
     /// Joins `join_table` where `left_field` (on main table) equals `right_field` (on join table).
@@ -218,11 +314,35 @@ impl<T: Storable> Query<T> {
         self.filter(Filter::Eq(field.into(), value.into()))
     }
 
+    /// Match `value`, or a NULL column when it is `None` (see [`Filter::eq_or_null`]).
+    pub fn eq_or_null(self, field: impl Into<String>, value: Option<impl Into<Value>>) -> Self {
+        self.filter(Filter::eq_or_null(field, value))
+    }
+
+    /// Exclude `value`, or NULL columns when it is `None` (see [`Filter::ne_null`]).
+    pub fn ne_null(self, field: impl Into<String>, value: Option<impl Into<Value>>) -> Self {
+        self.filter(Filter::ne_null(field, value))
+    }
+
     /// Add an IN filter (shorthand for Filter::In).
     pub fn r#in(self, field: impl Into<String>, values: impl Into<Value>) -> Self {
         self.filter(Filter::In(field.into(), values.into()))
     }
 
+    /// Add a subquery filter: `field` must appear in `column` of the rows
+    /// matched by `subquery`.
+    pub fn in_subquery<U>(
+        self,
+        field: impl Into<String>,
+        column: impl Into<String>,
+        subquery: Query<U>,
+    ) -> Self {
+        self.filter(Filter::InSubquery(
+            field.into(),
+            Box::new(Subquery::new(column, subquery)),
+        ))
+    }
+
     /// Add a greater-than filter.
     pub fn gt(self, field: impl Into<String>, value: impl Into<Value>) -> Self {
         self.filter(Filter::Gt(field.into(), value.into()))
@@ -269,6 +389,95 @@ impl<T: Storable> Query<T> {
         self.distinct_on.push(field.into());
         self
     }
+
+    /// Return only the row with the highest `order` value for each `partition` value.
+    ///
+    /// An alternative to `distinct_on` that PostgreSQL evaluates with
+    /// `ROW_NUMBER() OVER (PARTITION BY partition ORDER BY order DESC)`, which
+    /// performs well with a covering `(partition, order)` index. SurrealDB uses
+    /// a correlated subquery. Takes precedence over `distinct_on`.
+    pub fn latest_per(mut self, partition: impl Into<String>, order: impl Into<String>) -> Self {
+        self.latest_per = Some((partition.into(), order.into()));
+        self
+    }
+
+    /// Add an equality filter per entry of `map`, e.g. from an HTTP query string.
+    ///
+    /// Keys must be columns of `T`; anything else is rejected, so callers never
+    /// splice untrusted names into SQL. A `Value::Null` entry matches NULL.
+    /// Filters are added in key order.
+    pub fn filters_from(self, map: &HashMap<String, Value>) -> Result<Self, StorageError> {
+        let mut entries: Vec<_> = map.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        let mut query = self;
+        for (field, value) in entries {
+            if !T::columns().contains(&field.as_str()) {
+                return Err(StorageError::StorageError(format!(
+                    "Unknown column for {}: {}",
+                    T::table_name(),
+                    field
+                )));
+            }
+            query = match value {
+                Value::Null => query.filter(Filter::IsNull(field.clone())),
+                value => query.eq(field.clone(), value.clone()),
+            };
+        }
+        Ok(query)
+    }
+
+    /// Select each prefix as it stood at `at`: the highest version created no
+    /// later than `at`. Prefixes created after `at` are left out.
+    ///
+    /// The version is chosen first, so the query's other filters apply to it
+    /// rather than to earlier versions. Requires a versioned type with a
+    /// `#[created_at]` column. A version soft-deleted by `at` is left out,
+    /// one deleted later is not.
+    pub fn as_of(self, at: impl Into<Value>) -> Result<Self, StorageError> {
+        let at = at.into();
+        let column = |field: ManagedField| {
+            T::managed_columns()
+                .iter()
+                .find(|(_, managed)| *managed == field)
+                .map(|(column, _)| *column)
+                .ok_or_else(|| {
+                    StorageError::StorageError(format!(
+                        "{} has no {:?} column to query as of a time",
+                        T::table_name(),
+                        field
+                    ))
+                })
+        };
+        let (said, prefix, version, created_at) = (
+            column(ManagedField::Said)?,
+            column(ManagedField::Prefix)?,
+            column(ManagedField::Version)?,
+            column(ManagedField::CreatedAt)?,
+        );
+        let versions = Query::<T>::for_table(self.table.clone())
+            .include_deleted()
+            .lte(created_at, at.clone())
+            .latest_per(prefix, version);
+        let query = match T::deleted_at_column() {
+            Some(deleted_at) => self.include_deleted().filter(Filter::Or(vec![
+                Filter::IsNull(deleted_at.to_string()),
+                Filter::Gt(deleted_at.to_string(), at),
+            ])),
+            None => self,
+        };
+        Ok(query.in_subquery(said, said, versions))
+    }
+
+    /// Bound the partition column to `[from, to)` on a range-partitioned table.
+    ///
+    /// This lets the database prune partitions outside the range. It has no
+    /// effect for unpartitioned or hash-partitioned types.
+    pub fn within_partitions(self, from: impl Into<Value>, to: impl Into<Value>) -> Self {
+        match T::partitioning() {
+            Some(Partitioning::Range { column, .. }) => self.gte(column, from).lt(column, to),
+            _ => self,
+        }
+    }
 }
 
 impl<T: Storable> Default for Query<T> {
@@ -341,6 +550,14 @@ impl ColumnQuery {
     }
 }
 
+/// The filter leaving out soft-deleted rows, for types that have them.
+fn live_filters<T: Storable>() -> Vec<Filter> {
+    T::deleted_at_column()
+        .map(|column| Filter::IsNull(column.to_string()))
+        .into_iter()
+        .collect()
+}
+
 /// A DELETE query builder.
 #[derive(Debug, Clone)]
 pub struct Delete<T> {
@@ -348,6 +565,8 @@ pub struct Delete<T> {
     pub table: String,
     /// Filter conditions.
     pub filters: Vec<Filter>,
+    /// Permit deleting from a versioned table (see [`Delete::allow_versioned_delete`]).
+    pub allow_versioned: bool,
     pub(crate) _marker: PhantomData<T>,
 }
 
@@ -357,6 +576,7 @@ impl<T: Storable> Delete<T> {
         Self {
             table: T::table_name().to_string(),
             filters: Vec::new(),
+            allow_versioned: false,
             _marker: PhantomData,
         }
     }
@@ -366,10 +586,34 @@ impl<T: Storable> Delete<T> {
         Self {
             table: table.into(),
             filters: Vec::new(),
+            allow_versioned: false,
             _marker: PhantomData,
         }
     }
 
+    /// Allow this delete to remove rows of a versioned type.
+    ///
+    /// Executors with their delete guard on refuse such deletes otherwise,
+    /// since removing a version breaks verification of every later one in
+    /// its chain.
+    pub fn allow_versioned_delete(mut self) -> Self {
+        self.allow_versioned = true;
+        self
+    }
+
+    /// Fail with [`StorageError::VersionedDelete`] if this deletes from a versioned
+    /// table without [`Delete::allow_versioned_delete`]. Executors with their
+    /// delete guard on call this first.
+    pub fn check_allowed(&self) -> Result<(), StorageError> {
+        if T::is_versioned() && !self.allow_versioned {
+            return Err(StorageError::VersionedDelete(format!(
+                "Refusing to delete from versioned table {} without allow_versioned_delete()",
+                self.table
+            )));
+        }
+        Ok(())
+    }
+
     /// Add a filter condition.
     pub fn filter(mut self, filter: Filter) -> Self {
         self.filters.push(filter);
@@ -381,11 +625,26 @@ impl<T: Storable> Delete<T> {
         self.filter(Filter::Eq(field.into(), value.into()))
     }
 
+    /// Match `value`, or a NULL column when it is `None` (see [`Filter::eq_or_null`]).
+    pub fn eq_or_null(self, field: impl Into<String>, value: Option<impl Into<Value>>) -> Self {
+        self.filter(Filter::eq_or_null(field, value))
+    }
+
+    /// Exclude `value`, or NULL columns when it is `None` (see [`Filter::ne_null`]).
+    pub fn ne_null(self, field: impl Into<String>, value: Option<impl Into<Value>>) -> Self {
+        self.filter(Filter::ne_null(field, value))
+    }
+
     /// Add a greater-than-or-equal filter.
     pub fn gte(self, field: impl Into<String>, value: impl Into<Value>) -> Self {
         self.filter(Filter::Gte(field.into(), value.into()))
     }
 
+    /// Add a less-than filter.
+    pub fn lt(self, field: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.filter(Filter::Lt(field.into(), value.into()))
+    }
+
     /// Add an IN filter.
     pub fn r#in(self, field: impl Into<String>, values: impl Into<Value>) -> Self {
         self.filter(Filter::In(field.into(), values.into()))
@@ -398,6 +657,77 @@ impl<T: Storable> Default for Delete<T> {
     }
 }
 
+/// An aggregate over one column, computed by [`QueryExecutor::aggregate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Min,
+    Max,
+    Sum,
+}
+
+impl Aggregate {
+    /// Fold partial results (one per shard, say) into one. Nulls are
+    /// skipped; sums stay integers unless a part isn't one.
+    pub fn combine(
+        self,
+        parts: impl IntoIterator<Item = serde_json::Value>,
+    ) -> Result<Option<serde_json::Value>, StorageError> {
+        let mut result: Option<serde_json::Value> = None;
+        for part in parts.into_iter().filter(|part| !part.is_null()) {
+            result = Some(match result {
+                None => part,
+                Some(current) => self.fold(current, part)?,
+            });
+        }
+        Ok(result)
+    }
+
+    fn fold(
+        self,
+        current: serde_json::Value,
+        part: serde_json::Value,
+    ) -> Result<serde_json::Value, StorageError> {
+        use serde_json::Value as Json;
+        let incomparable = || {
+            StorageError::StorageError(format!(
+                "Cannot combine {:?} results {} and {}",
+                self, current, part
+            ))
+        };
+        let ordering = match (&current, &part) {
+            (Json::Number(a), Json::Number(b)) => match (a.as_i64(), b.as_i64()) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => a
+                    .as_f64()
+                    .partial_cmp(&b.as_f64())
+                    .ok_or_else(incomparable)?,
+            },
+            (Json::String(a), Json::String(b)) if self != Aggregate::Sum => a.cmp(b),
+            _ => return Err(incomparable()),
+        };
+        Ok(match self {
+            Aggregate::Min if ordering.is_gt() => part,
+            Aggregate::Max if ordering.is_lt() => part,
+            Aggregate::Min | Aggregate::Max => current,
+            Aggregate::Sum => match (current.as_i64(), part.as_i64()) {
+                (Some(a), Some(b)) => a.checked_add(b).ok_or_else(incomparable)?.into(),
+                _ => serde_json::Number::from_f64(
+                    current.as_f64().unwrap_or_default() + part.as_f64().unwrap_or_default(),
+                )
+                .map_or(Json::Null, Json::Number),
+            },
+        })
+    }
+}
+
+/// A prefix and the highest version among its matching rows, from
+/// [`QueryExecutor::list_prefix_versions`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PrefixVersion {
+    pub prefix: String,
+    pub version: u64,
+}
+
 /// Trait for executing queries against a database backend.
 ///
 /// Implemented by database-specific pool types (e.g., PgPool, Surreal<Client>).
@@ -421,15 +751,143 @@ pub trait QueryExecutor: Send + Sync {
     /// Check if any rows match the query (SELECT EXISTS).
     async fn exists<T: Storable + Send>(&self, query: Query<T>) -> Result<bool, StorageError>;
 
+    /// Count the rows matching the query, ignoring its limit and offset.
+    async fn count<T: Storable + Send>(&self, query: Query<T>) -> Result<u64, StorageError>;
+
+    /// Execute a SELECT query, also returning how many rows match it before
+    /// its limit and offset, for paginated responses.
+    ///
+    /// Runs a separate count unless the backend can do both in one statement.
+    async fn fetch_with_count<T: Storable + DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+    ) -> Result<(Vec<T>, u64), StorageError> {
+        let total = self.count(query.clone()).await?;
+        Ok((self.fetch(query).await?, total))
+    }
+
+    /// Fetch up to `limit` rows of `query` after `cursor`, keyset-paginated by
+    /// `columns`. See [`fetch_page`](crate::fetch_page).
+    async fn fetch_page<T: Storable + DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+        columns: &[&str],
+        cursor: Option<&Cursor>,
+        limit: u64,
+    ) -> Result<Page<T>, StorageError>
+    where
+        Self: Sized,
+    {
+        crate::fetch_page(self, query, columns, cursor, limit).await
+    }
+
+    /// Compute `aggregate` over `column` for the rows the query matches,
+    /// ignoring its order, limit and offset. `None` when no row has a value.
+    async fn aggregate<T: Storable + Send>(
+        &self,
+        query: Query<T>,
+        aggregate: Aggregate,
+        column: &str,
+    ) -> Result<Option<serde_json::Value>, StorageError>;
+
+    /// The smallest `column` value among the matching rows.
+    async fn min<T: Storable + Send, V: DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+        column: &str,
+    ) -> Result<Option<V>, StorageError> {
+        decode_aggregate(self.aggregate(query, Aggregate::Min, column).await?)
+    }
+
+    /// The largest `column` value among the matching rows, such as a
+    /// prefix's latest version.
+    async fn max<T: Storable + Send, V: DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+        column: &str,
+    ) -> Result<Option<V>, StorageError> {
+        decode_aggregate(self.aggregate(query, Aggregate::Max, column).await?)
+    }
+
+    /// The total of `column` over the matching rows.
+    async fn sum<T: Storable + Send, V: DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+        column: &str,
+    ) -> Result<Option<V>, StorageError> {
+        decode_aggregate(self.aggregate(query, Aggregate::Sum, column).await?)
+    }
+
+    /// The distinct prefixes among the rows the query matches, in ascending
+    /// order and paged by the query's limit and offset, without fetching the
+    /// rows themselves.
+    async fn list_prefixes<T: Storable + Send>(
+        &self,
+        query: Query<T>,
+    ) -> Result<Vec<String>, StorageError> {
+        let listed = self.list_prefix_versions(query).await?;
+        Ok(listed.into_iter().map(|listed| listed.prefix).collect())
+    }
+
+    /// [`list_prefixes`](Self::list_prefixes), with each prefix's highest
+    /// version among the matching rows.
+    async fn list_prefix_versions<T: Storable + Send>(
+        &self,
+        query: Query<T>,
+    ) -> Result<Vec<PrefixVersion>, StorageError>;
+
+    /// Up to `n` of the rows the query matches, chosen at random, for spot
+    /// checks that can't afford to verify a whole table. The query's order,
+    /// limit and offset are ignored.
+    async fn sample<T: Storable + DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+        n: u64,
+    ) -> Result<Vec<T>, StorageError>;
+
     /// Execute a DELETE query and return the number of rows affected.
     async fn delete<T: Storable + Send>(&self, delete: Delete<T>) -> Result<u64, StorageError>;
 
+    /// Stamp the `#[deleted_at]` column of the live rows `delete` matches,
+    /// keeping the rows for audit, and return how many were marked.
+    ///
+    /// Unlike [`QueryExecutor::delete`], this is allowed on versioned tables.
+    /// Executors that can't soft-delete (the default) return an error.
+    async fn soft_delete<T: Storable + Send>(
+        &self,
+        delete: Delete<T>,
+    ) -> Result<u64, StorageError> {
+        Err(StorageError::StorageError(format!(
+            "Can't soft-delete from {}: this executor doesn't support soft deletion",
+            delete.table
+        )))
+    }
+
     /// Insert an item into the database.
     async fn insert<T: Storable + serde::Serialize + Send + Sync>(
         &self,
         item: &T,
     ) -> Result<u64, StorageError>;
 
+    /// Insert an item unless a row with the same key already exists.
+    ///
+    /// Returns 1 if the item was written and 0 if it was already present.
+    async fn insert_or_ignore<T: Storable + serde::Serialize + Send + Sync>(
+        &self,
+        item: &T,
+    ) -> Result<u64, StorageError>;
+
+    /// Insert an item, rewriting the stored row with the same SAID.
+    ///
+    /// Useful for repairing how a row is stored (e.g. after a column type
+    /// change), since its content can't differ without its SAID changing.
+    /// Returns 1 if a row was written and 0 if the stored one already matched;
+    /// backends that can't tell always return 1.
+    async fn upsert<T: Storable + serde::Serialize + Send + Sync>(
+        &self,
+        item: &T,
+    ) -> Result<u64, StorageError>;
+
     /// Begin a transaction. The returned executor can be used for queries within the transaction.
     async fn begin_transaction(&self) -> Result<Self::Transaction, StorageError>;
 
@@ -439,6 +897,15 @@ pub trait QueryExecutor: Send + Sync {
     async fn fetch_column(&self, query: ColumnQuery) -> Result<Vec<String>, StorageError>;
 }
 
+fn decode_aggregate<V: DeserializeOwned>(
+    value: Option<serde_json::Value>,
+) -> Result<Option<V>, StorageError> {
+    value
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|e| StorageError::StorageError(e.to_string()))
+}
+
 /// Trait for executing queries within a transaction.
 #[async_trait]
 pub trait TransactionExecutor: Send + Sync {
@@ -543,4 +1010,168 @@ mod tests {
             Filter::Eq(field, Value::String(val)) if field == "status" && val == "active"
         ));
     }
+
+    #[test]
+    fn json_values_pass_through() {
+        let doc = serde_json::json!({"tags": ["a", "b"], "depth": 2});
+        let value = Value::from(doc.clone());
+        assert!(matches!(&value, Value::Json(v) if *v == doc));
+        assert_eq!(value.to_json(), doc);
+    }
+
+    #[test]
+    fn optional_filters_expand_to_null_checks() {
+        assert!(matches!(
+            Filter::eq_or_null("holder", Some("Ealice")),
+            Filter::Eq(field, Value::String(v)) if field == "holder" && v == "Ealice"
+        ));
+        assert!(matches!(
+            Filter::eq_or_null("holder", None::<&str>),
+            Filter::IsNull(field) if field == "holder"
+        ));
+        assert!(matches!(
+            Filter::ne_null("holder", Some("Ealice")),
+            Filter::Ne(field, _) if field == "holder"
+        ));
+        assert!(matches!(
+            Filter::ne_null("holder", None::<&str>),
+            Filter::IsNotNull(field) if field == "holder"
+        ));
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "records")]
+    struct Record {
+        #[said]
+        said: String,
+        kind: String,
+        owner: Option<String>,
+    }
+
+    #[test]
+    fn filters_from_validates_columns() {
+        let mut map = HashMap::new();
+        map.insert("kind".to_string(), Value::from("zone"));
+        map.insert("owner".to_string(), Value::Null);
+        let query = Query::<Record>::new().filters_from(&map).unwrap();
+        assert!(matches!(&query.filters[0], Filter::Eq(field, _) if field == "kind"));
+        assert!(matches!(&query.filters[1], Filter::IsNull(field) if field == "owner"));
+
+        map.insert("1=1; DROP TABLE records; --".to_string(), Value::from("x"));
+        assert!(Query::<Record>::new().filters_from(&map).is_err());
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "ledger")]
+    struct Entry {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+    }
+
+    #[test]
+    fn versioned_deletes_need_explicit_permission() {
+        Delete::<Record>::new().check_allowed().unwrap();
+        assert!(matches!(
+            Delete::<Entry>::new().eq("prefix", "E1").check_allowed(),
+            Err(StorageError::VersionedDelete(_))
+        ));
+        Delete::<Entry>::new()
+            .allow_versioned_delete()
+            .check_allowed()
+            .unwrap();
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "accounts")]
+    struct Account {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        #[created_at]
+        created_at: StorageDatetime,
+        status: String,
+    }
+
+    #[test]
+    fn as_of_picks_each_prefix_version_before_filtering() {
+        let at = StorageDatetime::now();
+        let (sql, params) = Query::<Account>::new()
+            .eq("status", "frozen")
+            .as_of(at.clone())
+            .unwrap()
+            .to_sql(crate::Dialect::Postgres);
+        assert_eq!(
+            sql,
+            "SELECT * FROM accounts WHERE status = $1 AND said IN (SELECT said FROM (SELECT said, \
+             ROW_NUMBER() OVER (PARTITION BY prefix ORDER BY version DESC) AS _row_number \
+             FROM accounts WHERE created_at <= $2) AS accounts WHERE _row_number = 1)"
+        );
+        assert!(matches!(&params[1].1, Value::Datetime(bound) if *bound == at));
+
+        assert!(Query::<Entry>::new().as_of(at).is_err());
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "profiles")]
+    struct Profile {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        #[created_at]
+        created_at: StorageDatetime,
+        #[deleted_at]
+        deleted_at: Option<StorageDatetime>,
+    }
+
+    #[test]
+    fn as_of_keeps_versions_deleted_after_the_time() {
+        let (sql, _) = Query::<Profile>::new()
+            .as_of(StorageDatetime::now())
+            .unwrap()
+            .to_sql(crate::Dialect::Postgres);
+        assert_eq!(
+            sql,
+            "SELECT * FROM profiles WHERE (deleted_at IS NULL OR deleted_at > $1) AND said IN \
+             (SELECT said FROM (SELECT said, ROW_NUMBER() OVER (PARTITION BY prefix ORDER BY \
+             version DESC) AS _row_number FROM profiles WHERE created_at <= $2) AS profiles \
+             WHERE _row_number = 1)"
+        );
+    }
+
+    #[test]
+    fn aggregates_combine_across_parts() {
+        use serde_json::json;
+        let parts = || vec![json!(4), serde_json::Value::Null, json!(9), json!(2)];
+        assert_eq!(Aggregate::Max.combine(parts()).unwrap(), Some(json!(9)));
+        assert_eq!(Aggregate::Min.combine(parts()).unwrap(), Some(json!(2)));
+        assert_eq!(Aggregate::Sum.combine(parts()).unwrap(), Some(json!(15)));
+        assert_eq!(
+            Aggregate::Sum.combine([json!(1), json!(0.5)]).unwrap(),
+            Some(json!(1.5))
+        );
+        assert_eq!(
+            Aggregate::Max
+                .combine([json!("2024-01-02T00:00:00Z"), json!("2024-03-01T00:00:00Z")])
+                .unwrap(),
+            Some(json!("2024-03-01T00:00:00Z"))
+        );
+        assert_eq!(Aggregate::Min.combine([]).unwrap(), None);
+        assert!(Aggregate::Sum.combine([json!("a"), json!("b")]).is_err());
+    }
 }
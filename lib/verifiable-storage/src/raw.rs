@@ -0,0 +1,119 @@
+//! Consistency between raw records and their columns.
+//!
+//! A `#[storable(raw_column = "...")]` row holds the JSON its SAID was
+//! computed over alongside the columns that JSON was split into. Reads use the
+//! raw record, so a type-mapping bug in the columns (timestamps losing
+//! precision, floats reformatted) goes unnoticed until something reads them
+//! directly. [`raw_drift`] compares a record rebuilt from its columns against
+//! its raw record and reports any [`RawDrift`]; backends scan whole tables with
+//! it (`check_raw_records` in `verifiable-storage-postgres`).
+
+use serde::{Deserialize, Serialize};
+
+use crate::{FieldChange, SelfAddressed, Storable, StorageError};
+
+/// How a record rebuilt from its columns differs from its raw record.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawDrift {
+    pub said: String,
+    /// Fields whose values differ, `from` the raw record `to` the columns.
+    /// Empty when only the formatting of the serialization differs.
+    pub changes: Vec<FieldChange>,
+    /// Whether the record rebuilt from columns still verifies against its SAID.
+    pub said_verifies: bool,
+}
+
+/// Records checked by a table scan, and those whose columns drifted.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawCheckReport {
+    /// Rows with a raw record that were compared.
+    pub checked: usize,
+    pub drifted: Vec<RawDrift>,
+}
+
+impl RawCheckReport {
+    pub fn is_consistent(&self) -> bool {
+        self.drifted.is_empty()
+    }
+}
+
+/// Compare `rebuilt`, read from a row's columns, with the row's `raw` record.
+/// `None` when it serializes to exactly the raw bytes.
+pub fn raw_drift<T: Storable + SelfAddressed>(
+    raw: &str,
+    rebuilt: &T,
+) -> Result<Option<RawDrift>, StorageError> {
+    let serialized = serde_json::to_string(rebuilt)?;
+    if serialized == raw {
+        return Ok(None);
+    }
+
+    let kept: serde_json::Map<String, serde_json::Value> = serde_json::from_str(raw)?;
+    let columns: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&serialized)?;
+    let mut changes: Vec<FieldChange> = kept
+        .iter()
+        .filter(|(key, value)| columns.get(*key) != Some(*value))
+        .map(|(key, value)| FieldChange {
+            field: key.clone(),
+            from: Some(value.clone()),
+            to: columns.get(key).cloned(),
+        })
+        .collect();
+    changes.extend(
+        columns
+            .iter()
+            .filter(|(key, _)| !kept.contains_key(*key))
+            .map(|(key, value)| FieldChange {
+                field: key.clone(),
+                from: None,
+                to: Some(value.clone()),
+            }),
+    );
+
+    Ok(Some(RawDrift {
+        said: rebuilt.get_said(),
+        changes,
+        said_verifies: rebuilt.verify_said().is_ok(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StorageDatetime;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "readings", raw_column = "raw")]
+    #[serde(rename_all = "camelCase")]
+    struct Reading {
+        #[said]
+        said: String,
+        #[created_at]
+        created_at: StorageDatetime,
+        value: f64,
+    }
+
+    #[test]
+    fn drift_names_the_fields_columns_changed() {
+        let reading = Reading::create(1.5).unwrap();
+        let raw = serde_json::to_string(&reading).unwrap();
+        assert_eq!(raw_drift(&raw, &reading).unwrap(), None);
+
+        let mut truncated = reading.clone();
+        truncated.created_at =
+            serde_json::from_value(serde_json::json!("2024-01-01T00:00:00Z")).unwrap();
+        let drift = raw_drift(&raw, &truncated).unwrap().unwrap();
+        assert_eq!(drift.said, reading.said);
+        assert_eq!(drift.changes.len(), 1);
+        assert_eq!(drift.changes[0].field, "createdAt");
+        assert!(!drift.said_verifies);
+
+        // The same values, formatted differently, still count as drift
+        let reformatted = raw.replace("\"value\":1.5", "\"value\":1.50");
+        let drift = raw_drift(&reformatted, &reading).unwrap().unwrap();
+        assert!(drift.changes.is_empty());
+        assert!(drift.said_verifies);
+    }
+}
@@ -0,0 +1,93 @@
+//! Read-only repository wrapper.
+//!
+//! `ReadOnlyRepository<R>` exposes the read methods of a repository and rejects
+//! every write with [`StorageError::ReadOnly`] without reaching the database.
+//! The wrapped repository can't be borrowed back out, so code holding a
+//! `ReadOnlyRepository` has no path to a write.
+
+use async_trait::async_trait;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{SelfAddressed, StorageError, UnversionedRepository, Versioned, VersionedRepository};
+
+/// Wraps a repository so that writes fail with [`StorageError::ReadOnly`].
+#[derive(Debug, Clone)]
+pub struct ReadOnlyRepository<R> {
+    inner: R,
+}
+
+impl<R> ReadOnlyRepository<R> {
+    /// Wrap `inner`, disabling its write methods.
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+}
+
+fn rejected(operation: &str) -> StorageError {
+    StorageError::ReadOnly(format!(
+        "{} is not allowed on a read-only repository",
+        operation
+    ))
+}
+
+#[async_trait]
+impl<T, R> VersionedRepository<T> for ReadOnlyRepository<R>
+where
+    T: SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+{
+    async fn create(&self, _item: T) -> Result<T, StorageError> {
+        Err(rejected("create"))
+    }
+
+    async fn update(&self, _item: T) -> Result<T, StorageError> {
+        Err(rejected("update"))
+    }
+
+    async fn insert(&self, _item: T) -> Result<T, StorageError> {
+        Err(rejected("insert"))
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_latest(prefix).await
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        self.inner.get_history(prefix).await
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        self.inner.exists(prefix).await
+    }
+
+    async fn delete_soft(&self, _said: &str) -> Result<bool, StorageError> {
+        Err(rejected("delete_soft"))
+    }
+}
+
+#[async_trait]
+impl<T, R> UnversionedRepository<T> for ReadOnlyRepository<R>
+where
+    T: SelfAddressed + Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    R: UnversionedRepository<T> + Send + Sync,
+{
+    async fn create(&self, _item: T) -> Result<T, StorageError> {
+        Err(rejected("create"))
+    }
+
+    async fn insert(&self, _item: T) -> Result<T, StorageError> {
+        Err(rejected("insert"))
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn delete_soft(&self, _said: &str) -> Result<bool, StorageError> {
+        Err(rejected("delete_soft"))
+    }
+}
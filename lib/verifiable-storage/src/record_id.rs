@@ -0,0 +1,105 @@
+//! Generated ids for auxiliary records that aren't self-addressed.
+//!
+//! Signatures, audit entries and similar side tables need a key of their own
+//! but have no meaningful content hash. Implement [`RecordId`] for them and
+//! key them with an [`IdGenerator`]: [`UuidV7`] (feature `uuid`) or [`Ulid`]
+//! (feature `ulid`), both time-ordered so ids sort roughly by creation.
+
+use crate::StorageError;
+
+/// A record keyed by a generated id rather than a SAID.
+pub trait RecordId {
+    /// The record's id; empty until one is assigned.
+    fn record_id(&self) -> &str;
+
+    fn set_record_id(&mut self, id: String);
+
+    /// Give the record an id from `generator` unless it already has one.
+    fn assign_id(&mut self, generator: &dyn IdGenerator) -> Result<(), StorageError> {
+        if self.record_id().is_empty() {
+            self.set_record_id(generator.generate()?);
+        }
+        Ok(())
+    }
+}
+
+/// A source of fresh record ids.
+pub trait IdGenerator: Send + Sync {
+    fn generate(&self) -> Result<String, StorageError>;
+}
+
+/// Generates UUIDv7 ids, e.g. `01890a5d-ac96-774b-bcce-b302099a8057`.
+#[cfg(feature = "uuid")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidV7;
+
+#[cfg(feature = "uuid")]
+impl IdGenerator for UuidV7 {
+    fn generate(&self) -> Result<String, StorageError> {
+        Ok(uuid::Uuid::now_v7().to_string())
+    }
+}
+
+/// Generates ULIDs, e.g. `01ARZ3NDEKTSV4RRFFQ69G5FAV`.
+#[cfg(feature = "ulid")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ulid;
+
+#[cfg(feature = "ulid")]
+impl IdGenerator for Ulid {
+    fn generate(&self) -> Result<String, StorageError> {
+        Ok(ulid::Ulid::new().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct AuditEntry {
+        id: String,
+    }
+
+    impl RecordId for AuditEntry {
+        fn record_id(&self) -> &str {
+            &self.id
+        }
+
+        fn set_record_id(&mut self, id: String) {
+            self.id = id;
+        }
+    }
+
+    struct Fixed;
+
+    impl IdGenerator for Fixed {
+        fn generate(&self) -> Result<String, StorageError> {
+            Ok("fixed".to_string())
+        }
+    }
+
+    #[test]
+    fn ids_are_assigned_once() {
+        let mut entry = AuditEntry { id: String::new() };
+        entry.assign_id(&Fixed).unwrap();
+        assert_eq!(entry.record_id(), "fixed");
+
+        let mut kept = AuditEntry {
+            id: "existing".to_string(),
+        };
+        kept.assign_id(&Fixed).unwrap();
+        assert_eq!(kept.record_id(), "existing");
+    }
+
+    #[cfg(all(feature = "uuid", feature = "ulid"))]
+    #[test]
+    fn generated_ids_are_time_ordered() {
+        let (a, b) = (UuidV7.generate().unwrap(), UuidV7.generate().unwrap());
+        assert_eq!(a.len(), 36);
+        assert!(a < b);
+        let (a, b) = (Ulid.generate().unwrap(), Ulid.generate().unwrap());
+        assert_eq!(a.len(), 26);
+        // Only the leading timestamp orders ULIDs made in the same millisecond
+        assert!(a[..10] <= b[..10]);
+    }
+}
@@ -4,10 +4,16 @@
 //! - `UnversionedRepository<T>`: For simple types with SAID-only lookup
 //! - `RepositoryConnection`: Database connection and initialization
 
+use std::collections::BTreeMap;
+
 use async_trait::async_trait;
 use serde::{Serialize, de::DeserializeOwned};
 
-use crate::{SelfAddressed, StorageError, Versioned};
+use crate::{
+    FieldDiff, SelfAddressed, Storable, StorageDatetime, StorageError, Versioned, diff_items,
+    in_force_at, is_expired,
+    sync::{Divergence, verify_continuation},
+};
 
 /// Connection configuration for database backends.
 ///
@@ -16,10 +22,38 @@ use crate::{SelfAddressed, StorageError, Versioned};
 pub enum ConnectionConfig {
     /// Connect using a database URL string.
     Url(String),
+    /// One config per named backend, for repositories spanning several databases.
+    Backends(BTreeMap<String, ConnectionConfig>),
     // Future: Credentials { host, port, user, pass, database }
     // Future: WithCert { url, cert_path, key_path }
 }
 
+impl ConnectionConfig {
+    /// A [`ConnectionConfig::Backends`] map from `(name, config)` pairs.
+    pub fn backends<K, V>(configs: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<ConnectionConfig>,
+    {
+        ConnectionConfig::Backends(
+            configs
+                .into_iter()
+                .map(|(name, config)| (name.into(), config.into()))
+                .collect(),
+        )
+    }
+
+    /// The config for the backend called `name`. A single URL serves every backend.
+    pub fn for_backend(&self, name: &str) -> Result<&ConnectionConfig, StorageError> {
+        match self {
+            ConnectionConfig::Url(_) => Ok(self),
+            ConnectionConfig::Backends(configs) => configs.get(name).ok_or_else(|| {
+                StorageError::StorageError(format!("No connection config for backend {}", name))
+            }),
+        }
+    }
+}
+
 impl From<&str> for ConnectionConfig {
     fn from(url: &str) -> Self {
         ConnectionConfig::Url(url.to_string())
@@ -116,6 +150,333 @@ where
     ///
     /// Returns `true` if at least one item exists for the given prefix.
     async fn exists(&self, prefix: &str) -> Result<bool, StorageError>;
+
+    /// Soft-delete the version with `said`: stamp its `#[deleted_at]` column,
+    /// keeping the row for audit. Lookups by SAID or prefix still return it,
+    /// marked, so the history stays verifiable; `Query` reads skip it.
+    ///
+    /// Returns `false` if no live version has that SAID. Repositories that
+    /// can't soft-delete (the default) return an error.
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        Err(soft_delete_unsupported(said))
+    }
+
+    /// Verify `item` descends from its prefix, using the stored history.
+    ///
+    /// Unlike `verify_prefix`, this works for any version; see
+    /// [`Versioned::verify_lineage`].
+    async fn verify_lineage(&self, item: &T) -> Result<(), StorageError> {
+        let history = self.get_history(&item.get_prefix()).await?;
+        item.verify_lineage(&history)
+    }
+
+    /// Store a complete lineage authored elsewhere, exactly as given.
+    ///
+    /// `items` must be versions 0..n of one prefix; they are checked with
+    /// [`verify_history`] and then inserted without `derive_prefix()` or
+    /// `increment()`. Database-backed repositories insert them in one
+    /// transaction; this default inserts them one at a time.
+    async fn import_history(&self, items: Vec<T>) -> Result<Vec<T>, StorageError>
+    where
+        T: 'static,
+    {
+        verify_history(&items)?;
+        for item in &items {
+            self.insert(item.clone()).await?;
+        }
+        Ok(items)
+    }
+
+    /// Get the latest version for a prefix, verified.
+    ///
+    /// The item's SAID is verified, then up to `depth` previous links are
+    /// followed, each hop checked to verify and to be the version the newer
+    /// one points at. If the walk reaches version 0, that inception must
+    /// derive the prefix. Any failure is returned as an error; `None` means
+    /// the prefix has no versions.
+    async fn get_latest_verified(
+        &self,
+        prefix: &str,
+        depth: usize,
+    ) -> Result<Option<VerifiedLatest<T>>, StorageError> {
+        let Some(item) = self.get_latest(prefix).await? else {
+            return Ok(None);
+        };
+        item.verify()?;
+
+        let mut checked_links = 0;
+        let mut current = item.clone();
+        while checked_links < depth {
+            let Some(previous_said) = current.get_previous() else {
+                break;
+            };
+            let previous = self.get_by_said(&previous_said).await?.ok_or_else(|| {
+                StorageError::InvalidSaid(format!(
+                    "Version {} of {} points at missing {}",
+                    current.get_version(),
+                    prefix,
+                    previous_said
+                ))
+            })?;
+            verify_link(&current, &previous)?;
+            checked_links += 1;
+            current = previous;
+        }
+
+        // A v0 with a valid SAID can still claim a prefix it didn't derive
+        let reached_inception = current.get_version() == 0;
+        if reached_inception {
+            current.verify_inception()?;
+        }
+
+        Ok(Some(VerifiedLatest {
+            reached_inception,
+            item,
+            checked_links,
+        }))
+    }
+
+    /// Check every stored version of a prefix: each verifies, carries the
+    /// prefix, has the next version number and links to the SAID before it.
+    ///
+    /// Problems are collected rather than returned as errors; a prefix with no
+    /// versions is [`StorageError::NotFound`].
+    async fn verify_history(&self, prefix: &str) -> Result<ChainVerificationReport, StorageError> {
+        let history = self.get_history(prefix).await?;
+        if history.is_empty() {
+            return Err(StorageError::NotFound(format!("History of {}", prefix)));
+        }
+        Ok(ChainVerificationReport::check(prefix, &history))
+    }
+
+    /// Get the latest version for a prefix unless it has expired.
+    ///
+    /// An expired latest version hides the prefix; earlier versions are not
+    /// consulted. See [`is_expired`](crate::is_expired).
+    async fn get_latest_active(&self, prefix: &str) -> Result<Option<T>, StorageError>
+    where
+        T: Storable,
+    {
+        match self.get_latest(prefix).await? {
+            Some(item) if !is_expired(&item)? => Ok(Some(item)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Get the version of a prefix in force at `at`.
+    ///
+    /// That is the highest version whose effective time (see
+    /// [`effective_time`](crate::effective_time)) is not after `at`. `None`
+    /// means no version had taken effect yet.
+    async fn get_effective(
+        &self,
+        prefix: &str,
+        at: &StorageDatetime,
+    ) -> Result<Option<T>, StorageError>
+    where
+        T: Storable,
+    {
+        let history = self.get_history(prefix).await?;
+        Ok(in_force_at(&history, at)?.cloned())
+    }
+
+    /// The fields that changed between two versions of a prefix.
+    ///
+    /// See [`diff_items`](crate::diff_items) for what is compared.
+    async fn diff(
+        &self,
+        prefix: &str,
+        from_version: u64,
+        to_version: u64,
+    ) -> Result<FieldDiff, StorageError>
+    where
+        T: Storable,
+    {
+        let history = self.get_history(prefix).await?;
+        let version = |version: u64| {
+            history
+                .iter()
+                .find(|item| item.get_version() == version)
+                .ok_or_else(|| StorageError::NotFound(format!("Version {} of {}", version, prefix)))
+        };
+        diff_items(
+            version(from_version)?,
+            from_version,
+            version(to_version)?,
+            to_version,
+        )
+    }
+}
+
+/// The latest version of a prefix, as verified by
+/// [`VersionedRepository::get_latest_verified`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedLatest<T> {
+    pub item: T,
+    /// Previous links followed and verified.
+    pub checked_links: usize,
+    /// Whether the walk reached version 0, so the whole lineage was checked
+    /// back to an inception that derives the prefix.
+    pub reached_inception: bool,
+}
+
+/// The result of [`VersionedRepository::verify_history`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainVerificationReport {
+    pub prefix: String,
+    /// Versions checked.
+    pub checked: usize,
+    /// SAID of the newest version checked.
+    pub head: Option<String>,
+    pub issues: Vec<ChainIssue>,
+}
+
+/// A problem with one version of a chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainIssue {
+    pub version: u64,
+    pub said: String,
+    pub problem: ChainProblem,
+}
+
+/// What is wrong with a version.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainProblem {
+    /// The SAID (or, for an inception, the prefix) doesn't match the content.
+    InvalidSaid(String),
+    /// The version carries another prefix.
+    WrongPrefix { found: String },
+    /// The version number isn't one more than the version before it.
+    VersionOutOfSequence { expected: u64 },
+    /// `previous` isn't the SAID of the version before it.
+    BrokenLink { expected: Option<String> },
+}
+
+impl ChainVerificationReport {
+    /// Check `history`, the versions of `prefix` in order.
+    pub fn check<T: Versioned>(prefix: &str, history: &[T]) -> Self {
+        let mut issues = Vec::new();
+        let mut expected_version = 0;
+        let mut expected_previous: Option<String> = None;
+        for item in history {
+            let mut flag = |problem| {
+                issues.push(ChainIssue {
+                    version: item.get_version(),
+                    said: item.get_said(),
+                    problem,
+                })
+            };
+            if let Err(e) = item.verify() {
+                flag(ChainProblem::InvalidSaid(e.to_string()));
+            }
+            if item.get_prefix() != prefix {
+                flag(ChainProblem::WrongPrefix {
+                    found: item.get_prefix(),
+                });
+            }
+            if item.get_version() != expected_version {
+                flag(ChainProblem::VersionOutOfSequence {
+                    expected: expected_version,
+                });
+            }
+            if item.get_previous() != expected_previous {
+                flag(ChainProblem::BrokenLink {
+                    expected: expected_previous.clone(),
+                });
+            }
+            // Later versions are judged against what is stored, not what was expected
+            expected_version = item.get_version() + 1;
+            expected_previous = Some(item.get_said());
+        }
+        Self {
+            prefix: prefix.to_string(),
+            checked: history.len(),
+            head: expected_previous,
+            issues,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Check `items` form a whole lineage: versions 0..n of one prefix, each
+/// verifying and linking to the one before, with non-decreasing `created_at`.
+pub fn verify_history<T: Versioned>(items: &[T]) -> Result<(), StorageError> {
+    let Some(first) = items.first() else {
+        return Err(StorageError::StorageError(
+            "Cannot import an empty history".to_string(),
+        ));
+    };
+    verify_continuation(&first.get_prefix(), None, items).map_err(
+        |divergence| match divergence {
+            Divergence::Invalid(e) => e,
+            Divergence::Conflict => {
+                StorageError::InvalidSaid(format!("History of {} conflicts", first.get_prefix()))
+            }
+        },
+    )?;
+    for pair in items.windows(2) {
+        if let [older, newer] = pair
+            && let (Some(older_at), Some(newer_at)) =
+                (older.get_created_at(), newer.get_created_at())
+            && newer_at < older_at
+        {
+            return Err(StorageError::InvalidSaid(format!(
+                "Version {} of {} predates the version before it",
+                newer.get_version(),
+                newer.get_prefix()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Put `history` in a deterministic order: by version, then `created_at`, then SAID.
+///
+/// Duplicate versions of a prefix (a fork, or a bug) would otherwise come back
+/// in whatever order the database chose, and chain verification would vary
+/// from run to run. With `reject_duplicates`, a repeated version is an error.
+pub fn order_history<T: Versioned>(
+    history: &mut [T],
+    reject_duplicates: bool,
+) -> Result<(), StorageError> {
+    history.sort_by(|a, b| {
+        (a.get_version(), a.get_created_at(), a.get_said()).cmp(&(
+            b.get_version(),
+            b.get_created_at(),
+            b.get_said(),
+        ))
+    });
+    if reject_duplicates
+        && let Some([first, _]) = history
+            .windows(2)
+            .find(|pair| pair[0].get_version() == pair[1].get_version())
+    {
+        return Err(StorageError::InvalidSaid(format!(
+            "{} has more than one version {}",
+            first.get_prefix(),
+            first.get_version()
+        )));
+    }
+    Ok(())
+}
+
+/// Check `older` verifies and is the version `newer` links back to.
+fn verify_link<T: Versioned>(newer: &T, older: &T) -> Result<(), StorageError> {
+    older.verify()?;
+    if newer.get_previous() != Some(older.get_said())
+        || older.get_prefix() != newer.get_prefix()
+        || older.get_version() + 1 != newer.get_version()
+    {
+        return Err(StorageError::InvalidSaid(format!(
+            "{} is not the version before {}",
+            older.get_said(),
+            newer.get_said()
+        )));
+    }
+    Ok(())
 }
 
 /// Repository trait for simple SelfAddressed types without versioning.
@@ -149,4 +510,161 @@ where
     ///
     /// Returns `None` if no item with the given SAID exists.
     async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError>;
+
+    /// Soft-delete the item with `said`: stamp its `#[deleted_at]` column,
+    /// keeping the row for audit. `get_by_said` still returns it, marked;
+    /// `Query` reads skip it.
+    ///
+    /// Returns `false` if no live item has that SAID. Repositories that
+    /// can't soft-delete (the default) return an error.
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        Err(soft_delete_unsupported(said))
+    }
+}
+
+fn soft_delete_unsupported(said: &str) -> StorageError {
+    StorageError::StorageError(format!(
+        "Can't soft-delete {}: this repository doesn't support soft deletion",
+        said
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[serde(rename_all = "camelCase")]
+    struct Page {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        body: String,
+    }
+
+    #[test]
+    fn backend_configs_are_looked_up_by_name() {
+        let single = ConnectionConfig::from("postgres://localhost/adns");
+        assert!(matches!(
+            single.for_backend("surreal"),
+            Ok(ConnectionConfig::Url(_))
+        ));
+
+        let mixed = ConnectionConfig::backends([
+            ("postgres", "postgres://localhost/adns"),
+            ("surreal", "ws://localhost:8000"),
+        ]);
+        assert!(matches!(
+            mixed.for_backend("surreal"),
+            Ok(ConnectionConfig::Url(url)) if url == "ws://localhost:8000"
+        ));
+        assert!(mixed.for_backend("redis").is_err());
+    }
+
+    #[test]
+    fn links_must_point_at_the_previous_version() {
+        let mut v0 = Page::new("v0".to_string());
+        v0.derive_prefix().unwrap();
+        let mut v1 = v0.clone();
+        v1.body = "v1".to_string();
+        v1.increment().unwrap();
+        let mut v2 = v1.clone();
+        v2.body = "v2".to_string();
+        v2.increment().unwrap();
+
+        verify_link(&v1, &v0).unwrap();
+        verify_link(&v2, &v1).unwrap();
+        assert!(verify_link(&v2, &v0).is_err());
+
+        let mut tampered = v1.clone();
+        tampered.body = "tampered".to_string();
+        assert!(verify_link(&v2, &tampered).is_err());
+
+        verify_history(&[v0.clone(), v1.clone(), v2.clone()]).unwrap();
+        assert!(verify_history(&[v1.clone(), v2.clone()]).is_err());
+        assert!(verify_history(&[v0, v2]).is_err());
+        assert!(verify_history::<Page>(&[]).is_err());
+    }
+
+    #[test]
+    fn inceptions_must_derive_their_prefix() {
+        let v0 = Page::create("v0".to_string()).unwrap();
+        v0.verify_inception().unwrap();
+
+        // A SAID recomputed over someone else's prefix still verifies
+        let mut forged = Page::create("forged".to_string()).unwrap();
+        forged.prefix = v0.get_prefix();
+        forged.derive_said().unwrap();
+        forged.verify().unwrap();
+        assert!(forged.verify_inception().is_err());
+    }
+
+    #[test]
+    fn forked_histories_order_the_same_every_time() {
+        let v0 = Page::create("v0".to_string()).unwrap();
+        let fork = |body: &str| {
+            let mut v1 = v0.clone();
+            v1.body = body.to_string();
+            v1.increment().unwrap();
+            v1
+        };
+        let (a, b) = (fork("a"), fork("b"));
+
+        let mut one = vec![a.clone(), v0.clone(), b.clone()];
+        let mut other = vec![b, a, v0];
+        order_history(&mut one, false).unwrap();
+        order_history(&mut other, false).unwrap();
+        let saids = |history: &[Page]| history.iter().map(|p| p.get_said()).collect::<Vec<_>>();
+        assert_eq!(saids(&one), saids(&other));
+        assert_eq!(one[0].version, 0);
+
+        assert!(order_history(&mut one, true).is_err());
+        order_history(&mut one[..2], true).unwrap();
+    }
+
+    #[test]
+    fn chain_reports_name_each_broken_version() {
+        let v0 = Page::create("v0".to_string()).unwrap();
+        let mut v1 = v0.clone();
+        v1.increment().unwrap();
+        let mut v2 = v1.clone();
+        v2.increment().unwrap();
+        let prefix = v0.get_prefix();
+
+        let report = ChainVerificationReport::check(&prefix, &[v0.clone(), v1.clone(), v2.clone()]);
+        assert!(report.is_valid());
+        assert_eq!((report.checked, report.head), (3, Some(v2.get_said())));
+
+        let mut tampered = v1.clone();
+        tampered.body = "tampered".to_string();
+        let report = ChainVerificationReport::check(&prefix, &[v0.clone(), tampered, v2.clone()]);
+        assert_eq!(report.issues.len(), 1);
+        assert!(matches!(
+            report.issues[0].problem,
+            ChainProblem::InvalidSaid(_)
+        ));
+
+        let report = ChainVerificationReport::check(&prefix, &[v0.clone(), v2.clone()]);
+        assert_eq!(
+            report
+                .issues
+                .iter()
+                .map(|issue| (issue.version, issue.problem.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (2, ChainProblem::VersionOutOfSequence { expected: 1 }),
+                (
+                    2,
+                    ChainProblem::BrokenLink {
+                        expected: Some(v0.get_said())
+                    }
+                ),
+            ]
+        );
+    }
 }
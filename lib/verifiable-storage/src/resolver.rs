@@ -0,0 +1,275 @@
+//! Read-only HTTP resolver over registered repositories.
+//!
+//! [`Resolver`] serves stored records as JSON with axum:
+//!
+//! - `GET /{table}/{said}`: a record by SAID
+//! - `GET /{table}/prefix/{prefix}/latest`: the latest version of a prefix
+//! - `GET /{table}/prefix/{prefix}/history`: every version of a prefix, oldest first
+//!
+//! Every record is verified before it is served (SAIDs, and for histories the
+//! whole chain), so a tampered row is reported as a server error rather than
+//! returned. Unknown tables and missing records are `404`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    SelfAddressed, Storable, StorageError, UnversionedRepository, Versioned, VersionedRepository,
+    sync::{Divergence, verify_continuation},
+};
+
+type Tables = Arc<HashMap<&'static str, Box<dyn ResolvedTable>>>;
+
+/// Builds an axum router serving verified reads from registered repositories.
+#[derive(Default)]
+pub struct Resolver {
+    tables: HashMap<&'static str, Box<dyn ResolvedTable>>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `T`'s table from a versioned repository.
+    pub fn versioned<T, R>(mut self, repository: R) -> Self
+    where
+        T: Storable + Versioned + Serialize + DeserializeOwned + 'static,
+        R: VersionedRepository<T> + Send + Sync + 'static,
+    {
+        self.tables.insert(
+            T::table_name(),
+            Box::new(VersionedTable {
+                repository,
+                _marker: std::marker::PhantomData::<fn() -> T>,
+            }),
+        );
+        self
+    }
+
+    /// Serve `T`'s table from an unversioned repository.
+    pub fn unversioned<T, R>(mut self, repository: R) -> Self
+    where
+        T: Storable + SelfAddressed + Serialize + DeserializeOwned + 'static,
+        R: UnversionedRepository<T> + Send + Sync + 'static,
+    {
+        self.tables.insert(
+            T::table_name(),
+            Box::new(UnversionedTable {
+                repository,
+                _marker: std::marker::PhantomData::<fn() -> T>,
+            }),
+        );
+        self
+    }
+
+    /// The resolver's routes, ready to be nested or served.
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/{table}/{said}", get(by_said))
+            .route("/{table}/prefix/{prefix}/latest", get(latest))
+            .route("/{table}/prefix/{prefix}/history", get(history))
+            .with_state(Arc::new(self.tables))
+    }
+}
+
+#[async_trait]
+trait ResolvedTable: Send + Sync {
+    async fn by_said(&self, said: &str) -> Result<serde_json::Value, StorageError>;
+    async fn latest(&self, prefix: &str) -> Result<serde_json::Value, StorageError>;
+    async fn history(&self, prefix: &str) -> Result<serde_json::Value, StorageError>;
+}
+
+struct VersionedTable<T, R> {
+    repository: R,
+    _marker: std::marker::PhantomData<fn() -> T>,
+}
+
+struct UnversionedTable<T, R> {
+    repository: R,
+    _marker: std::marker::PhantomData<fn() -> T>,
+}
+
+#[async_trait]
+impl<T, R> ResolvedTable for VersionedTable<T, R>
+where
+    T: Storable + Versioned + Serialize + DeserializeOwned + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+{
+    async fn by_said(&self, said: &str) -> Result<serde_json::Value, StorageError> {
+        let item = self.repository.get_by_said(said).await?;
+        verified(item.ok_or_else(|| not_found(said))?)
+    }
+
+    async fn latest(&self, prefix: &str) -> Result<serde_json::Value, StorageError> {
+        let item = self.repository.get_latest(prefix).await?;
+        verified(item.ok_or_else(|| not_found(prefix))?)
+    }
+
+    async fn history(&self, prefix: &str) -> Result<serde_json::Value, StorageError> {
+        verified_history(prefix, self.repository.get_history(prefix).await?)
+    }
+}
+
+#[async_trait]
+impl<T, R> ResolvedTable for UnversionedTable<T, R>
+where
+    T: Storable + SelfAddressed + Serialize + DeserializeOwned + 'static,
+    R: UnversionedRepository<T> + Send + Sync,
+{
+    async fn by_said(&self, said: &str) -> Result<serde_json::Value, StorageError> {
+        let item = self
+            .repository
+            .get_by_said(said)
+            .await?
+            .ok_or_else(|| not_found(said))?;
+        item.verify_said()?;
+        Ok(serde_json::to_value(item)?)
+    }
+
+    async fn latest(&self, _prefix: &str) -> Result<serde_json::Value, StorageError> {
+        Err(unversioned())
+    }
+
+    async fn history(&self, _prefix: &str) -> Result<serde_json::Value, StorageError> {
+        Err(unversioned())
+    }
+}
+
+fn verified<T: Versioned + Serialize>(item: T) -> Result<serde_json::Value, StorageError> {
+    item.verify()?;
+    Ok(serde_json::to_value(item)?)
+}
+
+fn verified_history<T: Versioned + Serialize>(
+    prefix: &str,
+    history: Vec<T>,
+) -> Result<serde_json::Value, StorageError> {
+    if history.is_empty() {
+        return Err(not_found(prefix));
+    }
+    verify_continuation(prefix, None, &history).map_err(|divergence| match divergence {
+        Divergence::Invalid(e) => e,
+        Divergence::Conflict => {
+            StorageError::InvalidSaid(format!("Conflicting chain for {}", prefix))
+        }
+    })?;
+    Ok(serde_json::to_value(history)?)
+}
+
+fn not_found(key: &str) -> StorageError {
+    StorageError::NotFound(key.to_string())
+}
+
+fn unversioned() -> StorageError {
+    StorageError::NotFound("Table is not versioned".to_string())
+}
+
+/// HTTP status for a failed read. Records failing verification are the
+/// server's fault, not the caller's.
+fn status(error: &StorageError) -> StatusCode {
+    match error {
+        StorageError::NotFound(_) => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn respond(result: Result<serde_json::Value, StorageError>) -> Response {
+    match result {
+        Ok(value) => Json(value).into_response(),
+        Err(e) => (
+            status(&e),
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+fn table<'a>(tables: &'a Tables, name: &str) -> Result<&'a dyn ResolvedTable, StorageError> {
+    tables
+        .get(name)
+        .map(|table| table.as_ref())
+        .ok_or_else(|| StorageError::NotFound(format!("Unknown table {}", name)))
+}
+
+async fn by_said(
+    State(tables): State<Tables>,
+    Path((name, said)): Path<(String, String)>,
+) -> Response {
+    respond(match table(&tables, &name) {
+        Ok(table) => table.by_said(&said).await,
+        Err(e) => Err(e),
+    })
+}
+
+async fn latest(
+    State(tables): State<Tables>,
+    Path((name, prefix)): Path<(String, String)>,
+) -> Response {
+    respond(match table(&tables, &name) {
+        Ok(table) => table.latest(&prefix).await,
+        Err(e) => Err(e),
+    })
+}
+
+async fn history(
+    State(tables): State<Tables>,
+    Path((name, prefix)): Path<(String, String)>,
+) -> Response {
+    respond(match table(&tables, &name) {
+        Ok(table) => table.history(&prefix).await,
+        Err(e) => Err(e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "pages")]
+    #[serde(rename_all = "camelCase")]
+    struct Page {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        body: String,
+    }
+
+    #[test]
+    fn histories_are_verified_before_serving() {
+        let mut page = Page::new("v0".to_string());
+        page.derive_prefix().unwrap();
+        let mut pages = vec![page.clone()];
+        page.body = "v1".to_string();
+        page.increment().unwrap();
+        pages.push(page);
+        let prefix = pages[0].prefix.clone();
+
+        let served = verified_history(&prefix, pages.clone()).unwrap();
+        assert_eq!(served.as_array().unwrap().len(), 2);
+
+        pages[1].body = "tampered".to_string();
+        let error = verified_history(&prefix, pages).unwrap_err();
+        assert_eq!(status(&error), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            status(&verified_history::<Page>(&prefix, vec![]).unwrap_err()),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
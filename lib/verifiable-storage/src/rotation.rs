@@ -0,0 +1,171 @@
+//! Pre-rotated keys carried on versioned records.
+//!
+//! A [`RotatableKeyRecord`] holds its current public key and a digest of the
+//! next one. Rotating reveals the key behind that digest and commits to a
+//! fresh next key in the same step, so a stolen current key can't be used to
+//! rotate to an attacker's key. [`RotatableKeyRecord::rotate`] enforces this
+//! before incrementing, and [`RotatableKeyRecord::verify_rotation`] checks a
+//! stored pair of versions the same way.
+
+use alloc::{format, string::String};
+
+use crate::{SAID_DIGEST_CODE, StorageDatetime, StorageError, Versioned};
+
+/// The digest committing to `key` (its qb64 text) as a record's next key.
+pub fn key_digest(key: &str) -> Result<String, StorageError> {
+    let hash = blake3::hash(key.as_bytes());
+    let digest = cesr::Digest::from_raw(SAID_DIGEST_CODE, hash.as_bytes().to_vec())?;
+    Ok(cesr::Matter::qb64(&digest))
+}
+
+/// A versioned record whose signing key rotates under pre-rotation rules.
+///
+/// A `None` next-key digest means the key was abandoned and can't rotate again.
+pub trait RotatableKeyRecord: Versioned {
+    fn current_key(&self) -> &str;
+    fn next_key_digest(&self) -> Option<&str>;
+    fn set_keys(&mut self, current_key: String, next_key_digest: Option<String>);
+
+    /// Make `new_key` current and commit to the next key by `next_key_digest`,
+    /// then `increment()`. `new_key` must match this version's commitment, and
+    /// the new commitment must not be to `new_key` itself.
+    #[cfg(feature = "std")]
+    fn rotate(
+        &mut self,
+        new_key: String,
+        next_key_digest: Option<String>,
+    ) -> Result<(), StorageError> {
+        let created_at = self.next_created_at();
+        self.rotate_at(new_key, next_key_digest, created_at)
+    }
+
+    /// Like `rotate()`, but stamps the new version with `created_at` through
+    /// `increment_at()`.
+    fn rotate_at(
+        &mut self,
+        new_key: String,
+        next_key_digest: Option<String>,
+        created_at: StorageDatetime,
+    ) -> Result<(), StorageError> {
+        check_rotation(
+            &self.get_prefix(),
+            self.next_key_digest(),
+            &new_key,
+            next_key_digest.as_deref(),
+        )?;
+        self.set_keys(new_key, next_key_digest);
+        self.increment_at(created_at)
+    }
+
+    /// Check this version's keys follow from `previous`: either unchanged, or
+    /// a rotation that reveals `previous`'s committed next key.
+    fn verify_rotation(&self, previous: &Self) -> Result<(), StorageError> {
+        if self.current_key() == previous.current_key()
+            && self.next_key_digest() == previous.next_key_digest()
+        {
+            return Ok(());
+        }
+        check_rotation(
+            &self.get_prefix(),
+            previous.next_key_digest(),
+            self.current_key(),
+            self.next_key_digest(),
+        )
+    }
+}
+
+fn check_rotation(
+    prefix: &str,
+    committed: Option<&str>,
+    new_key: &str,
+    next_key_digest: Option<&str>,
+) -> Result<(), StorageError> {
+    let Some(committed) = committed else {
+        return Err(StorageError::InvalidRotation(format!(
+            "{} has no next key committed; its key can't rotate",
+            prefix
+        )));
+    };
+    let revealed = key_digest(new_key)?;
+    if revealed != committed {
+        return Err(StorageError::InvalidRotation(format!(
+            "Key for {} does not match its commitment {}",
+            prefix, committed
+        )));
+    }
+    if next_key_digest == Some(revealed.as_str()) {
+        return Err(StorageError::InvalidRotation(format!(
+            "Next key for {} must differ from the key being revealed",
+            prefix
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[serde(rename_all = "camelCase")]
+    struct Signer {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        public_key: String,
+        next_key_digest: Option<String>,
+    }
+
+    impl RotatableKeyRecord for Signer {
+        fn current_key(&self) -> &str {
+            &self.public_key
+        }
+
+        fn next_key_digest(&self) -> Option<&str> {
+            self.next_key_digest.as_deref()
+        }
+
+        fn set_keys(&mut self, current_key: String, next_key_digest: Option<String>) {
+            self.public_key = current_key;
+            self.next_key_digest = next_key_digest;
+        }
+    }
+
+    #[test]
+    fn rotation_reveals_the_committed_key() {
+        let inception =
+            Signer::create("key-0".to_string(), Some(key_digest("key-1").unwrap())).unwrap();
+
+        let mut forged = inception.clone();
+        assert!(matches!(
+            forged.rotate("stolen".to_string(), Some(key_digest("key-2").unwrap())),
+            Err(StorageError::InvalidRotation(_))
+        ));
+        assert!(
+            inception
+                .clone()
+                .rotate("key-1".to_string(), Some(key_digest("key-1").unwrap()))
+                .is_err()
+        );
+
+        let mut rotated = inception.clone();
+        rotated
+            .rotate("key-1".to_string(), Some(key_digest("key-2").unwrap()))
+            .unwrap();
+        assert_eq!(rotated.version, 1);
+        rotated.verify_rotation(&inception).unwrap();
+
+        let mut abandoned = rotated.clone();
+        abandoned.rotate("key-2".to_string(), None).unwrap();
+        assert!(abandoned.clone().rotate("key-3".to_string(), None).is_err());
+
+        let mut tampered = abandoned.clone();
+        tampered.public_key = "other".to_string();
+        assert!(tampered.verify_rotation(&rotated).is_err());
+    }
+}
@@ -1,3 +1,10 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
 use cesr::Matter;
 use serde::Serialize;
 
@@ -13,6 +20,16 @@ pub trait SelfAddressed: Sized {
     fn get_said(&self) -> String;
 }
 
+/// Semantic checks on content, such as field lengths or invariants between
+/// fields.
+///
+/// With `#[validate]` on a `#[derive(SelfAddressed)]` type, `derive_said()`
+/// runs these first, so `create()` and `update()` refuse invalid content
+/// before it is digested. Fail with [`StorageError::ValidationFailed`].
+pub trait Validate {
+    fn validate(&self) -> Result<(), StorageError>;
+}
+
 /// Trait for versioned types with prefix, version, and previous pointer.
 ///
 /// The prefix is derived from the first SAID (at version 0) and provides a stable
@@ -27,10 +44,34 @@ pub trait SelfAddressed: Sized {
 /// - `#[created_at]` (optional) - timestamp, updated on increment
 pub trait Versioned: SelfAddressed + Clone {
     fn derive_prefix(&mut self) -> Result<(), StorageError>;
+
+    /// Recompute the prefix as if this were the inception (version 0).
+    ///
+    /// Only meaningful at version 0: a later version's prefix comes from its
+    /// chain, not its content, so this always fails there.
+    #[deprecated(
+        note = "only checks inceptions; use `verify_inception`, or `verify_lineage` for later versions"
+    )]
     fn verify_prefix(&self) -> Result<(), StorageError>;
     fn get_prefix(&self) -> String;
 
-    fn increment(&mut self) -> Result<(), StorageError>;
+    /// Advance to the next version, stamped with [`Versioned::next_created_at`].
+    #[cfg(feature = "std")]
+    fn increment(&mut self) -> Result<(), StorageError> {
+        let created_at = self.next_created_at();
+        self.increment_at(created_at)
+    }
+
+    /// Advance to the next version, stamped with `created_at`: for importing
+    /// historical records, and without std, where there is no clock.
+    fn increment_at(&mut self, created_at: StorageDatetime) -> Result<(), StorageError>;
+
+    /// The time `increment()` stamps the next version with: now, or with
+    /// `#[created_at(hybrid)]` strictly after this version.
+    #[cfg(feature = "std")]
+    fn next_created_at(&self) -> StorageDatetime {
+        StorageDatetime::now()
+    }
 
     /// Check if proposed update has no actual changes (only version/previous/created_at differ).
     /// Returns true if the proposed SAID matches what would be computed from self with
@@ -43,25 +84,349 @@ pub trait Versioned: SelfAddressed + Clone {
     fn get_created_at(&self) -> Option<StorageDatetime>;
 
     /// Verify the item based on its version:
-    /// - version 0: verify_prefix() (said == prefix)
+    /// - version 0: verify_inception() (said == prefix)
     /// - version > 0: verify_said() (said derived from content)
     fn verify(&self) -> Result<(), StorageError> {
         if self.get_version() == 0 {
-            self.verify_prefix()
+            self.verify_inception()
         } else {
             self.verify_said()
         }
     }
+
+    /// Verify a version 0 item: its SAID and prefix both derive from its content.
+    fn verify_inception(&self) -> Result<(), StorageError> {
+        if self.get_version() != 0 {
+            return Err(StorageError::InvalidSaid(format!(
+                "{} is version {}, not an inception; verify its lineage instead",
+                self.get_said(),
+                self.get_version()
+            )));
+        }
+        #[allow(deprecated)]
+        self.verify_prefix()
+    }
+
+    /// Verify this item descends from its prefix through `history`.
+    ///
+    /// `history` holds the prefix's versions in order from 0 (as returned by
+    /// `get_history`); versions after this item's are ignored. The inception
+    /// must derive the prefix, each later version must verify and link to the
+    /// one before it, and the version at this item's position must be this item.
+    fn verify_lineage(&self, history: &[Self]) -> Result<(), StorageError> {
+        let prefix = self.get_prefix();
+        let mut previous: Option<String> = None;
+        for (version, item) in (0..=self.get_version()).zip(history) {
+            if item.get_version() != version || item.get_prefix() != prefix {
+                return Err(StorageError::InvalidSaid(format!(
+                    "Expected version {} of {}, got version {} of {}",
+                    version,
+                    prefix,
+                    item.get_version(),
+                    item.get_prefix()
+                )));
+            }
+            item.verify()?;
+            if item.get_previous() != previous {
+                return Err(StorageError::InvalidSaid(format!(
+                    "Broken previous link at version {} of {}",
+                    version, prefix
+                )));
+            }
+            previous = Some(item.get_said());
+        }
+        if previous != Some(self.get_said()) {
+            return Err(StorageError::InvalidSaid(format!(
+                "{} is not version {} of {}",
+                self.get_said(),
+                self.get_version(),
+                prefix
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The CESR digest code SAIDs are encoded with by default.
+pub const SAID_DIGEST_CODE: cesr::DigestCode = cesr::DigestCode::Blake3;
+
+/// A hash a SAID can be computed with. Each has its own CESR code, so a SAID
+/// records which one made it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SaidAlgorithm {
+    #[default]
+    Blake3,
+    Sha3_256,
+    Sha2_256,
+    Blake2b256,
+}
+
+impl SaidAlgorithm {
+    /// The CESR digest code SAIDs from this algorithm carry.
+    pub fn digest_code(self) -> cesr::DigestCode {
+        match self {
+            SaidAlgorithm::Blake3 => cesr::DigestCode::Blake3,
+            SaidAlgorithm::Sha3_256 => cesr::DigestCode::Sha3,
+            SaidAlgorithm::Sha2_256 => cesr::DigestCode::Sha2,
+            SaidAlgorithm::Blake2b256 => cesr::DigestCode::Blake2b,
+        }
+    }
+
+    pub(crate) fn digest(self, bytes: &[u8]) -> Vec<u8> {
+        use sha2::Digest;
+        match self {
+            SaidAlgorithm::Blake3 => blake3::hash(bytes).as_bytes().to_vec(),
+            SaidAlgorithm::Sha3_256 => sha3::Sha3_256::digest(bytes).to_vec(),
+            SaidAlgorithm::Sha2_256 => sha2::Sha256::digest(bytes).to_vec(),
+            SaidAlgorithm::Blake2b256 => {
+                blake2::Blake2b::<blake2::digest::consts::U32>::digest(bytes).to_vec()
+            }
+        }
+    }
+}
+
+/// Raw size of the 256-bit digests SAIDs are computed with.
+const DIGEST_RAW_SIZE: usize = 32;
+
+/// The dummy-fill for a SAID field while its SAID is computed: `#` repeated
+/// to the qb64 length of a `code` digest.
+pub fn said_placeholder(code: cesr::DigestCode) -> Result<String, StorageError> {
+    let digest = cesr::Digest::from_raw(code, vec![0; DIGEST_RAW_SIZE])?;
+    Ok("#".repeat(digest.qb64().len()))
 }
 
 /// Compute a SAID (Self-Addressing IDentifier) from serializable data.
 ///
 /// Uses Blake3-256 hash encoded as CESR.
 pub fn compute_said<T: Serialize>(data: &T) -> Result<String, StorageError> {
+    compute_said_with(data, SaidAlgorithm::Blake3)
+}
+
+/// Compute a SAID from serializable data with the given hash.
+pub fn compute_said_with<T: Serialize>(
+    data: &T,
+    algorithm: SaidAlgorithm,
+) -> Result<String, StorageError> {
     let bytes = serde_json::to_vec(data)?;
+    let digest = cesr::Digest::from_raw(algorithm.digest_code(), algorithm.digest(&bytes))?;
+    Ok(digest.qb64())
+}
 
-    let hash = blake3::hash(&bytes);
-    let digest = cesr::Digest::from_raw(cesr::DigestCode::Blake3, hash.as_bytes().to_vec())?;
+/// Compute a SAID over only the given top-level JSON keys of `data`.
+///
+/// Keys keep their serialization order. Keys absent from the serialization
+/// (e.g. skipped `None` fields) are ignored.
+pub fn compute_said_over<T: Serialize>(data: &T, keys: &[&str]) -> Result<String, StorageError> {
+    compute_said_over_with(data, keys, SaidAlgorithm::Blake3)
+}
 
-    Ok(digest.qb64())
+/// [`compute_said_over`] with the given hash.
+pub fn compute_said_over_with<T: Serialize>(
+    data: &T,
+    keys: &[&str],
+    algorithm: SaidAlgorithm,
+) -> Result<String, StorageError> {
+    let serde_json::Value::Object(mut object) = serde_json::to_value(data)? else {
+        return Err(StorageError::InvalidSaid(
+            "SAID field subsets require data that serializes to an object".to_string(),
+        ));
+    };
+    object.retain(|key, _| keys.contains(&key.as_str()));
+    compute_said_with(&object, algorithm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[serde(rename_all = "camelCase")]
+    struct Claim {
+        #[said(fields = ["name", "owner_id"])]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        name: String,
+        owner_id: String,
+        display_note: String,
+    }
+
+    fn claim() -> Claim {
+        let mut claim = Claim::new("example".to_string(), "Eowner".to_string(), String::new());
+        claim.derive_prefix().unwrap();
+        claim
+    }
+
+    #[test]
+    fn later_versions_verify_through_their_lineage() {
+        let inception = claim();
+        let mut history = vec![inception.clone()];
+        let mut next = inception.clone();
+        for note in ["first", "second"] {
+            next.display_note = note.to_string();
+            next.name = format!("example {}", note);
+            next.increment().unwrap();
+            history.push(next.clone());
+        }
+
+        inception.verify_inception().unwrap();
+        assert!(history[2].verify_inception().is_err());
+        history[1].verify_lineage(&history).unwrap();
+        history[2].verify_lineage(&history).unwrap();
+
+        let mut forged = history[1].clone();
+        forged.name = "forged".to_string();
+        forged.derive_said().unwrap();
+        assert!(forged.verify_lineage(&history).is_err());
+        assert!(history[2].verify_lineage(&history[..2]).is_err());
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    struct Sha3Note {
+        #[said(algorithm = "sha3-256")]
+        said: String,
+        body: String,
+    }
+
+    #[test]
+    fn algorithms_are_recorded_in_the_said() {
+        let saids: Vec<String> = [
+            SaidAlgorithm::Blake3,
+            SaidAlgorithm::Sha3_256,
+            SaidAlgorithm::Sha2_256,
+            SaidAlgorithm::Blake2b256,
+        ]
+        .into_iter()
+        .map(|algorithm| compute_said_with(&"data", algorithm).unwrap())
+        .collect();
+        assert_eq!(saids[0], compute_said(&"data").unwrap());
+        assert!(saids.iter().all(|said| said.len() == saids[0].len()));
+        for (i, said) in saids.iter().enumerate() {
+            assert!(saids[i + 1..].iter().all(|other| other[..1] != said[..1]));
+        }
+
+        let note = Sha3Note::create("hello".to_string()).unwrap();
+        note.verify_said().unwrap();
+        assert_eq!(
+            note.said,
+            compute_said_with(
+                &Sha3Note {
+                    said: said_placeholder(SaidAlgorithm::Sha3_256.digest_code()).unwrap(),
+                    body: "hello".to_string(),
+                },
+                SaidAlgorithm::Sha3_256
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn placeholder_matches_said_length() {
+        let placeholder = said_placeholder(SAID_DIGEST_CODE).unwrap();
+        assert_eq!(placeholder.len(), compute_said(&"data").unwrap().len());
+        assert!(placeholder.chars().all(|c| c == '#'));
+        assert_eq!(placeholder, crate::keri::DUMMY);
+    }
+
+    #[test]
+    fn operational_fields_are_not_covered() {
+        let original = claim();
+        let mut edited = original.clone();
+        edited.display_note = "renamed in the UI".to_string();
+
+        edited.verify().unwrap();
+        assert_eq!(edited.said, original.said);
+    }
+
+    #[test]
+    fn declared_and_managed_fields_are_covered() {
+        let original = claim();
+
+        let mut tampered = original.clone();
+        tampered.owner_id = "Emallory".to_string();
+        assert!(tampered.verify().is_err());
+
+        let mut next = original.clone();
+        next.increment().unwrap();
+        assert_ne!(next.said, original.said);
+        next.verify_said().unwrap();
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[serde(rename_all = "camelCase")]
+    struct Imported {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        #[created_at]
+        created_at: StorageDatetime,
+        body: String,
+    }
+
+    #[test]
+    fn imports_keep_their_original_timestamps() {
+        let then: StorageDatetime = serde_json::from_str("\"2019-06-01T12:00:00Z\"").unwrap();
+        let later = then.clone() + std::time::Duration::from_secs(86400);
+
+        let v0 = Imported::create_with_timestamp("v0".to_string(), then.clone()).unwrap();
+        assert_eq!(v0.created_at, then);
+        v0.verify().unwrap();
+
+        let mut v1 = v0.clone();
+        v1.body = "v1".to_string();
+        v1.increment_at(later.clone()).unwrap();
+        assert_eq!(v1.created_at, later);
+        v1.verify_lineage(&[v0, v1.clone()]).unwrap();
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[serde(rename_all = "camelCase")]
+    #[validate]
+    struct Range {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        low: u64,
+        high: u64,
+    }
+
+    impl Validate for Range {
+        fn validate(&self) -> Result<(), StorageError> {
+            if self.low > self.high {
+                return Err(StorageError::ValidationFailed(format!(
+                    "low {} exceeds high {}",
+                    self.low, self.high
+                )));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn validation_runs_before_digests() {
+        assert!(matches!(
+            Range::create(5, 1),
+            Err(StorageError::ValidationFailed(_))
+        ));
+
+        let range = Range::create(1, 5).unwrap();
+        range.verify().unwrap();
+        let mut inverted = range.clone();
+        inverted.high = 0;
+        assert!(inverted.increment().is_err());
+    }
 }
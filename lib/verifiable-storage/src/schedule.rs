@@ -0,0 +1,78 @@
+//! Versions published ahead of the time they take effect.
+//!
+//! Mark a timestamp field with `#[column(effective_at)]` and a new version of
+//! a key or policy can be stored before it applies; until then the previous
+//! version stays in force. A version with no effective time (or a type with
+//! no such column) takes effect when it was created.
+
+use crate::{Storable, StorageDatetime, StorageError, Versioned, expiry::datetime_column};
+
+/// When `item` takes effect: its `effective_at` time, else its creation time.
+/// `None` means it has always been in force.
+pub fn effective_time<T: Storable + Versioned>(
+    item: &T,
+) -> Result<Option<StorageDatetime>, StorageError> {
+    let scheduled = match T::effective_at_column() {
+        Some(column) => datetime_column(item, column)?,
+        None => None,
+    };
+    Ok(scheduled.or_else(|| item.get_created_at()))
+}
+
+/// The highest version in `history` that is in force at `at`.
+pub fn in_force_at<'a, T: Storable + Versioned>(
+    history: &'a [T],
+    at: &StorageDatetime,
+) -> Result<Option<&'a T>, StorageError> {
+    let mut in_force = None;
+    for item in history {
+        if effective_time(item)?.is_none_or(|effective| effective <= *at)
+            && in_force.is_none_or(|current: &T| current.get_version() < item.get_version())
+        {
+            in_force = Some(item);
+        }
+    }
+    Ok(in_force)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "policies")]
+    #[serde(rename_all = "camelCase")]
+    struct Policy {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        #[created_at]
+        created_at: StorageDatetime,
+        rule: String,
+        #[column(effective_at)]
+        effective_at: Option<StorageDatetime>,
+    }
+
+    #[test]
+    fn scheduled_versions_wait_for_their_time() {
+        let now = StorageDatetime::now();
+        let mut v0 = Policy::new("allow".to_string(), None);
+        v0.derive_prefix().unwrap();
+        let mut v1 = v0.clone();
+        v1.rule = "deny".to_string();
+        v1.effective_at = Some(now.clone() + Duration::from_secs(3600));
+        v1.increment().unwrap();
+        let history = vec![v0, v1];
+
+        let current = in_force_at(&history, &(now.clone() + Duration::from_secs(60))).unwrap();
+        assert_eq!(current.unwrap().rule, "allow");
+        let later = in_force_at(&history, &(now + Duration::from_secs(7200))).unwrap();
+        assert_eq!(later.unwrap().rule, "deny");
+    }
+}
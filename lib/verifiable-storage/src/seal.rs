@@ -0,0 +1,256 @@
+//! Tamper-evidence against direct database edits.
+//!
+//! A SAID proves a row is consistent with itself, but anyone who can write to
+//! the database can edit a row and recompute its SAID. A seal is an
+//! HMAC-SHA256 over the row's content under a secret only the server holds,
+//! stored as hex in an `Option<String>` field marked `#[column(seal)]`.
+//! [`SealedRepository`] seals every write and checks every read, failing with
+//! [`StorageError::InvalidSeal`].
+//!
+//! The seal field is left out of the SAID, so sealing never changes a
+//! record's identity and rows can be resealed under a new secret.
+
+use std::fmt;
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use serde::{Serialize, de::DeserializeOwned};
+use sha2::Sha256;
+
+use crate::{
+    SelfAddressed, Storable, StorageError, UnversionedRepository, Versioned, VersionedRepository,
+    signing::{decode_hex, encode_hex},
+};
+
+/// Computes and checks row seals under a server secret.
+#[derive(Clone)]
+pub struct RowSealer {
+    key: Vec<u8>,
+}
+
+impl fmt::Debug for RowSealer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RowSealer").finish_non_exhaustive()
+    }
+}
+
+impl RowSealer {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+
+    /// The seal for `item`'s content, ignoring any seal it already carries.
+    pub fn seal<T: Storable + Serialize>(&self, item: &T) -> Result<String, StorageError> {
+        Ok(encode_hex(&self.mac(item)?.finalize().into_bytes()))
+    }
+
+    /// `item` with its seal field set.
+    pub fn apply<T>(&self, item: T) -> Result<T, StorageError>
+    where
+        T: Storable + Serialize + DeserializeOwned,
+    {
+        let seal = self.seal(&item)?;
+        let mut value = serde_json::to_value(item)?;
+        if let Some(object) = value.as_object_mut() {
+            object.insert(seal_key::<T>()?.to_string(), seal.into());
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Fail unless `item` carries a seal matching its content.
+    pub fn verify<T: Storable + Serialize>(&self, item: &T) -> Result<(), StorageError> {
+        let key = seal_key::<T>()?;
+        let value = serde_json::to_value(item)?;
+        let Some(seal) = value.get(key).and_then(|seal| seal.as_str()) else {
+            return Err(StorageError::InvalidSeal(format!(
+                "{} {} is not sealed",
+                T::table_name(),
+                item.id()
+            )));
+        };
+        let mismatch = || {
+            StorageError::InvalidSeal(format!(
+                "Seal of {} {} does not match its content",
+                T::table_name(),
+                item.id()
+            ))
+        };
+        let seal = decode_hex(seal).map_err(|_| mismatch())?;
+        self.mac(item)?.verify_slice(&seal).map_err(|_| mismatch())
+    }
+
+    fn mac<T: Storable + Serialize>(&self, item: &T) -> Result<Hmac<Sha256>, StorageError> {
+        let key = seal_key::<T>()?;
+        let serde_json::Value::Object(mut object) = serde_json::to_value(item)? else {
+            return Err(StorageError::StorageError(format!(
+                "{} does not serialize to an object",
+                T::table_name()
+            )));
+        };
+        object.remove(key);
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key)
+            .map_err(|e| StorageError::StorageError(e.to_string()))?;
+        mac.update(&serde_json::to_vec(&object)?);
+        Ok(mac)
+    }
+}
+
+/// The JSON key of `T`'s seal column.
+fn seal_key<T: Storable>() -> Result<&'static str, StorageError> {
+    T::seal_column()
+        .and_then(|column| T::columns().iter().position(|c| *c == column))
+        .and_then(|position| T::json_keys().get(position).copied())
+        .ok_or_else(|| {
+            StorageError::StorageError(format!("{} has no seal column", T::table_name()))
+        })
+}
+
+/// Wraps a repository so that writes are sealed and reads are checked.
+///
+/// `create` and `update` derive the SAID here and write through the inner
+/// repository's `insert`, since the seal has to cover the final content.
+#[derive(Debug, Clone)]
+pub struct SealedRepository<R> {
+    inner: R,
+    sealer: RowSealer,
+}
+
+impl<R> SealedRepository<R> {
+    pub fn new(inner: R, sealer: RowSealer) -> Self {
+        Self { inner, sealer }
+    }
+
+    fn check<T: Storable + Serialize>(&self, item: Option<T>) -> Result<Option<T>, StorageError> {
+        if let Some(item) = &item {
+            self.sealer.verify(item)?;
+        }
+        Ok(item)
+    }
+}
+
+#[async_trait]
+impl<T, R> VersionedRepository<T> for SealedRepository<R>
+where
+    T: Storable + SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+{
+    async fn create(&self, mut item: T) -> Result<T, StorageError> {
+        item.derive_prefix()?;
+        self.insert(item).await
+    }
+
+    async fn update(&self, mut item: T) -> Result<T, StorageError> {
+        item.increment()?;
+        self.insert(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.inner.insert(self.sealer.apply(item)?).await
+    }
+
+    async fn import_history(&self, items: Vec<T>) -> Result<Vec<T>, StorageError> {
+        let sealed = items
+            .into_iter()
+            .map(|item| self.sealer.apply(item))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.inner.import_history(sealed).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.check(self.inner.get_by_said(said).await?)
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        self.check(self.inner.get_latest(prefix).await?)
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        let history = self.inner.get_history(prefix).await?;
+        for item in &history {
+            self.sealer.verify(item)?;
+        }
+        Ok(history)
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        self.inner.exists(prefix).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[async_trait]
+impl<T, R> UnversionedRepository<T> for SealedRepository<R>
+where
+    T: Storable + SelfAddressed + Serialize + DeserializeOwned + Clone + 'static,
+    R: UnversionedRepository<T> + Send + Sync,
+{
+    async fn create(&self, mut item: T) -> Result<T, StorageError> {
+        item.derive_said()?;
+        self.insert(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.inner.insert(self.sealer.apply(item)?).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.check(self.inner.get_by_said(said).await?)
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "balances")]
+    #[serde(rename_all = "camelCase")]
+    struct Balance {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        account: String,
+        amount: u64,
+        #[column(seal)]
+        row_seal: Option<String>,
+    }
+
+    #[test]
+    fn seals_catch_edits_that_recompute_the_said() {
+        let sealer = RowSealer::new(b"server secret".to_vec());
+        let balance = Balance::create("alice".to_string(), 10).unwrap();
+        assert!(balance.row_seal.is_none());
+        assert!(sealer.verify(&balance).is_err());
+
+        let sealed = sealer.apply(balance.clone()).unwrap();
+        sealed.verify().unwrap();
+        assert_eq!(sealed.said, balance.said);
+        sealer.verify(&sealed).unwrap();
+
+        let mut forged = sealed.clone();
+        forged.amount = 1_000_000;
+        forged.derive_prefix().unwrap();
+        forged.verify().unwrap();
+        assert!(matches!(
+            sealer.verify(&forged),
+            Err(StorageError::InvalidSeal(_))
+        ));
+        assert!(
+            RowSealer::new(b"other secret".to_vec())
+                .verify(&sealed)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,681 @@
+//! Horizontal sharding across multiple executors.
+//!
+//! `ShardedExecutor<E>` spreads data over N executors (typically one pool per
+//! database) by hashing a shard key — the `prefix` column by default, so a whole
+//! version chain lives on one shard. Operations whose filters pin the shard key
+//! (`eq`/`in` on the shard column) go only to the owning shards; everything else
+//! is scattered to every shard and the results merged, honouring order, limit,
+//! offset, and distinct-on across shards.
+//!
+//! Changing the number of shards (or the resolver) moves keys between shards, so
+//! resharding requires migrating data.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset};
+use futures_util::future::try_join_all;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    Aggregate, ColumnQuery, Delete, Filter, Order, PrefixVersion, Query, QueryExecutor, Storable,
+    StorageError, TransactionExecutor, Value,
+};
+
+/// Column used as the shard key when none is configured.
+const DEFAULT_SHARD_COLUMN: &str = "prefix";
+
+/// Fallback shard key column for types without the configured one.
+const ID_COLUMN: &str = "said";
+
+/// Maps a shard key to a shard index.
+pub trait ShardResolver: Send + Sync {
+    /// Return the shard (in `0..shard_count`) that owns `key`.
+    fn resolve(&self, key: &str, shard_count: usize) -> usize;
+}
+
+/// Default resolver: Blake3 of the key, modulo the shard count.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HashShardResolver;
+
+impl ShardResolver for HashShardResolver {
+    fn resolve(&self, key: &str, shard_count: usize) -> usize {
+        let hash = blake3::hash(key.as_bytes());
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&hash.as_bytes()[..8]);
+        (u64::from_le_bytes(bytes) % shard_count.max(1) as u64) as usize
+    }
+}
+
+/// Routes operations to one of several executors by shard key.
+pub struct ShardedExecutor<E> {
+    shards: Arc<Vec<E>>,
+    resolver: Arc<dyn ShardResolver>,
+    shard_column: String,
+}
+
+impl<E> Clone for ShardedExecutor<E> {
+    fn clone(&self) -> Self {
+        Self {
+            shards: self.shards.clone(),
+            resolver: self.resolver.clone(),
+            shard_column: self.shard_column.clone(),
+        }
+    }
+}
+
+impl<E: QueryExecutor> ShardedExecutor<E> {
+    /// Shard across `shards`, keyed on the `prefix` column.
+    pub fn new(shards: Vec<E>) -> Result<Self, StorageError> {
+        if shards.is_empty() {
+            return Err(StorageError::StorageError(
+                "ShardedExecutor requires at least one shard".to_string(),
+            ));
+        }
+        Ok(Self {
+            shards: Arc::new(shards),
+            resolver: Arc::new(HashShardResolver),
+            shard_column: DEFAULT_SHARD_COLUMN.to_string(),
+        })
+    }
+
+    /// Use a custom resolver instead of [`HashShardResolver`].
+    pub fn with_resolver(mut self, resolver: impl ShardResolver + 'static) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    /// Shard on `column` instead of `prefix`.
+    ///
+    /// Types without this column are sharded by their SAID.
+    pub fn with_shard_column(mut self, column: impl Into<String>) -> Self {
+        self.shard_column = column.into();
+        self
+    }
+
+    /// Number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The shard that owns `key`.
+    pub fn shard_for(&self, key: &str) -> usize {
+        self.resolver
+            .resolve(key, self.shards.len())
+            .min(self.shards.len() - 1)
+    }
+
+    /// The executor for shard `index`.
+    pub fn shard(&self, index: usize) -> Option<&E> {
+        self.shards.get(index)
+    }
+
+    /// The shard key column for `T`: the configured column if `T` has it, else the SAID.
+    fn key_column<T: Storable>(&self) -> Option<&'static str> {
+        let columns = T::columns();
+        columns
+            .iter()
+            .find(|c| **c == self.shard_column)
+            .or_else(|| columns.iter().find(|c| **c == ID_COLUMN))
+            .copied()
+    }
+
+    /// Shards that can hold rows matching `filters`, in ascending order.
+    fn targets(&self, filters: &[Filter], column: Option<&str>) -> Vec<usize> {
+        let keys = column.and_then(|column| routing_keys(filters, column));
+        match keys {
+            Some(keys) => {
+                let mut targets: Vec<usize> = keys.iter().map(|k| self.shard_for(k)).collect();
+                targets.sort_unstable();
+                targets.dedup();
+                targets
+            }
+            None => (0..self.shards.len()).collect(),
+        }
+    }
+
+    /// The shard that owns `item`.
+    fn item_shard<T: Storable>(&self, item: &T) -> Result<usize, StorageError> {
+        let key = match self.key_column::<T>().and_then(json_key::<T>) {
+            Some(json_key) => {
+                let value = serde_json::to_value(item)
+                    .map_err(|e| StorageError::StorageError(e.to_string()))?;
+                value
+                    .get(json_key)
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+            }
+            None => None,
+        };
+        Ok(self.shard_for(key.as_deref().unwrap_or(item.id())))
+    }
+}
+
+#[async_trait]
+impl<E: QueryExecutor> QueryExecutor for ShardedExecutor<E> {
+    type Transaction = ShardedTransaction<E>;
+
+    async fn fetch<T: Storable + DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+    ) -> Result<Vec<T>, StorageError> {
+        let targets = self.targets(&query.filters, self.key_column::<T>());
+        if let [shard] = targets.as_slice() {
+            return self.shards[*shard].fetch(query).await;
+        }
+
+        // Each shard may hold the whole page, so ask each for offset + limit rows
+        let mut per_shard = query.clone();
+        per_shard.offset = None;
+        per_shard.limit = query.limit.map(|l| l + query.offset.unwrap_or(0));
+
+        let results = try_join_all(
+            targets
+                .iter()
+                .map(|&shard| self.shards[shard].fetch(per_shard.clone())),
+        )
+        .await?;
+
+        merge_rows(results.into_iter().flatten().collect(), &query)
+    }
+
+    async fn fetch_optional<T: Storable + DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+    ) -> Result<Option<T>, StorageError> {
+        let targets = self.targets(&query.filters, self.key_column::<T>());
+        if let [shard] = targets.as_slice() {
+            return self.shards[*shard].fetch_optional(query).await;
+        }
+        Ok(self.fetch(query.limit(1)).await?.into_iter().next())
+    }
+
+    async fn exists<T: Storable + Send>(&self, query: Query<T>) -> Result<bool, StorageError> {
+        let targets = self.targets(&query.filters, self.key_column::<T>());
+        let results = try_join_all(
+            targets
+                .iter()
+                .map(|&shard| self.shards[shard].exists(query.clone())),
+        )
+        .await?;
+        Ok(results.into_iter().any(|found| found))
+    }
+
+    async fn count<T: Storable + Send>(&self, query: Query<T>) -> Result<u64, StorageError> {
+        let targets = self.targets(&query.filters, self.key_column::<T>());
+        let results = try_join_all(
+            targets
+                .iter()
+                .map(|&shard| self.shards[shard].count(query.clone())),
+        )
+        .await?;
+        Ok(results.into_iter().sum())
+    }
+
+    async fn aggregate<T: Storable + Send>(
+        &self,
+        query: Query<T>,
+        aggregate: Aggregate,
+        column: &str,
+    ) -> Result<Option<serde_json::Value>, StorageError> {
+        let targets = self.targets(&query.filters, self.key_column::<T>());
+        let results = try_join_all(
+            targets
+                .iter()
+                .map(|&shard| self.shards[shard].aggregate(query.clone(), aggregate, column)),
+        )
+        .await?;
+        aggregate.combine(results.into_iter().flatten())
+    }
+
+    /// Each shard contributes in proportion to how many rows it matches, so
+    /// the combined sample stays uniform across shards.
+    async fn sample<T: Storable + DeserializeOwned + Send>(
+        &self,
+        query: Query<T>,
+        n: u64,
+    ) -> Result<Vec<T>, StorageError> {
+        let targets = self.targets(&query.filters, self.key_column::<T>());
+        if let [shard] = targets.as_slice() {
+            return self.shards[*shard].sample(query, n).await;
+        }
+
+        let counts = try_join_all(
+            targets
+                .iter()
+                .map(|&shard| self.shards[shard].count(query.clone())),
+        )
+        .await?;
+        let shares = sample_shares(&counts, n);
+
+        let results = try_join_all(
+            targets
+                .iter()
+                .zip(shares)
+                .filter(|(_, share)| *share > 0)
+                .map(|(&shard, share)| self.shards[shard].sample(query.clone(), share)),
+        )
+        .await?;
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    async fn list_prefix_versions<T: Storable + Send>(
+        &self,
+        query: Query<T>,
+    ) -> Result<Vec<PrefixVersion>, StorageError> {
+        let targets = self.targets(&query.filters, self.key_column::<T>());
+        if let [shard] = targets.as_slice() {
+            return self.shards[*shard].list_prefix_versions(query).await;
+        }
+
+        // A prefix may span shards, so each lists up to offset + limit of its own
+        let mut per_shard = query.clone();
+        per_shard.offset = None;
+        per_shard.limit = query.limit.map(|l| l + query.offset.unwrap_or(0));
+
+        let results = try_join_all(
+            targets
+                .iter()
+                .map(|&shard| self.shards[shard].list_prefix_versions(per_shard.clone())),
+        )
+        .await?;
+
+        let mut merged = BTreeMap::new();
+        for listed in results.into_iter().flatten() {
+            let version = merged.entry(listed.prefix).or_insert(listed.version);
+            *version = (*version).max(listed.version);
+        }
+        Ok(merged
+            .into_iter()
+            .map(|(prefix, version)| PrefixVersion { prefix, version })
+            .skip(query.offset.unwrap_or(0) as usize)
+            .take(query.limit.map_or(usize::MAX, |l| l as usize))
+            .collect())
+    }
+
+    async fn delete<T: Storable + Send>(&self, delete: Delete<T>) -> Result<u64, StorageError> {
+        let targets = self.targets(&delete.filters, self.key_column::<T>());
+        let results = try_join_all(
+            targets
+                .iter()
+                .map(|&shard| self.shards[shard].delete(delete.clone())),
+        )
+        .await?;
+        Ok(results.into_iter().sum())
+    }
+
+    async fn soft_delete<T: Storable + Send>(
+        &self,
+        delete: Delete<T>,
+    ) -> Result<u64, StorageError> {
+        let targets = self.targets(&delete.filters, self.key_column::<T>());
+        let results = try_join_all(
+            targets
+                .iter()
+                .map(|&shard| self.shards[shard].soft_delete(delete.clone())),
+        )
+        .await?;
+        Ok(results.into_iter().sum())
+    }
+
+    async fn insert<T: Storable + serde::Serialize + Send + Sync>(
+        &self,
+        item: &T,
+    ) -> Result<u64, StorageError> {
+        let shard = self.item_shard(item)?;
+        self.shards[shard].insert(item).await
+    }
+
+    async fn insert_or_ignore<T: Storable + serde::Serialize + Send + Sync>(
+        &self,
+        item: &T,
+    ) -> Result<u64, StorageError> {
+        let shard = self.item_shard(item)?;
+        self.shards[shard].insert_or_ignore(item).await
+    }
+
+    async fn upsert<T: Storable + serde::Serialize + Send + Sync>(
+        &self,
+        item: &T,
+    ) -> Result<u64, StorageError> {
+        let shard = self.item_shard(item)?;
+        self.shards[shard].upsert(item).await
+    }
+
+    async fn begin_transaction(&self) -> Result<Self::Transaction, StorageError> {
+        Ok(ShardedTransaction {
+            executor: self.clone(),
+            active: None,
+        })
+    }
+
+    async fn fetch_column(&self, query: ColumnQuery) -> Result<Vec<String>, StorageError> {
+        let targets = self.targets(&query.filters, Some(&self.shard_column));
+        if let [shard] = targets.as_slice() {
+            return self.shards[*shard].fetch_column(query).await;
+        }
+
+        let results = try_join_all(
+            targets
+                .iter()
+                .map(|&shard| self.shards[shard].fetch_column(query.clone())),
+        )
+        .await?;
+        let mut values: Vec<String> = results.into_iter().flatten().collect();
+
+        match query.order {
+            Some(Order::Asc) => values.sort(),
+            Some(Order::Desc) => values.sort_by(|a, b| b.cmp(a)),
+            None => {}
+        }
+        if query.distinct {
+            let mut seen = HashSet::new();
+            values.retain(|v| seen.insert(v.clone()));
+        }
+        if let Some(limit) = query.limit {
+            values.truncate(limit as usize);
+        }
+        Ok(values)
+    }
+}
+
+/// A transaction on a single shard of a [`ShardedExecutor`].
+///
+/// The underlying transaction starts lazily on the shard of the first routed
+/// operation (usually `acquire_advisory_lock(prefix)`); operations that can't
+/// be routed to that shard fail rather than silently spanning shards.
+pub struct ShardedTransaction<E: QueryExecutor> {
+    executor: ShardedExecutor<E>,
+    active: Option<(usize, E::Transaction)>,
+}
+
+impl<E: QueryExecutor> ShardedTransaction<E> {
+    /// The transaction on `shard`, starting it if this is the first operation.
+    async fn on_shard(&mut self, shard: usize) -> Result<&mut E::Transaction, StorageError> {
+        if let Some((pinned, _)) = &self.active
+            && *pinned != shard
+        {
+            return Err(StorageError::StorageError(format!(
+                "Transaction is bound to shard {} but the operation targets shard {}",
+                pinned, shard
+            )));
+        }
+
+        if self.active.is_none() {
+            let tx = self.executor.shards[shard].begin_transaction().await?;
+            self.active = Some((shard, tx));
+        }
+
+        match &mut self.active {
+            Some((_, tx)) => Ok(tx),
+            None => Err(StorageError::StorageError(
+                "Shard transaction was not started".to_string(),
+            )),
+        }
+    }
+
+    /// The single shard `filters` route to.
+    fn single_target(
+        &self,
+        filters: &[Filter],
+        column: Option<&str>,
+    ) -> Result<usize, StorageError> {
+        match self.executor.targets(filters, column).as_slice() {
+            [shard] => Ok(*shard),
+            _ => Err(StorageError::StorageError(format!(
+                "Cross-shard operations are not supported in a transaction; filter on `{}`",
+                column.unwrap_or(&self.executor.shard_column)
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl<E: QueryExecutor> TransactionExecutor for ShardedTransaction<E> {
+    async fn fetch<T: Storable + DeserializeOwned + Send>(
+        &mut self,
+        query: Query<T>,
+    ) -> Result<Vec<T>, StorageError> {
+        let shard = self.single_target(&query.filters, self.executor.key_column::<T>())?;
+        self.on_shard(shard).await?.fetch(query).await
+    }
+
+    async fn delete<T: Storable + Send>(&mut self, delete: Delete<T>) -> Result<u64, StorageError> {
+        let shard = self.single_target(&delete.filters, self.executor.key_column::<T>())?;
+        self.on_shard(shard).await?.delete(delete).await
+    }
+
+    async fn insert<T: Storable + serde::Serialize + Send + Sync>(
+        &mut self,
+        item: &T,
+    ) -> Result<u64, StorageError> {
+        let shard = self.executor.item_shard(item)?;
+        self.on_shard(shard).await?.insert(item).await
+    }
+
+    async fn acquire_advisory_lock(&mut self, key: &str) -> Result<(), StorageError> {
+        let shard = self.executor.shard_for(key);
+        self.on_shard(shard).await?.acquire_advisory_lock(key).await
+    }
+
+    async fn commit(self) -> Result<(), StorageError> {
+        match self.active {
+            Some((_, tx)) => tx.commit().await,
+            None => Ok(()),
+        }
+    }
+
+    async fn rollback(self) -> Result<(), StorageError> {
+        match self.active {
+            Some((_, tx)) => tx.rollback().await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Shard keys pinned by an `eq`/`in` filter on `column`, if any.
+fn routing_keys(filters: &[Filter], column: &str) -> Option<Vec<String>> {
+    filters.iter().find_map(|filter| match filter {
+        Filter::Eq(field, Value::String(key)) if field == column => Some(vec![key.clone()]),
+        Filter::In(field, Value::Strings(keys)) if field == column => Some(keys.clone()),
+        _ => None,
+    })
+}
+
+/// JSON key for a column of `T`.
+fn json_key<T: Storable>(column: &str) -> Option<&'static str> {
+    T::columns()
+        .iter()
+        .position(|c| *c == column)
+        .and_then(|i| T::json_keys().get(i).copied())
+}
+
+/// Split `n` across shards matching `counts` rows, in proportion to their
+/// counts and never more than a shard holds. Rounding remainders go to the
+/// shards with the largest fractional shares.
+fn sample_shares(counts: &[u64], n: u64) -> Vec<u64> {
+    let total: u64 = counts.iter().sum();
+    if total <= n {
+        return counts.to_vec();
+    }
+
+    let exact: Vec<f64> = counts
+        .iter()
+        .map(|&count| count as f64 * n as f64 / total as f64)
+        .collect();
+    let mut shares: Vec<u64> = exact.iter().map(|share| share.floor() as u64).collect();
+    let mut by_remainder: Vec<usize> = (0..counts.len()).collect();
+    by_remainder.sort_by(|&a, &b| {
+        (exact[b] - exact[b].floor())
+            .partial_cmp(&(exact[a] - exact[a].floor()))
+            .unwrap_or(Ordering::Equal)
+    });
+    let short = n.saturating_sub(shares.iter().sum());
+    for &shard in by_remainder.iter().take(short as usize) {
+        shares[shard] += 1;
+    }
+    shares
+}
+
+/// Apply a query's latest-per, ordering, distinct-on, offset, and limit to rows gathered from
+/// several shards.
+fn merge_rows<T: Storable>(rows: Vec<T>, query: &Query<T>) -> Result<Vec<T>, StorageError> {
+    let sort_fields: Vec<(&str, Order)> = query
+        .order_by
+        .iter()
+        .map(|(field, order)| (json_key::<T>(field).unwrap_or(field.as_str()), *order))
+        .collect();
+    let distinct_fields: Vec<&str> = query
+        .distinct_on
+        .iter()
+        .map(|field| json_key::<T>(field).unwrap_or(field.as_str()))
+        .collect();
+
+    let mut keyed = Vec::with_capacity(rows.len());
+    for row in rows {
+        let value =
+            serde_json::to_value(&row).map_err(|e| StorageError::StorageError(e.to_string()))?;
+        keyed.push((value, row));
+    }
+
+    if let Some((partition, order)) = &query.latest_per {
+        let partition = json_key::<T>(partition).unwrap_or(partition.as_str());
+        let order = json_key::<T>(order).unwrap_or(order.as_str());
+        keyed.sort_by(|(a, _), (b, _)| compare_values(b.get(order), a.get(order)));
+        let mut seen = HashSet::new();
+        keyed.retain(|(value, _)| {
+            seen.insert(
+                value
+                    .get(partition)
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            )
+        });
+    }
+
+    if !sort_fields.is_empty() {
+        keyed.sort_by(|(a, _), (b, _)| {
+            for (field, order) in &sort_fields {
+                let ordering = compare_values(a.get(*field), b.get(*field));
+                let ordering = match order {
+                    Order::Asc => ordering,
+                    Order::Desc => ordering.reverse(),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            Ordering::Equal
+        });
+    }
+
+    if !distinct_fields.is_empty() && query.latest_per.is_none() {
+        let mut seen = HashSet::new();
+        keyed.retain(|(value, _)| {
+            let key: Vec<String> = distinct_fields
+                .iter()
+                .map(|field| value.get(*field).map(|v| v.to_string()).unwrap_or_default())
+                .collect();
+            seen.insert(key)
+        });
+    }
+
+    let offset = query.offset.unwrap_or(0) as usize;
+    let limit = query.limit.map(|l| l as usize).unwrap_or(usize::MAX);
+    Ok(keyed
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(|(_, row)| row)
+        .collect())
+}
+
+/// Compare two JSON field values the way PostgreSQL would order them (nulls last).
+fn compare_values(a: Option<&serde_json::Value>, b: Option<&serde_json::Value>) -> Ordering {
+    use serde_json::Value as Json;
+
+    let a = a.filter(|v| !v.is_null());
+    let b = b.filter(|v| !v.is_null());
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(Json::Number(x)), Some(Json::Number(y))) => match (x.as_i64(), y.as_i64()) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            _ => x
+                .as_f64()
+                .partial_cmp(&y.as_f64())
+                .unwrap_or(Ordering::Equal),
+        },
+        (Some(Json::String(x)), Some(Json::String(y))) => {
+            // Timestamps serialize with a variable number of fractional digits,
+            // so compare them as instants rather than as text
+            match (
+                DateTime::<FixedOffset>::parse_from_rfc3339(x),
+                DateTime::<FixedOffset>::parse_from_rfc3339(y),
+            ) {
+                (Ok(x), Ok(y)) => x.cmp(&y),
+                _ => x.cmp(y),
+            }
+        }
+        (Some(Json::Bool(x)), Some(Json::Bool(y))) => x.cmp(y),
+        (Some(x), Some(y)) => x.to_string().cmp(&y.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn hash_resolver_is_stable_and_in_range() {
+        let resolver = HashShardResolver;
+        for key in ["Eabc", "Edef", "Eghi", "Ejkl"] {
+            let shard = resolver.resolve(key, 4);
+            assert!(shard < 4);
+            assert_eq!(shard, resolver.resolve(key, 4));
+        }
+        assert_eq!(resolver.resolve("Eabc", 1), 0);
+    }
+
+    #[test]
+    fn routing_keys_from_eq_and_in() {
+        let eq = [Filter::Eq("prefix".to_string(), Value::from("Eabc"))];
+        assert_eq!(routing_keys(&eq, "prefix"), Some(vec!["Eabc".to_string()]));
+
+        let any = [Filter::In(
+            "prefix".to_string(),
+            Value::from(vec!["Eabc", "Edef"]),
+        )];
+        assert_eq!(
+            routing_keys(&any, "prefix"),
+            Some(vec!["Eabc".to_string(), "Edef".to_string()])
+        );
+
+        let other = [Filter::Eq("said".to_string(), Value::from("Exyz"))];
+        assert_eq!(routing_keys(&other, "prefix"), None);
+    }
+
+    #[test]
+    fn compare_values_orders_timestamps_and_nulls() {
+        let earlier = json!("2024-01-01T00:00:00.5Z");
+        let later = json!("2024-01-01T00:00:01Z");
+        assert_eq!(compare_values(Some(&earlier), Some(&later)), Ordering::Less);
+        assert_eq!(compare_values(None, Some(&later)), Ordering::Greater);
+        assert_eq!(
+            compare_values(Some(&json!(10)), Some(&json!(9))),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn sample_shares_follow_shard_sizes() {
+        assert_eq!(sample_shares(&[600, 300, 100], 10), vec![6, 3, 1]);
+        assert_eq!(sample_shares(&[2, 1, 1], 3), vec![1, 1, 1]);
+        assert_eq!(sample_shares(&[5, 0, 3], 100), vec![5, 0, 3]);
+        assert_eq!(sample_shares(&[1, 1, 1], 2).iter().sum::<u64>(), 2);
+    }
+}
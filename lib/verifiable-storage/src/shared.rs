@@ -0,0 +1,133 @@
+//! Sharing repositories between handlers.
+//!
+//! `Arc<R>` is a repository wherever `R` is, so one repository can sit in
+//! axum or tower state and be cloned into every request. The aliases
+//! [`SharedVersionedRepository`] and [`SharedUnversionedRepository`] erase the
+//! concrete type as well, letting services depend on the trait alone:
+//!
+//! ```text
+//! let domains: SharedVersionedRepository<Domain> = Arc::new(DomainRepository::new(pool));
+//! let app = Router::new().route("/domains/{prefix}", get(latest)).with_state(domains);
+//! ```
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{SelfAddressed, StorageError, UnversionedRepository, Versioned, VersionedRepository};
+
+/// A type-erased, cheaply cloned [`VersionedRepository`].
+pub type SharedVersionedRepository<T> = Arc<dyn VersionedRepository<T> + Send + Sync>;
+
+/// A type-erased, cheaply cloned [`UnversionedRepository`].
+pub type SharedUnversionedRepository<T> = Arc<dyn UnversionedRepository<T> + Send + Sync>;
+
+/// Forwards every method, including `import_history`, so a repository's own
+/// overrides still apply through the `Arc`.
+#[async_trait]
+impl<T, R> VersionedRepository<T> for Arc<R>
+where
+    T: SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    R: VersionedRepository<T> + Send + Sync + ?Sized,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        (**self).create(item).await
+    }
+
+    async fn update(&self, item: T) -> Result<T, StorageError> {
+        (**self).update(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        (**self).insert(item).await
+    }
+
+    async fn import_history(&self, items: Vec<T>) -> Result<Vec<T>, StorageError> {
+        (**self).import_history(items).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        (**self).get_by_said(said).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        (**self).get_latest(prefix).await
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        (**self).get_history(prefix).await
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        (**self).exists(prefix).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        (**self).delete_soft(said).await
+    }
+}
+
+#[async_trait]
+impl<T, R> UnversionedRepository<T> for Arc<R>
+where
+    T: SelfAddressed + Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    R: UnversionedRepository<T> + Send + Sync + ?Sized,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        (**self).create(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        (**self).insert(item).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        (**self).get_by_said(said).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        (**self).delete_soft(said).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReadOnlyRepository, StorageDatetime};
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "zones")]
+    #[serde(rename_all = "camelCase")]
+    struct Zone {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        #[created_at]
+        created_at: StorageDatetime,
+    }
+
+    fn versioned<T, R>(_: &R)
+    where
+        T: SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + Send + Sync,
+        R: VersionedRepository<T> + Clone + Send + Sync + 'static,
+    {
+    }
+
+    #[test]
+    fn shared_repositories_compose_with_wrappers() {
+        fn check(shared: SharedVersionedRepository<Zone>) {
+            versioned::<Zone, _>(&shared);
+            versioned::<Zone, _>(&ReadOnlyRepository::new(shared.clone()));
+            let wrapped: SharedVersionedRepository<Zone> =
+                Arc::new(ReadOnlyRepository::new(shared));
+            versioned::<Zone, _>(&wrapped);
+        }
+        let _ = check;
+    }
+}
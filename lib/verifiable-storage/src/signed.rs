@@ -0,0 +1,198 @@
+//! Signatures stored alongside the records they sign.
+//!
+//! A [`RecordSignature`] is its own self-addressed record in a companion
+//! table, pointing at the SAID it signs; the type is the application's, so it
+//! can carry whatever a verifier needs (a public key, a key index, a
+//! receipt). A [`SignedRepository`] writes an item together with its
+//! signatures and reads them back by SAID, for any backend. The Postgres and
+//! SurrealDB derives implement it with `#[stored(signatures = MySignature)]`:
+//!
+//! ```text
+//! #[derive(SelfAddressed, Serialize, Deserialize, Clone)]
+//! #[storable(table = "event_signatures")]
+//! pub struct EventSignature {
+//!     #[said]
+//!     pub said: String,
+//!     pub event_said: String,
+//!     pub public_key: String,
+//!     pub signature: String,
+//! }
+//!
+//! impl RecordSignature for EventSignature {
+//!     fn signed_said_column() -> &'static str { "event_said" }
+//!     fn signed_said(&self) -> &str { &self.event_said }
+//! }
+//! ```
+//!
+//! On Postgres the signatures table comes from a migration, indexed on the
+//! signed SAID so `fetch_signatures` stays cheap:
+//!
+//! ```text
+//! CREATE TABLE event_signatures (
+//!     said TEXT PRIMARY KEY,
+//!     event_said TEXT NOT NULL,
+//!     public_key TEXT NOT NULL,
+//!     signature TEXT NOT NULL
+//! );
+//! CREATE INDEX event_signatures_event_said ON event_signatures (event_said);
+//! ```
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{SelfAddressed, Storable, StorageError, Versioned, VersionedRepository};
+
+/// A stored signature over another record's SAID.
+pub trait RecordSignature:
+    Storable + SelfAddressed + Serialize + DeserializeOwned + Clone + Send + Sync + 'static
+{
+    /// The column holding the signed SAID.
+    fn signed_said_column() -> &'static str;
+
+    /// The SAID this signature covers.
+    fn signed_said(&self) -> &str;
+}
+
+/// Storage for items of type `T` signed with signatures of type `S`.
+#[async_trait]
+pub trait SignedRepository<T, S>: Send + Sync
+where
+    T: SelfAddressed + Send + Sync + 'static,
+    S: RecordSignature,
+{
+    /// Store `item`, which must already have its SAID, with `signatures` over it.
+    ///
+    /// Backends with transactions (Postgres) commit the item and its
+    /// signatures together. Elsewhere signatures are written first and ignored
+    /// if already stored, so an item is never visible unsigned and a failed
+    /// call can be retried.
+    async fn create_with_signatures(&self, item: T, signatures: Vec<S>) -> Result<T, StorageError>;
+
+    /// All stored signatures over any of `saids`, in no particular order.
+    async fn fetch_signatures(&self, saids: &[String]) -> Result<Vec<S>, StorageError>;
+
+    /// The signatures over `said`.
+    async fn get_signatures(&self, said: &str) -> Result<Vec<S>, StorageError> {
+        self.fetch_signatures(&[said.to_string()]).await
+    }
+
+    /// The signatures over each of `saids`, keyed by SAID. SAIDs without
+    /// signatures are left out.
+    async fn get_signatures_by_saids(
+        &self,
+        saids: &[String],
+    ) -> Result<HashMap<String, Vec<S>>, StorageError> {
+        Ok(group_signatures(self.fetch_signatures(saids).await?))
+    }
+
+    /// Every version of `prefix` with its signatures, oldest first. Fails if
+    /// any version is unsigned.
+    async fn get_signed_history(&self, prefix: &str) -> Result<Vec<(T, Vec<S>)>, StorageError>
+    where
+        Self: VersionedRepository<T>,
+        T: Versioned + Serialize + DeserializeOwned + Clone,
+    {
+        let history = self.get_history(prefix).await?;
+        let saids: Vec<String> = history.iter().map(|item| item.get_said()).collect();
+        let mut signatures = self.get_signatures_by_saids(&saids).await?;
+        history
+            .into_iter()
+            .map(|item| {
+                let said = item.get_said();
+                match signatures.remove(&said) {
+                    Some(signed) => Ok((item, signed)),
+                    None => Err(StorageError::InvalidSignature(format!(
+                        "No signatures found for {}",
+                        said
+                    ))),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Fail unless every signature covers `item`'s SAID. Implementations call
+/// this before writing anything.
+pub fn check_signatures_cover<T: SelfAddressed, S: RecordSignature>(
+    item: &T,
+    signatures: &[S],
+) -> Result<(), StorageError> {
+    let said = item.get_said();
+    match signatures.iter().find(|s| s.signed_said() != said) {
+        Some(stray) => Err(StorageError::InvalidSignature(format!(
+            "Signature {} covers {}, not {}",
+            stray.get_said(),
+            stray.signed_said(),
+            said
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn group_signatures<S: RecordSignature>(signatures: Vec<S>) -> HashMap<String, Vec<S>> {
+    let mut grouped: HashMap<String, Vec<S>> = HashMap::new();
+    for signature in signatures {
+        grouped
+            .entry(signature.signed_said().to_string())
+            .or_default()
+            .push(signature);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "receipts")]
+    #[serde(rename_all = "camelCase")]
+    struct Receipt {
+        #[said]
+        said: String,
+        body: String,
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "receipt_signatures")]
+    #[serde(rename_all = "camelCase")]
+    struct ReceiptSignature {
+        #[said]
+        said: String,
+        receipt_said: String,
+        signature: String,
+    }
+
+    impl RecordSignature for ReceiptSignature {
+        fn signed_said_column() -> &'static str {
+            "receipt_said"
+        }
+
+        fn signed_said(&self) -> &str {
+            &self.receipt_said
+        }
+    }
+
+    #[test]
+    fn signatures_must_cover_the_item_and_group_by_said() {
+        let receipt = Receipt::create("paid".to_string()).unwrap();
+        let other = Receipt::create("refunded".to_string()).unwrap();
+        let sign = |said: &str, signature: &str| {
+            ReceiptSignature::create(said.to_string(), signature.to_string()).unwrap()
+        };
+
+        let signatures = vec![sign(&receipt.said, "a"), sign(&receipt.said, "b")];
+        check_signatures_cover(&receipt, &signatures).unwrap();
+        let stray = vec![sign(&receipt.said, "a"), sign(&other.said, "c")];
+        assert!(matches!(
+            check_signatures_cover(&receipt, &stray),
+            Err(StorageError::InvalidSignature(_))
+        ));
+
+        let grouped = group_signatures([signatures, stray].concat());
+        assert_eq!(grouped[&receipt.said].len(), 3);
+        assert_eq!(grouped[&other.said].len(), 1);
+    }
+}
@@ -0,0 +1,160 @@
+//! Signatures over SAIDs.
+//!
+//! A SAID commits to its record, so signing the SAID's qb64 text signs the
+//! record without re-serializing it. [`sign_said`] and
+//! [`verify_said_signature`] do exactly that through the [`Signer`] and
+//! [`Verifier`] traits; with the `ed25519` feature, `ed25519_dalek` keys
+//! implement them.
+//!
+//! Keys that can't live in process memory (an HSM over PKCS#11, a cloud KMS)
+//! implement [`AsyncSigner`] instead; every [`Signer`] is also one, so code
+//! that signs, like [`sign_said_async`] and
+//! [`SignedResponse::sign_async`](crate::SignedResponse::sign_async), takes
+//! either. A KMS-backed signer is a thin wrapper over its client:
+//!
+//! ```text
+//! struct KmsSigner { client: aws_sdk_kms::Client, key_id: String }
+//!
+//! #[async_trait]
+//! impl AsyncSigner for KmsSigner {
+//!     async fn sign(&self, message: &[u8]) -> Result<Vec<u8>, StorageError> {
+//!         let output = self.client.sign().key_id(&self.key_id).message(message.into())
+//!             .signing_algorithm(SigningAlgorithmSpec::EcdsaSha256).send().await
+//!             .map_err(|e| StorageError::StorageError(e.to_string()))?;
+//!         Ok(output.signature.map(Blob::into_inner).unwrap_or_default())
+//!     }
+//! }
+//! ```
+
+use alloc::{string::ToString, vec::Vec};
+
+#[cfg(feature = "std")]
+use async_trait::async_trait;
+
+use crate::StorageError;
+
+/// A private key, or a handle to one, that signs messages.
+pub trait Signer: Send + Sync {
+    /// Sign `message`, returning the raw signature bytes.
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, StorageError>;
+}
+
+/// A public key that checks signatures made by its [`Signer`].
+pub trait Verifier {
+    /// Fail with [`StorageError::InvalidSignature`] unless `signature` is over `message`.
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), StorageError>;
+}
+
+/// A signer reached asynchronously, such as a key held by an HSM or KMS.
+#[cfg(feature = "std")]
+#[async_trait]
+pub trait AsyncSigner: Send + Sync {
+    /// Sign `message`, returning the raw signature bytes.
+    async fn sign(&self, message: &[u8]) -> Result<Vec<u8>, StorageError>;
+}
+
+#[cfg(feature = "std")]
+#[async_trait]
+impl<S: Signer> AsyncSigner for S {
+    async fn sign(&self, message: &[u8]) -> Result<Vec<u8>, StorageError> {
+        Signer::sign(self, message)
+    }
+}
+
+/// Sign `said`'s qb64 text.
+pub fn sign_said(said: &str, signer: &impl Signer) -> Result<Vec<u8>, StorageError> {
+    signer.sign(said_bytes(said)?)
+}
+
+/// Check `signature` was made over `said` by the holder of `key`.
+pub fn verify_said_signature(
+    said: &str,
+    signature: &[u8],
+    key: &impl Verifier,
+) -> Result<(), StorageError> {
+    key.verify(said_bytes(said)?, signature)
+}
+
+/// [`sign_said`] with a signer that may be remote.
+#[cfg(feature = "std")]
+pub async fn sign_said_async(
+    said: &str,
+    signer: &(impl AsyncSigner + ?Sized),
+) -> Result<Vec<u8>, StorageError> {
+    signer.sign(said_bytes(said)?).await
+}
+
+/// Lowercase hex, for carrying raw signatures in text.
+#[cfg(feature = "std")]
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn decode_hex(hex: &str) -> Result<Vec<u8>, StorageError> {
+    let invalid = || StorageError::InvalidSignature(format!("Malformed hex signature: {}", hex));
+    if !hex.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+fn said_bytes(said: &str) -> Result<&[u8], StorageError> {
+    if said.is_empty() {
+        return Err(StorageError::InvalidSaid(
+            "Cannot sign an empty SAID".to_string(),
+        ));
+    }
+    Ok(said.as_bytes())
+}
+
+#[cfg(feature = "ed25519")]
+impl Signer for ed25519_dalek::SigningKey {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, StorageError> {
+        Ok(ed25519_dalek::Signer::sign(self, message)
+            .to_bytes()
+            .to_vec())
+    }
+}
+
+#[cfg(feature = "ed25519")]
+impl Verifier for ed25519_dalek::VerifyingKey {
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), StorageError> {
+        let signature = ed25519_dalek::Signature::from_slice(signature)
+            .map_err(|e| StorageError::InvalidSignature(e.to_string()))?;
+        self.verify_strict(message, &signature)
+            .map_err(|e| StorageError::InvalidSignature(e.to_string()))
+    }
+}
+
+#[cfg(all(test, feature = "ed25519"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ed25519_signatures_cover_the_said() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let said = crate::compute_said(&"record").unwrap();
+        let signature = sign_said(&said, &key).unwrap();
+        assert_eq!(signature.len(), 64);
+        verify_said_signature(&said, &signature, &key.verifying_key()).unwrap();
+
+        let other = crate::compute_said(&"other").unwrap();
+        assert!(matches!(
+            verify_said_signature(&other, &signature, &key.verifying_key()),
+            Err(StorageError::InvalidSignature(_))
+        ));
+        assert!(matches!(
+            verify_said_signature(&said, &signature[..32], &key.verifying_key()),
+            Err(StorageError::InvalidSignature(_))
+        ));
+        assert!(sign_said("", &key).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! Limits on the serialized size of written records.
+//!
+//! `SizeLimitedRepository<R>` refuses writes whose JSON serialization exceeds
+//! a configured number of bytes, failing with [`StorageError::PayloadTooLarge`]
+//! before the inner repository digests or stores anything. Every checked
+//! record's size is reported to a [`MetricsHook`], so oversized fields show up
+//! in the payload distribution before they reach the limit.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    MetricsHook, NoopMetrics, SelfAddressed, Storable, StorageError, UnversionedRepository,
+    Versioned, VersionedRepository,
+};
+
+/// Wraps a repository so that writes larger than a byte limit are rejected.
+#[derive(Clone)]
+pub struct SizeLimitedRepository<R> {
+    inner: R,
+    max_bytes: usize,
+    metrics: Arc<dyn MetricsHook>,
+}
+
+impl<R> SizeLimitedRepository<R> {
+    /// Wrap `inner`, allowing records of up to `max_bytes` serialized bytes.
+    pub fn new(inner: R, max_bytes: usize) -> Self {
+        Self {
+            inner,
+            max_bytes,
+            metrics: Arc::new(NoopMetrics),
+        }
+    }
+
+    /// Report the size of every checked record to `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<dyn MetricsHook>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    fn check<T: Storable + SelfAddressed + Serialize>(&self, item: &T) -> Result<(), StorageError> {
+        let size = serde_json::to_vec(item)?.len();
+        self.metrics.on_payload(T::table_name(), size);
+        within_limit(item, size, self.max_bytes)
+    }
+}
+
+/// Fail if `item` serializes to more than `max_bytes`, returning its size
+/// otherwise.
+pub fn check_payload_size<T: Storable + SelfAddressed + Serialize>(
+    item: &T,
+    max_bytes: usize,
+) -> Result<usize, StorageError> {
+    let size = serde_json::to_vec(item)?.len();
+    within_limit(item, size, max_bytes)?;
+    Ok(size)
+}
+
+fn within_limit<T: Storable + SelfAddressed>(
+    item: &T,
+    size: usize,
+    max_bytes: usize,
+) -> Result<(), StorageError> {
+    if size > max_bytes {
+        return Err(StorageError::PayloadTooLarge(format!(
+            "{} {} is {} bytes, over the {} byte limit",
+            T::table_name(),
+            item.get_said(),
+            size,
+            max_bytes
+        )));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl<T, R> VersionedRepository<T> for SizeLimitedRepository<R>
+where
+    T: Storable + SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        self.check(&item)?;
+        self.inner.create(item).await
+    }
+
+    async fn update(&self, item: T) -> Result<T, StorageError> {
+        self.check(&item)?;
+        self.inner.update(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.check(&item)?;
+        self.inner.insert(item).await
+    }
+
+    async fn import_history(&self, items: Vec<T>) -> Result<Vec<T>, StorageError> {
+        for item in &items {
+            self.check(item)?;
+        }
+        self.inner.import_history(items).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_latest(prefix).await
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        self.inner.get_history(prefix).await
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        self.inner.exists(prefix).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[async_trait]
+impl<T, R> UnversionedRepository<T> for SizeLimitedRepository<R>
+where
+    T: Storable + SelfAddressed + Serialize + DeserializeOwned + Clone + 'static,
+    R: UnversionedRepository<T> + Send + Sync,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        self.check(&item)?;
+        self.inner.create(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        self.check(&item)?;
+        self.inner.insert(item).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "notes")]
+    #[serde(rename_all = "camelCase")]
+    struct Note {
+        #[said]
+        said: String,
+        body: String,
+    }
+
+    #[test]
+    fn payloads_over_the_limit_are_rejected() {
+        let short = Note::create("short".to_string()).unwrap();
+        let size = check_payload_size(&short, 1024).unwrap();
+        assert_eq!(size, serde_json::to_vec(&short).unwrap().len());
+        assert_eq!(check_payload_size(&short, size).unwrap(), size);
+
+        let long = Note::create("x".repeat(4096)).unwrap();
+        assert!(matches!(
+            check_payload_size(&long, 1024),
+            Err(StorageError::PayloadTooLarge(_))
+        ));
+    }
+}
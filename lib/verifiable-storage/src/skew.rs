@@ -0,0 +1,163 @@
+//! Rejection of timestamps from the future.
+//!
+//! `SkewCheckedRepository<R>` refuses writes whose `#[created_at]` time is
+//! later than now plus an allowed clock skew, failing with
+//! [`StorageError::FutureTimestamp`]. Without it, a forged future timestamp is
+//! stored and linked into the chain like any other. `update` is not checked,
+//! since `increment()` stamps the new version with the current time.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    SelfAddressed, Storable, StorageDatetime, StorageError, UnversionedRepository, Versioned,
+    VersionedRepository, expiry::datetime_column, storable::ManagedField,
+};
+
+/// Wraps a repository so that writes stamped in the future are rejected.
+#[derive(Debug, Clone)]
+pub struct SkewCheckedRepository<R> {
+    inner: R,
+    max_skew: Duration,
+}
+
+impl<R> SkewCheckedRepository<R> {
+    /// Wrap `inner`, allowing `created_at` to run up to `max_skew` ahead of the local clock.
+    pub fn new(inner: R, max_skew: Duration) -> Self {
+        Self { inner, max_skew }
+    }
+}
+
+/// Fail if `item`'s created_at is more than `max_skew` past now. Types without
+/// a created_at column pass.
+pub fn check_created_at<T: Storable + SelfAddressed>(
+    item: &T,
+    max_skew: Duration,
+) -> Result<(), StorageError> {
+    let Some((column, _)) = T::managed_columns()
+        .iter()
+        .find(|(_, field)| *field == ManagedField::CreatedAt)
+    else {
+        return Ok(());
+    };
+    match datetime_column(item, column)? {
+        Some(created_at) if created_at > StorageDatetime::now() + max_skew => {
+            Err(StorageError::FutureTimestamp(format!(
+                "{} {} was created at {}",
+                T::table_name(),
+                item.get_said(),
+                created_at
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl<T, R> VersionedRepository<T> for SkewCheckedRepository<R>
+where
+    T: Storable + SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        check_created_at(&item, self.max_skew)?;
+        self.inner.create(item).await
+    }
+
+    async fn update(&self, item: T) -> Result<T, StorageError> {
+        self.inner.update(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        check_created_at(&item, self.max_skew)?;
+        self.inner.insert(item).await
+    }
+
+    async fn import_history(&self, items: Vec<T>) -> Result<Vec<T>, StorageError> {
+        for item in &items {
+            check_created_at(item, self.max_skew)?;
+        }
+        self.inner.import_history(items).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_latest(prefix).await
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        self.inner.get_history(prefix).await
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        self.inner.exists(prefix).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[async_trait]
+impl<T, R> UnversionedRepository<T> for SkewCheckedRepository<R>
+where
+    T: Storable + SelfAddressed + Serialize + DeserializeOwned + Clone + 'static,
+    R: UnversionedRepository<T> + Send + Sync,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        check_created_at(&item, self.max_skew)?;
+        self.inner.create(item).await
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        check_created_at(&item, self.max_skew)?;
+        self.inner.insert(item).await
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "receipts")]
+    #[serde(rename_all = "camelCase")]
+    struct Receipt {
+        #[said]
+        said: String,
+        #[created_at]
+        created_at: StorageDatetime,
+        body: String,
+    }
+
+    #[test]
+    fn future_timestamps_beyond_the_skew_are_rejected() {
+        let skew = Duration::from_secs(30);
+        let now = Receipt::create("now".to_string()).unwrap();
+        check_created_at(&now, skew).unwrap();
+
+        let soon = StorageDatetime::now() + Duration::from_secs(5);
+        let within = Receipt::create_with_timestamp("soon".to_string(), soon).unwrap();
+        check_created_at(&within, skew).unwrap();
+
+        let later = StorageDatetime::now() + Duration::from_secs(3600);
+        let forged = Receipt::create_with_timestamp("later".to_string(), later).unwrap();
+        assert!(matches!(
+            check_created_at(&forged, skew),
+            Err(StorageError::FutureTimestamp(_))
+        ));
+    }
+}
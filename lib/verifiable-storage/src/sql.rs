@@ -0,0 +1,847 @@
+//! SQL rendering for queries.
+//!
+//! Executors render every statement through these functions, so the output of
+//! `to_sql` is exactly what runs against the database. Rendering is pure, which
+//! lets downstream crates snapshot-test their query construction without one.
+
+use crate::{
+    Aggregate, ColumnQuery, Delete, Filter, Join, ManagedField, Order, Query, Storable,
+    StorageDatetime, StorageError, Value,
+};
+
+/// The SQL dialect to render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// PostgreSQL, with positional `$1`, `$2`, ... placeholders.
+    Postgres,
+    /// SurrealQL, with named `$p0`, `$p1`, ... placeholders (numbered by filter position,
+    /// so a subquery in filter 2 binds `$p2_0`, `$p2_1`, ...).
+    Surreal,
+}
+
+/// Bound values keyed by placeholder (including the `$`), in binding order.
+pub type SqlParams = Vec<(String, Value)>;
+
+impl<T> Query<T> {
+    /// Render the SELECT statement and its parameters.
+    pub fn to_sql(&self, dialect: Dialect) -> (String, SqlParams) {
+        // Use table.* when joining to only return columns from the main table
+        let select_cols = if self.joins.is_empty() {
+            "*".to_string()
+        } else {
+            format!("{}.*", self.table)
+        };
+
+        let mut params = Vec::new();
+        let sql = select_sql(self, &select_cols, dialect, "", &mut params);
+        (sql, params)
+    }
+
+    /// Render a statement checking whether any row matches the query's filters.
+    ///
+    /// PostgreSQL returns a single boolean; SurrealQL returns a `count` row.
+    pub fn to_exists_sql(&self, dialect: Dialect) -> (String, SqlParams) {
+        let (where_clause, params) = where_clause(&self.filters, dialect);
+        let sql = match dialect {
+            Dialect::Postgres => format!(
+                "SELECT EXISTS(SELECT 1 FROM {}{})",
+                self.table, where_clause
+            ),
+            Dialect::Surreal => format!(
+                "SELECT count() FROM {}{} GROUP ALL",
+                self.table, where_clause
+            ),
+        };
+        (sql, params)
+    }
+}
+
+/// The column [`Query::to_counted_sql`] carries the total in.
+pub const TOTAL_COUNT_COLUMN: &str = "_total_count";
+
+/// The smallest percent [`Query::to_tablesample_sql`] renders; it has six decimal places.
+const MIN_TABLESAMPLE_PERCENT: f64 = 0.000001;
+
+impl<T: Clone> Query<T> {
+    /// Render a statement counting every row the query matches, ignoring its
+    /// limit and offset.
+    ///
+    /// PostgreSQL returns a single bigint; SurrealQL returns a `count` row.
+    pub fn to_count_sql(&self, dialect: Dialect) -> (String, SqlParams) {
+        let (inner, params) = self.unpaged().to_sql(dialect);
+        let sql = match dialect {
+            Dialect::Postgres => format!("SELECT COUNT(*) FROM ({}) AS counted", inner),
+            Dialect::Surreal => format!("SELECT count() FROM ({}) GROUP ALL", inner),
+        };
+        (sql, params)
+    }
+
+    /// Render a statement computing `aggregate` over `column` for every row
+    /// the query matches, ignoring its limit and offset.
+    ///
+    /// PostgreSQL returns the result as JSON text (NULL without rows);
+    /// SurrealQL returns a `value` row, or none without rows.
+    pub fn to_aggregate_sql(
+        &self,
+        aggregate: Aggregate,
+        column: &str,
+        dialect: Dialect,
+    ) -> (String, SqlParams) {
+        let (inner, params) = self.unpaged().to_sql(dialect);
+        let function = match aggregate {
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::Sum => "sum",
+        };
+        let sql = match dialect {
+            Dialect::Postgres => format!(
+                "SELECT to_jsonb({}({}))::text FROM ({}) AS aggregated",
+                function.to_uppercase(),
+                column,
+                inner
+            ),
+            Dialect::Surreal => format!(
+                "SELECT math::{}({}) AS value FROM ({}) GROUP ALL",
+                function, column, inner
+            ),
+        };
+        (sql, params)
+    }
+
+    /// Render the query's page with every row also carrying the number of
+    /// rows matched before paging, in [`TOTAL_COUNT_COLUMN`] (PostgreSQL only).
+    ///
+    /// The window runs over the unpaged query, aliased to the table's name so
+    /// the ordering still resolves.
+    pub fn to_counted_sql(&self) -> (String, SqlParams) {
+        let (inner, params) = self.unpaged().to_sql(Dialect::Postgres);
+        let alias = self.table.rsplit('.').next().unwrap_or(&self.table);
+        let mut sql = format!(
+            "SELECT *, COUNT(*) OVER () AS {} FROM ({}) AS {}{}",
+            TOTAL_COUNT_COLUMN,
+            inner,
+            alias,
+            order_clause(&self.order_by)
+        );
+        sql.push_str(&page_clause(self.limit, self.offset, Dialect::Postgres));
+        (sql, params)
+    }
+
+    /// Render a statement picking up to `n` of the matching rows at random,
+    /// ignoring the query's order, limit and offset. Every matching row is
+    /// read; on large PostgreSQL tables prefer [`Self::to_tablesample_sql`].
+    pub fn to_sample_sql(&self, n: u64, dialect: Dialect) -> (String, SqlParams) {
+        let mut unordered = self.unpaged();
+        unordered.order_by.clear();
+        let (inner, params) = unordered.to_sql(dialect);
+        let sql = match dialect {
+            Dialect::Postgres => format!(
+                "SELECT * FROM ({}) AS sampled ORDER BY random() LIMIT {}",
+                inner, n
+            ),
+            Dialect::Surreal => format!("SELECT * FROM ({}) ORDER BY rand() LIMIT {}", inner, n),
+        };
+        (sql, params)
+    }
+
+    /// Render a PostgreSQL statement reading about `percent` of the table's
+    /// pages with `TABLESAMPLE SYSTEM` and picking up to `n` of the matching
+    /// rows on them at random. `percent` is clamped to (0, 100] and rendered
+    /// to six decimal places.
+    ///
+    /// `None` for queries with joins, distinct-on or latest-per, which don't
+    /// select from the table alone.
+    pub fn to_tablesample_sql(&self, n: u64, percent: f64) -> Option<(String, SqlParams)> {
+        if !self.joins.is_empty() || !self.distinct_on.is_empty() || self.latest_per.is_some() {
+            return None;
+        }
+        // NaN falls to the floor too
+        let percent = if percent >= MIN_TABLESAMPLE_PERCENT {
+            percent.min(100.0)
+        } else {
+            MIN_TABLESAMPLE_PERCENT
+        };
+        let (where_clause, params) = where_clause(&self.filters, Dialect::Postgres);
+        let sql = format!(
+            "SELECT * FROM {} TABLESAMPLE SYSTEM ({:.6}){} ORDER BY random() LIMIT {}",
+            self.table, percent, where_clause, n
+        );
+        Some((sql, params))
+    }
+
+    fn unpaged(&self) -> Self {
+        let mut unpaged = self.clone();
+        unpaged.limit = None;
+        unpaged.offset = None;
+        unpaged
+    }
+}
+
+impl<T: Storable> Query<T> {
+    /// Render a statement listing the distinct prefixes the query matches, in
+    /// ascending order, each with its highest matching version, paged by the
+    /// query's limit and offset.
+    ///
+    /// Both dialects return `prefix` and `version` rows.
+    pub fn to_prefix_sql(&self, dialect: Dialect) -> (String, SqlParams) {
+        let prefix = T::managed_column(ManagedField::Prefix);
+        let version = T::managed_column(ManagedField::Version);
+
+        let (inner, params) = self.unpaged().to_sql(dialect);
+        let page = page_clause(self.limit, self.offset, dialect);
+        let sql = match dialect {
+            Dialect::Postgres => format!(
+                "SELECT {prefix} AS prefix, MAX({version}) AS version FROM ({inner}) AS listed \
+                 GROUP BY {prefix} ORDER BY prefix ASC{page}"
+            ),
+            Dialect::Surreal => format!(
+                "SELECT {prefix} AS prefix, math::max({version}) AS version FROM ({inner}) \
+                 GROUP BY prefix ORDER BY prefix ASC{page}"
+            ),
+        };
+        (sql, params)
+    }
+}
+
+impl<T> Delete<T> {
+    /// Render the DELETE statement and its parameters.
+    pub fn to_sql(&self, dialect: Dialect) -> (String, SqlParams) {
+        let (where_clause, params) = where_clause(&self.filters, dialect);
+        (
+            format!("DELETE FROM {}{}", self.table, where_clause),
+            params,
+        )
+    }
+}
+
+impl<T: Storable> Delete<T> {
+    /// Render an UPDATE stamping the matched live rows' `#[deleted_at]` column
+    /// with `at`, leaving the rows in place.
+    pub fn to_soft_delete_sql(
+        &self,
+        at: StorageDatetime,
+        dialect: Dialect,
+    ) -> Result<(String, SqlParams), StorageError> {
+        let column = T::deleted_at_column().ok_or_else(|| {
+            StorageError::SchemaMismatch(format!(
+                "{} has no #[deleted_at] column to soft-delete with",
+                self.table
+            ))
+        })?;
+        let mut filters = self.filters.clone();
+        filters.push(Filter::IsNull(column.to_string()));
+        let (where_clause, mut params) = where_clause(&filters, dialect);
+        let placeholder = match dialect {
+            Dialect::Postgres => format!("${}", params.len() + 1),
+            Dialect::Surreal => "$deleted_at".to_string(),
+        };
+        let sql = format!(
+            "UPDATE {} SET {} = {}{}",
+            self.table, column, placeholder, where_clause
+        );
+        params.push((placeholder, Value::Datetime(at)));
+        Ok((sql, params))
+    }
+}
+
+impl ColumnQuery {
+    /// Render the single-column SELECT statement and its parameters.
+    pub fn to_sql(&self, dialect: Dialect) -> (String, SqlParams) {
+        let (where_clause, params) = where_clause(&self.filters, dialect);
+        let order_clause = match self.order {
+            Some(Order::Asc) => format!(" ORDER BY {} ASC", self.column),
+            Some(Order::Desc) => format!(" ORDER BY {} DESC", self.column),
+            None => String::new(),
+        };
+        let limit_clause = self
+            .limit
+            .map(|l| format!(" LIMIT {}", l))
+            .unwrap_or_default();
+
+        let column = match (dialect, self.distinct) {
+            (Dialect::Postgres, true) => format!("DISTINCT {}", self.column),
+            // SurrealDB uses array::distinct() for distinct values
+            (Dialect::Surreal, true) => format!("VALUE array::distinct({})", self.column),
+            (_, false) => self.column.clone(),
+        };
+
+        let sql = format!(
+            "SELECT {} FROM {}{}{}{}",
+            column, self.table, where_clause, order_clause, limit_clause
+        );
+        (sql, params)
+    }
+}
+
+/// Build a SELECT of `select_cols`, appending its parameters to `params`.
+fn select_sql<T>(
+    query: &Query<T>,
+    select_cols: &str,
+    dialect: Dialect,
+    scope: &str,
+    params: &mut SqlParams,
+) -> String {
+    let join_clause = join_clause(&query.table, &query.joins, dialect);
+    let where_clause = render_where(&query.filters, dialect, scope, params);
+    let order_clause = order_clause(&query.order_by);
+
+    let mut sql = match (dialect, &query.latest_per) {
+        (Dialect::Postgres, Some((partition, order))) => {
+            // Number rows within each partition, then keep the first. The
+            // derived table takes the main table's name so qualified ORDER BY
+            // fields still resolve.
+            format!(
+                "SELECT {} FROM (SELECT {}, ROW_NUMBER() OVER (PARTITION BY {} ORDER BY {} DESC) AS _row_number \
+                 FROM {}{}{}) AS {} WHERE _row_number = 1{}",
+                select_cols,
+                select_cols,
+                partition,
+                order,
+                query.table,
+                join_clause,
+                where_clause,
+                query.table,
+                order_clause
+            )
+        }
+        (Dialect::Surreal, Some((partition, order))) => {
+            // Keep rows whose order value is the partition's highest among rows
+            // matching the same filters. Named parameters can be bound twice.
+            let inner_where = render_where(&query.filters, dialect, scope, &mut Vec::new());
+            let inner_where = if inner_where.is_empty() {
+                " WHERE ".to_string()
+            } else {
+                format!("{} AND ", inner_where)
+            };
+            let latest = format!(
+                "{order} = (SELECT VALUE {order} FROM {}{}{partition} = $parent.{partition} \
+                 ORDER BY {order} DESC LIMIT 1)[0]",
+                query.table, inner_where
+            );
+            let where_clause = if where_clause.is_empty() {
+                format!(" WHERE {}", latest)
+            } else {
+                format!("{} AND {}", where_clause, latest)
+            };
+            format!(
+                "SELECT {} FROM {}{}{}{}",
+                select_cols, query.table, join_clause, where_clause, order_clause
+            )
+        }
+        (Dialect::Postgres, None) => {
+            let distinct_clause = if query.distinct_on.is_empty() {
+                String::new()
+            } else {
+                format!("DISTINCT ON ({}) ", query.distinct_on.join(", "))
+            };
+            format!(
+                "SELECT {}{} FROM {}{}{}{}",
+                distinct_clause, select_cols, query.table, join_clause, where_clause, order_clause
+            )
+        }
+        (Dialect::Surreal, None) => {
+            // SurrealDB's GROUP BY returns one row per unique combination
+            let group_clause = if query.distinct_on.is_empty() {
+                String::new()
+            } else {
+                format!(" GROUP BY {}", query.distinct_on.join(", "))
+            };
+            format!(
+                "SELECT {} FROM {}{}{}{}{}",
+                select_cols, query.table, join_clause, where_clause, group_clause, order_clause
+            )
+        }
+    };
+
+    sql.push_str(&page_clause(query.limit, query.offset, dialect));
+    sql
+}
+
+/// The LIMIT and OFFSET (or START) clauses, with leading spaces.
+fn page_clause(limit: Option<u64>, offset: Option<u64>, dialect: Dialect) -> String {
+    let mut clause = String::new();
+    if let Some(limit) = limit {
+        clause.push_str(&format!(" LIMIT {}", limit));
+    }
+    if let Some(offset) = offset {
+        match dialect {
+            Dialect::Postgres => clause.push_str(&format!(" OFFSET {}", offset)),
+            Dialect::Surreal => clause.push_str(&format!(" START {}", offset)),
+        }
+    }
+    clause
+}
+
+/// Build a WHERE clause (with leading space) and its parameters.
+fn where_clause(filters: &[Filter], dialect: Dialect) -> (String, SqlParams) {
+    let mut params = Vec::new();
+    let clause = render_where(filters, dialect, "", &mut params);
+    (clause, params)
+}
+
+/// Build a WHERE clause (with leading space), appending its parameters to `params`.
+///
+/// Postgres placeholders continue from those already in `params`. Surreal
+/// placeholders are named by filter position within `scope`, so a subquery at
+/// position 2 binds `$p2_0`, `$p2_1`, ...
+fn render_where(
+    filters: &[Filter],
+    dialect: Dialect,
+    scope: &str,
+    params: &mut SqlParams,
+) -> String {
+    if filters.is_empty() {
+        return String::new();
+    }
+
+    let clauses = render_clauses(filters, dialect, scope, params);
+    format!(" WHERE {}", clauses.join(" AND "))
+}
+
+/// Render each filter as a condition, appending parameters to `params`.
+fn render_clauses(
+    filters: &[Filter],
+    dialect: Dialect,
+    scope: &str,
+    params: &mut SqlParams,
+) -> Vec<String> {
+    let mut clauses = Vec::with_capacity(filters.len());
+
+    for (i, filter) in filters.iter().enumerate() {
+        let (field, op, value) = match filter {
+            Filter::Eq(field, value) => (field, "=", value),
+            Filter::Ne(field, value) => (field, "!=", value),
+            Filter::Gt(field, value) => (field, ">", value),
+            Filter::Gte(field, value) => (field, ">=", value),
+            Filter::Lt(field, value) => (field, "<", value),
+            Filter::Lte(field, value) => (field, "<=", value),
+            Filter::In(field, value) => (field, "IN", value),
+            Filter::IsNull(field) => {
+                clauses.push(format!("{} IS NULL", field));
+                continue;
+            }
+            Filter::IsNotNull(field) => {
+                clauses.push(format!("{} IS NOT NULL", field));
+                continue;
+            }
+            Filter::InSubquery(field, subquery) => {
+                let scope = format!("{}{}_", scope, i);
+                let clause = match dialect {
+                    Dialect::Postgres => {
+                        let sql =
+                            select_sql(&subquery.query, &subquery.column, dialect, &scope, params);
+                        format!("{} IN ({})", field, sql)
+                    }
+                    Dialect::Surreal => {
+                        let column = format!("VALUE {}", subquery.column);
+                        let sql = select_sql(&subquery.query, &column, dialect, &scope, params);
+                        format!("{} INSIDE ({})", field, sql)
+                    }
+                };
+                clauses.push(clause);
+                continue;
+            }
+            Filter::Or(alternatives) => {
+                let scope = format!("{}{}_", scope, i);
+                let alternatives = render_clauses(alternatives, dialect, &scope, params);
+                clauses.push(if alternatives.is_empty() {
+                    "FALSE".to_string()
+                } else {
+                    format!("({})", alternatives.join(" OR "))
+                });
+                continue;
+            }
+            Filter::And(conjuncts) => {
+                let scope = format!("{}{}_", scope, i);
+                let conjuncts = render_clauses(conjuncts, dialect, &scope, params);
+                clauses.push(if conjuncts.is_empty() {
+                    "TRUE".to_string()
+                } else {
+                    format!("({})", conjuncts.join(" AND "))
+                });
+                continue;
+            }
+        };
+
+        let placeholder = match dialect {
+            Dialect::Postgres => format!("${}", params.len() + 1),
+            Dialect::Surreal => format!("$p{}{}", scope, i),
+        };
+        let clause = match (op, dialect) {
+            ("IN", Dialect::Postgres) => format!("{} = ANY({})", field, placeholder),
+            ("IN", Dialect::Surreal) => format!("{} CONTAINS {}", placeholder, field),
+            _ => format!("{} {} {}", field, op, placeholder),
+        };
+
+        clauses.push(clause);
+        params.push((placeholder, value.clone()));
+    }
+
+    clauses
+}
+
+/// Build an ORDER BY clause (with leading space).
+fn order_clause(order_by: &[(String, Order)]) -> String {
+    if order_by.is_empty() {
+        return String::new();
+    }
+
+    let clauses: Vec<String> = order_by
+        .iter()
+        .map(|(field, order)| {
+            let dir = match order {
+                Order::Asc => "ASC",
+                Order::Desc => "DESC",
+            };
+            format!("{} {}", field, dir)
+        })
+        .collect();
+
+    format!(" ORDER BY {}", clauses.join(", "))
+}
+
+/// Build JOIN clauses (each with a leading space).
+fn join_clause(main_table: &str, joins: &[Join], dialect: Dialect) -> String {
+    let keyword = match dialect {
+        Dialect::Postgres => "JOIN",
+        Dialect::Surreal => "INNER JOIN",
+    };
+
+    joins
+        .iter()
+        .map(|join| {
+            format!(
+                " {} {} ON {}.{} = {}.{}",
+                keyword, join.table, main_table, join.left_field, join.table, join.right_field
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Storable;
+
+    #[derive(Clone, serde::Serialize, serde::Deserialize)]
+    struct Row {
+        said: String,
+    }
+
+    impl Storable for Row {
+        fn table_name() -> &'static str {
+            "events"
+        }
+        fn columns() -> &'static [&'static str] {
+            &["said"]
+        }
+        fn column_types() -> &'static [&'static str] {
+            &["text"]
+        }
+        fn json_keys() -> &'static [&'static str] {
+            &["said"]
+        }
+        fn insert_sql() -> &'static str {
+            "INSERT INTO events (said) VALUES ($1)"
+        }
+        fn select_all_sql() -> &'static str {
+            "SELECT * FROM events"
+        }
+        fn select_by_id_sql() -> &'static str {
+            "SELECT * FROM events WHERE said = $1"
+        }
+        fn id(&self) -> &str {
+            &self.said
+        }
+        fn is_versioned() -> bool {
+            false
+        }
+    }
+
+    fn placeholders(params: &SqlParams) -> Vec<&str> {
+        params.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    fn query() -> Query<Row> {
+        Query::for_table("events")
+            .eq("prefix", "Eabc")
+            .filter(Filter::IsNull("deleted_at".to_string()))
+            .r#in("kind", vec!["icp", "rot"])
+            .order_by("version", Order::Desc)
+            .limit(10)
+            .offset(20)
+    }
+
+    #[test]
+    fn select_postgres() {
+        let (sql, params) = query().to_sql(Dialect::Postgres);
+        assert_eq!(
+            sql,
+            "SELECT * FROM events WHERE prefix = $1 AND deleted_at IS NULL AND kind = ANY($2) \
+             ORDER BY version DESC LIMIT 10 OFFSET 20"
+        );
+        assert_eq!(placeholders(&params), vec!["$1", "$2"]);
+        assert!(matches!(&params[0].1, Value::String(s) if s == "Eabc"));
+    }
+
+    #[test]
+    fn select_surreal() {
+        let (sql, params) = query().to_sql(Dialect::Surreal);
+        assert_eq!(
+            sql,
+            "SELECT * FROM events WHERE prefix = $p0 AND deleted_at IS NULL AND $p2 CONTAINS kind \
+             ORDER BY version DESC LIMIT 10 START 20"
+        );
+        assert_eq!(placeholders(&params), vec!["$p0", "$p2"]);
+    }
+
+    #[test]
+    fn select_distinct_on_with_join() {
+        let query = Query::<Row>::for_table("events")
+            .join("sigs", "said", "event_said")
+            .distinct_on("prefix")
+            .order_by("prefix", Order::Asc);
+
+        assert_eq!(
+            query.to_sql(Dialect::Postgres).0,
+            "SELECT DISTINCT ON (prefix) events.* FROM events JOIN sigs ON events.said = sigs.event_said \
+             ORDER BY prefix ASC"
+        );
+        assert_eq!(
+            query.to_sql(Dialect::Surreal).0,
+            "SELECT events.* FROM events INNER JOIN sigs ON events.said = sigs.event_said \
+             GROUP BY prefix ORDER BY prefix ASC"
+        );
+    }
+
+    #[test]
+    fn latest_per_uses_row_number() {
+        let query = Query::<Row>::for_table("domains")
+            .eq("name", "example.com")
+            .latest_per("prefix", "version")
+            .order_by("prefix", Order::Asc)
+            .limit(5);
+
+        let (sql, params) = query.to_sql(Dialect::Postgres);
+        assert_eq!(
+            sql,
+            "SELECT * FROM (SELECT *, ROW_NUMBER() OVER (PARTITION BY prefix ORDER BY version DESC) AS _row_number \
+             FROM domains WHERE name = $1) AS domains WHERE _row_number = 1 ORDER BY prefix ASC LIMIT 5"
+        );
+        assert_eq!(placeholders(&params), vec!["$1"]);
+
+        let (sql, params) = query.to_sql(Dialect::Surreal);
+        assert_eq!(
+            sql,
+            "SELECT * FROM domains WHERE name = $p0 AND version = (SELECT VALUE version FROM domains \
+             WHERE name = $p0 AND prefix = $parent.prefix ORDER BY version DESC LIMIT 1)[0] \
+             ORDER BY prefix ASC LIMIT 5"
+        );
+        assert_eq!(placeholders(&params), vec!["$p0"]);
+    }
+
+    #[test]
+    fn exists_and_delete() {
+        let query = Query::<Row>::for_table("events").eq("prefix", "Eabc");
+        assert_eq!(
+            query.to_exists_sql(Dialect::Postgres).0,
+            "SELECT EXISTS(SELECT 1 FROM events WHERE prefix = $1)"
+        );
+        assert_eq!(
+            query.to_exists_sql(Dialect::Surreal).0,
+            "SELECT count() FROM events WHERE prefix = $p0 GROUP ALL"
+        );
+
+        let delete = Delete::<Row>::for_table("events").gte("version", 3u64);
+        let (sql, params) = delete.to_sql(Dialect::Postgres);
+        assert_eq!(sql, "DELETE FROM events WHERE version >= $1");
+        assert!(matches!(params[0].1, Value::UInt(3)));
+    }
+
+    #[test]
+    fn counts_ignore_the_page() {
+        let (sql, params) = query().to_count_sql(Dialect::Postgres);
+        assert_eq!(
+            sql,
+            "SELECT COUNT(*) FROM (SELECT * FROM events WHERE prefix = $1 AND deleted_at IS NULL \
+             AND kind = ANY($2) ORDER BY version DESC) AS counted"
+        );
+        assert_eq!(placeholders(&params), vec!["$1", "$2"]);
+        assert!(
+            query()
+                .to_count_sql(Dialect::Surreal)
+                .0
+                .ends_with("ORDER BY version DESC) GROUP ALL")
+        );
+
+        let (sql, _) = query().to_counted_sql();
+        assert!(sql.starts_with("SELECT *, COUNT(*) OVER () AS _total_count FROM (SELECT * FROM"));
+        assert!(sql.ends_with(") AS events ORDER BY version DESC LIMIT 10 OFFSET 20"));
+    }
+
+    #[test]
+    fn aggregates_wrap_the_unpaged_query() {
+        let latest = Query::<Row>::for_table("events")
+            .eq("prefix", "Eabc")
+            .limit(5);
+        assert_eq!(
+            latest
+                .to_aggregate_sql(Aggregate::Max, "version", Dialect::Postgres)
+                .0,
+            "SELECT to_jsonb(MAX(version))::text FROM (SELECT * FROM events WHERE prefix = $1) \
+             AS aggregated"
+        );
+        assert_eq!(
+            latest
+                .to_aggregate_sql(Aggregate::Sum, "size", Dialect::Surreal)
+                .0,
+            "SELECT math::sum(size) AS value FROM (SELECT * FROM events WHERE prefix = $p0) \
+             GROUP ALL"
+        );
+    }
+
+    #[test]
+    fn prefixes_group_the_unpaged_query() {
+        let query = Query::<Row>::for_table("events")
+            .eq("kind", "icp")
+            .limit(100)
+            .offset(200);
+        assert_eq!(
+            query.to_prefix_sql(Dialect::Postgres).0,
+            "SELECT prefix AS prefix, MAX(version) AS version FROM \
+             (SELECT * FROM events WHERE kind = $1) AS listed \
+             GROUP BY prefix ORDER BY prefix ASC LIMIT 100 OFFSET 200"
+        );
+        assert_eq!(
+            query.to_prefix_sql(Dialect::Surreal).0,
+            "SELECT prefix AS prefix, math::max(version) AS version FROM \
+             (SELECT * FROM events WHERE kind = $p0) \
+             GROUP BY prefix ORDER BY prefix ASC LIMIT 100 START 200"
+        );
+    }
+
+    #[test]
+    fn samples_ignore_order_and_paging() {
+        assert_eq!(
+            query().to_sample_sql(50, Dialect::Postgres).0,
+            "SELECT * FROM (SELECT * FROM events WHERE prefix = $1 AND deleted_at IS NULL \
+             AND kind = ANY($2)) AS sampled ORDER BY random() LIMIT 50"
+        );
+        assert_eq!(
+            query().to_sample_sql(50, Dialect::Surreal).0,
+            "SELECT * FROM (SELECT * FROM events WHERE prefix = $p0 AND deleted_at IS NULL \
+             AND $p2 CONTAINS kind) ORDER BY rand() LIMIT 50"
+        );
+        assert_eq!(
+            query().to_tablesample_sql(50, 0.5).unwrap().0,
+            "SELECT * FROM events TABLESAMPLE SYSTEM (0.500000) WHERE prefix = $1 \
+             AND deleted_at IS NULL AND kind = ANY($2) ORDER BY random() LIMIT 50"
+        );
+        let percent_of = |percent: f64| {
+            let sql = query().to_tablesample_sql(50, percent).unwrap().0;
+            sql.split(['(', ')']).nth(1).unwrap().to_string()
+        };
+        assert_eq!(percent_of(250.0), "100.000000");
+        assert_eq!(percent_of(1e-12), "0.000001");
+        assert_eq!(percent_of(0.0), "0.000001");
+        assert_eq!(percent_of(f64::NAN), "0.000001");
+        assert!(
+            query()
+                .latest_per("prefix", "version")
+                .to_tablesample_sql(50, 0.5)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn subquery_parameters_follow_outer() {
+        let revoked = Query::<Row>::for_table("revocations")
+            .eq("kind", "rev")
+            .gte("version", 2u64);
+        let query = Query::<Row>::for_table("domains")
+            .eq("name", "example.com")
+            .in_subquery("prefix", "prefix", revoked)
+            .lt("version", 9u64);
+
+        let (sql, params) = query.to_sql(Dialect::Postgres);
+        assert_eq!(
+            sql,
+            "SELECT * FROM domains WHERE name = $1 AND prefix IN \
+             (SELECT prefix FROM revocations WHERE kind = $2 AND version >= $3) AND version < $4"
+        );
+        assert_eq!(placeholders(&params), vec!["$1", "$2", "$3", "$4"]);
+        assert!(matches!(params[3].1, Value::UInt(9)));
+
+        let (sql, params) = query.to_sql(Dialect::Surreal);
+        assert_eq!(
+            sql,
+            "SELECT * FROM domains WHERE name = $p0 AND prefix INSIDE \
+             (SELECT VALUE prefix FROM revocations WHERE kind = $p1_0 AND version >= $p1_1) AND version < $p2"
+        );
+        assert_eq!(placeholders(&params), vec!["$p0", "$p1_0", "$p1_1", "$p2"]);
+    }
+
+    #[test]
+    fn column_query() {
+        let query = ColumnQuery::new("events", "prefix")
+            .distinct()
+            .gt("Eabc")
+            .order(Order::Asc)
+            .limit(50);
+
+        assert_eq!(
+            query.to_sql(Dialect::Postgres).0,
+            "SELECT DISTINCT prefix FROM events WHERE prefix > $1 ORDER BY prefix ASC LIMIT 50"
+        );
+        assert_eq!(
+            query.to_sql(Dialect::Surreal).0,
+            "SELECT VALUE array::distinct(prefix) FROM events WHERE prefix > $p0 ORDER BY prefix ASC LIMIT 50"
+        );
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "notes")]
+    struct Note {
+        #[said]
+        said: String,
+        #[deleted_at]
+        deleted_at: Option<StorageDatetime>,
+        body: String,
+    }
+
+    #[test]
+    fn soft_deleted_rows_are_left_out_unless_asked_for() {
+        let query = Query::<Note>::new().eq("body", "hi");
+        assert_eq!(
+            query.to_sql(Dialect::Postgres).0,
+            "SELECT * FROM notes WHERE deleted_at IS NULL AND body = $1"
+        );
+        assert_eq!(
+            query.include_deleted().to_sql(Dialect::Postgres).0,
+            "SELECT * FROM notes WHERE body = $1"
+        );
+
+        let delete = Delete::<Note>::new().eq("said", "Eabc");
+        let (sql, params) = delete
+            .to_soft_delete_sql(StorageDatetime::now(), Dialect::Postgres)
+            .unwrap();
+        assert_eq!(
+            sql,
+            "UPDATE notes SET deleted_at = $2 WHERE said = $1 AND deleted_at IS NULL"
+        );
+        assert_eq!(placeholders(&params), vec!["$1", "$2"]);
+        assert!(matches!(params[1].1, Value::Datetime(_)));
+        let (sql, _) = delete
+            .to_soft_delete_sql(StorageDatetime::now(), Dialect::Surreal)
+            .unwrap();
+        assert_eq!(
+            sql,
+            "UPDATE notes SET deleted_at = $deleted_at WHERE said = $p0 AND deleted_at IS NULL"
+        );
+
+        assert!(matches!(
+            Delete::<Row>::new().to_soft_delete_sql(StorageDatetime::now(), Dialect::Postgres),
+            Err(StorageError::SchemaMismatch(_))
+        ));
+    }
+}
@@ -4,6 +4,15 @@
 //! Add `#[storable(table = "table_name")]` to a `#[derive(SelfAddressed)]` type
 //! to generate the implementation.
 
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+
+use serde::Serialize;
+
+use crate::{Partitioning, SchemaFingerprint, StorageError};
+
 /// Trait for types that can be stored in a database.
 ///
 /// This trait provides the metadata and methods needed for database operations.
@@ -33,10 +42,35 @@
 /// # Column Naming
 ///
 /// Database columns use snake_case (Rust field names). JSON serialization
-/// uses whatever serde is configured for (typically camelCase for SAID computation).
+/// uses whatever serde is configured for (typically camelCase for SAID computation);
+/// `json_keys()` follows the struct's `#[serde(rename_all = "...")]` and each
+/// field's `#[serde(rename = "...")]`. Where serde's naming can't be read from
+/// the attributes, `#[storable(json_naming = "snake_case")]` sets the rule.
 ///
 /// Use `#[column(skip)]` to exclude a field from database storage.
 /// Use `#[column(name = "custom_name")]` to override the column name.
+/// Use `#[column(expires_at)]` to mark the timestamp after which a row is expired.
+/// Use `#[column(effective_at)]` to mark the timestamp a version takes effect.
+/// Use `#[column(access_policy)]` to mark the record's `AccessPolicy`.
+/// Use `#[column(assert = "...")]` to add a database-side constraint (used by
+/// SurrealDB schemafull definitions, where the value is `$value`).
+///
+/// # Raw records
+///
+/// `#[storable(raw_column = "raw")]` adds a text column holding each record's
+/// serialized JSON, the exact bytes its SAID was computed over (with the SAID
+/// filled in). Backends read records back from it rather than reassembling
+/// them from columns, so a column type that round-trips lossily (timestamp
+/// precision, numeric widening) can't change what verification digests. Rows
+/// without it, written before the column was added, are read from columns.
+///
+/// # Partitioning
+///
+/// Add `partition_by` to the storable attribute to declare a partitioned table:
+/// - `#[storable(table = "events", partition_by = "created_at", interval = "month")]`
+///   for range partitions (`day`, `month`, or `year`)
+/// - `#[storable(table = "events", partition_by = "prefix", partitions = 16)]`
+///   for hash partitions
 pub trait Storable: serde::Serialize + serde::de::DeserializeOwned + Clone + Send + Sync {
     /// The database table name for this type.
     fn table_name() -> &'static str;
@@ -46,13 +80,21 @@ pub trait Storable: serde::Serialize + serde::de::DeserializeOwned + Clone + Sen
 
     /// Column types in order (database-agnostic).
     /// Used by executors to bind null values with the correct type.
-    /// Values: "text", "datetime", "bigint", "integer", "boolean", "json"
+    /// Values: "text", "datetime", "bigint", "integer", "boolean", "json", "cbor"
     fn column_types() -> &'static [&'static str];
 
-    /// JSON key names in order (camelCase for serde).
+    /// JSON key names in order, as serde names them.
     /// Corresponds 1:1 with columns().
     fn json_keys() -> &'static [&'static str];
 
+    /// The shape of each column's serialized value, in `columns()` order, for
+    /// backends that type values rather than columns. Values: "string", "int",
+    /// "float", "bool", "datetime", "array", "object", or "any" where serde may
+    /// shape it more than one way (structs, enums). Empty when unknown.
+    fn value_kinds() -> &'static [&'static str] {
+        &[]
+    }
+
     /// INSERT SQL with positional placeholders ($1, $2, ...).
     fn insert_sql() -> &'static str;
 
@@ -72,4 +114,372 @@ pub trait Storable: serde::Serialize + serde::de::DeserializeOwned + Clone + Sen
 
     /// Check if this type is versioned.
     fn is_versioned() -> bool;
+
+    /// How the table is partitioned, if at all.
+    fn partitioning() -> Option<Partitioning> {
+        None
+    }
+
+    /// Columns holding storage-managed fields, and which field each holds.
+    fn managed_columns() -> &'static [(&'static str, ManagedField)] {
+        &[]
+    }
+
+    /// The column holding `field`, or its conventional name if none is declared.
+    fn managed_column(field: ManagedField) -> &'static str {
+        Self::managed_columns()
+            .iter()
+            .find(|(_, managed)| *managed == field)
+            .map_or(field.default_column(), |(column, _)| column)
+    }
+
+    /// The `#[column(expires_at)]` column, if rows expire.
+    fn expires_at_column() -> Option<&'static str> {
+        None
+    }
+
+    /// The `#[column(effective_at)]` column, if versions are scheduled.
+    fn effective_at_column() -> Option<&'static str> {
+        None
+    }
+
+    /// The `#[column(access_policy)]` column, if records carry access policies.
+    fn access_policy_column() -> Option<&'static str> {
+        None
+    }
+
+    /// The `#[column(seal)]` column, if rows carry a storage seal.
+    fn seal_column() -> Option<&'static str> {
+        None
+    }
+
+    /// The `#[deleted_at]` column, if records are soft-deleted. Queries
+    /// leave out rows with it set unless built with `include_deleted()`.
+    fn deleted_at_column() -> Option<&'static str> {
+        None
+    }
+
+    /// The `#[storable(raw_column = "...")]` column, if rows also keep the
+    /// record's JSON serialization exactly as it was digested. It is written
+    /// on insert after the columns above, and rows are read back from it
+    /// when it is set.
+    fn raw_column() -> Option<&'static str> {
+        None
+    }
+
+    /// Columns whose fields are `Option`s and may be null.
+    fn nullable_columns() -> &'static [&'static str] {
+        &[]
+    }
+
+    /// `#[column(assert = "...")]` expressions, by column.
+    fn column_asserts() -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+
+    /// `#[column(max_length/pattern/range = ...)]` constraints, by column.
+    fn column_constraints() -> &'static [(&'static str, ColumnConstraint)] {
+        &[]
+    }
+
+    /// A hex digest of the table's layout: its name and each column's name
+    /// and type, in order. Any reordering, rename or type change alters it,
+    /// so it can be compared at startup to catch drift from the deployed schema.
+    fn schema_hash() -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(Self::table_name().as_bytes());
+        for (column, column_type) in Self::columns().iter().zip(Self::column_types()) {
+            hasher.update(b"\n");
+            hasher.update(column.as_bytes());
+            hasher.update(b" ");
+            hasher.update(column_type.as_bytes());
+        }
+        if let Some(raw) = Self::raw_column() {
+            hasher.update(b"\n");
+            hasher.update(raw.as_bytes());
+            hasher.update(b" raw");
+        }
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// This type's [`SchemaFingerprint`] under its own table name.
+    fn schema_fingerprint() -> SchemaFingerprint {
+        SchemaFingerprint::for_table::<Self>(Self::table_name())
+    }
+}
+
+/// The value of `column` in `item`'s serde form; `None` if absent or null.
+#[cfg(feature = "std")]
+pub(crate) fn column_value<T: Storable>(
+    item: &T,
+    column: &str,
+) -> Result<Option<serde_json::Value>, crate::StorageError> {
+    let json_key = T::columns()
+        .iter()
+        .position(|c| *c == column)
+        .and_then(|position| T::json_keys().get(position))
+        .ok_or_else(|| {
+            crate::StorageError::StorageError(format!(
+                "{} has no column {}",
+                T::table_name(),
+                column
+            ))
+        })?;
+    match serde_json::to_value(item)?.get(json_key) {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(value) => Ok(Some(value.clone())),
+    }
+}
+
+/// Whether `item` carries a deletion mark in its `#[deleted_at]` column.
+#[cfg(feature = "std")]
+pub fn is_soft_deleted<T: Storable>(item: &T) -> Result<bool, crate::StorageError> {
+    match T::deleted_at_column() {
+        Some(column) => Ok(column_value(item, column)?.is_some()),
+        None => Ok(false),
+    }
+}
+
+/// A value constraint declared on a column, checked by the generated
+/// `Validate` impl and by the database's CHECK constraints or asserts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnConstraint {
+    /// `#[column(max_length = N)]`: text of at most N characters
+    MaxLength(usize),
+    /// `#[column(pattern = "...")]`: text matching the regex somewhere; anchor
+    /// it with `^` and `$` to match the whole value. Checked in Rust only with
+    /// the `std` feature
+    Pattern(&'static str),
+    /// `#[column(range = "a..b")]` or `"a..=b"`: an integer in `min..=max`
+    Range(i64, i64),
+}
+
+/// Check `item` against `T`'s column constraints. Null values pass.
+pub fn check_column_constraints<T: Storable + Serialize>(item: &T) -> Result<(), StorageError> {
+    if T::column_constraints().is_empty() {
+        return Ok(());
+    }
+    let value = serde_json::to_value(item)?;
+    for (column, constraint) in T::column_constraints() {
+        let Some(value) = T::columns()
+            .iter()
+            .position(|c| c == column)
+            .and_then(|position| T::json_keys().get(position))
+            .and_then(|key| value.get(*key))
+            .filter(|value| !value.is_null())
+        else {
+            continue;
+        };
+        let fail = |reason: String| {
+            StorageError::ValidationFailed(format!("{}.{} {}", T::table_name(), column, reason))
+        };
+        match constraint {
+            ColumnConstraint::MaxLength(max) => {
+                let text = value
+                    .as_str()
+                    .ok_or_else(|| fail("is not text".to_string()))?;
+                if text.chars().count() > *max {
+                    return Err(fail(format!("is longer than {} characters", max)));
+                }
+            }
+            #[cfg(feature = "std")]
+            ColumnConstraint::Pattern(pattern) => {
+                let text = value
+                    .as_str()
+                    .ok_or_else(|| fail("is not text".to_string()))?;
+                if !compiled_pattern(pattern)?.is_match(text) {
+                    return Err(fail(format!("does not match {}", pattern)));
+                }
+            }
+            // Left to the database's CHECK constraint without std
+            #[cfg(not(feature = "std"))]
+            ColumnConstraint::Pattern(_) => {}
+            ColumnConstraint::Range(min, max) => {
+                let in_range = value.as_i64().is_some_and(|n| (*min..=*max).contains(&n));
+                if !in_range {
+                    return Err(fail(format!("is outside {}..={}", min, max)));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The regex for a `#[column(pattern)]`, compiled on first use and shared
+/// rather than cloned.
+#[cfg(feature = "std")]
+fn compiled_pattern(pattern: &'static str) -> Result<std::sync::Arc<regex::Regex>, StorageError> {
+    use std::collections::HashMap;
+    use std::sync::{Arc, LazyLock, Mutex};
+
+    static COMPILED: LazyLock<Mutex<HashMap<&'static str, Arc<regex::Regex>>>> =
+        LazyLock::new(Default::default);
+
+    let mut compiled = match COMPILED.lock() {
+        Ok(compiled) => compiled,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if let Some(regex) = compiled.get(pattern) {
+        return Ok(Arc::clone(regex));
+    }
+    let regex = Arc::new(
+        regex::Regex::new(pattern).map_err(|e| StorageError::StorageError(e.to_string()))?,
+    );
+    compiled.insert(pattern, Arc::clone(&regex));
+    Ok(regex)
+}
+
+/// A field set by SAID derivation or versioning rather than by callers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManagedField {
+    /// `#[said]`
+    Said,
+    /// `#[prefix]`
+    Prefix,
+    /// `#[previous]`
+    Previous,
+    /// `#[version]`
+    Version,
+    /// `#[created_at]`
+    CreatedAt,
+}
+
+impl ManagedField {
+    /// The column name used when a type doesn't declare one.
+    pub fn default_column(self) -> &'static str {
+        match self {
+            ManagedField::Said => "said",
+            ManagedField::Prefix => "prefix",
+            ManagedField::Previous => "previous",
+            ManagedField::Version => "version",
+            ManagedField::CreatedAt => "created_at",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "keys")]
+    struct Declared {
+        #[said]
+        said: String,
+        algorithm: String,
+        rotations: u64,
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "keys")]
+    struct Reordered {
+        #[column(position = 2)]
+        rotations: u64,
+        #[said]
+        #[column(position = 0)]
+        said: String,
+        #[column(position = 1)]
+        algorithm: String,
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "keys")]
+    struct Swapped {
+        #[said]
+        said: String,
+        rotations: u64,
+        algorithm: String,
+    }
+
+    #[test]
+    fn positions_pin_the_column_layout() {
+        assert_eq!(Reordered::columns(), Declared::columns());
+        assert_eq!(Reordered::json_keys(), Declared::json_keys());
+        assert_eq!(Reordered::insert_sql(), Declared::insert_sql());
+        assert_eq!(Reordered::schema_hash(), Declared::schema_hash());
+        assert_ne!(Swapped::schema_hash(), Declared::schema_hash());
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "keys", raw_column = "raw")]
+    struct Kept {
+        #[said]
+        said: String,
+        algorithm: String,
+        rotations: u64,
+    }
+
+    #[test]
+    fn raw_columns_are_inserted_after_the_fields() {
+        assert_eq!(Declared::raw_column(), None);
+        assert_eq!(Kept::raw_column(), Some("raw"));
+        assert_eq!(Kept::columns(), Declared::columns());
+        assert_eq!(
+            Kept::insert_sql(),
+            "INSERT INTO keys (said, algorithm, rotations, raw) VALUES ($1, $2, $3, $4)"
+        );
+        assert_ne!(Kept::schema_hash(), Declared::schema_hash());
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::Projection)]
+    #[storable(table = "key_usage_view")]
+    #[serde(rename_all = "camelCase")]
+    struct KeyUsage {
+        algorithm: String,
+        #[column(name = "uses")]
+        use_count: i64,
+        last_used: Option<crate::StorageDatetime>,
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "grants")]
+    #[serde(rename_all = "snake_case")]
+    struct Grant {
+        #[said]
+        said: String,
+        #[created_at]
+        created_at: crate::StorageDatetime,
+        #[serde(rename = "holderId")]
+        holder_id: String,
+        #[serde(rename(serialize = "pubKey", deserialize = "pubKey"))]
+        pub_key: String,
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[storable(table = "grants", json_naming = "camelCase")]
+    #[cfg_attr(all(), serde(rename_all = "camelCase"))]
+    struct LegacyGrant {
+        #[said]
+        said: String,
+        holder_id: String,
+    }
+
+    #[test]
+    fn json_keys_follow_serde_renames() {
+        assert_eq!(
+            Grant::json_keys(),
+            ["said", "created_at", "holderId", "pubKey"]
+        );
+        assert_eq!(LegacyGrant::json_keys(), ["said", "holderId"]);
+        let grant = Grant::create("E123".to_string(), "D456".to_string()).unwrap();
+        let json = serde_json::to_value(&grant).unwrap();
+        for key in Grant::json_keys() {
+            assert!(json.get(key).is_some(), "missing {}", key);
+        }
+    }
+
+    #[test]
+    fn projections_describe_their_view() {
+        assert_eq!(KeyUsage::table_name(), "key_usage_view");
+        assert_eq!(KeyUsage::columns(), ["algorithm", "uses", "last_used"]);
+        assert_eq!(KeyUsage::json_keys(), ["algorithm", "useCount", "lastUsed"]);
+        assert_eq!(KeyUsage::nullable_columns(), ["last_used"]);
+        assert!(!KeyUsage::is_versioned());
+        let usage = KeyUsage {
+            algorithm: "ed25519".to_string(),
+            use_count: 3,
+            last_used: None,
+        };
+        assert_eq!(usage.id(), "");
+    }
 }
@@ -0,0 +1,306 @@
+//! Peer-to-peer reconciliation of versioned event logs.
+//!
+//! Two services holding the same prefixes reconcile without a broker:
+//!
+//! 1. The puller asks the peer for its heads (latest version and SAID) of a
+//!    set of prefixes.
+//! 2. For every prefix where the peer is ahead, it requests the missing range
+//!    of versions.
+//! 3. The range is verified as a continuation of the local chain (SAIDs,
+//!    prefix, version sequence and previous links) before it is inserted.
+//!
+//! Reconciliation is pull-based; running it in both directions brings two
+//! nodes level. Divergent chains are reported, never overwritten. Messages
+//! travel over any [`SyncTransport`], and [`SyncNode::handle`] answers them.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{SelfAddressed, StorageError, Versioned, VersionedRepository};
+
+/// The latest version of a prefix held by a node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefixHead {
+    pub prefix: String,
+    pub version: u64,
+    pub said: String,
+}
+
+/// A request from a pulling node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SyncRequest {
+    /// Heads for these prefixes; prefixes the peer doesn't hold are omitted.
+    Heads { prefixes: Vec<String> },
+    /// Every version of `prefix` from `from_version` on, in order.
+    #[serde(rename_all = "camelCase")]
+    Range { prefix: String, from_version: u64 },
+}
+
+/// A node's answer to a [`SyncRequest`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "items", rename_all = "camelCase")]
+pub enum SyncResponse<T> {
+    Heads(Vec<PrefixHead>),
+    Range(Vec<T>),
+}
+
+/// Carries sync requests to a peer, e.g. over HTTP or a message bus.
+#[async_trait]
+pub trait SyncTransport<T>: Send + Sync {
+    async fn send(&self, request: SyncRequest) -> Result<SyncResponse<T>, StorageError>;
+}
+
+/// Outcome of reconciling a set of prefixes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Versions inserted locally.
+    pub applied: usize,
+    /// Prefixes already level with (or ahead of) the peer.
+    pub up_to_date: usize,
+    /// Prefixes whose local and remote chains diverge.
+    pub conflicts: Vec<String>,
+    /// Prefixes whose remote range failed verification, with the reason.
+    pub rejected: Vec<(String, String)>,
+}
+
+/// Serves and pulls sync requests for a repository.
+#[derive(Debug, Clone)]
+pub struct SyncNode<R> {
+    repository: R,
+}
+
+impl<R> SyncNode<R> {
+    pub fn new(repository: R) -> Self {
+        Self { repository }
+    }
+
+    /// Answer a peer's request from the local repository.
+    pub async fn handle<T>(&self, request: SyncRequest) -> Result<SyncResponse<T>, StorageError>
+    where
+        T: SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + Send + Sync,
+        R: VersionedRepository<T>,
+    {
+        match request {
+            SyncRequest::Heads { prefixes } => {
+                let mut heads = Vec::with_capacity(prefixes.len());
+                for prefix in prefixes {
+                    if let Some(head) = self.head(&prefix).await? {
+                        heads.push(head);
+                    }
+                }
+                Ok(SyncResponse::Heads(heads))
+            }
+            SyncRequest::Range {
+                prefix,
+                from_version,
+            } => {
+                let history = self.repository.get_history(&prefix).await?;
+                Ok(SyncResponse::Range(
+                    history
+                        .into_iter()
+                        .filter(|item| item.get_version() >= from_version)
+                        .collect(),
+                ))
+            }
+        }
+    }
+
+    /// Pull whatever `peer` has beyond the local chains of `prefixes`.
+    pub async fn reconcile<T>(
+        &self,
+        peer: &impl SyncTransport<T>,
+        prefixes: Vec<String>,
+    ) -> Result<SyncReport, StorageError>
+    where
+        T: SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + Send + Sync,
+        R: VersionedRepository<T>,
+    {
+        let SyncResponse::Heads(remote_heads) = peer.send(SyncRequest::Heads { prefixes }).await?
+        else {
+            return Err(unexpected("heads"));
+        };
+
+        let mut report = SyncReport::default();
+        for remote in remote_heads {
+            let local = self.head(&remote.prefix).await?;
+            let from_version = match &local {
+                Some(local) if local.version >= remote.version => {
+                    if local.version == remote.version && local.said != remote.said {
+                        report.conflicts.push(remote.prefix);
+                    } else {
+                        report.up_to_date += 1;
+                    }
+                    continue;
+                }
+                Some(local) => local.version + 1,
+                None => 0,
+            };
+
+            let request = SyncRequest::Range {
+                prefix: remote.prefix.clone(),
+                from_version,
+            };
+            let SyncResponse::Range(items) = peer.send(request).await? else {
+                return Err(unexpected("range"));
+            };
+
+            match verify_continuation(&remote.prefix, local.as_ref(), &items) {
+                Ok(()) => {
+                    for item in items {
+                        self.repository.insert(item).await?;
+                        report.applied += 1;
+                    }
+                }
+                Err(Divergence::Conflict) => report.conflicts.push(remote.prefix),
+                Err(Divergence::Invalid(e)) => report.rejected.push((remote.prefix, e.to_string())),
+            }
+        }
+        Ok(report)
+    }
+
+    async fn head<T>(&self, prefix: &str) -> Result<Option<PrefixHead>, StorageError>
+    where
+        T: SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + Send + Sync,
+        R: VersionedRepository<T>,
+    {
+        Ok(self
+            .repository
+            .get_latest(prefix)
+            .await?
+            .map(|item| PrefixHead {
+                prefix: item.get_prefix(),
+                version: item.get_version(),
+                said: item.get_said(),
+            }))
+    }
+}
+
+pub(crate) enum Divergence {
+    /// The range doesn't build on the local head.
+    Conflict,
+    /// The range is not a valid chain.
+    Invalid(StorageError),
+}
+
+/// Check `items` continue the chain of `prefix` after `local`.
+pub(crate) fn verify_continuation<T: Versioned>(
+    prefix: &str,
+    local: Option<&PrefixHead>,
+    items: &[T],
+) -> Result<(), Divergence> {
+    let invalid = |message: String| Divergence::Invalid(StorageError::InvalidSaid(message));
+    let first_version = local.map_or(0, |head| head.version + 1);
+    let mut expected_previous = local.map(|head| head.said.clone());
+
+    for (i, item) in items.iter().enumerate() {
+        let expected_version = first_version + i as u64;
+        item.verify().map_err(Divergence::Invalid)?;
+        if item.get_prefix() != prefix {
+            return Err(invalid(format!(
+                "Item {} belongs to {}, not {}",
+                item.get_said(),
+                item.get_prefix(),
+                prefix
+            )));
+        }
+        if item.get_version() != expected_version {
+            return Err(invalid(format!(
+                "Expected version {} of {}, got {}",
+                expected_version,
+                prefix,
+                item.get_version()
+            )));
+        }
+        if item.get_previous() != expected_previous {
+            // Only the first link can disagree because of local state
+            return Err(if i == 0 && local.is_some() {
+                Divergence::Conflict
+            } else {
+                invalid(format!("Broken previous link at {}", item.get_said()))
+            });
+        }
+        expected_previous = Some(item.get_said());
+    }
+    Ok(())
+}
+
+fn unexpected(expected: &str) -> StorageError {
+    StorageError::StorageError(format!(
+        "Peer sent an unexpected response to a {} request",
+        expected
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[serde(rename_all = "camelCase")]
+    struct Event {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        body: String,
+    }
+
+    fn chain(len: usize) -> Vec<Event> {
+        let mut event = Event::new("genesis".to_string());
+        event.derive_prefix().unwrap();
+        let mut events = vec![event.clone()];
+        for i in 1..len {
+            event.body = format!("update {}", i);
+            event.increment().unwrap();
+            events.push(event.clone());
+        }
+        events
+    }
+
+    fn head(event: &Event) -> PrefixHead {
+        PrefixHead {
+            prefix: event.prefix.clone(),
+            version: event.version,
+            said: event.said.clone(),
+        }
+    }
+
+    #[test]
+    fn ranges_continue_the_local_head() {
+        let events = chain(4);
+        let prefix = events[0].prefix.as_str();
+        assert!(verify_continuation(prefix, None, &events).is_ok());
+        assert!(verify_continuation(prefix, Some(&head(&events[1])), &events[2..]).is_ok());
+        assert!(matches!(
+            verify_continuation(prefix, Some(&head(&events[0])), &events[2..]),
+            Err(Divergence::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn forks_are_conflicts_and_tampering_is_invalid() {
+        let events = chain(3);
+        let prefix = events[0].prefix.as_str();
+
+        let mut fork = events[1].clone();
+        fork.body = "fork".to_string();
+        fork.derive_said().unwrap();
+        assert!(matches!(
+            verify_continuation(prefix, Some(&head(&fork)), &events[2..]),
+            Err(Divergence::Conflict)
+        ));
+
+        let mut tampered = events[1..].to_vec();
+        tampered[0].body = "tampered".to_string();
+        assert!(matches!(
+            verify_continuation(prefix, Some(&head(&events[0])), &tampered),
+            Err(Divergence::Invalid(_))
+        ));
+    }
+}
@@ -1,7 +1,9 @@
-use std::ops::Add;
-use std::time::Duration;
+use core::ops::{Add, Sub};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SubsecRound, Timelike, Utc};
 use serde::{Deserialize, Serialize};
 
 // Verifiable storage timestamp with microsecond precision
@@ -14,14 +16,33 @@ mod inner {
     use super::*;
     use surrealdb::sql::Datetime as SurrealDatetime;
 
-    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
     pub struct StorageDatetime(pub SurrealDatetime);
 
+    impl<'de> Deserialize<'de> for StorageDatetime {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            // Same shape as the derived impl, then the precision check
+            #[derive(Deserialize)]
+            #[serde(rename = "StorageDatetime")]
+            struct Unchecked(SurrealDatetime);
+
+            let Unchecked(datetime) = Unchecked::deserialize(deserializer)?;
+            check_micros(&datetime.clone().into()).map_err(serde::de::Error::custom)?;
+            Ok(StorageDatetime(datetime))
+        }
+    }
+
     impl StorageDatetime {
         pub fn now() -> Self {
             StorageDatetime(datetime_micros())
         }
 
+        /// This time with anything below a microsecond dropped.
+        pub fn truncate_micros(&self) -> Self {
+            let inner: DateTime<Utc> = self.0.clone().into();
+            StorageDatetime(SurrealDatetime::from(inner.trunc_subsecs(6)))
+        }
+
         pub fn is_from_future(&self) -> bool {
             Self::now() < *self
         }
@@ -42,6 +63,17 @@ mod inner {
         }
     }
 
+    impl Sub<Duration> for StorageDatetime {
+        type Output = StorageDatetime;
+
+        fn sub(self, rhs: Duration) -> Self::Output {
+            let inner: DateTime<Utc> = self.0.clone().into();
+            let new_time =
+                inner - chrono::Duration::from_std(rhs).unwrap_or(chrono::Duration::zero());
+            StorageDatetime(SurrealDatetime::from(new_time))
+        }
+    }
+
     impl std::fmt::Display for StorageDatetime {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             write!(f, "{}", self.0)
@@ -87,6 +119,7 @@ mod inner {
 #[cfg(not(feature = "surrealdb"))]
 mod inner {
     use super::*;
+    use alloc::string::String;
     use serde::{Deserializer, Serializer};
 
     #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -102,17 +135,27 @@ mod inner {
     impl<'de> Deserialize<'de> for StorageDatetime {
         fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
             let s = String::deserialize(deserializer)?;
-            DateTime::parse_from_rfc3339(&s)
-                .map(|dt| StorageDatetime(dt.with_timezone(&Utc)))
-                .map_err(serde::de::Error::custom)
+            let datetime = DateTime::parse_from_rfc3339(&s)
+                .map(|dt| dt.with_timezone(&Utc))
+                .map_err(serde::de::Error::custom)?;
+            check_micros(&datetime).map_err(serde::de::Error::custom)?;
+            Ok(StorageDatetime(datetime))
         }
     }
 
     impl StorageDatetime {
+        /// Now, to the microsecond. Without std there is no clock to read.
+        #[cfg(feature = "std")]
         pub fn now() -> Self {
             StorageDatetime(datetime_micros())
         }
 
+        /// This time with anything below a microsecond dropped.
+        pub fn truncate_micros(&self) -> Self {
+            StorageDatetime(self.0.trunc_subsecs(6))
+        }
+
+        #[cfg(feature = "std")]
         pub fn is_from_future(&self) -> bool {
             Self::now() < *self
         }
@@ -122,6 +165,7 @@ mod inner {
         }
     }
 
+    #[cfg(feature = "std")]
     impl Default for StorageDatetime {
         fn default() -> Self {
             Self::now()
@@ -138,8 +182,18 @@ mod inner {
         }
     }
 
-    impl std::fmt::Display for StorageDatetime {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    impl Sub<Duration> for StorageDatetime {
+        type Output = StorageDatetime;
+
+        fn sub(self, rhs: Duration) -> Self::Output {
+            let new_time =
+                self.0 - chrono::Duration::from_std(rhs).unwrap_or(chrono::Duration::zero());
+            StorageDatetime(new_time)
+        }
+    }
+
+    impl core::fmt::Display for StorageDatetime {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
             write!(f, "{}", self.0.format("%Y-%m-%dT%H:%M:%S%.6fZ"))
         }
     }
@@ -157,6 +211,7 @@ mod inner {
     }
 
     /// Create a DateTime truncated to microsecond precision (6 decimal places)
+    #[cfg(feature = "std")]
     fn datetime_micros() -> DateTime<Utc> {
         let now = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
             Ok(time) => time,
@@ -173,3 +228,149 @@ mod inner {
 }
 
 pub use inner::StorageDatetime;
+
+/// Backends keep timestamps to the microsecond, so a finer one would come
+/// back changed and no longer match the SAID it was digested into.
+fn check_micros(datetime: &DateTime<Utc>) -> Result<(), alloc::string::String> {
+    if datetime.nanosecond() % 1_000 != 0 {
+        return Err(alloc::format!(
+            "{} has sub-microsecond precision; truncate it with truncate_micros()",
+            datetime.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)
+        ));
+    }
+    Ok(())
+}
+
+/// A hybrid logical clock: physical time, but never behind the latest
+/// timestamp it has issued or observed.
+///
+/// When the wall clock hasn't advanced (or lags a timestamp seen from a
+/// skewed node), the next tick is one microsecond past the latest, so the
+/// logical counter is folded into the microseconds and timestamps stay plain
+/// RFC 3339 values that compare in issue order.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct HybridClock {
+    latest: Mutex<Option<StorageDatetime>>,
+}
+
+#[cfg(feature = "std")]
+impl HybridClock {
+    pub const fn new() -> Self {
+        Self {
+            latest: Mutex::new(None),
+        }
+    }
+
+    /// The next timestamp, strictly after every one issued or observed.
+    pub fn tick(&self) -> StorageDatetime {
+        let mut latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+        let now = StorageDatetime::now();
+        let next = match latest.take() {
+            Some(last) if now <= last => last + Duration::from_micros(1),
+            _ => now,
+        };
+        *latest = Some(next.clone());
+        next
+    }
+
+    /// Record a timestamp from elsewhere (e.g. a replicated version) so
+    /// later ticks come after it.
+    pub fn observe(&self, seen: &StorageDatetime) {
+        let mut latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+        if latest.as_ref().is_none_or(|last| last < seen) {
+            *latest = Some(seen.clone());
+        }
+    }
+
+    /// The next timestamp, strictly after `previous` as well.
+    pub fn tick_after(&self, previous: &StorageDatetime) -> StorageDatetime {
+        self.observe(previous);
+        self.tick()
+    }
+}
+
+#[cfg(feature = "std")]
+static PROCESS_CLOCK: HybridClock = HybridClock::new();
+
+#[cfg(feature = "std")]
+impl StorageDatetime {
+    /// A timestamp from the process-wide [`HybridClock`].
+    pub fn hybrid_now() -> Self {
+        PROCESS_CLOCK.tick()
+    }
+
+    /// A timestamp from the process-wide [`HybridClock`], strictly after `previous`.
+    pub fn hybrid_after(previous: &Self) -> Self {
+        PROCESS_CLOCK.tick_after(previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+    #[serde(rename_all = "camelCase")]
+    struct Counter {
+        #[said]
+        said: String,
+        #[prefix]
+        prefix: String,
+        #[previous]
+        previous: Option<String>,
+        #[version]
+        version: u64,
+        #[created_at(hybrid)]
+        created_at: StorageDatetime,
+    }
+
+    #[test]
+    fn rapid_versions_get_increasing_timestamps() {
+        use crate::Versioned;
+        let mut counter = Counter::new();
+        counter.derive_prefix().unwrap();
+        // A version arriving from a node whose clock runs ahead
+        counter.created_at = StorageDatetime::now() + Duration::from_secs(60);
+        for _ in 0..100 {
+            let before = counter.created_at.clone();
+            counter.increment().unwrap();
+            assert!(counter.created_at > before);
+        }
+    }
+
+    #[test]
+    fn timestamps_round_trip_byte_for_byte() {
+        let now = StorageDatetime::now();
+        let json = serde_json::to_string(&now).unwrap();
+        let back: StorageDatetime = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, now);
+        assert_eq!(serde_json::to_string(&back).unwrap(), json);
+
+        let nanos = "2024-05-01T12:00:00.123456789Z";
+        assert!(serde_json::from_value::<StorageDatetime>(nanos.into()).is_err());
+        let precise: DateTime<Utc> = nanos.parse().unwrap();
+        let truncated = StorageDatetime(precise.into()).truncate_micros();
+        assert_eq!(
+            serde_json::to_value(&truncated).unwrap(),
+            "2024-05-01T12:00:00.123456Z"
+        );
+        assert_eq!(truncated.truncate_micros(), truncated);
+    }
+
+    #[test]
+    fn hybrid_ticks_strictly_increase() {
+        let clock = HybridClock::new();
+        let mut last = clock.tick();
+        for _ in 0..1000 {
+            let next = clock.tick();
+            assert!(next > last);
+            last = next;
+        }
+
+        let skewed = StorageDatetime::now() + Duration::from_secs(3600);
+        let after = clock.tick_after(&skewed);
+        assert!(after > skewed);
+        assert!(clock.tick() > after);
+    }
+}
@@ -0,0 +1,663 @@
+//! Append-only transparency log over stored SAIDs.
+//!
+//! Each table's SAIDs form the leaves of an RFC 9162 (Certificate
+//! Transparency v2) Merkle tree, hashed with Blake3: leaves are
+//! `H(0x00 || said)` and interior nodes `H(0x01 || left || right)`. The log
+//! keeps its leaves and signed tree heads in two companion tables:
+//!
+//! ```text
+//! CREATE TABLE transparency_leaves (
+//!     said TEXT PRIMARY KEY,
+//!     log TEXT NOT NULL,
+//!     leaf_index BIGINT NOT NULL,
+//!     record_said TEXT NOT NULL,
+//!     UNIQUE (log, leaf_index)
+//! );
+//! CREATE TABLE transparency_heads (
+//!     said TEXT PRIMARY KEY,
+//!     log TEXT NOT NULL,
+//!     tree_size BIGINT NOT NULL,
+//!     root_hash TEXT NOT NULL,
+//!     signature TEXT NOT NULL,
+//!     created_at TIMESTAMPTZ NOT NULL
+//! );
+//! ```
+//!
+//! Call [`TransparencyLog::sign_head`] on a schedule to publish tree heads.
+//! Clients check heads with [`SignedTreeHead::verify`], that a SAID is in the
+//! log with [`InclusionProof::verify`], and that the log only grew between two
+//! heads with [`ConsistencyProof::verify`]. Hashes are hex-encoded.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    Order, Query, QueryExecutor, ResponseSigner, ResponseVerifier, SelfAddressed, Storable,
+    StorageDatetime, StorageError, TransactionExecutor, UnversionedRepository, Versioned,
+    VersionedRepository, compute_said,
+};
+
+type Hash = [u8; 32];
+
+/// A leaf of a transparency log.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, crate::SelfAddressed)]
+#[storable(table = "transparency_leaves")]
+#[serde(rename_all = "camelCase")]
+pub struct LogLeaf {
+    #[said]
+    pub said: String,
+    pub log: String,
+    pub leaf_index: u64,
+    pub record_said: String,
+}
+
+/// A signed commitment to the first `tree_size` leaves of a log.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, crate::SelfAddressed)]
+#[storable(table = "transparency_heads")]
+#[serde(rename_all = "camelCase")]
+pub struct SignedTreeHead {
+    #[said]
+    pub said: String,
+    pub log: String,
+    pub tree_size: u64,
+    pub root_hash: String,
+    /// Signature over the digest of `log`, `tree_size`, `root_hash` and `created_at`.
+    pub signature: String,
+    #[created_at]
+    pub created_at: StorageDatetime,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SignedFields<'a> {
+    log: &'a str,
+    tree_size: u64,
+    root_hash: &'a str,
+    created_at: &'a StorageDatetime,
+}
+
+impl SignedTreeHead {
+    fn signed_digest(&self) -> Result<String, StorageError> {
+        compute_said(&SignedFields {
+            log: &self.log,
+            tree_size: self.tree_size,
+            root_hash: &self.root_hash,
+            created_at: &self.created_at,
+        })
+    }
+
+    /// Check the head's SAID and signature.
+    pub fn verify(&self, verifier: &impl ResponseVerifier) -> Result<(), StorageError> {
+        self.verify_said()?;
+        verifier.verify(self.signed_digest()?.as_bytes(), &self.signature)
+    }
+}
+
+/// Audit path proving a SAID is the `leaf_index`th leaf of a tree of `tree_size`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InclusionProof {
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    pub path: Vec<String>,
+}
+
+impl InclusionProof {
+    /// Check that `record_said` is included under `root_hash`.
+    pub fn verify(&self, record_said: &str, root_hash: &str) -> Result<(), StorageError> {
+        let path = decode_all(&self.path)?;
+        if verify_inclusion(
+            &leaf_hash(record_said),
+            self.leaf_index,
+            self.tree_size,
+            &path,
+            &decode(root_hash)?,
+        ) {
+            Ok(())
+        } else {
+            Err(StorageError::InvalidSaid(format!(
+                "{} is not leaf {} of the tree with root {}",
+                record_said, self.leaf_index, root_hash
+            )))
+        }
+    }
+}
+
+/// Proof that a tree of `first_size` is a prefix of a tree of `second_size`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsistencyProof {
+    pub first_size: u64,
+    pub second_size: u64,
+    pub path: Vec<String>,
+}
+
+impl ConsistencyProof {
+    /// Check that the tree with `second_root` extends the tree with `first_root`.
+    pub fn verify(&self, first_root: &str, second_root: &str) -> Result<(), StorageError> {
+        let path = decode_all(&self.path)?;
+        if verify_consistency(
+            self.first_size,
+            self.second_size,
+            &decode(first_root)?,
+            &decode(second_root)?,
+            &path,
+        ) {
+            Ok(())
+        } else {
+            Err(StorageError::InvalidSaid(format!(
+                "Tree {} (size {}) does not extend tree {} (size {})",
+                second_root, self.second_size, first_root, self.first_size
+            )))
+        }
+    }
+}
+
+/// A transparency log for one table, stored through a [`QueryExecutor`].
+#[derive(Debug, Clone)]
+pub struct TransparencyLog<E, S> {
+    executor: E,
+    signer: S,
+    log: String,
+}
+
+impl<E: QueryExecutor, S: ResponseSigner> TransparencyLog<E, S> {
+    /// A log named `log`, with tree heads signed by `signer`.
+    pub fn new(executor: E, signer: S, log: impl Into<String>) -> Self {
+        Self {
+            executor,
+            signer,
+            log: log.into(),
+        }
+    }
+
+    /// The log for `T`'s table.
+    pub fn for_table<T: Storable>(executor: E, signer: S) -> Self {
+        Self::new(executor, signer, T::table_name())
+    }
+
+    /// Append `record_said` and return its leaf index.
+    pub async fn append(&self, record_said: &str) -> Result<u64, StorageError> {
+        let mut tx = self.executor.begin_transaction().await?;
+        tx.acquire_advisory_lock(&format!("transparency:{}", self.log))
+            .await?;
+        let last = tx
+            .fetch(
+                Query::<LogLeaf>::new()
+                    .eq("log", self.log.as_str())
+                    .order_by("leaf_index", Order::Desc)
+                    .limit(1),
+            )
+            .await?;
+        let leaf_index = last.first().map_or(0, |leaf| leaf.leaf_index + 1);
+
+        let mut leaf = LogLeaf::new(self.log.clone(), leaf_index, record_said.to_string());
+        leaf.derive_said()?;
+        tx.insert(&leaf).await?;
+        tx.commit().await?;
+        Ok(leaf_index)
+    }
+
+    /// Number of leaves in the log.
+    pub async fn size(&self) -> Result<u64, StorageError> {
+        Ok(self.leaves().await?.len() as u64)
+    }
+
+    /// Sign and store a head over the current tree.
+    pub async fn sign_head(&self) -> Result<SignedTreeHead, StorageError> {
+        let leaves = self.leaf_hashes().await?;
+        let mut head = SignedTreeHead::new(
+            self.log.clone(),
+            leaves.len() as u64,
+            encode(&merkle_root(&leaves)),
+            String::new(),
+        );
+        head.signature = self.signer.sign(head.signed_digest()?.as_bytes())?;
+        head.derive_said()?;
+        self.executor.insert(&head).await?;
+        Ok(head)
+    }
+
+    /// The most recently signed head, if any.
+    pub async fn latest_head(&self) -> Result<Option<SignedTreeHead>, StorageError> {
+        self.executor
+            .fetch_optional(
+                Query::<SignedTreeHead>::new()
+                    .eq("log", self.log.as_str())
+                    .order_by("tree_size", Order::Desc)
+                    .order_by("created_at", Order::Desc)
+                    .limit(1),
+            )
+            .await
+    }
+
+    /// Prove `record_said` is in the tree of `tree_size` leaves.
+    pub async fn inclusion_proof(
+        &self,
+        record_said: &str,
+        tree_size: u64,
+    ) -> Result<InclusionProof, StorageError> {
+        let leaves = self.leaves().await?;
+        if tree_size > leaves.len() as u64 {
+            return Err(StorageError::StorageError(format!(
+                "Tree size {} exceeds {} (size {})",
+                tree_size,
+                self.log,
+                leaves.len()
+            )));
+        }
+        let leaf_index = leaves
+            .iter()
+            .take(tree_size as usize)
+            .position(|leaf| leaf.record_said == record_said)
+            .ok_or_else(|| {
+                StorageError::NotFound(format!(
+                    "{} is not in the first {} leaves of {}",
+                    record_said, tree_size, self.log
+                ))
+            })? as u64;
+        let hashes = hashes_of(&leaves[..tree_size as usize]);
+        Ok(InclusionProof {
+            leaf_index,
+            tree_size,
+            path: inclusion_path(leaf_index as usize, &hashes)
+                .iter()
+                .map(encode)
+                .collect(),
+        })
+    }
+
+    /// Prove the tree of `second_size` leaves extends the tree of `first_size`.
+    pub async fn consistency_proof(
+        &self,
+        first_size: u64,
+        second_size: u64,
+    ) -> Result<ConsistencyProof, StorageError> {
+        let leaves = self.leaf_hashes().await?;
+        if first_size > second_size || second_size > leaves.len() as u64 {
+            return Err(StorageError::StorageError(format!(
+                "Invalid consistency range {}..{} for {} (size {})",
+                first_size,
+                second_size,
+                self.log,
+                leaves.len()
+            )));
+        }
+        Ok(ConsistencyProof {
+            first_size,
+            second_size,
+            path: consistency_path(first_size as usize, &leaves[..second_size as usize])
+                .iter()
+                .map(encode)
+                .collect(),
+        })
+    }
+
+    async fn leaves(&self) -> Result<Vec<LogLeaf>, StorageError> {
+        self.executor
+            .fetch(
+                Query::<LogLeaf>::new()
+                    .eq("log", self.log.as_str())
+                    .order_by("leaf_index", Order::Asc),
+            )
+            .await
+    }
+
+    async fn leaf_hashes(&self) -> Result<Vec<Hash>, StorageError> {
+        Ok(hashes_of(&self.leaves().await?))
+    }
+}
+
+/// Wraps a repository so every written SAID is appended to a transparency log.
+///
+/// The record is written first; if the append then fails, the error is
+/// returned and the record stays stored without a leaf.
+#[derive(Debug, Clone)]
+pub struct LoggedRepository<R, E, S> {
+    inner: R,
+    log: TransparencyLog<E, S>,
+}
+
+impl<R, E, S> LoggedRepository<R, E, S> {
+    /// Wrap `inner`, appending its writes to `log`.
+    pub fn new(inner: R, log: TransparencyLog<E, S>) -> Self {
+        Self { inner, log }
+    }
+
+    /// The log, for tree heads and proofs.
+    pub fn log(&self) -> &TransparencyLog<E, S> {
+        &self.log
+    }
+}
+
+#[async_trait]
+impl<T, R, E, S> VersionedRepository<T> for LoggedRepository<R, E, S>
+where
+    T: SelfAddressed + Versioned + Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    R: VersionedRepository<T> + Send + Sync,
+    E: QueryExecutor,
+    S: ResponseSigner,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.create(item).await?;
+        self.log.append(&item.get_said()).await?;
+        Ok(item)
+    }
+
+    async fn update(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.update(item).await?;
+        self.log.append(&item.get_said()).await?;
+        Ok(item)
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.insert(item).await?;
+        self.log.append(&item.get_said()).await?;
+        Ok(item)
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_latest(prefix).await
+    }
+
+    async fn get_history(&self, prefix: &str) -> Result<Vec<T>, StorageError> {
+        self.inner.get_history(prefix).await
+    }
+
+    async fn exists(&self, prefix: &str) -> Result<bool, StorageError> {
+        self.inner.exists(prefix).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
+
+#[async_trait]
+impl<T, R, E, S> UnversionedRepository<T> for LoggedRepository<R, E, S>
+where
+    T: SelfAddressed + Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    R: UnversionedRepository<T> + Send + Sync,
+    E: QueryExecutor,
+    S: ResponseSigner,
+{
+    async fn create(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.create(item).await?;
+        self.log.append(&item.get_said()).await?;
+        Ok(item)
+    }
+
+    async fn insert(&self, item: T) -> Result<T, StorageError> {
+        let item = self.inner.insert(item).await?;
+        self.log.append(&item.get_said()).await?;
+        Ok(item)
+    }
+
+    async fn get_by_said(&self, said: &str) -> Result<Option<T>, StorageError> {
+        self.inner.get_by_said(said).await
+    }
+
+    async fn delete_soft(&self, said: &str) -> Result<bool, StorageError> {
+        self.inner.delete_soft(said).await
+    }
+}
+
+fn hashes_of(leaves: &[LogLeaf]) -> Vec<Hash> {
+    leaves
+        .iter()
+        .map(|leaf| leaf_hash(&leaf.record_said))
+        .collect()
+}
+
+pub(crate) fn leaf_hash(said: &str) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[0]);
+    hasher.update(said.as_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[1]);
+    hasher.update(left);
+    hasher.update(right);
+    *hasher.finalize().as_bytes()
+}
+
+/// Largest power of two strictly less than `n` (n > 1).
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+/// MTH(D[n]) from RFC 9162 section 2.1.1.
+pub(crate) fn merkle_root(leaves: &[Hash]) -> Hash {
+    match leaves.len() {
+        0 => *blake3::hash(&[]).as_bytes(),
+        1 => leaves[0],
+        n => {
+            let k = split(n);
+            node_hash(&merkle_root(&leaves[..k]), &merkle_root(&leaves[k..]))
+        }
+    }
+}
+
+/// PATH(m, D[n]) from RFC 9162 section 2.1.3.1.
+fn inclusion_path(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+    let n = leaves.len();
+    if n <= 1 {
+        return Vec::new();
+    }
+    let k = split(n);
+    let (mut path, sibling) = if m < k {
+        (inclusion_path(m, &leaves[..k]), merkle_root(&leaves[k..]))
+    } else {
+        (
+            inclusion_path(m - k, &leaves[k..]),
+            merkle_root(&leaves[..k]),
+        )
+    };
+    path.push(sibling);
+    path
+}
+
+/// PROOF(m, D[n]) from RFC 9162 section 2.1.4.1.
+fn consistency_path(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+    if m == 0 || m == leaves.len() {
+        return Vec::new();
+    }
+    subproof(m, leaves, true)
+}
+
+fn subproof(m: usize, leaves: &[Hash], complete: bool) -> Vec<Hash> {
+    let n = leaves.len();
+    if m == n {
+        return if complete {
+            Vec::new()
+        } else {
+            vec![merkle_root(leaves)]
+        };
+    }
+    let k = split(n);
+    let (mut path, sibling) = if m <= k {
+        (
+            subproof(m, &leaves[..k], complete),
+            merkle_root(&leaves[k..]),
+        )
+    } else {
+        (
+            subproof(m - k, &leaves[k..], false),
+            merkle_root(&leaves[..k]),
+        )
+    };
+    path.push(sibling);
+    path
+}
+
+/// RFC 9162 section 2.1.3.2.
+fn verify_inclusion(leaf: &Hash, index: u64, size: u64, path: &[Hash], root: &Hash) -> bool {
+    if index >= size {
+        return false;
+    }
+    let (mut f, mut s) = (index, size - 1);
+    let mut r = *leaf;
+    for p in path {
+        if s == 0 {
+            return false;
+        }
+        if f & 1 == 1 || f == s {
+            r = node_hash(p, &r);
+            while f & 1 == 0 && f != 0 {
+                f >>= 1;
+                s >>= 1;
+            }
+        } else {
+            r = node_hash(&r, p);
+        }
+        f >>= 1;
+        s >>= 1;
+    }
+    s == 0 && &r == root
+}
+
+/// RFC 9162 section 2.1.4.2.
+fn verify_consistency(
+    first: u64,
+    second: u64,
+    first_root: &Hash,
+    second_root: &Hash,
+    path: &[Hash],
+) -> bool {
+    if first > second {
+        return false;
+    }
+    if first == second {
+        return path.is_empty() && first_root == second_root;
+    }
+    if first == 0 {
+        return path.is_empty();
+    }
+
+    let mut path = path.to_vec();
+    if first.is_power_of_two() {
+        path.insert(0, *first_root);
+    }
+    let Some((start, rest)) = path.split_first() else {
+        return false;
+    };
+
+    let (mut f, mut s) = (first - 1, second - 1);
+    while f & 1 == 1 {
+        f >>= 1;
+        s >>= 1;
+    }
+    let (mut fr, mut sr) = (*start, *start);
+    for c in rest {
+        if s == 0 {
+            return false;
+        }
+        if f & 1 == 1 || f == s {
+            fr = node_hash(c, &fr);
+            sr = node_hash(c, &sr);
+            while f & 1 == 0 && f != 0 {
+                f >>= 1;
+                s >>= 1;
+            }
+        } else {
+            sr = node_hash(&sr, c);
+        }
+        f >>= 1;
+        s >>= 1;
+    }
+    s == 0 && &fr == first_root && &sr == second_root
+}
+
+pub(crate) fn encode(hash: &Hash) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode(hex: &str) -> Result<Hash, StorageError> {
+    let invalid = || StorageError::InvalidSaid(format!("Invalid tree hash `{}`", hex));
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut hash = [0u8; 32];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(hash)
+}
+
+fn decode_all(hexes: &[String]) -> Result<Vec<Hash>, StorageError> {
+    hexes.iter().map(|hex| decode(hex)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(n: usize) -> Vec<Hash> {
+        (0..n).map(|i| leaf_hash(&format!("E{}", i))).collect()
+    }
+
+    #[test]
+    fn inclusion_proofs_verify_for_every_leaf() {
+        for n in 1..=9 {
+            let tree = leaves(n);
+            let root = merkle_root(&tree);
+            for m in 0..n {
+                let path = inclusion_path(m, &tree);
+                assert!(verify_inclusion(&tree[m], m as u64, n as u64, &path, &root));
+                if n > 1 {
+                    let other = &tree[(m + 1) % n];
+                    assert!(!verify_inclusion(other, m as u64, n as u64, &path, &root));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn consistency_proofs_verify_between_sizes() {
+        let tree = leaves(9);
+        for second in 1..=9 {
+            let second_root = merkle_root(&tree[..second]);
+            for first in 1..=second {
+                let first_root = merkle_root(&tree[..first]);
+                let path = consistency_path(first, &tree[..second]);
+                assert!(verify_consistency(
+                    first as u64,
+                    second as u64,
+                    &first_root,
+                    &second_root,
+                    &path
+                ));
+                if first < second {
+                    let forged = merkle_root(&leaves(first + 1)[1..]);
+                    assert!(!verify_consistency(
+                        first as u64,
+                        second as u64,
+                        &forged,
+                        &second_root,
+                        &path
+                    ));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn proofs_round_trip_through_hex() {
+        let tree = leaves(5);
+        let root = encode(&merkle_root(&tree));
+        let proof = InclusionProof {
+            leaf_index: 3,
+            tree_size: 5,
+            path: inclusion_path(3, &tree).iter().map(encode).collect(),
+        };
+        proof.verify("E3", &root).unwrap();
+        assert!(proof.verify("E4", &root).is_err());
+    }
+}
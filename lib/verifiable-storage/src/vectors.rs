@@ -0,0 +1,123 @@
+//! Golden test vectors for SAID computation.
+//!
+//! The vectors ship with the crate (`vectors/said-v1.json`) and pin the exact
+//! digests this crate produces. Implementations in other languages can check
+//! themselves against the file directly; [`verify_vectors`] checks this crate,
+//! so a serialization change that would alter existing SAIDs fails loudly.
+
+use serde::Deserialize;
+
+use crate::{StorageError, compute_said};
+
+/// The bundled vector file.
+pub const SAID_VECTORS_V1: &str = include_str!("../vectors/said-v1.json");
+
+/// The 44-character placeholder a SAID field holds while its digest is computed.
+const PLACEHOLDER: &str = "############################################";
+
+/// A single golden vector.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaidVector {
+    pub description: String,
+    /// The field holding the SAID.
+    pub said_field: String,
+    /// The prefix field, for versioned inceptions (replaced by the placeholder too).
+    #[serde(default)]
+    pub prefix_field: Option<String>,
+    /// The object to digest. Its SAID (and prefix) values are ignored.
+    pub input: serde_json::Value,
+    /// The expected SAID.
+    pub said: String,
+}
+
+#[derive(Deserialize)]
+struct VectorFile {
+    vectors: Vec<SaidVector>,
+}
+
+/// Parse the bundled vectors.
+pub fn said_vectors() -> Result<Vec<SaidVector>, StorageError> {
+    let file: VectorFile = serde_json::from_str(SAID_VECTORS_V1)?;
+    Ok(file.vectors)
+}
+
+impl SaidVector {
+    /// Compute this vector's SAID with this crate.
+    pub fn compute(&self) -> Result<String, StorageError> {
+        let mut input = self.input.clone();
+        let Some(object) = input.as_object_mut() else {
+            return Err(StorageError::StorageError(format!(
+                "Vector input is not an object: {}",
+                self.description
+            )));
+        };
+
+        for field in std::iter::once(&self.said_field).chain(&self.prefix_field) {
+            object.insert(
+                field.clone(),
+                serde_json::Value::String(PLACEHOLDER.to_string()),
+            );
+        }
+
+        compute_said(&input)
+    }
+}
+
+/// Check every bundled vector against this crate's SAID computation.
+///
+/// Returns the number of vectors checked, or `InvalidSaid` naming each mismatch.
+pub fn verify_vectors() -> Result<usize, StorageError> {
+    let vectors = said_vectors()?;
+    let mut mismatches = Vec::new();
+
+    for vector in &vectors {
+        let said = vector.compute()?;
+        if said != vector.said {
+            mismatches.push(format!(
+                "{}: expected {}, got {}",
+                vector.description, vector.said, said
+            ));
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(vectors.len())
+    } else {
+        Err(StorageError::InvalidSaid(mismatches.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_vectors_verify() {
+        assert_eq!(verify_vectors().unwrap(), 5);
+    }
+
+    #[test]
+    fn derived_types_match_vectors() {
+        #[derive(Clone, serde::Serialize, serde::Deserialize, crate::SelfAddressed)]
+        #[serde(rename_all = "camelCase")]
+        struct AuditRecord {
+            #[said]
+            said: String,
+            recorded_at: String,
+            data: String,
+        }
+
+        let vector = &said_vectors().unwrap()[0];
+        let mut record: AuditRecord = serde_json::from_value(vector.input.clone()).unwrap();
+        crate::SelfAddressed::derive_said(&mut record).unwrap();
+        assert_eq!(record.said, vector.said);
+    }
+
+    #[test]
+    fn mismatches_are_reported() {
+        let mut vector = said_vectors().unwrap().remove(0);
+        vector.input["data"] = serde_json::Value::from("changed");
+        assert_ne!(vector.compute().unwrap(), vector.said);
+    }
+}